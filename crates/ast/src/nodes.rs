@@ -1,3 +1,4 @@
+use std::fmt;
 use std::hash::{Hash, Hasher};
 
 use common::Span;
@@ -36,6 +37,14 @@ pub struct Function {
     pub ret_ty: Option<Type>,
     pub body: Block,
     pub public: bool,
+    /// Generic type parameters, e.g. `<T: Ord>`
+    pub generics: Vec<GenericParam>,
+    /// Declared with `const def`, so calls can be evaluated at compile time
+    pub is_const: bool,
+    /// Annotations written above the definition, e.g. `@skip("slow")`
+    pub annotations: Vec<Annotation>,
+    /// The function's name where it is defined
+    pub span: Option<Span>,
 }
 
 impl Function {
@@ -51,6 +60,10 @@ impl Function {
             ret_ty,
             body,
             public: false,
+            generics: Vec::new(),
+            is_const: false,
+            annotations: Vec::new(),
+            span: None,
         }
     }
 
@@ -66,6 +79,81 @@ impl Function {
             ret_ty,
             body,
             public: true,
+            generics: Vec::new(),
+            is_const: false,
+            annotations: Vec::new(),
+            span: None,
+        }
+    }
+
+    pub fn with_generics(mut self, generics: Vec<GenericParam>) -> Self {
+        self.generics = generics;
+        self
+    }
+
+    pub fn with_const(mut self, is_const: bool) -> Self {
+        self.is_const = is_const;
+        self
+    }
+
+    pub fn with_annotations(mut self, annotations: Vec<Annotation>) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// The annotation called `name`, if the function has one
+    pub fn annotation(&self, name: &str) -> Option<&Annotation> {
+        self.annotations
+            .iter()
+            .find(|annotation| annotation.name == name)
+    }
+
+    /// A trait method declared without a body, which implementors must provide
+    pub fn is_signature(&self) -> bool {
+        self.body.statements.is_empty()
+    }
+
+    /// A function whose body contains `yield`; calling it produces an
+    /// iterator over the yielded values instead of running the body
+    pub fn is_generator(&self) -> bool {
+        self.body.contains_yield()
+    }
+}
+
+/// `@name` or `@name(args)` above a function definition. The compiler
+/// ignores annotations; tools such as `otter test` give them meaning.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub name: String,
+    pub args: Vec<Expr>,
+}
+
+impl Annotation {
+    pub fn new(name: impl Into<String>, args: Vec<Expr>) -> Self {
+        Self {
+            name: name.into(),
+            args,
+        }
+    }
+}
+
+/// A generic type parameter and the traits it must implement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenericParam {
+    pub name: String,
+    pub bounds: Vec<String>,
+}
+
+impl GenericParam {
+    pub fn new(name: impl Into<String>, bounds: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            bounds,
         }
     }
 }
@@ -73,7 +161,17 @@ impl Function {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Type {
     Simple(String),
-    Generic { base: String, args: Vec<Type> },
+    Generic {
+        base: String,
+        args: Vec<Type>,
+    },
+    /// Tuple type, e.g. `(int, str)`
+    Tuple(Vec<Type>),
+    /// Function type, e.g. `fn(int, str) -> bool`
+    Function {
+        params: Vec<Type>,
+        ret: Option<Box<Type>>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -146,12 +244,29 @@ pub enum Statement {
     // Variable declarations and assignments
     Let {
         name: String,
+        ty: Option<Type>,
         expr: Expr,
         public: bool,
         span: Option<Span>,
     },
-    Assignment {
+    /// `const NAME: Type = expr`, a module-level value fixed at compile time
+    Const {
         name: String,
+        ty: Type,
+        expr: Expr,
+        public: bool,
+        span: Option<Span>,
+    },
+    /// `let (a, b) = expr`, binding every name in the pattern
+    Destructure {
+        pattern: Pattern,
+        expr: Expr,
+        span: Option<Span>,
+    },
+    /// `target = expr`, where the target is an identifier, an index
+    /// (`items[i]`) or a member (`point.x`)
+    Assignment {
+        target: Expr,
         expr: Expr,
         span: Option<Span>,
     },
@@ -164,7 +279,7 @@ pub enum Statement {
         else_block: Option<Block>,
     },
     For {
-        var: String,
+        var: Pattern,
         iterable: Expr,
         body: Block,
         var_span: Option<Span>,
@@ -177,6 +292,8 @@ pub enum Statement {
     Continue,
     Pass,
     Return(Option<Expr>),
+    /// `yield expr`, producing the next value of a generator
+    Yield(Expr),
 
     // Function definitions
     Function(Function),
@@ -201,6 +318,18 @@ pub enum Statement {
         public: bool,
         generics: Vec<String>, // Generic type parameters
     },
+    /// `trait Name:` with method signatures and default methods
+    Trait {
+        name: String,
+        methods: Vec<Function>, // Signature-only methods have an empty body
+        public: bool,
+    },
+    /// `impl Trait for Type:` providing the trait's methods for a type
+    Impl {
+        trait_name: String,
+        type_name: String,
+        methods: Vec<Function>,
+    },
 
     // Expressions as statements
     Expr(Expr),
@@ -228,6 +357,12 @@ pub enum Statement {
         finally_block: Option<Block>,
     },
     Raise(Option<Expr>),
+    /// `with manager as alias:`, releasing the manager however the body exits
+    With {
+        manager: Expr,
+        alias: Option<String>,
+        body: Block,
+    },
 }
 
 impl Statement {
@@ -235,17 +370,22 @@ impl Statement {
     pub fn recursive_count(&self) -> usize {
         match self {
             Statement::Let { .. }
+            | Statement::Const { .. }
+            | Statement::Destructure { .. }
             | Statement::Assignment { .. }
             | Statement::Break
             | Statement::Continue
             | Statement::Pass
             | Statement::Return(_)
+            | Statement::Yield(_)
             | Statement::Expr(_)
             | Statement::Use { .. }
             | Statement::PubUse { .. }
             | Statement::Struct { .. }
             | Statement::Enum { .. }
             | Statement::TypeAlias { .. }
+            | Statement::Trait { .. }
+            | Statement::Impl { .. }
             | Statement::Raise(_) => 1,
 
             Statement::If {
@@ -264,9 +404,9 @@ impl Statement {
                 }
                 count
             }
-            Statement::For { body, .. } | Statement::While { body, .. } => {
-                1 + body.recursive_count()
-            }
+            Statement::For { body, .. }
+            | Statement::While { body, .. }
+            | Statement::With { body, .. } => 1 + body.recursive_count(),
             Statement::Function(func) => 1 + func.body.recursive_count(),
             Statement::Block(block) => block.recursive_count(),
             Statement::Try {
@@ -290,11 +430,49 @@ impl Statement {
         }
     }
 
+    /// Whether a `yield` is reachable from this statement without entering a
+    /// nested function definition
+    pub fn contains_yield(&self) -> bool {
+        match self {
+            Statement::Yield(_) => true,
+            Statement::If {
+                then_block,
+                elif_blocks,
+                else_block,
+                ..
+            } => {
+                then_block.contains_yield()
+                    || elif_blocks.iter().any(|(_, block)| block.contains_yield())
+                    || else_block.as_ref().is_some_and(Block::contains_yield)
+            }
+            Statement::For { body, .. }
+            | Statement::While { body, .. }
+            | Statement::With { body, .. } => body.contains_yield(),
+            Statement::Block(block) => block.contains_yield(),
+            Statement::Try {
+                body,
+                handlers,
+                else_block,
+                finally_block,
+            } => {
+                body.contains_yield()
+                    || handlers.iter().any(|handler| handler.body.contains_yield())
+                    || else_block.as_ref().is_some_and(Block::contains_yield)
+                    || finally_block.as_ref().is_some_and(Block::contains_yield)
+            }
+            _ => false,
+        }
+    }
+
     /// Check if statement is pure (has no side effects)
     pub fn is_pure(&self) -> bool {
         matches!(
             self,
-            Statement::Let { .. } | Statement::Break | Statement::Continue | Statement::Pass
+            Statement::Let { .. }
+                | Statement::Const { .. }
+                | Statement::Break
+                | Statement::Continue
+                | Statement::Pass
         )
     }
 }
@@ -309,6 +487,11 @@ impl Block {
     pub fn is_empty(&self) -> bool {
         self.statements.is_empty()
     }
+
+    /// Whether any statement in the block yields
+    pub fn contains_yield(&self) -> bool {
+        self.statements.iter().any(Statement::contains_yield)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -325,11 +508,26 @@ pub enum Expr {
         object: Box<Expr>,
         field: String,
     },
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        /// The subscript between the brackets
+        span: Option<Span>,
+    },
+    Slice {
+        object: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        /// The bounds between the brackets
+        span: Option<Span>,
+    },
 
     // Function calls
     Call {
         func: Box<Expr>,
         args: Vec<Expr>,
+        /// From the start of the callee to the closing parenthesis
+        span: Option<Span>,
     },
 
     // Binary operations
@@ -345,6 +543,12 @@ pub enum Expr {
         expr: Box<Expr>,
     },
 
+    /// Numeric conversion, `expr as u8`
+    Cast {
+        expr: Box<Expr>,
+        ty: Type,
+    },
+
     // Control flow expressions
     If {
         cond: Box<Expr>,
@@ -367,6 +571,7 @@ pub enum Expr {
     // Collection literals
     Array(Vec<Expr>),
     Dict(Vec<(Expr, Expr)>), // Key-value pairs
+    Tuple(Vec<Expr>),
     ListComprehension {
         element: Box<Expr>,
         var: String,
@@ -455,6 +660,82 @@ pub enum Pattern {
         patterns: Vec<Pattern>,
         rest: Option<String>, // Variable name for rest pattern
     },
+    /// Tuple pattern ((a, b))
+    Tuple(Vec<Pattern>),
+    /// Or pattern (1 | 2 | 3); every alternative binds the same names
+    Or(Vec<Pattern>),
+    /// Half-open range pattern (0..10)
+    Range { start: Literal, end: Literal },
+    /// Pattern that also binds the whole matched value (Some(x) as whole)
+    Binding { pattern: Box<Pattern>, name: String },
+    /// String prefix pattern ("GET "..path), binding the remainder
+    StringPrefix {
+        prefix: String,
+        rest: Option<String>,
+    },
+    /// Dict shape pattern ({"id": id}); matches dicts holding at least these keys
+    Dict(Vec<(String, Pattern)>),
+}
+
+impl Pattern {
+    /// Names bound by this pattern, in source order
+    pub fn bindings(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_bindings(&mut names);
+        names
+    }
+
+    fn collect_bindings<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => {}
+            Pattern::Identifier(name) => {
+                if name != "_" {
+                    names.push(name);
+                }
+            }
+            Pattern::EnumVariant { fields, .. } | Pattern::Tuple(fields) => {
+                for field in fields {
+                    field.collect_bindings(names);
+                }
+            }
+            Pattern::Struct { fields, .. } => {
+                for (field, pattern) in fields {
+                    match pattern {
+                        Some(pattern) => pattern.collect_bindings(names),
+                        None => names.push(field),
+                    }
+                }
+            }
+            Pattern::Array { patterns, rest } => {
+                for pattern in patterns {
+                    pattern.collect_bindings(names);
+                }
+                if let Some(rest) = rest {
+                    names.push(rest);
+                }
+            }
+            // Alternatives bind the same names, so the first one speaks for all
+            Pattern::Or(alternatives) => {
+                if let Some(first) = alternatives.first() {
+                    first.collect_bindings(names);
+                }
+            }
+            Pattern::Binding { pattern, name } => {
+                pattern.collect_bindings(names);
+                names.push(name);
+            }
+            Pattern::StringPrefix { rest, .. } => {
+                if let Some(rest) = rest {
+                    names.push(rest);
+                }
+            }
+            Pattern::Dict(entries) => {
+                for (_, pattern) in entries {
+                    pattern.collect_bindings(names);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -471,6 +752,18 @@ pub enum BinaryOp {
     Mul,
     Div,
     Mod,
+    /// `**`
+    Pow,
+    /// `//`, rounding towards negative infinity
+    FloorDiv,
+
+    // Bitwise (integers only)
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    /// `>>`, arithmetic (sign-extending)
+    Shr,
 
     // Comparison
     Eq,
@@ -491,19 +784,152 @@ pub enum BinaryOp {
 pub enum UnaryOp {
     Neg,
     Not,
+    /// `~`, bitwise complement
+    BitNot,
+}
+
+/// Fixed-width numeric types, named by literal suffixes such as `255u8` and
+/// by `as` conversions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumericType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+    F32,
+    F64,
+}
+
+impl NumericType {
+    pub const ALL: [NumericType; 11] = [
+        NumericType::I8,
+        NumericType::I16,
+        NumericType::I32,
+        NumericType::I64,
+        NumericType::U8,
+        NumericType::U16,
+        NumericType::U32,
+        NumericType::U64,
+        NumericType::Usize,
+        NumericType::F32,
+        NumericType::F64,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|ty| ty.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumericType::I8 => "i8",
+            NumericType::I16 => "i16",
+            NumericType::I32 => "i32",
+            NumericType::I64 => "i64",
+            NumericType::U8 => "u8",
+            NumericType::U16 => "u16",
+            NumericType::U32 => "u32",
+            NumericType::U64 => "u64",
+            NumericType::Usize => "usize",
+            NumericType::F32 => "f32",
+            NumericType::F64 => "f64",
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, NumericType::F32 | NumericType::F64)
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(
+            self,
+            NumericType::I8 | NumericType::I16 | NumericType::I32 | NumericType::I64
+        )
+    }
+
+    /// Width in bits; `usize` is 64 bits on every supported target
+    pub fn bits(self) -> u32 {
+        match self {
+            NumericType::I8 | NumericType::U8 => 8,
+            NumericType::I16 | NumericType::U16 => 16,
+            NumericType::I32 | NumericType::U32 | NumericType::F32 => 32,
+            NumericType::I64 | NumericType::U64 | NumericType::Usize | NumericType::F64 => 64,
+        }
+    }
+
+    /// Smallest and largest value of an integer type
+    pub fn int_range(self) -> Option<(i128, i128)> {
+        if self.is_float() {
+            return None;
+        }
+        let bits = self.bits();
+        Some(if self.is_signed() {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct NumberLiteral {
     pub value: f64,
+    /// Exact value of an integer literal, which `value` rounds beyond 2^53
+    pub integer: Option<i128>,
     pub is_float_literal: bool,
+    /// Type named by a suffix, as in `255u8` or `1.5f32`
+    pub suffix: Option<NumericType>,
 }
 
 impl NumberLiteral {
     pub fn new(value: f64, is_float_literal: bool) -> Self {
         Self {
             value,
+            integer: None,
             is_float_literal,
+            suffix: None,
+        }
+    }
+
+    pub fn from_integer(value: i128) -> Self {
+        Self {
+            value: value as f64,
+            integer: Some(value),
+            is_float_literal: false,
+            suffix: None,
+        }
+    }
+
+    pub fn with_suffix(mut self, suffix: Option<NumericType>) -> Self {
+        self.suffix = suffix;
+        self
+    }
+
+    /// The literal under a unary minus, as in `-128i8`
+    pub fn negated(self) -> Self {
+        Self {
+            value: -self.value,
+            integer: self.integer.map(|value| -value),
+            ..self
+        }
+    }
+
+    /// The value as an integer: exact for integer literals, truncated
+    /// otherwise
+    pub fn int_value(&self) -> i128 {
+        self.integer.unwrap_or(self.value as i128)
+    }
+}
+
+impl fmt::Display for NumberLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.integer {
+            Some(value) => write!(f, "{value}"),
+            None => write!(f, "{}", self.value),
         }
     }
 }
@@ -512,6 +938,8 @@ impl PartialEq for NumberLiteral {
     fn eq(&self, other: &Self) -> bool {
         self.is_float_literal == other.is_float_literal
             && self.value.to_bits() == other.value.to_bits()
+            && self.integer == other.integer
+            && self.suffix == other.suffix
     }
 }
 
@@ -520,7 +948,9 @@ impl Eq for NumberLiteral {}
 impl Hash for NumberLiteral {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.value.to_bits().hash(state);
+        self.integer.hash(state);
         self.is_float_literal.hash(state);
+        self.suffix.hash(state);
     }
 }
 
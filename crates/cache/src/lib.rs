@@ -4,9 +4,9 @@ pub mod metadata;
 pub mod path;
 
 // Re-exports for convenience
-pub use manager::{CacheEntry, CacheManager};
+pub use manager::{CacheEntry, CacheManager, CacheStats};
 pub use metadata::CacheMetadata;
-pub use path::{cache_key_for_file, cache_root, ensure_cache_dir};
+pub use path::{build_cache_dir, cache_key_for_file, cache_root, ensure_cache_dir};
 
 /// Build options for caching
 #[derive(Debug, Clone)]
@@ -17,6 +17,12 @@ pub struct CacheBuildOptions {
     pub release: bool,
     pub lto: bool,
     pub emit_ir: bool,
+    /// Target triple requested for cross-compilation (`None` means the host).
+    pub target: Option<String>,
+    /// Version of the LLVM toolchain producing the binary.
+    pub llvm_version: Option<String>,
+    /// Whether the binary is a test harness rather than the program itself.
+    pub test_harness: bool,
 }
 
 /// Compilation inputs for caching
@@ -25,6 +31,8 @@ pub struct CompilationInputs {
     pub source_path: std::path::PathBuf,
     pub dependencies: Vec<String>,
     pub imports: Vec<String>,
+    /// In-memory source text; when absent the file at `source_path` is hashed.
+    pub source: Option<String>,
 }
 
 impl CompilationInputs {
@@ -33,6 +41,12 @@ impl CompilationInputs {
             source_path,
             dependencies,
             imports: Vec::new(),
+            source: None,
         }
     }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
 }
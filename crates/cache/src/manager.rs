@@ -1,10 +1,38 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+
+use super::metadata::{CacheMetadata, unix_now};
+
+const METADATA_FILE: &str = "metadata.json";
+const ENTRIES_DIR: &str = "entries";
+const SOURCES_DIR: &str = "sources";
+/// How many builds (e.g. debug and release) are remembered per source file.
+const MAX_KEYS_PER_SOURCE: usize = 8;
+/// Prefix of the per-process directories builds are staged in.
+const STAGING_PREFIX: &str = "staging-";
+/// How long a build may run before what it staged counts as left behind by
+/// an interrupted build.
+const ORPHAN_GRACE_SECS: u64 = 60 * 60;
+
+#[cfg(target_os = "windows")]
+const BINARY_NAME: &str = "program.exe";
+#[cfg(not(target_os = "windows"))]
+const BINARY_NAME: &str = "program";
 
 /// Compilation cache manager
+///
+/// Entries are content addressed and persisted on disk:
+///
+/// ```text
+/// <root>/entries/<key>/program        compiled binary
+/// <root>/entries/<key>/metadata.json  serialized `CacheMetadata`
+/// <root>/entries/<key>/staging-<pid>/ build in progress
+/// <root>/sources/<path hash>          recent keys built from a source file
+/// ```
 pub struct CacheManager {
-    _cache_dir: PathBuf,
-    entries: HashMap<String, CacheEntry>,
+    root: PathBuf,
 }
 
 #[derive(Debug, Clone)]
@@ -16,62 +44,620 @@ pub struct CacheEntry {
     pub binary_path: PathBuf,
 }
 
-impl CacheManager {
-    pub fn new() -> Self {
+impl CacheEntry {
+    fn from_metadata(metadata: CacheMetadata) -> Self {
         Self {
-            _cache_dir: PathBuf::from("./cache"),
-            entries: HashMap::new(),
+            path: metadata.cache_path.clone(),
+            last_modified: metadata.created_at,
+            size: metadata.binary_size,
+            binary_path: metadata.binary_path.clone(),
+            metadata,
         }
     }
+}
+
+/// Aggregate numbers reported by `otter cache stats`.
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub stale_entries: usize,
+    pub total_size: u64,
+    pub total_hits: u64,
+    pub oldest_entry: Option<u64>,
+    pub newest_entry: Option<u64>,
+}
+
+impl CacheManager {
+    pub fn new() -> Self {
+        let root = super::path::build_cache_dir().unwrap_or_else(|_| PathBuf::from("./cache"));
+        Self::with_root(root)
+    }
 
-    pub fn get(&self, key: &str) -> Option<&CacheEntry> {
-        self.entries.get(key)
+    pub fn with_root(root: PathBuf) -> Self {
+        Self { root }
     }
 
-    pub fn put(&mut self, key: String, entry: CacheEntry) {
-        self.entries.insert(key, entry);
+    pub fn root(&self) -> &Path {
+        &self.root
     }
 
-    pub fn clear(&mut self) {
-        self.entries.clear();
+    /// Remove every cached build.
+    pub fn clear(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        for dir in [ENTRIES_DIR, SOURCES_DIR] {
+            let path = self.root.join(dir);
+            if path.exists() {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        Ok(())
     }
 
+    /// Compute the content-addressed key for a compilation.
+    ///
+    /// The key covers the source text, every transitive module listed in
+    /// `inputs.imports`, the extra `inputs.dependencies`, the build options,
+    /// the target triple, the LLVM version and the compiler `version`.
     pub fn fingerprint(
         &self,
-        _inputs: &super::CompilationInputs,
-        _options: &super::CacheBuildOptions,
-        _version: &str,
+        inputs: &super::CompilationInputs,
+        options: &super::CacheBuildOptions,
+        version: &str,
     ) -> String {
-        // Simple fingerprinting - in a real implementation this would hash the inputs
-        format!(
-            "cache_key_{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-        )
+        let mut hasher = Sha1::new();
+
+        hash_field(&mut hasher, "otter", version.as_bytes());
+        hash_field(
+            &mut hasher,
+            "llvm",
+            options
+                .llvm_version
+                .as_deref()
+                .unwrap_or("unknown")
+                .as_bytes(),
+        );
+        let host = format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS);
+        hash_field(
+            &mut hasher,
+            "target",
+            options.target.as_deref().unwrap_or(&host).as_bytes(),
+        );
+        hash_field(&mut hasher, "release", &[options.release as u8]);
+        hash_field(&mut hasher, "lto", &[options.lto as u8]);
+        hash_field(&mut hasher, "emit_ir", &[options.emit_ir as u8]);
+        hash_field(&mut hasher, "test_harness", &[options.test_harness as u8]);
+
+        // Failing `test` assertions report the path they were compiled from
+        hash_field(
+            &mut hasher,
+            "source_path",
+            inputs.source_path.to_string_lossy().as_bytes(),
+        );
+        match &inputs.source {
+            Some(source) => hash_field(&mut hasher, "source", source.as_bytes()),
+            None => hash_file(&mut hasher, "source", &inputs.source_path),
+        }
+
+        for dependency in &inputs.dependencies {
+            hash_field(&mut hasher, "dependency", dependency.as_bytes());
+        }
+
+        // Module resolution order depends on hash map iteration, so sort first
+        let mut imports = inputs.imports.clone();
+        imports.sort();
+        imports.dedup();
+        for import in &imports {
+            let path = Path::new(import);
+            hash_field(&mut hasher, "module", import.as_bytes());
+            hash_file(&mut hasher, "module_source", path);
+        }
+
+        hex(&hasher.finalize())
     }
 
+    /// Look up a validated entry by its key.
     pub fn lookup(&self, key: &str) -> Option<CacheEntry> {
-        self.entries.get(key).cloned()
+        let metadata = self.read_metadata(key)?;
+        if metadata.key != key || !metadata.is_valid() {
+            return None;
+        }
+
+        Some(CacheEntry::from_metadata(metadata))
     }
 
-    pub fn binary_path(&self, key: &str) -> Option<PathBuf> {
-        self.entries.get(key).map(|entry| entry.binary_path.clone())
+    /// Every entry with readable metadata, including stale ones, newest first.
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let Ok(dir) = fs::read_dir(self.root.join(ENTRIES_DIR)) else {
+            return Vec::new();
+        };
+
+        let mut entries: Vec<CacheEntry> = dir
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let key = entry.file_name().to_string_lossy().into_owned();
+                self.read_metadata(&key)
+            })
+            .map(CacheEntry::from_metadata)
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.metadata.last_used()));
+        entries
     }
 
-    pub fn store(
-        &mut self,
-        metadata: &super::metadata::CacheMetadata,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let entry = CacheEntry {
-            path: metadata.cache_path.clone(),
-            last_modified: metadata.created_at,
-            size: metadata.binary_size,
-            metadata: metadata.clone(),
-            binary_path: metadata.binary_path.clone(),
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats::default();
+        for entry in self.entries() {
+            stats.entries += 1;
+            if !entry.metadata.is_valid() {
+                stats.stale_entries += 1;
+            }
+            stats.total_size += entry.size;
+            stats.total_hits += entry.metadata.hit_count;
+            let created = entry.metadata.created_at;
+            stats.oldest_entry = Some(stats.oldest_entry.map_or(created, |t| t.min(created)));
+            stats.newest_entry = Some(stats.newest_entry.map_or(created, |t| t.max(created)));
+        }
+        stats
+    }
+
+    /// Delete an entry directory, returning the number of bytes freed.
+    pub fn remove(&self, key: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let dir = self.entry_dir(key);
+        if !dir.exists() {
+            return Ok(0);
+        }
+        let freed = dir_size(&dir);
+        fs::remove_dir_all(&dir)?;
+        Ok(freed)
+    }
+
+    /// Remove entries that were not built or hit since `cutoff` (unix seconds),
+    /// along with entries whose binary is gone and what interrupted builds left.
+    pub fn prune_unused_since(
+        &self,
+        cutoff: u64,
+    ) -> Result<Vec<CacheEntry>, Box<dyn std::error::Error>> {
+        self.remove_orphans(unix_now().saturating_sub(ORPHAN_GRACE_SECS))?;
+        let mut removed = Vec::new();
+        for entry in self.entries() {
+            if entry.metadata.last_used() < cutoff || !entry.metadata.is_valid() {
+                self.remove(&entry.metadata.key)?;
+                removed.push(entry);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Evict least recently used entries until the binaries fit in `max_size` bytes.
+    /// Stale entries and what interrupted builds left are always evicted first.
+    pub fn evict_to_size(
+        &self,
+        max_size: u64,
+    ) -> Result<Vec<CacheEntry>, Box<dyn std::error::Error>> {
+        self.remove_orphans(unix_now().saturating_sub(ORPHAN_GRACE_SECS))?;
+        let mut entries = self.entries();
+        // Eviction order: stale entries, then least recently used
+        entries.sort_by_key(|entry| (entry.metadata.is_valid(), entry.metadata.last_used()));
+
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut removed = Vec::new();
+        for entry in entries {
+            if total <= max_size && entry.metadata.is_valid() {
+                break;
+            }
+            self.remove(&entry.metadata.key)?;
+            total = total.saturating_sub(entry.size);
+            removed.push(entry);
+        }
+        Ok(removed)
+    }
+
+    /// Look up an entry before the module graph is known.
+    ///
+    /// Candidate keys previously built from the same source file are re-fingerprinted
+    /// with the modules they recorded, so any change to the source, a module, the
+    /// options or the toolchain turns the candidate into a miss.
+    pub fn lookup_source(
+        &self,
+        inputs: &super::CompilationInputs,
+        options: &super::CacheBuildOptions,
+        version: &str,
+    ) -> Option<CacheEntry> {
+        for key in self.source_keys(&inputs.source_path) {
+            let Some(entry) = self.lookup(&key) else {
+                continue;
+            };
+
+            let mut candidate = inputs.clone();
+            candidate.imports = entry.metadata.imports.clone();
+            if self.fingerprint(&candidate, options, version) == key {
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    /// Directory holding the binary and metadata for `key`.
+    pub fn entry_dir(&self, key: &str) -> PathBuf {
+        self.root.join(ENTRIES_DIR).join(key)
+    }
+
+    /// Final location of the binary for `key`.
+    pub fn binary_path(&self, key: &str) -> PathBuf {
+        self.entry_dir(key).join(BINARY_NAME)
+    }
+
+    /// Process-unique path to build into before the binary is installed with
+    /// [`CacheManager::install_binary`], so concurrent builds never share files.
+    /// The build's intermediate files go in the same staging directory.
+    pub fn staging_path(&self, key: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let dir = self
+            .entry_dir(key)
+            .join(format!("{}{}", STAGING_PREFIX, std::process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(dir.join(BINARY_NAME))
+    }
+
+    /// Move a staged binary into its final location and drop its staging directory.
+    pub fn install_binary(
+        &self,
+        key: &str,
+        staged: &Path,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let target = self.binary_path(key);
+        let result = fs::rename(staged, &target);
+        self.discard_staging(key, staged);
+        result?;
+        Ok(target)
+    }
+
+    /// Remove what a build staged at `staged` left behind, along with the entry
+    /// directory when nothing else is in it.
+    pub fn discard_staging(&self, key: &str, staged: &Path) {
+        if let Some(dir) = staged.parent() {
+            fs::remove_dir_all(dir).ok();
+        }
+        // Fails, as it should, when the entry holds a binary or metadata
+        fs::remove_dir(self.entry_dir(key)).ok();
+    }
+
+    /// Remove entry directories without metadata and staging directories that
+    /// were last touched before `cutoff` (unix seconds): what builds that were
+    /// interrupted before storing their entry left. Returns the bytes freed.
+    pub fn remove_orphans(&self, cutoff: u64) -> Result<u64, Box<dyn std::error::Error>> {
+        let Ok(dir) = fs::read_dir(self.root.join(ENTRIES_DIR)) else {
+            return Ok(0);
+        };
+
+        let mut freed = 0;
+        for entry in dir.filter_map(|entry| entry.ok()) {
+            let key = entry.file_name().to_string_lossy().into_owned();
+            let orphans = if self.read_metadata(&key).is_some() {
+                staging_dirs(&entry.path())
+            } else {
+                vec![entry.path()]
+            };
+            for orphan in orphans {
+                if modified_secs(&orphan) < cutoff {
+                    freed += dir_size(&orphan);
+                    fs::remove_dir_all(&orphan)?;
+                }
+            }
+        }
+        Ok(freed)
+    }
+
+    /// Persist `metadata` next to its binary and remember the key for its source file.
+    pub fn store(&self, metadata: &CacheMetadata) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = self.entry_dir(&metadata.key);
+        fs::create_dir_all(&dir)?;
+        write_atomic(
+            &dir.join(METADATA_FILE),
+            serde_json::to_string_pretty(metadata)?.as_bytes(),
+        )?;
+
+        let mut keys = self.source_keys(&metadata.source_path);
+        keys.retain(|key| key != &metadata.key);
+        keys.insert(0, metadata.key.clone());
+        keys.truncate(MAX_KEYS_PER_SOURCE);
+
+        let sources = self.root.join(SOURCES_DIR);
+        fs::create_dir_all(&sources)?;
+        write_atomic(
+            &sources.join(source_index_name(&metadata.source_path)),
+            keys.join("\n").as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Update the `last_hit` timestamp of an entry.
+    pub fn record_hit(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(mut metadata) = self.read_metadata(key) else {
+            return Ok(());
         };
-        self.entries.insert(metadata.key.clone(), entry);
+        metadata.last_hit = Some(unix_now());
+        metadata.hit_count += 1;
+        write_atomic(
+            &self.entry_dir(key).join(METADATA_FILE),
+            serde_json::to_string_pretty(&metadata)?.as_bytes(),
+        )?;
         Ok(())
     }
+
+    fn read_metadata(&self, key: &str) -> Option<CacheMetadata> {
+        let contents = fs::read(self.entry_dir(key).join(METADATA_FILE)).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    fn source_keys(&self, source_path: &Path) -> Vec<String> {
+        let index = self
+            .root
+            .join(SOURCES_DIR)
+            .join(source_index_name(source_path));
+        fs::read_to_string(index)
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Default for CacheManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(dir) = fs::read_dir(path) else {
+        return 0;
+    };
+    dir.filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(ty) if ty.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+fn staging_dirs(entry_dir: &Path) -> Vec<PathBuf> {
+    let Ok(dir) = fs::read_dir(entry_dir) else {
+        return Vec::new();
+    };
+    dir.filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(STAGING_PREFIX)
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Last modification of `path` in unix seconds, 0 when unknown.
+fn modified_secs(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn hash_field(hasher: &mut Sha1, label: &str, value: &[u8]) {
+    // Length-prefix every field so adjacent values can't run together
+    hasher.update(label.as_bytes());
+    hasher.update((value.len() as u64).to_le_bytes());
+    hasher.update(value);
+}
+
+fn hash_file(hasher: &mut Sha1, label: &str, path: &Path) {
+    match fs::read(path) {
+        Ok(contents) => hash_field(hasher, label, &contents),
+        // A missing file can never match a real build, but still yields a stable key
+        Err(_) => hash_field(hasher, "missing", path.to_string_lossy().as_bytes()),
+    }
+}
+
+fn source_index_name(source_path: &Path) -> String {
+    let canonical = source_path
+        .canonicalize()
+        .unwrap_or_else(|_| source_path.to_path_buf());
+    let mut hasher = Sha1::new();
+    hasher.update(canonical.to_string_lossy().as_bytes());
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CacheBuildOptions, CompilationInputs};
+
+    fn options(release: bool) -> CacheBuildOptions {
+        CacheBuildOptions {
+            enable_cache: true,
+            cache_dir: PathBuf::new(),
+            max_cache_size: 0,
+            release,
+            lto: release,
+            emit_ir: false,
+            target: None,
+            llvm_version: Some("18.1".to_string()),
+            test_harness: false,
+        }
+    }
+
+    fn store_entry(manager: &CacheManager, inputs: &CompilationInputs, key: &str) {
+        let staged = manager.staging_path(key).unwrap();
+        fs::write(&staged, b"binary").unwrap();
+        let binary = manager.install_binary(key, &staged).unwrap();
+        let metadata = CacheMetadata::new(
+            key.to_string(),
+            "0.1.0",
+            Some("18.1".to_string()),
+            inputs.source_path.clone(),
+            Vec::new(),
+            binary,
+            6,
+            1,
+            manager.entry_dir(key),
+            inputs.imports.clone(),
+        );
+        manager.store(&metadata).unwrap();
+    }
+
+    #[test]
+    fn fingerprint_tracks_source_modules_and_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.ot");
+        let module = dir.path().join("util.ot");
+        fs::write(&source, "def main():\n    pass\n").unwrap();
+        fs::write(&module, "pub def helper():\n    pass\n").unwrap();
+
+        let manager = CacheManager::with_root(dir.path().join("cache"));
+        let mut inputs = CompilationInputs::new(source.clone(), Vec::new());
+        inputs.imports = vec![module.display().to_string()];
+
+        let key = manager.fingerprint(&inputs, &options(false), "0.1.0");
+        assert_eq!(key, manager.fingerprint(&inputs, &options(false), "0.1.0"));
+        assert_ne!(key, manager.fingerprint(&inputs, &options(true), "0.1.0"));
+        let harness = CacheBuildOptions {
+            test_harness: true,
+            ..options(false)
+        };
+        assert_ne!(key, manager.fingerprint(&inputs, &harness, "0.1.0"));
+        assert_ne!(key, manager.fingerprint(&inputs, &options(false), "0.2.0"));
+
+        let moved = dir.path().join("copy.ot");
+        fs::copy(&source, &moved).unwrap();
+        let mut moved_inputs = CompilationInputs::new(moved, Vec::new());
+        moved_inputs.imports = inputs.imports.clone();
+        assert_ne!(
+            key,
+            manager.fingerprint(&moved_inputs, &options(false), "0.1.0")
+        );
+
+        fs::write(&module, "pub def helper():\n    return\n").unwrap();
+        assert_ne!(key, manager.fingerprint(&inputs, &options(false), "0.1.0"));
+    }
+
+    #[test]
+    fn stored_entries_survive_new_manager_and_are_validated() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.ot");
+        let module = dir.path().join("util.ot");
+        fs::write(&source, "def main():\n    pass\n").unwrap();
+        fs::write(&module, "pub def helper():\n    pass\n").unwrap();
+
+        let root = dir.path().join("cache");
+        let mut inputs = CompilationInputs::new(source.clone(), Vec::new());
+        inputs.imports = vec![module.display().to_string()];
+
+        let manager = CacheManager::with_root(root.clone());
+        let key = manager.fingerprint(&inputs, &options(false), "0.1.0");
+        store_entry(&manager, &inputs, &key);
+
+        let reopened = CacheManager::with_root(root);
+        assert!(reopened.lookup(&key).is_some());
+
+        // Quick lookup without knowing the module graph yet
+        let bare = CompilationInputs::new(source.clone(), Vec::new());
+        let entry = reopened
+            .lookup_source(&bare, &options(false), "0.1.0")
+            .expect("source lookup should hit");
+        assert_eq!(entry.metadata.key, key);
+
+        // Editing a transitive module invalidates the quick lookup
+        fs::write(&module, "pub def helper():\n    return\n").unwrap();
+        assert!(
+            reopened
+                .lookup_source(&bare, &options(false), "0.1.0")
+                .is_none()
+        );
+
+        // A truncated binary is rejected
+        fs::write(reopened.binary_path(&key), b"bin").unwrap();
+        assert!(reopened.lookup(&key).is_none());
+    }
+
+    #[test]
+    fn eviction_removes_least_recently_used_entries_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CacheManager::with_root(dir.path().join("cache"));
+
+        for (idx, key) in ["old", "middle", "recent"].iter().enumerate() {
+            let source = dir.path().join(format!("{key}.ot"));
+            fs::write(&source, "def main():\n    pass\n").unwrap();
+            let inputs = CompilationInputs::new(source, Vec::new());
+            store_entry(&manager, &inputs, key);
+
+            let mut metadata = manager.read_metadata(key).unwrap();
+            metadata.created_at = 1_000 + idx as u64;
+            manager.store(&metadata).unwrap();
+        }
+        manager.record_hit("old").unwrap();
+
+        // Each binary is 6 bytes; keep room for two
+        let evicted = manager.evict_to_size(12).unwrap();
+        let evicted: Vec<_> = evicted.iter().map(|e| e.metadata.key.as_str()).collect();
+        assert_eq!(evicted, vec!["middle"]);
+
+        let remaining: Vec<_> = manager
+            .entries()
+            .into_iter()
+            .map(|e| e.metadata.key)
+            .collect();
+        assert_eq!(remaining, vec!["old".to_string(), "recent".to_string()]);
+        assert_eq!(manager.stats().total_hits, 1);
+    }
+
+    #[test]
+    fn failed_and_interrupted_builds_leave_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CacheManager::with_root(dir.path().join("cache"));
+
+        // A failed build removes its staging directory and the empty entry
+        let staged = manager.staging_path("failed").unwrap();
+        fs::write(staged.with_extension("o"), b"object").unwrap();
+        manager.discard_staging("failed", &staged);
+        assert!(!manager.entry_dir("failed").exists());
+
+        // An installed build keeps only the binary and its metadata
+        let source = dir.path().join("main.ot");
+        fs::write(&source, "def main():\n    pass\n").unwrap();
+        let inputs = CompilationInputs::new(source, Vec::new());
+        store_entry(&manager, &inputs, "built");
+        let mut files: Vec<_> = fs::read_dir(manager.entry_dir("built"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, [METADATA_FILE, BINARY_NAME]);
+
+        // An interrupted build is invisible to `entries` but still collected
+        let interrupted = manager.staging_path("interrupted").unwrap();
+        fs::write(&interrupted, b"partial").unwrap();
+        let rebuild = manager.staging_path("built").unwrap();
+        fs::write(&rebuild, b"partial").unwrap();
+        assert_eq!(manager.entries().len(), 1);
+
+        // Builds still within the grace period are left alone
+        assert_eq!(manager.remove_orphans(0).unwrap(), 0);
+        assert_eq!(manager.remove_orphans(unix_now() + 1).unwrap(), 14);
+        assert!(!manager.entry_dir("interrupted").exists());
+        assert!(!rebuild.parent().unwrap().exists());
+        assert!(manager.lookup("built").is_some());
+    }
 }
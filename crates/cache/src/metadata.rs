@@ -5,20 +5,30 @@ use std::path::PathBuf;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheMetadata {
     pub key: String,
+    #[serde(default)]
+    pub version: String,
     pub source_path: PathBuf,
     pub cache_path: PathBuf,
     pub created_at: u64,
     pub dependencies: Vec<String>,
+    /// Transitive module files that were hashed into `key`.
+    #[serde(default)]
+    pub imports: Vec<String>,
     pub binary_path: PathBuf,
     pub binary_size: u64,
     pub build_time_ms: u64,
     pub llvm_version: Option<String>,
+    /// Unix timestamp of the most recent cache hit, if any.
+    #[serde(default)]
+    pub last_hit: Option<u64>,
+    #[serde(default)]
+    pub hit_count: u64,
 }
 
 impl CacheMetadata {
     pub fn new(
         key: String,
-        _version: &str,
+        version: &str,
         llvm_version: Option<String>,
         source_path: PathBuf,
         dependencies: Vec<String>,
@@ -26,21 +36,22 @@ impl CacheMetadata {
         binary_size: u64,
         build_time_ms: u64,
         cache_path: PathBuf,
-        _imports: Vec<String>,
+        imports: Vec<String>,
     ) -> Self {
         Self {
             key,
+            version: version.to_string(),
             source_path,
             cache_path,
             binary_path,
-            created_at: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            created_at: unix_now(),
             dependencies,
+            imports,
             binary_size,
             build_time_ms,
             llvm_version,
+            last_hit: None,
+            hit_count: 0,
         }
     }
 
@@ -48,17 +59,26 @@ impl CacheMetadata {
         self.binary_size
     }
 
+    /// Most recent use of the entry: the last hit, or the build time if it was never hit.
+    pub fn last_used(&self) -> u64 {
+        self.last_hit
+            .unwrap_or(self.created_at)
+            .max(self.created_at)
+    }
+
     pub fn is_valid(&self) -> bool {
-        // Check if source file still exists and hasn't been modified
-        if let Ok(metadata) = std::fs::metadata(&self.source_path) {
-            if let Ok(modified) = metadata.modified() {
-                let modified_secs = modified
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                return modified_secs <= self.created_at;
-            }
+        // The key already covers the source and module contents, so an entry is only
+        // stale when its binary went missing or was replaced underneath us.
+        match std::fs::metadata(&self.binary_path) {
+            Ok(metadata) => metadata.is_file() && metadata.len() == self.binary_size,
+            Err(_) => false,
         }
-        false
     }
 }
+
+pub(crate) fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -2,6 +2,11 @@ use std::path::{Path, PathBuf};
 
 /// Cache path utilities
 pub fn cache_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    // Allow CI and tests to relocate the cache without touching the user's home
+    if let Some(dir) = std::env::var_os("OTTER_CACHE_DIR").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
     let mut cache_dir = directories::BaseDirs::new()
         .ok_or("Could not determine cache directory")?
         .cache_dir()
@@ -16,6 +21,11 @@ pub fn ensure_cache_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
     Ok(cache_dir)
 }
 
+/// Directory holding compiled program binaries, next to the `ffi` bridge cache.
+pub fn build_cache_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(cache_root()?.join("builds"))
+}
+
 pub fn cache_key_for_file(path: &Path) -> String {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
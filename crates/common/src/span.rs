@@ -32,6 +32,20 @@ impl Span {
     pub fn contains(&self, pos: usize) -> bool {
         pos >= self.start && pos < self.end
     }
+
+    /// 1-based line and column (in characters) where the span starts in `source`
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        let before = source.get(..self.start).unwrap_or(source);
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+        (line, column)
+    }
+
+    /// The source text the span covers
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        source.get(self.start..self.end).unwrap_or("")
+    }
 }
 
 impl From<Span> for std::ops::Range<usize> {
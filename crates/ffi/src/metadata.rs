@@ -35,7 +35,7 @@ struct FunctionEntry {
     /// Fully-qualified Rust path for the function body (e.g. "reqwest::blocking::get").
     #[serde(default)]
     rust_path: Option<String>,
-    /// Parameter type identifiers (Unit, Bool, I32, I64, F64, Str).
+    /// Parameter type identifiers (Unit, Bool, I32, I64, F64, Str, or a sized numeric type).
     #[serde(default)]
     params: Vec<String>,
     /// Return type identifier.
@@ -275,8 +275,16 @@ fn parse_type(identifier: &str) -> Result<TypeSpec> {
         "f64" | "float64" | "double" => Ok(TypeSpec::F64),
         "str" | "string" => Ok(TypeSpec::Str),
         "opaque" | "handle" => Ok(TypeSpec::Opaque),
+        "i8" => Ok(TypeSpec::I8),
+        "i16" => Ok(TypeSpec::I16),
+        "u8" => Ok(TypeSpec::U8),
+        "u16" => Ok(TypeSpec::U16),
+        "u32" => Ok(TypeSpec::U32),
+        "u64" => Ok(TypeSpec::U64),
+        "usize" => Ok(TypeSpec::Usize),
+        "f32" => Ok(TypeSpec::F32),
         other => Err(anyhow!(
-            "unsupported FFI type identifier `{}` (expected unit, bool, i32, i64, f64, str, or opaque)",
+            "unsupported FFI type identifier `{}` (expected unit, bool, a numeric type such as i32 or u8, str, or opaque)",
             other
         )),
     }
@@ -430,6 +438,14 @@ pub enum RustTypeRef {
     I64,
     F64,
     Str,
+    I8,
+    I16,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+    F32,
     /// Fully-qualified nominal type
     Path {
         path: RustPath,
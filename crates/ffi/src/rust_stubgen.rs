@@ -58,6 +58,14 @@ pub enum TypeSpec {
     F64,
     Str,
     Opaque,
+    I8,
+    I16,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+    F32,
 }
 
 impl TypeSpec {
@@ -70,6 +78,14 @@ impl TypeSpec {
             TypeSpec::F64 => "f64",
             TypeSpec::Str => "*const ::std::os::raw::c_char",
             TypeSpec::Opaque => "i64",
+            TypeSpec::I8 => "i8",
+            TypeSpec::I16 => "i16",
+            TypeSpec::U8 => "u8",
+            TypeSpec::U16 => "u16",
+            TypeSpec::U32 => "u32",
+            TypeSpec::U64 => "u64",
+            TypeSpec::Usize => "usize",
+            TypeSpec::F32 => "f32",
         }
     }
 
@@ -82,6 +98,14 @@ impl TypeSpec {
             TypeSpec::F64 => "0.0",
             TypeSpec::Str => "::std::ptr::null_mut()",
             TypeSpec::Opaque => "0",
+            TypeSpec::I8
+            | TypeSpec::I16
+            | TypeSpec::U8
+            | TypeSpec::U16
+            | TypeSpec::U32
+            | TypeSpec::U64
+            | TypeSpec::Usize => "0",
+            TypeSpec::F32 => "0.0",
         }
     }
 
@@ -94,6 +118,14 @@ impl TypeSpec {
             TypeSpec::F64 => "FfiType::F64",
             TypeSpec::Str => "FfiType::Str",
             TypeSpec::Opaque => "FfiType::Opaque",
+            TypeSpec::I8 => "FfiType::I8",
+            TypeSpec::I16 => "FfiType::I16",
+            TypeSpec::U8 => "FfiType::U8",
+            TypeSpec::U16 => "FfiType::U16",
+            TypeSpec::U32 => "FfiType::U32",
+            TypeSpec::U64 => "FfiType::U64",
+            TypeSpec::Usize => "FfiType::Usize",
+            TypeSpec::F32 => "FfiType::F32",
         }
     }
 }
@@ -344,7 +376,7 @@ impl RustStubGenerator {
         );
 
         source.push_str(
-            "#[repr(u8)]\n#[derive(Clone, Copy, Debug, StableAbi)]\npub enum FfiType {\n    Unit,\n    Bool,\n    I32,\n    I64,\n    F64,\n    Str,\n    Opaque,\n}\n\n",
+            "#[repr(u8)]\n#[derive(Clone, Copy, Debug, StableAbi)]\npub enum FfiType {\n    Unit,\n    Bool,\n    I32,\n    I64,\n    F64,\n    Str,\n    Opaque,\n    List,\n    Map,\n    I8,\n    I16,\n    U8,\n    U16,\n    U32,\n    U64,\n    Usize,\n    F32,\n}\n\n",
         );
         source.push_str(
             "#[repr(C)]\n#[derive(Clone, StableAbi)]\npub struct StableFunction {\n    pub name: RString,\n    pub symbol: RString,\n    pub params: RVec<FfiType>,\n    pub result: FfiType,\n}\n\n",
@@ -698,6 +730,32 @@ impl RustStubGenerator {
                     ));
                     call_args.push(arg_name);
                 }
+                (
+                    TypeSpec::I8
+                    | TypeSpec::I16
+                    | TypeSpec::U8
+                    | TypeSpec::U16
+                    | TypeSpec::U32
+                    | TypeSpec::U64
+                    | TypeSpec::Usize
+                    | TypeSpec::F32,
+                    ArgContext::Json {
+                        indent,
+                        array_name,
+                        func_name,
+                    },
+                ) => {
+                    setup.push(format!(
+                        "{indent}let {arg_name}_value = {array}.get({idx}).ok_or_else(|| format!(\"missing argument {idx} for {func}\"))?;\n{indent}let {arg_name}: {rust} = serde_json::from_value({arg_name}_value.clone()).map_err(|_| format!(\"argument {idx} for {func} must fit in {rust}\"))?;\n",
+                        indent = indent,
+                        arg_name = arg_name,
+                        array = array_name,
+                        idx = idx,
+                        func = func_name,
+                        rust = param.to_rust()
+                    ));
+                    call_args.push(arg_name);
+                }
                 (
                     TypeSpec::Bool,
                     ArgContext::Json {
@@ -771,7 +829,15 @@ impl RustStubGenerator {
             | TypeSpec::I32
             | TypeSpec::I64
             | TypeSpec::F64
-            | TypeSpec::Opaque => {
+            | TypeSpec::Opaque
+            | TypeSpec::I8
+            | TypeSpec::I16
+            | TypeSpec::U8
+            | TypeSpec::U16
+            | TypeSpec::U32
+            | TypeSpec::U64
+            | TypeSpec::Usize
+            | TypeSpec::F32 => {
                 format!("json!({})", ident)
             }
         }
@@ -787,6 +853,14 @@ fn map_rust_type_to_spec(ty: &RustTypeRef) -> Option<TypeSpec> {
         RustTypeRef::I64 => Some(TypeSpec::I64),
         RustTypeRef::F64 => Some(TypeSpec::F64),
         RustTypeRef::Str => Some(TypeSpec::Str),
+        RustTypeRef::I8 => Some(TypeSpec::I8),
+        RustTypeRef::I16 => Some(TypeSpec::I16),
+        RustTypeRef::U8 => Some(TypeSpec::U8),
+        RustTypeRef::U16 => Some(TypeSpec::U16),
+        RustTypeRef::U32 => Some(TypeSpec::U32),
+        RustTypeRef::U64 => Some(TypeSpec::U64),
+        RustTypeRef::Usize => Some(TypeSpec::Usize),
+        RustTypeRef::F32 => Some(TypeSpec::F32),
         RustTypeRef::Option { inner } => map_rust_type_to_spec(inner).or(Some(TypeSpec::Opaque)),
         RustTypeRef::Result { ok, .. } => map_rust_type_to_spec(ok).or(Some(TypeSpec::Opaque)),
         RustTypeRef::Ref { inner, .. } => map_rust_type_to_spec(inner),
@@ -809,5 +883,13 @@ fn rust_value_ty(spec: &TypeSpec) -> &'static str {
         TypeSpec::F64 => "f64",
         TypeSpec::Str => "String",
         TypeSpec::Opaque => "i64",
+        TypeSpec::I8 => "i8",
+        TypeSpec::I16 => "i16",
+        TypeSpec::U8 => "u8",
+        TypeSpec::U16 => "u16",
+        TypeSpec::U32 => "u32",
+        TypeSpec::U64 => "u64",
+        TypeSpec::Usize => "usize",
+        TypeSpec::F32 => "f32",
     }
 }
@@ -431,7 +431,14 @@ fn parse_rust_type(ty_value: &serde_json::Value) -> Option<RustTypeRef> {
             "i32" => return Some(RustTypeRef::I32),
             "i64" => return Some(RustTypeRef::I64),
             "f64" => return Some(RustTypeRef::F64),
-            "f32" => return Some(RustTypeRef::F64),
+            "i8" => return Some(RustTypeRef::I8),
+            "i16" => return Some(RustTypeRef::I16),
+            "u8" => return Some(RustTypeRef::U8),
+            "u16" => return Some(RustTypeRef::U16),
+            "u32" => return Some(RustTypeRef::U32),
+            "u64" => return Some(RustTypeRef::U64),
+            "usize" => return Some(RustTypeRef::Usize),
+            "f32" => return Some(RustTypeRef::F32),
             "&str" | "str" | "String" => return Some(RustTypeRef::Str),
             _ => {}
         }
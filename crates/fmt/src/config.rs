@@ -0,0 +1,149 @@
+//! Project-level formatter settings loaded from `otterfmt.toml`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// File name searched for in each ancestor directory of a formatted file
+pub const CONFIG_FILE_NAME: &str = "otterfmt.toml";
+
+/// Which quote character string literals are printed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    #[default]
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    pub fn quote(self) -> char {
+        match self {
+            QuoteStyle::Double => '"',
+            QuoteStyle::Single => '\'',
+        }
+    }
+}
+
+/// Formatter settings
+///
+/// ```toml
+/// indent_width = 4
+/// max_width = 100
+/// trailing_commas = true
+/// quote_style = "double"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatConfig {
+    /// Spaces per indentation level
+    pub indent_width: usize,
+    /// Preferred maximum line width
+    pub max_width: usize,
+    /// Add a trailing comma after the last element of multi-line collections
+    pub trailing_commas: bool,
+    /// Quote character used for string literals
+    pub quote_style: QuoteStyle,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            max_width: 100,
+            trailing_commas: true,
+            quote_style: QuoteStyle::Double,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+}
+
+impl FormatConfig {
+    /// Parse settings from the contents of an `otterfmt.toml`
+    pub fn from_toml(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Load settings from an `otterfmt.toml` at `path`
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_toml(&contents).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Find the nearest `otterfmt.toml` at or above `path`, which may be a
+    /// file or a directory.
+    pub fn find(path: &Path) -> Option<PathBuf> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let start = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent()?
+        };
+        start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Settings that apply to `path`: the nearest `otterfmt.toml`, or the defaults
+    pub fn discover(path: &Path) -> Result<Self, ConfigError> {
+        match Self::find(path) {
+            Some(config_path) => Self::load(&config_path),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_fall_back_to_defaults() {
+        let config = FormatConfig::from_toml("indent_width = 2\nquote_style = \"single\"\n")
+            .expect("config should parse");
+        assert_eq!(config.indent_width, 2);
+        assert_eq!(config.quote_style, QuoteStyle::Single);
+        assert_eq!(config.max_width, FormatConfig::default().max_width);
+        assert!(config.trailing_commas);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(FormatConfig::from_toml("tab_width = 2\n").is_err());
+    }
+
+    #[test]
+    fn discovery_walks_up_from_the_file() {
+        let root = tempfile::tempdir().expect("temp dir");
+        let nested = root.path().join("src").join("util");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.path().join(CONFIG_FILE_NAME), "max_width = 80\n").unwrap();
+        let file = nested.join("strings.ot");
+        fs::write(&file, "pass\n").unwrap();
+
+        let config = FormatConfig::discover(&file).expect("config should load");
+        assert_eq!(config.max_width, 80);
+    }
+}
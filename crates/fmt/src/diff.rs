@@ -0,0 +1,276 @@
+//! Unified diffs between a file and its formatted form, for `otter fmt --diff`.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One step of the edit script, with the line index in each file at that point
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    edit: Edit,
+    old: usize,
+    new: usize,
+}
+
+/// Render a unified diff from `original` to `formatted`, or an empty string
+/// if they are identical.
+pub fn unified_diff(path: &str, original: &str, formatted: &str) -> String {
+    let old: Vec<&str> = original.split_inclusive('\n').collect();
+    let new: Vec<&str> = formatted.split_inclusive('\n').collect();
+    let steps = edit_script(&old, &new);
+
+    let mut output = String::new();
+    let mut hunks = hunk_ranges(&steps).peekable();
+    if hunks.peek().is_none() {
+        return output;
+    }
+
+    let _ = writeln!(output, "--- {path}");
+    let _ = writeln!(output, "+++ {path} (formatted)");
+    for (start, end) in hunks {
+        let hunk = &steps[start..end];
+        let old_count = hunk.iter().filter(|s| s.edit != Edit::Insert).count();
+        let new_count = hunk.iter().filter(|s| s.edit != Edit::Delete).count();
+        let _ = writeln!(
+            output,
+            "@@ -{} +{} @@",
+            range_header(hunk[0].old, old_count),
+            range_header(hunk[0].new, new_count)
+        );
+        for step in hunk {
+            let (marker, line) = match step.edit {
+                Edit::Equal => (' ', old[step.old]),
+                Edit::Delete => ('-', old[step.old]),
+                Edit::Insert => ('+', new[step.new]),
+            };
+            output.push(marker);
+            output.push_str(line.strip_suffix('\n').unwrap_or(line));
+            output.push('\n');
+            if !line.ends_with('\n') {
+                output.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    output
+}
+
+/// A run of lines in the original text replaced by new text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    /// Byte range of the replaced lines in the original text
+    pub original: Range<usize>,
+    /// Zero-based index of the first replaced line
+    pub first_line: usize,
+    /// Number of original lines replaced (zero for a pure insertion)
+    pub line_count: usize,
+    /// Lines replacing them, including their line breaks
+    pub replacement: String,
+}
+
+/// The line-level changes turning `original` into `formatted`, so editors can
+/// apply them without replacing the whole file.
+pub fn line_changes(original: &str, formatted: &str) -> Vec<LineChange> {
+    let old: Vec<&str> = original.split_inclusive('\n').collect();
+    let new: Vec<&str> = formatted.split_inclusive('\n').collect();
+    let steps = edit_script(&old, &new);
+
+    let mut line_offsets = Vec::with_capacity(old.len() + 1);
+    let mut offset = 0;
+    for line in &old {
+        line_offsets.push(offset);
+        offset += line.len();
+    }
+    line_offsets.push(offset);
+
+    let mut changes = Vec::new();
+    let mut index = 0;
+    while index < steps.len() {
+        if steps[index].edit == Edit::Equal {
+            index += 1;
+            continue;
+        }
+        let first = steps[index];
+        let mut replacement = String::new();
+        let mut line_count = 0;
+        while let Some(step) = steps.get(index).filter(|step| step.edit != Edit::Equal) {
+            match step.edit {
+                Edit::Delete => line_count += 1,
+                Edit::Insert => replacement.push_str(new[step.new]),
+                Edit::Equal => unreachable!(),
+            }
+            index += 1;
+        }
+        changes.push(LineChange {
+            original: line_offsets[first.old]..line_offsets[first.old + line_count],
+            first_line: first.old,
+            line_count,
+            replacement,
+        });
+    }
+    changes
+}
+
+fn range_header(start: usize, count: usize) -> String {
+    // An empty range names the line before it, per the unified format
+    let line = if count == 0 { start } else { start + 1 };
+    if count == 1 {
+        line.to_string()
+    } else {
+        format!("{line},{count}")
+    }
+}
+
+/// Group changed steps into hunks, merging changes whose context overlaps
+fn hunk_ranges(steps: &[Step]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut changes = steps
+        .iter()
+        .enumerate()
+        .filter(|(_, step)| step.edit != Edit::Equal)
+        .map(|(index, _)| index)
+        .peekable();
+
+    std::iter::from_fn(move || {
+        let first = changes.next()?;
+        let mut last = first;
+        while let Some(&next) = changes.peek() {
+            if next - last > 2 * CONTEXT_LINES {
+                break;
+            }
+            last = next;
+            changes.next();
+        }
+        Some((
+            first.saturating_sub(CONTEXT_LINES),
+            (last + CONTEXT_LINES + 1).min(steps.len()),
+        ))
+    })
+}
+
+/// Longest-common-subsequence edit script over lines. Formatting changes are
+/// usually local, so the shared prefix and suffix are matched up front.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Step> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j] = length of the LCS of old_mid[i..] and new_mid[j..]
+    let width = new_mid.len() + 1;
+    let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::with_capacity(old.len().max(new.len()));
+    let equal = |old, new| Step {
+        edit: Edit::Equal,
+        old,
+        new,
+    };
+    steps.extend((0..prefix).map(|i| equal(i, i)));
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        let step = if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            Edit::Equal
+        } else if i < old_mid.len()
+            && (j == new_mid.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            Edit::Delete
+        } else {
+            Edit::Insert
+        };
+        steps.push(Step {
+            edit: step,
+            old: prefix + i,
+            new: prefix + j,
+        });
+        match step {
+            Edit::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+
+    let old_tail = old.len() - suffix;
+    let new_tail = new.len() - suffix;
+    steps.extend((0..suffix).map(|k| equal(old_tail + k, new_tail + k)));
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_inputs_produce_no_diff() {
+        assert_eq!(unified_diff("a.ot", "pass\n", "pass\n"), "");
+    }
+
+    #[test]
+    fn changed_line_is_reported_with_context() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let formatted = "a\nb\nc\nd\nE\nf\ng\nh\n";
+        let diff = unified_diff("x.ot", original, formatted);
+        assert_eq!(
+            diff,
+            "--- x.ot\n+++ x.ot (formatted)\n@@ -2,7 +2,7 @@\n b\n c\n d\n-e\n+E\n f\n g\n h\n"
+        );
+    }
+
+    #[test]
+    fn line_changes_cover_only_the_changed_lines() {
+        let original = "a\nb\nc\nd\n";
+        let formatted = "a\nB\nc\nd\ne\n";
+        assert_eq!(
+            line_changes(original, formatted),
+            vec![
+                LineChange {
+                    original: 2..4,
+                    first_line: 1,
+                    line_count: 1,
+                    replacement: "B\n".to_string(),
+                },
+                LineChange {
+                    original: 8..8,
+                    first_line: 4,
+                    line_count: 0,
+                    replacement: "e\n".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_final_newline_is_marked() {
+        let diff = unified_diff("x.ot", "pass", "pass\n");
+        assert_eq!(
+            diff,
+            "--- x.ot\n+++ x.ot (formatted)\n@@ -1 +1 @@\n-pass\n\\ No newline at end of file\n+pass\n"
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Wadler-style document layout.
+//!
+//! Expressions are described as a [`Doc`] tree of text, optional line breaks
+//! and groups. When rendering, each group is printed on one line if it fits
+//! in the remaining width; otherwise its line breaks become newlines and its
+//! nested content is indented.
+
+#[derive(Debug, Clone)]
+pub(crate) enum Doc {
+    Text(String),
+    /// A space when the enclosing group is flat, a newline when it breaks
+    Line,
+    /// Nothing when the enclosing group is flat, a newline when it breaks
+    SoftLine,
+    /// Text that only appears when the enclosing group breaks
+    IfBreak(String),
+    Concat(Vec<Doc>),
+    /// Indent any line breaks inside by the given number of columns
+    Nest(usize, Box<Doc>),
+    /// Lay out the content flat if it fits, broken otherwise
+    Group(Box<Doc>),
+}
+
+impl Doc {
+    pub(crate) fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    pub(crate) fn concat(docs: impl IntoIterator<Item = Doc>) -> Doc {
+        Doc::Concat(docs.into_iter().collect())
+    }
+
+    pub(crate) fn nest(self, indent: usize) -> Doc {
+        Doc::Nest(indent, Box::new(self))
+    }
+
+    pub(crate) fn group(self) -> Doc {
+        Doc::Group(Box::new(self))
+    }
+
+    /// Join `docs` with `separator` in between
+    pub(crate) fn join(docs: impl IntoIterator<Item = Doc>, separator: Doc) -> Doc {
+        let mut parts = Vec::new();
+        for (index, doc) in docs.into_iter().enumerate() {
+            if index > 0 {
+                parts.push(separator.clone());
+            }
+            parts.push(doc);
+        }
+        Doc::Concat(parts)
+    }
+
+    /// `open`, then `items` separated by commas and indented on their own
+    /// lines when the group breaks, then `close`.
+    pub(crate) fn bracketed(
+        open: &str,
+        items: Vec<Doc>,
+        close: &str,
+        indent: usize,
+        trailing_comma: bool,
+    ) -> Doc {
+        if items.is_empty() {
+            return Doc::text(format!("{open}{close}"));
+        }
+        let trailing = if trailing_comma {
+            Doc::IfBreak(",".to_string())
+        } else {
+            Doc::text("")
+        };
+        Doc::concat([
+            Doc::text(open),
+            Doc::concat([
+                Doc::SoftLine,
+                Doc::join(items, Doc::concat([Doc::text(","), Doc::Line])),
+                trailing,
+            ])
+            .nest(indent),
+            Doc::SoftLine,
+            Doc::text(close),
+        ])
+        .group()
+    }
+
+    /// Lay the document out in `width` columns, starting at `column` with
+    /// continuation lines indented by `indent`.
+    pub(crate) fn render(&self, width: usize, column: usize, indent: usize) -> String {
+        let mut output = String::new();
+        let mut column = column;
+        let mut stack = vec![(indent, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    output.push_str(text);
+                    column = match text.rfind('\n') {
+                        Some(newline) => text[newline + 1..].chars().count(),
+                        None => column + text.chars().count(),
+                    };
+                }
+                Doc::Line | Doc::SoftLine if mode == Mode::Break => {
+                    output.push('\n');
+                    output.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Doc::Line => {
+                    output.push(' ');
+                    column += 1;
+                }
+                Doc::SoftLine => {}
+                Doc::IfBreak(text) => {
+                    if mode == Mode::Break {
+                        output.push_str(text);
+                        column += text.chars().count();
+                    }
+                }
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+                }
+                Doc::Nest(extra, doc) => stack.push((indent + extra, mode, doc)),
+                Doc::Group(doc) => {
+                    let remaining = width as isize - column as isize;
+                    let mode = if mode == Mode::Flat || fits(remaining, doc, &stack) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    stack.push((indent, mode, doc));
+                }
+            }
+        }
+
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Whether `doc` laid out flat, followed by the rest of the current line,
+/// fits in `remaining` columns.
+fn fits(mut remaining: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut pending = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        if remaining < 0 {
+            return false;
+        }
+        let (mode, doc) = match pending.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => match text.find('\n') {
+                // Only the first line of multi-line text shares this line
+                Some(newline) => return remaining >= text[..newline].chars().count() as isize,
+                None => remaining -= text.chars().count() as isize,
+            },
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+            Doc::Line => remaining -= 1,
+            Doc::SoftLine => {}
+            Doc::IfBreak(text) => {
+                if mode == Mode::Break {
+                    remaining -= text.chars().count() as isize;
+                }
+            }
+            Doc::Concat(docs) => pending.extend(docs.iter().rev().map(|doc| (mode, doc))),
+            Doc::Nest(_, doc) | Doc::Group(doc) => pending.push((mode, doc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[&str]) -> Doc {
+        Doc::concat([
+            Doc::text(name),
+            Doc::bracketed(
+                "(",
+                args.iter().map(|a| Doc::text(*a)).collect(),
+                ")",
+                4,
+                true,
+            ),
+        ])
+    }
+
+    #[test]
+    fn group_stays_flat_when_it_fits() {
+        assert_eq!(call("f", &["a", "b"]).render(20, 0, 0), "f(a, b)");
+    }
+
+    #[test]
+    fn group_breaks_with_trailing_comma_when_too_wide() {
+        let doc = call("compute", &["first_argument", "second_argument"]);
+        assert_eq!(
+            doc.render(30, 0, 0),
+            "compute(\n    first_argument,\n    second_argument,\n)"
+        );
+    }
+
+    #[test]
+    fn outer_group_breaks_before_inner_group() {
+        let inner = call("g", &["x", "y"]);
+        let doc = Doc::concat([
+            Doc::text("f"),
+            Doc::bracketed("(", vec![inner, Doc::text("long_name_here")], ")", 4, false),
+        ]);
+        assert_eq!(
+            doc.render(20, 0, 0),
+            "f(\n    g(x, y),\n    long_name_here\n)"
+        );
+    }
+}
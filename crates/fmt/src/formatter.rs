@@ -1,17 +1,69 @@
 use ast::nodes::{Block, Expr, Function, Program, Statement};
+use lexer::LexerError;
+use parser::ParserError;
+use thiserror::Error;
+
+use crate::config::FormatConfig;
+use crate::doc::Doc;
+use crate::trivia;
+
+/// Errors that stop a source file from being formatted
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("failed to tokenize source")]
+    Lex(Vec<LexerError>),
+    #[error("failed to parse source")]
+    Parse(Vec<ParserError>),
+    #[error("formatter produced output that could not be tokenized")]
+    InvalidOutput(Vec<LexerError>),
+    #[error(
+        "formatter produced {formatted_lines} lines of code for {source_lines}, so comments cannot be placed"
+    )]
+    LineMismatch {
+        source_lines: usize,
+        formatted_lines: usize,
+    },
+}
 
 /// Formats OtterLang code
 pub struct Formatter {
-    indent_size: usize,
+    config: FormatConfig,
 }
 
 impl Formatter {
     pub fn new() -> Self {
-        Self { indent_size: 4 }
+        Self::with_config(FormatConfig::default())
     }
 
     pub fn with_indent_size(indent_size: usize) -> Self {
-        Self { indent_size }
+        Self::with_config(FormatConfig {
+            indent_width: indent_size,
+            ..FormatConfig::default()
+        })
+    }
+
+    pub fn with_config(config: FormatConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &FormatConfig {
+        &self.config
+    }
+
+    /// Format source text, keeping its comments and blank lines
+    pub fn format_source(&self, source: &str) -> Result<String, FormatError> {
+        let (tokens, trivia) = lexer::tokenize_with_trivia(source).map_err(FormatError::Lex)?;
+        let program = parser::parse(&tokens).map_err(FormatError::Parse)?;
+
+        let formatted = self.format_program(&program);
+        let formatted_tokens = lexer::tokenize(&formatted).map_err(FormatError::InvalidOutput)?;
+
+        trivia::reattach(source, &tokens, &trivia, &formatted, &formatted_tokens)
+    }
+
+    /// Format a single expression on one line
+    pub fn format_expression(&self, expr: &Expr) -> String {
+        self.format_expr(expr, 0)
     }
 
     /// Format a program
@@ -29,24 +81,38 @@ impl Formatter {
     fn format_statement(&self, stmt: &Statement, indent: usize) -> String {
         match stmt {
             Statement::Let {
-                name, expr, public, ..
+                name,
+                ty,
+                expr,
+                public,
+                ..
             } => {
                 let pub_str = if *public { "pub " } else { "" };
-                format!(
-                    "{}{}let {} = {}\n",
-                    self.indent(indent),
-                    pub_str,
-                    name,
-                    self.format_expr(expr, indent)
-                )
+                let ty_str = ty
+                    .as_ref()
+                    .map(|ty| format!(": {}", self.format_type(ty)))
+                    .unwrap_or_default();
+                let prefix = format!("{}let {}{} = ", pub_str, name, ty_str);
+                format!("{}\n", self.layout(indent, &prefix, expr, ""))
             }
-            Statement::Assignment { name, expr, .. } => {
-                format!(
-                    "{}{} = {}\n",
-                    self.indent(indent),
-                    name,
-                    self.format_expr(expr, indent)
-                )
+            Statement::Const {
+                name,
+                ty,
+                expr,
+                public,
+                ..
+            } => {
+                let pub_str = if *public { "pub " } else { "" };
+                let prefix = format!("{}const {}: {} = ", pub_str, name, self.format_type(ty));
+                format!("{}\n", self.layout(indent, &prefix, expr, ""))
+            }
+            Statement::Destructure { pattern, expr, .. } => {
+                let prefix = format!("let {} = ", self.format_pattern(pattern));
+                format!("{}\n", self.layout(indent, &prefix, expr, ""))
+            }
+            Statement::Assignment { target, expr, .. } => {
+                let prefix = format!("{} = ", self.format_expr(target, indent));
+                format!("{}\n", self.layout(indent, &prefix, expr, ""))
             }
             Statement::Function(f) => self.format_function(f, indent),
             Statement::If {
@@ -61,43 +127,40 @@ impl Formatter {
                 body,
                 ..
             } => {
+                // Tuple loop variables are written bare: `for k, v in ...`
+                let var = match var {
+                    ast::nodes::Pattern::Tuple(items) if items.len() > 1 => items
+                        .iter()
+                        .map(|p| self.format_pattern(p))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    other => self.format_pattern(other),
+                };
                 format!(
-                    "{}for {} in {}:\n{}",
-                    self.indent(indent),
-                    var,
-                    self.format_expr(iterable, indent),
+                    "{}\n{}",
+                    self.layout(indent, &format!("for {} in ", var), iterable, ":"),
                     self.format_block(body, indent + 1)
                 )
             }
             Statement::While { cond, body } => {
                 format!(
-                    "{}while {}:\n{}",
-                    self.indent(indent),
-                    self.format_expr(cond, indent),
+                    "{}\n{}",
+                    self.layout(indent, "while ", cond, ":"),
                     self.format_block(body, indent + 1)
                 )
             }
             Statement::Return(expr) => {
                 if let Some(expr) = expr {
-                    format!(
-                        "{}return {}\n",
-                        self.indent(indent),
-                        self.format_expr(expr, indent)
-                    )
+                    format!("{}\n", self.layout(indent, "return ", expr, ""))
                 } else {
                     format!("{}return\n", self.indent(indent))
                 }
             }
+            Statement::Yield(expr) => format!("{}\n", self.layout(indent, "yield ", expr, "")),
             Statement::Break => format!("{}break\n", self.indent(indent)),
             Statement::Continue => format!("{}continue\n", self.indent(indent)),
             Statement::Pass => format!("{}pass\n", self.indent(indent)),
-            Statement::Expr(expr) => {
-                format!(
-                    "{}{}\n",
-                    self.indent(indent),
-                    self.format_expr(expr, indent)
-                )
-            }
+            Statement::Expr(expr) => format!("{}\n", self.layout(indent, "", expr, "")),
             Statement::Struct {
                 name,
                 fields,
@@ -191,6 +254,34 @@ impl Formatter {
                     self.format_type(target)
                 )
             }
+            Statement::Trait {
+                name,
+                methods,
+                public,
+            } => {
+                let pub_str = if *public { "pub " } else { "" };
+                let mut result = format!("{}{}trait {}:\n", self.indent(indent), pub_str, name);
+                for method in methods {
+                    result.push_str(&self.format_function(method, indent + 1));
+                }
+                result
+            }
+            Statement::Impl {
+                trait_name,
+                type_name,
+                methods,
+            } => {
+                let mut result = format!(
+                    "{}impl {} for {}:\n",
+                    self.indent(indent),
+                    trait_name,
+                    type_name
+                );
+                for method in methods {
+                    result.push_str(&self.format_function(method, indent + 1));
+                }
+                result
+            }
             Statement::Use { imports } => {
                 let modules: Vec<String> = imports
                     .iter()
@@ -255,18 +346,35 @@ impl Formatter {
 
                 output
             }
-            Statement::Raise(expr) => {
-                let expr_str = match expr {
-                    Some(e) => format!(" {}", self.format_expr(e, indent)),
-                    None => String::new(),
+            Statement::Raise(expr) => match expr {
+                Some(e) => format!("{}\n", self.layout(indent, "raise ", e, "")),
+                None => format!("{}raise\n", self.indent(indent)),
+            },
+            Statement::With {
+                manager,
+                alias,
+                body,
+            } => {
+                let suffix = match alias {
+                    Some(alias) => format!(" as {}:", alias),
+                    None => ":".to_string(),
                 };
-                format!("{}raise{}\n", self.indent(indent), expr_str)
+                format!(
+                    "{}\n{}",
+                    self.layout(indent, "with ", manager, &suffix),
+                    self.format_block(body, indent + 1)
+                )
             }
         }
     }
 
     fn format_function(&self, f: &Function, indent: usize) -> String {
-        let pub_str = if f.public { "pub " } else { "" };
+        let pub_str = match (f.public, f.is_const) {
+            (true, true) => "pub const ",
+            (true, false) => "pub ",
+            (false, true) => "const ",
+            (false, false) => "",
+        };
         let params_str = f
             .params
             .iter()
@@ -289,15 +397,56 @@ impl Formatter {
         } else {
             String::new()
         };
-        format!(
-            "{}{}def {}({}){}:\n{}",
+        let gen_str = if f.generics.is_empty() {
+            String::new()
+        } else {
+            let params = f
+                .generics
+                .iter()
+                .map(|param| {
+                    if param.bounds.is_empty() {
+                        param.name.clone()
+                    } else {
+                        format!("{}: {}", param.name, param.bounds.join(" + "))
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("<{}>", params)
+        };
+        let annotations: String = f
+            .annotations
+            .iter()
+            .map(|annotation| {
+                let args = if annotation.args.is_empty() {
+                    String::new()
+                } else {
+                    let args = annotation
+                        .args
+                        .iter()
+                        .map(|arg| self.format_expr(arg, indent))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("({})", args)
+                };
+                format!("{}@{}{}\n", self.indent(indent), annotation.name, args)
+            })
+            .collect();
+        let header = format!(
+            "{}{}{}def {}{}({}){}",
+            annotations,
             self.indent(indent),
             pub_str,
             f.name,
+            gen_str,
             params_str,
-            ret_str,
-            self.format_block(&f.body, indent + 1)
-        )
+            ret_str
+        );
+        if f.is_signature() {
+            // Trait method without a default body
+            return format!("{}\n", header);
+        }
+        format!("{}:\n{}", header, self.format_block(&f.body, indent + 1))
     }
 
     fn format_block(&self, block: &Block, indent: usize) -> String {
@@ -317,16 +466,14 @@ impl Formatter {
         indent: usize,
     ) -> String {
         let mut result = format!(
-            "{}if {}:\n{}",
-            self.indent(indent),
-            self.format_expr(cond, indent),
+            "{}\n{}",
+            self.layout(indent, "if ", cond, ":"),
             self.format_block(then_block, indent + 1)
         );
         for (elif_cond, elif_block) in elif_blocks {
             result.push_str(&format!(
-                "{}elif {}:\n{}",
-                self.indent(indent),
-                self.format_expr(elif_cond, indent),
+                "{}\n{}",
+                self.layout(indent, "elif ", elif_cond, ":"),
                 self.format_block(elif_block, indent + 1)
             ));
         }
@@ -340,140 +487,168 @@ impl Formatter {
         result
     }
 
+    /// Print `prefix`, `expr` and `suffix` on a line at `indent`, breaking the
+    /// expression over several lines if it does not fit in the maximum width.
+    fn layout(&self, indent: usize, prefix: &str, expr: &Expr, suffix: &str) -> String {
+        let margin = self.indent(indent);
+        let doc = Doc::concat([self.expr_doc(expr, indent), Doc::text(suffix)]);
+        let body = doc.render(
+            self.config.max_width,
+            margin.len() + prefix.chars().count(),
+            margin.len(),
+        );
+        format!("{margin}{prefix}{body}")
+    }
+
+    /// Print an expression on a single line
     fn format_expr(&self, expr: &Expr, indent: usize) -> String {
+        self.expr_doc(expr, indent).render(usize::MAX / 2, 0, 0)
+    }
+
+    fn expr_doc(&self, expr: &Expr, indent: usize) -> Doc {
         match expr {
-            Expr::Literal(lit) => self.format_literal(lit),
-            Expr::Identifier { name, .. } => name.clone(),
+            Expr::Literal(lit) => Doc::text(self.format_literal(lit)),
+            Expr::Identifier { name, .. } => Doc::text(name),
             Expr::Binary { op, left, right } => {
-                format!(
-                    "{} {} {}",
-                    self.format_expr(left, indent),
-                    self.format_binary_op(op),
-                    self.format_expr(right, indent)
-                )
-            }
-            Expr::Unary { op, expr } => {
-                format!(
-                    "{}{}",
-                    self.format_unary_op(op),
-                    self.format_expr(expr, indent)
-                )
-            }
-            Expr::Call { func, args } => {
-                let args_str = args
-                    .iter()
-                    .map(|arg| self.format_expr(arg, indent))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                format!("{}({})", self.format_expr(func, indent), args_str)
+                // Operators are left-associative, so an equal-precedence right
+                // operand keeps its parentheses; `**` groups to the right and
+                // takes a unary right operand
+                let precedence = binary_precedence(op);
+                let (left_min, right_min) = match op {
+                    ast::nodes::BinaryOp::Pow => (precedence + 1, PRECEDENCE_UNARY),
+                    _ => (precedence, precedence + 1),
+                };
+                Doc::concat([
+                    self.operand_doc(left, left_min, indent),
+                    Doc::text(format!(" {} ", self.format_binary_op(op))),
+                    self.operand_doc(right, right_min, indent),
+                ])
             }
-            Expr::Member { object, field } => {
-                format!("{}.{}", self.format_expr(object, indent), field)
+            Expr::Unary { op, expr } => Doc::concat([
+                Doc::text(self.format_unary_op(op)),
+                self.operand_doc(expr, PRECEDENCE_POWER, indent),
+            ]),
+            Expr::Cast { expr, ty } => Doc::concat([
+                self.operand_doc(expr, PRECEDENCE_CAST, indent),
+                Doc::text(format!(" as {}", self.format_type(ty))),
+            ]),
+            Expr::Call { .. } | Expr::Member { .. } | Expr::Index { .. } | Expr::Slice { .. } => {
+                self.chain_doc(expr, indent)
             }
             Expr::If {
                 cond,
                 then_branch,
                 else_branch,
             } => {
-                let else_str = if let Some(else_expr) = else_branch {
-                    format!(" else {}", self.format_expr(else_expr, indent))
-                } else {
-                    String::new()
-                };
-                format!(
-                    "{} if {} else {}",
-                    self.format_expr(then_branch, indent),
-                    self.format_expr(cond, indent),
-                    else_str
-                )
-            }
-            Expr::Range { start, end } => {
-                format!(
-                    "{}..{}",
-                    self.format_expr(start, indent),
-                    self.format_expr(end, indent)
-                )
-            }
-            Expr::Array(elements) => {
-                let elements_str = elements
-                    .iter()
-                    .map(|e| self.format_expr(e, indent))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                format!("[{}]", elements_str)
+                let mut parts = vec![
+                    self.expr_doc(then_branch, indent),
+                    Doc::text(" if "),
+                    self.expr_doc(cond, indent),
+                ];
+                if let Some(else_expr) = else_branch {
+                    parts.push(Doc::text(" else "));
+                    parts.push(self.expr_doc(else_expr, indent));
+                }
+                Doc::concat(parts)
             }
-            Expr::Dict(pairs) => {
-                let pairs_str = pairs
+            Expr::Range { start, end } => Doc::concat([
+                self.operand_doc(start, PRECEDENCE_RANGE + 1, indent),
+                Doc::text(".."),
+                self.operand_doc(end, PRECEDENCE_RANGE + 1, indent),
+            ]),
+            Expr::Array(elements) => self.collection_doc(
+                "[",
+                elements.iter().map(|e| self.expr_doc(e, indent)).collect(),
+                "]",
+            ),
+            Expr::Tuple(elements) => match elements.as_slice() {
+                [single] => Doc::concat([
+                    Doc::text("("),
+                    self.expr_doc(single, indent),
+                    Doc::text(",)"),
+                ]),
+                _ => self.collection_doc(
+                    "(",
+                    elements.iter().map(|e| self.expr_doc(e, indent)).collect(),
+                    ")",
+                ),
+            },
+            Expr::Dict(pairs) => self.collection_doc(
+                "{",
+                pairs
                     .iter()
                     .map(|(k, v)| {
-                        format!(
-                            "{}: {}",
-                            self.format_expr(k, indent),
-                            self.format_expr(v, indent)
-                        )
+                        Doc::concat([
+                            self.expr_doc(k, indent),
+                            Doc::text(": "),
+                            self.expr_doc(v, indent),
+                        ])
                     })
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                format!("{{{}}}", pairs_str)
-            }
+                    .collect(),
+                "}",
+            ),
             Expr::ListComprehension {
                 element,
                 var,
                 iterable,
                 condition,
-            } => {
-                let cond_str = condition
-                    .as_ref()
-                    .map(|cond| format!(" if {}", self.format_expr(cond, indent)))
-                    .unwrap_or_default();
-                format!(
-                    "[{} for {} in {}{}]",
-                    self.format_expr(element, indent),
-                    var,
-                    self.format_expr(iterable, indent),
-                    cond_str
-                )
-            }
+            } => self.comprehension_doc(
+                "[",
+                self.expr_doc(element, indent),
+                var,
+                iterable,
+                condition.as_deref(),
+                "]",
+                indent,
+            ),
             Expr::DictComprehension {
                 key,
                 value,
                 var,
                 iterable,
                 condition,
-            } => {
-                let cond_str = condition
-                    .as_ref()
-                    .map(|cond| format!(" if {}", self.format_expr(cond, indent)))
-                    .unwrap_or_default();
-                format!(
-                    "{{{}: {} for {} in {}{}}}",
-                    self.format_expr(key, indent),
-                    self.format_expr(value, indent),
-                    var,
-                    self.format_expr(iterable, indent),
-                    cond_str
-                )
-            }
+            } => self.comprehension_doc(
+                "{",
+                Doc::concat([
+                    self.expr_doc(key, indent),
+                    Doc::text(": "),
+                    self.expr_doc(value, indent),
+                ]),
+                var,
+                iterable,
+                condition.as_deref(),
+                "}",
+                indent,
+            ),
             Expr::Match { value, arms } => {
-                let mut result = format!("match {}:\n", self.format_expr(value, indent));
-                for arm in arms {
-                    result.push_str(&format!(
-                        "{}    case {} => {}\n",
-                        self.indent(indent),
-                        self.format_pattern(&arm.pattern),
-                        self.format_expr(&arm.body, indent)
-                    ));
-                }
-                result
+                // The enclosing statement supplies the final newline
+                let arms_str = arms
+                    .iter()
+                    .map(|arm| {
+                        format!(
+                            "{}case {}:\n{}",
+                            self.indent(indent + 1),
+                            self.format_pattern(&arm.pattern),
+                            self.layout(indent + 2, "", &arm.body, "")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                Doc::concat([
+                    Doc::text("match "),
+                    self.expr_doc(value, indent),
+                    Doc::text(format!(":\n{}", arms_str)),
+                ])
             }
             Expr::Struct { name, fields } => {
                 // Pythonic style: Point(x=1.0, y=2.0)
-                let fields_str = fields
+                let fields = fields
                     .iter()
-                    .map(|(fname, val)| format!("{}={}", fname, self.format_expr(val, indent)))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                format!("{}({})", name, fields_str)
+                    .map(|(fname, val)| {
+                        Doc::concat([Doc::text(format!("{}=", fname)), self.expr_doc(val, indent)])
+                    })
+                    .collect();
+                Doc::concat([Doc::text(name), self.collection_doc("(", fields, ")")])
             }
             Expr::Lambda {
                 params,
@@ -506,31 +681,177 @@ impl Formatter {
                 } else {
                     String::new()
                 };
-                format!(
-                    "lambda {}{}:\n{}",
-                    params_display,
-                    ret_str,
-                    self.format_block(body, indent + 1)
-                )
+                match body.statements.as_slice() {
+                    [Statement::Expr(expr)] => Doc::concat([
+                        Doc::text(format!("lambda {}{}: ", params_display, ret_str)),
+                        self.expr_doc(expr, indent),
+                    ]),
+                    _ => Doc::text(format!(
+                        "lambda {}{}:\n{}",
+                        params_display,
+                        ret_str,
+                        self.format_block(body, indent + 1)
+                    )),
+                }
             }
-            Expr::Await(expr) => format!("await {}", self.format_expr(expr, indent)),
-            Expr::Spawn(expr) => format!("spawn {}", self.format_expr(expr, indent)),
+            Expr::Await(expr) => Doc::concat([
+                Doc::text("await "),
+                self.operand_doc(expr, PRECEDENCE_POSTFIX, indent),
+            ]),
+            Expr::Spawn(expr) => Doc::concat([
+                Doc::text("spawn "),
+                self.operand_doc(expr, PRECEDENCE_POSTFIX, indent),
+            ]),
             Expr::FString { parts } => {
+                // Interpolations cannot span lines, so they are always printed flat
                 let parts_str = parts
                     .iter()
                     .map(|part| match part {
-                        ast::nodes::FStringPart::Text(s) => s.clone(),
+                        ast::nodes::FStringPart::Text(s) => self.escape_string(s),
                         ast::nodes::FStringPart::Expr(e) => {
                             format!("{{{}}}", self.format_expr(e, indent))
                         }
                     })
                     .collect::<Vec<_>>()
                     .join("");
-                format!("f\"{}\"", parts_str)
+                let quote = self.config.quote_style.quote();
+                Doc::text(format!("f{quote}{parts_str}{quote}"))
             }
         }
     }
 
+    /// `expr`, parenthesized if it binds more loosely than `min_precedence`
+    fn operand_doc(&self, expr: &Expr, min_precedence: u8, indent: usize) -> Doc {
+        let doc = self.expr_doc(expr, indent);
+        if expr_precedence(expr) < min_precedence {
+            Doc::concat([Doc::text("("), doc, Doc::text(")")])
+        } else {
+            doc
+        }
+    }
+
+    /// Comma-separated items that move onto their own lines when too long
+    fn collection_doc(&self, open: &str, items: Vec<Doc>, close: &str) -> Doc {
+        Doc::bracketed(
+            open,
+            items,
+            close,
+            self.config.indent_width,
+            self.config.trailing_commas,
+        )
+    }
+
+    /// `[element for var in iterable if condition]`, with each clause on its
+    /// own line when too long
+    #[allow(clippy::too_many_arguments)]
+    fn comprehension_doc(
+        &self,
+        open: &str,
+        element: Doc,
+        var: &str,
+        iterable: &Expr,
+        condition: Option<&Expr>,
+        close: &str,
+        indent: usize,
+    ) -> Doc {
+        let mut clauses = vec![
+            Doc::SoftLine,
+            element,
+            Doc::Line,
+            Doc::text(format!("for {} in ", var)),
+            self.expr_doc(iterable, indent),
+        ];
+        if let Some(condition) = condition {
+            clauses.push(Doc::Line);
+            clauses.push(Doc::text("if "));
+            clauses.push(self.expr_doc(condition, indent));
+        }
+        Doc::concat([
+            Doc::text(open),
+            Doc::concat(clauses).nest(self.config.indent_width),
+            Doc::SoftLine,
+            Doc::text(close),
+        ])
+        .group()
+    }
+
+    /// Calls, member accesses and subscripts. A chain with two or more method
+    /// calls puts each `.method(...)` on its own line inside parentheses when
+    /// too long.
+    fn chain_doc(&self, expr: &Expr, indent: usize) -> Doc {
+        let mut links = Vec::new();
+        let mut base = expr;
+        let mut method_calls = 0;
+        loop {
+            match base {
+                Expr::Call { func, args, .. } => {
+                    let args = args.iter().map(|arg| self.expr_doc(arg, indent)).collect();
+                    let args = self.collection_doc("(", args, ")");
+                    if let Expr::Member { object, field } = func.as_ref() {
+                        method_calls += 1;
+                        links.push(Doc::concat([Doc::text(format!(".{}", field)), args]));
+                        base = object;
+                    } else {
+                        links.push(args);
+                        base = func;
+                    }
+                }
+                Expr::Member { object, field } => {
+                    links.push(Doc::text(format!(".{}", field)));
+                    base = object;
+                }
+                Expr::Index { object, index, .. } => {
+                    links.push(Doc::concat([
+                        Doc::text("["),
+                        self.expr_doc(index, indent),
+                        Doc::text("]"),
+                    ]));
+                    base = object;
+                }
+                Expr::Slice {
+                    object, start, end, ..
+                } => {
+                    let bound = |bound: &Option<Box<Expr>>| match bound {
+                        Some(bound) => self.expr_doc(bound, indent),
+                        None => Doc::text(""),
+                    };
+                    links.push(Doc::concat([
+                        Doc::text("["),
+                        bound(start),
+                        Doc::text(":"),
+                        bound(end),
+                        Doc::text("]"),
+                    ]));
+                    base = object;
+                }
+                _ => break,
+            }
+        }
+        links.reverse();
+        let base = self.operand_doc(base, PRECEDENCE_POSTFIX, indent);
+
+        if method_calls < 2 {
+            return Doc::concat(std::iter::once(base).chain(links));
+        }
+
+        let links = links
+            .into_iter()
+            .flat_map(|link| [Doc::SoftLine, link])
+            .collect::<Vec<_>>();
+        Doc::concat([
+            Doc::IfBreak("(".to_string()),
+            Doc::concat([
+                Doc::SoftLine,
+                base,
+                Doc::concat(links).nest(self.config.indent_width),
+            ])
+            .nest(self.config.indent_width),
+            Doc::SoftLine,
+            Doc::IfBreak(")".to_string()),
+        ])
+        .group()
+    }
+
     fn format_pattern(&self, pattern: &ast::nodes::Pattern) -> String {
         match pattern {
             ast::nodes::Pattern::Wildcard => "_".to_string(),
@@ -541,15 +862,20 @@ impl Formatter {
                 variant,
                 fields,
             } => {
-                if fields.is_empty() {
+                let name = if enum_name.is_empty() {
+                    variant.clone()
+                } else {
                     format!("{}.{}", enum_name, variant)
+                };
+                if fields.is_empty() {
+                    name
                 } else {
                     let inner = fields
                         .iter()
                         .map(|p| self.format_pattern(p))
                         .collect::<Vec<_>>()
                         .join(", ");
-                    format!("{}.{}({})", enum_name, variant, inner)
+                    format!("{}({})", name, inner)
                 }
             }
             ast::nodes::Pattern::Struct { name, fields } => {
@@ -564,7 +890,7 @@ impl Formatter {
                     })
                     .collect::<Vec<_>>()
                     .join(", ");
-                format!("{}({})", name, fields_str)
+                format!("{} {{ {} }}", name, fields_str)
             }
             ast::nodes::Pattern::Array { patterns, rest } => {
                 let patterns_str = patterns
@@ -573,11 +899,53 @@ impl Formatter {
                     .collect::<Vec<_>>()
                     .join(", ");
                 let rest_str = if let Some(rest_var) = rest {
-                    format!(", ..{}", rest_var)
+                    format!("..{}", rest_var)
                 } else {
                     String::new()
                 };
-                format!("[{}{}]", patterns_str, rest_str)
+                format!("[{}]{}", patterns_str, rest_str)
+            }
+            ast::nodes::Pattern::Tuple(items) => {
+                let items_str = items
+                    .iter()
+                    .map(|p| self.format_pattern(p))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if items.len() == 1 {
+                    format!("({},)", items_str)
+                } else {
+                    format!("({})", items_str)
+                }
+            }
+            ast::nodes::Pattern::Or(alternatives) => alternatives
+                .iter()
+                .map(|p| self.format_pattern(p))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            ast::nodes::Pattern::Range { start, end } => {
+                format!(
+                    "{}..{}",
+                    self.format_literal(start),
+                    self.format_literal(end)
+                )
+            }
+            ast::nodes::Pattern::Binding { pattern, name } => {
+                format!("{} as {}", self.format_pattern(pattern), name)
+            }
+            ast::nodes::Pattern::StringPrefix { prefix, rest } => {
+                let prefix = self.format_literal(&ast::nodes::Literal::String(prefix.clone()));
+                format!("{}..{}", prefix, rest.as_deref().unwrap_or("_"))
+            }
+            ast::nodes::Pattern::Dict(entries) => {
+                let entries_str = entries
+                    .iter()
+                    .map(|(key, p)| {
+                        let key = self.format_literal(&ast::nodes::Literal::String(key.clone()));
+                        format!("{}: {}", key, self.format_pattern(p))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{{{}}}", entries_str)
             }
         }
     }
@@ -585,14 +953,22 @@ impl Formatter {
     fn format_literal(&self, lit: &ast::nodes::Literal) -> String {
         match lit {
             ast::nodes::Literal::Number(n) => {
-                if !n.is_float_literal && n.value.fract() == 0.0 {
-                    format!("{}", n.value as i64)
+                let digits = if !n.is_float_literal && n.value.fract() == 0.0 {
+                    format!("{}", n.int_value())
                 } else {
-                    n.value.to_string()
+                    // Debug keeps the trailing `.0` that marks a float literal
+                    format!("{:?}", n.value)
+                };
+                match n.suffix {
+                    Some(suffix) => format!("{}{}", digits, suffix.name()),
+                    None => digits,
                 }
             }
             ast::nodes::Literal::Bool(b) => b.to_string(),
-            ast::nodes::Literal::String(s) => format!("\"{}\"", s),
+            ast::nodes::Literal::String(s) => {
+                let quote = self.config.quote_style.quote();
+                format!("{quote}{}{quote}", self.escape_string(s))
+            }
             ast::nodes::Literal::None => "None".to_string(),
             ast::nodes::Literal::Unit => "()".to_string(),
         }
@@ -613,6 +989,29 @@ impl Formatter {
                     format!("{}<{}>", base, args_str)
                 }
             }
+            ast::nodes::Type::Tuple(items) => {
+                let items_str = items
+                    .iter()
+                    .map(|t| self.format_type(t))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if items.len() == 1 {
+                    format!("({},)", items_str)
+                } else {
+                    format!("({})", items_str)
+                }
+            }
+            ast::nodes::Type::Function { params, ret } => {
+                let params_str = params
+                    .iter()
+                    .map(|t| self.format_type(t))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match ret {
+                    Some(ret) => format!("fn({}) -> {}", params_str, self.format_type(ret)),
+                    None => format!("fn({})", params_str),
+                }
+            }
         }
     }
 
@@ -623,6 +1022,13 @@ impl Formatter {
             ast::nodes::BinaryOp::Sub => "-",
             ast::nodes::BinaryOp::Div => "/",
             ast::nodes::BinaryOp::Mod => "%",
+            ast::nodes::BinaryOp::Pow => "**",
+            ast::nodes::BinaryOp::FloorDiv => "//",
+            ast::nodes::BinaryOp::BitAnd => "&",
+            ast::nodes::BinaryOp::BitOr => "|",
+            ast::nodes::BinaryOp::BitXor => "^",
+            ast::nodes::BinaryOp::Shl => "<<",
+            ast::nodes::BinaryOp::Shr => ">>",
             ast::nodes::BinaryOp::Eq => "==",
             ast::nodes::BinaryOp::Ne => "!=",
             ast::nodes::BinaryOp::Lt => "<",
@@ -640,11 +1046,83 @@ impl Formatter {
         match op {
             ast::nodes::UnaryOp::Not => "not ",
             ast::nodes::UnaryOp::Neg => "-",
+            ast::nodes::UnaryOp::BitNot => "~",
         }
     }
 
+    /// Undo the lexer's escape processing so string contents print back as valid literals
+    fn escape_string(&self, value: &str) -> String {
+        let quote = self.config.quote_style.quote();
+        let mut escaped = String::with_capacity(value.len());
+        for ch in value.chars() {
+            match ch {
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                '\r' => escaped.push_str("\\r"),
+                ch if ch == quote => {
+                    escaped.push('\\');
+                    escaped.push(ch);
+                }
+                _ => escaped.push(ch),
+            }
+        }
+        escaped
+    }
+
     fn indent(&self, level: usize) -> String {
-        " ".repeat(level * self.indent_size)
+        " ".repeat(level * self.config.indent_width)
+    }
+}
+
+// Binding strength of each expression form, loosest first, mirroring the
+// layers of the parser's expression grammar
+const PRECEDENCE_OR: u8 = 1;
+const PRECEDENCE_AND: u8 = 2;
+const PRECEDENCE_COMPARISON: u8 = 3;
+const PRECEDENCE_RANGE: u8 = 4;
+const PRECEDENCE_BIT_OR: u8 = 5;
+const PRECEDENCE_BIT_XOR: u8 = 6;
+const PRECEDENCE_BIT_AND: u8 = 7;
+const PRECEDENCE_SHIFT: u8 = 8;
+const PRECEDENCE_SUM: u8 = 9;
+const PRECEDENCE_PRODUCT: u8 = 10;
+const PRECEDENCE_CAST: u8 = 11;
+const PRECEDENCE_UNARY: u8 = 12;
+const PRECEDENCE_POWER: u8 = 13;
+const PRECEDENCE_POSTFIX: u8 = 14;
+
+fn binary_precedence(op: &ast::nodes::BinaryOp) -> u8 {
+    use ast::nodes::BinaryOp;
+    match op {
+        BinaryOp::Or => PRECEDENCE_OR,
+        BinaryOp::And => PRECEDENCE_AND,
+        BinaryOp::Eq
+        | BinaryOp::Ne
+        | BinaryOp::Lt
+        | BinaryOp::LtEq
+        | BinaryOp::Gt
+        | BinaryOp::GtEq
+        | BinaryOp::Is
+        | BinaryOp::IsNot => PRECEDENCE_COMPARISON,
+        BinaryOp::BitOr => PRECEDENCE_BIT_OR,
+        BinaryOp::BitXor => PRECEDENCE_BIT_XOR,
+        BinaryOp::BitAnd => PRECEDENCE_BIT_AND,
+        BinaryOp::Shl | BinaryOp::Shr => PRECEDENCE_SHIFT,
+        BinaryOp::Add | BinaryOp::Sub => PRECEDENCE_SUM,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Mod => PRECEDENCE_PRODUCT,
+        BinaryOp::Pow => PRECEDENCE_POWER,
+    }
+}
+
+fn expr_precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Binary { op, .. } => binary_precedence(op),
+        Expr::Range { .. } => PRECEDENCE_RANGE,
+        Expr::Cast { .. } => PRECEDENCE_CAST,
+        Expr::Unary { .. } | Expr::Await(_) | Expr::Spawn(_) => PRECEDENCE_UNARY,
+        Expr::If { .. } | Expr::Lambda { .. } | Expr::Match { .. } => 0,
+        _ => PRECEDENCE_POSTFIX,
     }
 }
 
@@ -653,3 +1131,276 @@ impl Default for Formatter {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        Formatter::new()
+            .format_source(source)
+            .expect("source should format")
+    }
+
+    #[test]
+    fn comments_and_blank_lines_survive_formatting() {
+        let source = "\
+# Module header
+
+use otter:io  # console output
+
+
+def main():
+    # greet first
+    let name   =   \"otter\"
+
+    io.println(name)  # trailing
+    # end of body
+
+# closing remark
+";
+        let expected = "\
+# Module header
+
+use otter:io  # console output
+
+
+def main():
+    # greet first
+    let name = \"otter\"
+
+    io.println(name)  # trailing
+    # end of body
+
+# closing remark
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn formatting_is_idempotent_with_comments() {
+        let source = "def f(x: int) -> int:\n  # two-space source indent\n  if x > 1:\n      return x  # big\n  return 0\n";
+        let once = format(source);
+        assert_eq!(
+            once,
+            "def f(x: int) -> int:\n    # two-space source indent\n    if x > 1:\n        return x  # big\n    return 0\n"
+        );
+        assert_eq!(format(&once), once);
+    }
+
+    #[test]
+    fn comments_stay_put_after_a_collapsed_call() {
+        let source = "\
+def main():
+    let total = add(
+        1,  # first
+        2,
+    )
+    # after the call
+    io.println(total)  # shown
+";
+        let expected = "\
+def main():
+    let total = add(1, 2)  # first
+    # after the call
+    io.println(total)  # shown
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn output_with_other_lines_is_refused() {
+        let source = "let a = 1\n# between\nlet b = 2\n";
+        let (tokens, trivia) = lexer::tokenize_with_trivia(source).unwrap();
+        let formatted = "let a = 1\n";
+        let formatted_tokens = lexer::tokenize(formatted).unwrap();
+
+        let result = trivia::reattach(source, &tokens, &trivia, formatted, &formatted_tokens);
+        assert!(matches!(
+            result,
+            Err(FormatError::LineMismatch {
+                source_lines: 2,
+                formatted_lines: 1,
+            })
+        ));
+    }
+
+    #[test]
+    fn runs_of_blank_lines_are_capped() {
+        let source = "let a = 1\n\n\n\n\nlet b = 2.0\n";
+        assert_eq!(format(source), "let a = 1\n\n\nlet b = 2.0\n");
+    }
+
+    #[test]
+    fn let_annotations_are_kept() {
+        let source = "pub let scores:dict<str,  i64> = {}\nlet ratio :f64 = 0.5\n";
+        assert_eq!(
+            format(source),
+            "pub let scores: dict<str, i64> = {}\nlet ratio: f64 = 0.5\n"
+        );
+    }
+
+    #[test]
+    fn subscripts_and_assignment_targets() {
+        let source =
+            "let head = items[ : n]\ngrid[i][j + 1] = (a + b)[0]\nself.total += prices[1:]\n";
+        assert_eq!(
+            format(source),
+            "let head = items[:n]\ngrid[i][j + 1] = (a + b)[0]\nself.total = self.total + prices[1:]\n"
+        );
+    }
+
+    #[test]
+    fn tuples_and_destructuring() {
+        let source = "let pair:(int,str) = ( 1,\"a\" )\nlet (q,(r,_)) = split(7)\nlet one = (x ,)\nfor (k,v) in d.items():\n    pass\n";
+        assert_eq!(
+            format(source),
+            "let pair: (int, str) = (1, \"a\")\nlet (q, (r, _)) = split(7)\nlet one = (x,)\nfor k, v in d.items():\n    pass\n"
+        );
+    }
+
+    #[test]
+    fn traits_impls_and_bounds() {
+        let source = "pub trait Ord:\n    def cmp(self,other:Self)->int\n    def max(self,other:Self)->Self:\n        return self\nimpl Ord for int:\n    def cmp(self,other:int)->int:\n        return self-other\ndef biggest<T:Ord+Show,U>(a:T,b:T)->T:\n    return a.max(b)\n";
+        assert_eq!(
+            format(source),
+            "pub trait Ord:\n    def cmp(self, other: Self) -> int\n    def max(self, other: Self) -> Self:\n        return self\nimpl Ord for int:\n    def cmp(self, other: int) -> int:\n        return self - other\ndef biggest<T: Ord + Show, U>(a: T, b: T) -> T:\n    return a.max(b)\n"
+        );
+    }
+
+    #[test]
+    fn operators_keep_needed_parentheses() {
+        let source = "let a = (x|y)&(z^w)\nlet b = x<<(1+2)>>k\nlet c = -2**-y**2\nlet d = (-2)**2\nlet e = (x**y)**z\nlet f = (a or b) and not c\nlet g = -(-x)//~m\n";
+        assert_eq!(
+            format(source),
+            "let a = (x | y) & (z ^ w)\nlet b = x << 1 + 2 >> k\nlet c = -2 ** -y ** 2\nlet d = (-2) ** 2\nlet e = (x ** y) ** z\nlet f = (a or b) and not c\nlet g = -(-x) // ~m\n"
+        );
+    }
+
+    #[test]
+    fn config_controls_indent_and_quotes() {
+        let formatter = Formatter::with_config(FormatConfig {
+            indent_width: 2,
+            quote_style: crate::QuoteStyle::Single,
+            ..FormatConfig::default()
+        });
+        let formatted = formatter
+            .format_source("def f():\n    print(\"it's\")\n")
+            .expect("source should format");
+        assert_eq!(formatted, "def f():\n  print('it\\'s')\n");
+    }
+
+    fn format_width(source: &str, max_width: usize) -> String {
+        Formatter::with_config(FormatConfig {
+            max_width,
+            ..FormatConfig::default()
+        })
+        .format_source(source)
+        .expect("source should format")
+    }
+
+    #[test]
+    fn long_calls_and_collections_break_inside_brackets() {
+        let source = "let totals = summarize(first_values, [100, 200, 300, 400], {\"key\": lookup(name)})  # note\n";
+        let formatted = format_width(source, 40);
+        assert_eq!(
+            formatted,
+            "\
+let totals = summarize(
+    first_values,
+    [100, 200, 300, 400],
+    {\"key\": lookup(name)},
+)  # note
+"
+        );
+        assert_eq!(format_width(&formatted, 40), formatted);
+        assert_eq!(format_width(&formatted, 100), source);
+    }
+
+    #[test]
+    fn comprehension_clauses_break_onto_lines() {
+        let source =
+            "def f():\n    return [transform(item) for item in collection if keep(item)]\n";
+        assert_eq!(
+            format_width(source, 40),
+            "\
+def f():
+    return [
+        transform(item)
+        for item in collection
+        if keep(item)
+    ]
+"
+        );
+    }
+
+    #[test]
+    fn method_chains_break_before_each_call() {
+        let source = "let result = builder.add(first).add(second).build()\n";
+        let formatted = format_width(source, 30);
+        assert_eq!(
+            formatted,
+            "let result = (\n    builder\n        .add(first)\n        .add(second)\n        .build()\n)\n"
+        );
+        assert_eq!(format_width(&formatted, 100), source);
+    }
+
+    #[test]
+    fn parentheses_are_kept_where_precedence_needs_them() {
+        let source = "let a = (x + y) / 2.0 - (b - c)\nlet d = -(x * y) + (p.q)(1) * z\n";
+        assert_eq!(
+            format(source),
+            "let a = (x + y) / 2.0 - (b - c)\nlet d = -(x * y) + p.q(1) * z\n"
+        );
+    }
+
+    #[test]
+    fn yield_statements_round_trip() {
+        let source =
+            "def evens(n: int) -> Iterator<int>:\n    for i in 0..n:\n        yield i * 2\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn with_statements_round_trip() {
+        let source = "def main():\n    with net.dial(addr) as conn:\n        net.send(conn, \"hi\")\n    with sync.lock(m):\n        pass\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn constants_round_trip() {
+        let source =
+            "pub const LIMIT: int = 1 << 10\nconst def double(x: int) -> int:\n    return x * 2\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn function_types_round_trip() {
+        let source = "struct Button:\n    on_click: fn(str) -> bool\n\nlet handlers: list<fn(int)> = []\nlet add = lambda (x: int) -> int: x + step\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn match_patterns_round_trip() {
+        let source = "let r = match value:\n    case 1 | 2 as n:\n        n\n    case -5..10:\n        0\n    case Point { x, y: Some(_) }:\n        x\n    case (\"GET \"..path, [first]..rest):\n        1\n    case {\"id\": id}:\n        id\n    case \"v\".._:\n        2\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn numeric_suffixes_and_casts_round_trip() {
+        let source = "let mask = 255u8\nlet ratio = 1.5f32\nlet x = (a + b) as u16 * 2\nlet y = -(n as i8)\nlet z = -n as u64 as f64\nlet top = 18446744073709551615u64\nlet odd = 9007199254740993\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn annotations_round_trip() {
+        let source = "# slow on CI\n@ignore\n@timeout(5)\ndef test_load():\n    pass\n\n@skip(\"needs network\")\npub def test_fetch():\n    pass\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn string_literals_are_re_escaped() {
+        let source = "let s = \"say \\\"hi\\\"\\n\"\n";
+        assert_eq!(format(source), source);
+    }
+}
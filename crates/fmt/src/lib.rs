@@ -2,6 +2,12 @@
 //!
 //! Formats OtterLang source code according to standard style guidelines.
 
+mod config;
+mod diff;
+mod doc;
 mod formatter;
+mod trivia;
 
-pub use formatter::Formatter;
+pub use config::{CONFIG_FILE_NAME, ConfigError, FormatConfig, QuoteStyle};
+pub use diff::{LineChange, line_changes, unified_diff};
+pub use formatter::{FormatError, Formatter};
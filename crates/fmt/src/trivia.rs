@@ -0,0 +1,233 @@
+//! Re-attaching comments and blank lines to formatted output.
+//!
+//! The AST carries no trivia, so the formatter prints code first and then
+//! weaves the comments and blank lines collected by the lexer back in. Both
+//! the original source and the formatted output are split into logical
+//! lines (a line break at bracket depth zero ends one), which pair up one to
+//! one because the formatter never merges or splits statements. Output that
+//! breaks that rule is refused rather than given misplaced comments.
+
+use lexer::{Token, TokenKind, Trivia, TriviaKind};
+
+use crate::FormatError;
+
+/// Upper bound on consecutive blank lines between top-level items
+const MAX_BLANK_LINES_TOP_LEVEL: usize = 2;
+/// Upper bound on consecutive blank lines inside a block
+const MAX_BLANK_LINES_NESTED: usize = 1;
+
+#[derive(Debug, Clone, Copy)]
+struct LogicalLine {
+    /// Offset of the start of the physical line holding the first token
+    line_start: usize,
+    /// Offset of the first token
+    start: usize,
+    /// Offset just past the last token
+    end: usize,
+}
+
+impl LogicalLine {
+    fn indent(&self) -> usize {
+        self.start - self.line_start
+    }
+}
+
+fn logical_lines(source: &str, tokens: &[Token]) -> Vec<LogicalLine> {
+    let mut lines = Vec::new();
+    let mut current: Option<LogicalLine> = None;
+    let mut depth = 0usize;
+
+    for token in tokens {
+        match token.kind {
+            TokenKind::Indent | TokenKind::Dedent | TokenKind::Eof => continue,
+            TokenKind::Newline if depth == 0 => {
+                lines.extend(current.take());
+                continue;
+            }
+            TokenKind::Newline => continue,
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+            TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+
+        let start = token.span.start();
+        let end = token.span.end();
+        match current.as_mut() {
+            Some(line) => line.end = end,
+            None => {
+                current = Some(LogicalLine {
+                    line_start: line_start_of(source, start),
+                    start,
+                    end,
+                })
+            }
+        }
+    }
+
+    lines.extend(current);
+    lines
+}
+
+fn line_start_of(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |pos| pos + 1)
+}
+
+/// Merge `trivia` from `source` into `formatted`, whose tokens are `formatted_tokens`.
+pub(crate) fn reattach(
+    source: &str,
+    source_tokens: &[Token],
+    trivia: &[Trivia],
+    formatted: &str,
+    formatted_tokens: &[Token],
+) -> Result<String, FormatError> {
+    let source_lines = logical_lines(source, source_tokens);
+    let output_lines = logical_lines(formatted, formatted_tokens);
+    if source_lines.len() != output_lines.len() {
+        return Err(FormatError::LineMismatch {
+            source_lines: source_lines.len(),
+            formatted_lines: output_lines.len(),
+        });
+    }
+
+    let mut writer = TriviaWriter {
+        source,
+        output: String::new(),
+        pending_blank_lines: 0,
+        open_indents: Vec::new(),
+    };
+    let mut trivia = trivia.iter().peekable();
+
+    for (source_line, output_line) in source_lines.iter().zip(&output_lines) {
+        let indents = (source_line.indent(), output_line.indent());
+
+        // Everything before the line: own-line comments and blank lines
+        while let Some(item) = trivia.next_if(|item| item.span.start() < source_line.start) {
+            writer.leading(item, Some(indents));
+        }
+
+        // Comments inside a line that spans several physical lines in the
+        // source are hoisted above it; the last trailing comment stays put.
+        let mut inline = Vec::new();
+        while let Some(item) = trivia.next_if(|item| item.span.start() < source_line.end) {
+            if item.is_comment() {
+                inline.push(item);
+            }
+        }
+        if let Some(item) = trivia.next_if(|item| {
+            is_trailing(item) && on_same_line(source, source_line.end, item.span.start())
+        }) {
+            inline.push(item);
+        }
+
+        let trailing = inline.pop();
+        for item in inline {
+            writer.comment(comment_text(item), output_line.indent());
+        }
+        writer.code(
+            &formatted[output_line.line_start..output_line.end],
+            trailing.map(comment_text),
+        );
+
+        writer.enter_line(indents);
+    }
+
+    // Comments after the last line of code
+    for item in trivia {
+        writer.leading(item, None);
+    }
+
+    Ok(writer.output)
+}
+
+fn is_trailing(item: &Trivia) -> bool {
+    matches!(item.kind, TriviaKind::TrailingComment(_))
+}
+
+fn on_same_line(source: &str, from: usize, to: usize) -> bool {
+    !source[from..to].contains('\n')
+}
+
+fn comment_text(item: &Trivia) -> &str {
+    match &item.kind {
+        TriviaKind::Comment(text) | TriviaKind::TrailingComment(text) => text,
+        TriviaKind::BlankLine => "",
+    }
+}
+
+struct TriviaWriter<'a> {
+    source: &'a str,
+    output: String,
+    pending_blank_lines: usize,
+    /// (source indent, output indent) of the last line of code written and
+    /// of each block still open around it
+    open_indents: Vec<(usize, usize)>,
+}
+
+impl TriviaWriter<'_> {
+    fn leading(&mut self, item: &Trivia, next: Option<(usize, usize)>) {
+        match &item.kind {
+            TriviaKind::BlankLine => self.pending_blank_lines += 1,
+            TriviaKind::Comment(text) | TriviaKind::TrailingComment(text) => {
+                let column = item.span.start() - line_start_of(self.source, item.span.start());
+                let indent = self.comment_indent(column, next);
+                self.comment(text, indent);
+            }
+        }
+    }
+
+    fn enter_line(&mut self, indents: (usize, usize)) {
+        while self
+            .open_indents
+            .last()
+            .is_some_and(|(source_indent, _)| *source_indent >= indents.0)
+        {
+            self.open_indents.pop();
+        }
+        self.open_indents.push(indents);
+    }
+
+    /// Pick the indentation of the deepest surrounding line of code that the
+    /// comment was aligned with in the source.
+    fn comment_indent(&self, column: usize, next: Option<(usize, usize)>) -> usize {
+        self.open_indents
+            .iter()
+            .chain(next.iter())
+            .filter(|(source_indent, _)| *source_indent <= column)
+            .max_by_key(|(source_indent, _)| *source_indent)
+            .map_or(0, |(_, output_indent)| *output_indent)
+    }
+
+    fn comment(&mut self, text: &str, indent: usize) {
+        self.blank_lines(indent);
+        self.output.push_str(&" ".repeat(indent));
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn code(&mut self, text: &str, trailing: Option<&str>) {
+        let indent = text.len() - text.trim_start_matches(' ').len();
+        self.blank_lines(indent);
+        self.output.push_str(text);
+        if let Some(comment) = trailing {
+            self.output.push_str("  ");
+            self.output.push_str(comment);
+        }
+        self.output.push('\n');
+    }
+
+    fn blank_lines(&mut self, indent: usize) {
+        let limit = if indent == 0 {
+            MAX_BLANK_LINES_TOP_LEVEL
+        } else {
+            MAX_BLANK_LINES_NESTED
+        };
+        if !self.output.is_empty() {
+            for _ in 0..self.pending_blank_lines.min(limit) {
+                self.output.push('\n');
+            }
+        }
+        self.pending_blank_lines = 0;
+    }
+}
@@ -1,5 +1,5 @@
 pub mod token;
 pub mod tokenizer;
 
-pub use token::{Token, TokenKind};
-pub use tokenizer::{LexResult, LexerError, tokenize};
+pub use token::{Token, TokenKind, Trivia, TriviaKind};
+pub use tokenizer::{LexResult, LexerError, tokenize, tokenize_with_trivia};
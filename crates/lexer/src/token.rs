@@ -39,6 +39,13 @@ pub enum TokenKind {
     Raise,
     Struct,
     Enum,
+    Trait,
+    Impl,
+    And,
+    Or,
+    Yield,
+    With,
+    Const,
 
     // Identifiers
     Identifier(String),
@@ -63,6 +70,8 @@ pub enum TokenKind {
     RBracket,
     Comma,
     Dot,
+    /// `@` in front of an annotation such as `@skip("reason")`
+    At,
 
     // Operators
     Arrow,
@@ -80,6 +89,12 @@ pub enum TokenKind {
     Percent,
     Pipe,
     Amp,
+    Caret,
+    Tilde,
+    /// `<<`; there is no `>>` token, see the tokenizer
+    Shl,
+    StarStar,
+    SlashSlash,
     Bang,
 
     // Assignment operators
@@ -87,6 +102,12 @@ pub enum TokenKind {
     MinusEq,
     StarEq,
     SlashEq,
+    StarStarEq,
+    SlashSlashEq,
+    AmpEq,
+    PipeEq,
+    CaretEq,
+    ShlEq,
 
     // Range operator
     DoubleDot,
@@ -132,6 +153,13 @@ impl Hash for TokenKind {
             TokenKind::Raise => 31u16.hash(state),
             TokenKind::Struct => 32u16.hash(state),
             TokenKind::Enum => 33u16.hash(state),
+            TokenKind::Trait => 34u16.hash(state),
+            TokenKind::Impl => 35u16.hash(state),
+            TokenKind::And => 36u16.hash(state),
+            TokenKind::Or => 37u16.hash(state),
+            TokenKind::Yield => 38u16.hash(state),
+            TokenKind::With => 39u16.hash(state),
+            TokenKind::Const => 40u16.hash(state),
 
             // Identifiers
             TokenKind::Identifier(name) => {
@@ -174,6 +202,7 @@ impl Hash for TokenKind {
             TokenKind::RBracket => b']'.hash(state),
             TokenKind::Comma => b','.hash(state),
             TokenKind::Dot => b'.'.hash(state),
+            TokenKind::At => b'@'.hash(state),
 
             // Operators
             TokenKind::Arrow => 400u16.hash(state),
@@ -191,6 +220,11 @@ impl Hash for TokenKind {
             TokenKind::Percent => b'%'.hash(state),
             TokenKind::Pipe => b'|'.hash(state),
             TokenKind::Amp => b'&'.hash(state),
+            TokenKind::Caret => b'^'.hash(state),
+            TokenKind::Tilde => b'~'.hash(state),
+            TokenKind::Shl => 405u16.hash(state),
+            TokenKind::StarStar => 406u16.hash(state),
+            TokenKind::SlashSlash => 407u16.hash(state),
             TokenKind::Bang => b'!'.hash(state),
 
             // Assignment operators
@@ -198,6 +232,12 @@ impl Hash for TokenKind {
             TokenKind::MinusEq => 501u16.hash(state),
             TokenKind::StarEq => 502u16.hash(state),
             TokenKind::SlashEq => 503u16.hash(state),
+            TokenKind::StarStarEq => 504u16.hash(state),
+            TokenKind::SlashSlashEq => 505u16.hash(state),
+            TokenKind::AmpEq => 506u16.hash(state),
+            TokenKind::PipeEq => 507u16.hash(state),
+            TokenKind::CaretEq => 508u16.hash(state),
+            TokenKind::ShlEq => 509u16.hash(state),
 
             // Range operator
             TokenKind::DoubleDot => 600u16.hash(state),
@@ -247,6 +287,13 @@ impl TokenKind {
             TokenKind::Raise => "raise",
             TokenKind::Struct => "struct",
             TokenKind::Enum => "enum",
+            TokenKind::Trait => "trait",
+            TokenKind::Impl => "impl",
+            TokenKind::And => "and",
+            TokenKind::Or => "or",
+            TokenKind::Yield => "yield",
+            TokenKind::With => "with",
+            TokenKind::Const => "const",
 
             // Identifiers
             TokenKind::Identifier(_) => "identifier",
@@ -271,6 +318,7 @@ impl TokenKind {
             TokenKind::RBracket => "]",
             TokenKind::Comma => ",",
             TokenKind::Dot => ".",
+            TokenKind::At => "@",
 
             // Operators
             TokenKind::Arrow => "->",
@@ -288,6 +336,11 @@ impl TokenKind {
             TokenKind::Percent => "%",
             TokenKind::Pipe => "|",
             TokenKind::Amp => "&",
+            TokenKind::Caret => "^",
+            TokenKind::Tilde => "~",
+            TokenKind::Shl => "<<",
+            TokenKind::StarStar => "**",
+            TokenKind::SlashSlash => "//",
             TokenKind::Bang => "!",
 
             // Assignment operators
@@ -295,6 +348,12 @@ impl TokenKind {
             TokenKind::MinusEq => "-=",
             TokenKind::StarEq => "*=",
             TokenKind::SlashEq => "/=",
+            TokenKind::StarStarEq => "**=",
+            TokenKind::SlashSlashEq => "//=",
+            TokenKind::AmpEq => "&=",
+            TokenKind::PipeEq => "|=",
+            TokenKind::CaretEq => "^=",
+            TokenKind::ShlEq => "<<=",
 
             // Range operator
             TokenKind::DoubleDot => "..",
@@ -366,6 +425,13 @@ impl Token {
                 | TokenKind::Raise
                 | TokenKind::Struct
                 | TokenKind::Enum
+                | TokenKind::Trait
+                | TokenKind::Impl
+                | TokenKind::And
+                | TokenKind::Or
+                | TokenKind::Yield
+                | TokenKind::With
+                | TokenKind::Const
         )
     }
 
@@ -407,11 +473,22 @@ impl Token {
                 | TokenKind::Arrow
                 | TokenKind::Pipe
                 | TokenKind::Amp
+                | TokenKind::Caret
+                | TokenKind::Tilde
+                | TokenKind::Shl
+                | TokenKind::StarStar
+                | TokenKind::SlashSlash
                 | TokenKind::Bang
                 | TokenKind::PlusEq
                 | TokenKind::MinusEq
                 | TokenKind::StarEq
                 | TokenKind::SlashEq
+                | TokenKind::StarStarEq
+                | TokenKind::SlashSlashEq
+                | TokenKind::AmpEq
+                | TokenKind::PipeEq
+                | TokenKind::CaretEq
+                | TokenKind::ShlEq
                 | TokenKind::DoubleDot
         )
     }
@@ -438,3 +515,33 @@ impl Hash for Token {
         self.span.hash(state);
     }
 }
+
+/// Source text that carries no meaning for the parser but matters to tooling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A comment on its own line, including the leading `#`
+    Comment(String),
+    /// A comment following code on the same line, including the leading `#`
+    TrailingComment(String),
+    /// An empty or whitespace-only line
+    BlankLine,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+impl Trivia {
+    pub fn new(kind: TriviaKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn is_comment(&self) -> bool {
+        matches!(
+            self.kind,
+            TriviaKind::Comment(_) | TriviaKind::TrailingComment(_)
+        )
+    }
+}
@@ -1,4 +1,4 @@
-use crate::token::{Token, TokenKind};
+use crate::token::{Token, TokenKind, Trivia, TriviaKind};
 use common::Span;
 
 use thiserror::Error;
@@ -84,10 +84,6 @@ impl LexerError {
                         )
                     }
                     '~' => diag = diag.with_suggestion("Did you mean tilde (~) or negation (not)?"),
-                    '@' => {
-                        diag = diag
-                            .with_suggestion("Did you mean the at symbol (@) or member access (.)?")
-                    }
                     _ => {
                         if ch.is_ascii_punctuation() {
                             diag = diag.with_suggestion("Check for typos or invalid characters");
@@ -103,11 +99,19 @@ impl LexerError {
 
 pub type LexResult<T> = Result<T, Vec<LexerError>>;
 
+/// Type suffixes a number literal may end with; they stay part of its token
+const NUMBER_SUFFIXES: [&str; 11] = [
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "usize", "f32", "f64",
+];
+
 // Optimized lexer state machine
 struct LexerState {
     tokens: Vec<Token>,
     errors: Vec<LexerError>,
+    trivia: Option<Vec<Trivia>>,
     indent_stack: Vec<usize>,
+    /// Open `(`, `[` and `{`; line breaks inside them join lines
+    bracket_depth: usize,
     source: Vec<u8>,
     offset: usize,
     line: usize,
@@ -119,7 +123,9 @@ impl LexerState {
         Self {
             tokens: Vec::new(),
             errors: Vec::new(),
+            trivia: None,
             indent_stack: vec![0],
+            bracket_depth: 0,
             source: source.as_bytes().to_vec(),
             offset: 0,
             line: 1,
@@ -180,11 +186,25 @@ impl LexerState {
 
     fn emit_newline_token(&mut self) {
         if let Some(len) = self.current_newline_len() {
-            self.emit_token(TokenKind::Newline, self.offset, len);
+            if self.bracket_depth == 0 {
+                self.emit_token(TokenKind::Newline, self.offset, len);
+            }
             self.advance(1);
         }
     }
 
+    fn open_bracket(&mut self, kind: TokenKind) {
+        self.bracket_depth += 1;
+        self.emit_token(kind, self.offset, 1);
+        self.advance(1);
+    }
+
+    fn close_bracket(&mut self, kind: TokenKind) {
+        self.bracket_depth = self.bracket_depth.saturating_sub(1);
+        self.emit_token(kind, self.offset, 1);
+        self.advance(1);
+    }
+
     fn create_span(&self, start: usize, len: usize) -> Span {
         Span::new(start, start + len)
     }
@@ -194,20 +214,60 @@ impl LexerState {
         self.tokens.push(Token::new(kind, span));
     }
 
+    fn emit_trivia(&mut self, kind: TriviaKind, start: usize, end: usize) {
+        if let Some(trivia) = self.trivia.as_mut() {
+            trivia.push(Trivia::new(kind, Span::new(start, end)));
+        }
+    }
+
     fn emit_error(&mut self, error: LexerError) {
         self.errors.push(error);
     }
 
+    /// Copy the (possibly multi-byte) character at the cursor into `out`
+    fn push_source_char(&mut self, out: &mut String) {
+        let len = match self.current_char() {
+            Some(byte) if byte >= 0xF0 => 4,
+            Some(byte) if byte >= 0xE0 => 3,
+            Some(byte) if byte >= 0xC0 => 2,
+            Some(_) => 1,
+            None => return,
+        };
+        let end = (self.offset + len).min(self.source.len());
+        out.push_str(&String::from_utf8_lossy(&self.source[self.offset..end]));
+        self.advance(end - self.offset);
+    }
+
     fn is_at_end(&self) -> bool {
         self.offset >= self.source.len()
     }
 }
 
 pub fn tokenize(source: &str) -> LexResult<Vec<Token>> {
+    let state = run_lexer(LexerState::new(source));
+    if state.errors.is_empty() {
+        Ok(state.tokens)
+    } else {
+        Err(state.errors)
+    }
+}
+
+/// Tokenize while also collecting comments and blank lines, which the
+/// parser never sees but the formatter needs to re-emit.
+pub fn tokenize_with_trivia(source: &str) -> LexResult<(Vec<Token>, Vec<Trivia>)> {
     let mut state = LexerState::new(source);
+    state.trivia = Some(Vec::new());
+    let state = run_lexer(state);
+    if state.errors.is_empty() {
+        Ok((state.tokens, state.trivia.unwrap_or_default()))
+    } else {
+        Err(state.errors)
+    }
+}
 
+fn run_lexer(mut state: LexerState) -> LexerState {
     // Pre-allocate capacity for better performance
-    let estimated_tokens = source.len() / 4; // Rough estimate
+    let estimated_tokens = state.source.len() / 4; // Rough estimate
     state.tokens.reserve(estimated_tokens);
 
     while !state.is_at_end() {
@@ -216,12 +276,7 @@ pub fn tokenize(source: &str) -> LexResult<Vec<Token>> {
 
     // Finalize indentation and add EOF
     state.finalize_indentation();
-
-    if state.errors.is_empty() {
-        Ok(state.tokens)
-    } else {
-        Err(state.errors)
-    }
+    state
 }
 
 impl LexerState {
@@ -233,6 +288,7 @@ impl LexerState {
         while let Some(ch) = self.current_char() {
             if self.current_newline_len().is_some() {
                 // Empty line, just add newline
+                self.emit_trivia(TriviaKind::BlankLine, line_start, self.offset);
                 self.emit_newline_token();
                 return;
             }
@@ -253,7 +309,7 @@ impl LexerState {
                 }
                 b'#' => {
                     // Comment line, skip to end
-                    self.skip_to_end_of_line();
+                    self.skip_comment(false);
                     return;
                 }
                 _ => break,
@@ -271,8 +327,10 @@ impl LexerState {
             return;
         }
 
-        // Handle indentation changes
-        self.handle_indentation(indent_width, line_start);
+        // Handle indentation changes; continuation lines inside brackets have none
+        if self.bracket_depth == 0 {
+            self.handle_indentation(indent_width, line_start);
+        }
 
         // Tokenize the rest of the line
         self.tokenize_line_content(rest_start);
@@ -344,7 +402,7 @@ impl LexerState {
 
             match ch {
                 b'#' => {
-                    self.skip_to_end_of_line();
+                    self.skip_comment(true);
                     return;
                 }
                 b' ' | b'\t' => {
@@ -361,30 +419,12 @@ impl LexerState {
         let _token_start = self.offset;
 
         match self.current_char().unwrap() {
-            b'(' => {
-                self.emit_token(TokenKind::LParen, self.offset, 1);
-                self.advance(1);
-            }
-            b')' => {
-                self.emit_token(TokenKind::RParen, self.offset, 1);
-                self.advance(1);
-            }
-            b'{' => {
-                self.emit_token(TokenKind::LBrace, self.offset, 1);
-                self.advance(1);
-            }
-            b'}' => {
-                self.emit_token(TokenKind::RBrace, self.offset, 1);
-                self.advance(1);
-            }
-            b'[' => {
-                self.emit_token(TokenKind::LBracket, self.offset, 1);
-                self.advance(1);
-            }
-            b']' => {
-                self.emit_token(TokenKind::RBracket, self.offset, 1);
-                self.advance(1);
-            }
+            b'(' => self.open_bracket(TokenKind::LParen),
+            b')' => self.close_bracket(TokenKind::RParen),
+            b'{' => self.open_bracket(TokenKind::LBrace),
+            b'}' => self.close_bracket(TokenKind::RBrace),
+            b'[' => self.open_bracket(TokenKind::LBracket),
+            b']' => self.close_bracket(TokenKind::RBracket),
             b',' => {
                 self.emit_token(TokenKind::Comma, self.offset, 1);
                 self.advance(1);
@@ -425,34 +465,79 @@ impl LexerState {
                     self.advance(1);
                 }
             },
-            b'*' => {
-                if self.peek_char(1) == Some(b'=') {
+            b'*' => match (self.peek_char(1), self.peek_char(2)) {
+                (Some(b'*'), Some(b'=')) => {
+                    self.emit_token(TokenKind::StarStarEq, self.offset, 3);
+                    self.advance(3);
+                }
+                (Some(b'*'), _) => {
+                    self.emit_token(TokenKind::StarStar, self.offset, 2);
+                    self.advance(2);
+                }
+                (Some(b'='), _) => {
                     self.emit_token(TokenKind::StarEq, self.offset, 2);
                     self.advance(2);
-                } else {
+                }
+                _ => {
                     self.emit_token(TokenKind::Star, self.offset, 1);
                     self.advance(1);
                 }
-            }
-            b'/' => {
-                if self.peek_char(1) == Some(b'=') {
+            },
+            b'/' => match (self.peek_char(1), self.peek_char(2)) {
+                (Some(b'/'), Some(b'=')) => {
+                    self.emit_token(TokenKind::SlashSlashEq, self.offset, 3);
+                    self.advance(3);
+                }
+                (Some(b'/'), _) => {
+                    self.emit_token(TokenKind::SlashSlash, self.offset, 2);
+                    self.advance(2);
+                }
+                (Some(b'='), _) => {
                     self.emit_token(TokenKind::SlashEq, self.offset, 2);
                     self.advance(2);
-                } else {
+                }
+                _ => {
                     self.emit_token(TokenKind::Slash, self.offset, 1);
                     self.advance(1);
                 }
-            }
+            },
             b'%' => {
                 self.emit_token(TokenKind::Percent, self.offset, 1);
                 self.advance(1);
             }
             b'|' => {
-                self.emit_token(TokenKind::Pipe, self.offset, 1);
-                self.advance(1);
+                if self.peek_char(1) == Some(b'=') {
+                    self.emit_token(TokenKind::PipeEq, self.offset, 2);
+                    self.advance(2);
+                } else {
+                    self.emit_token(TokenKind::Pipe, self.offset, 1);
+                    self.advance(1);
+                }
             }
             b'&' => {
-                self.emit_token(TokenKind::Amp, self.offset, 1);
+                if self.peek_char(1) == Some(b'=') {
+                    self.emit_token(TokenKind::AmpEq, self.offset, 2);
+                    self.advance(2);
+                } else {
+                    self.emit_token(TokenKind::Amp, self.offset, 1);
+                    self.advance(1);
+                }
+            }
+            b'^' => {
+                if self.peek_char(1) == Some(b'=') {
+                    self.emit_token(TokenKind::CaretEq, self.offset, 2);
+                    self.advance(2);
+                } else {
+                    self.emit_token(TokenKind::Caret, self.offset, 1);
+                    self.advance(1);
+                }
+            }
+            b'~' => {
+                self.emit_token(TokenKind::Tilde, self.offset, 1);
+                self.advance(1);
+            }
+            b'@' => {
+                self.emit_token(TokenKind::At, self.offset, 1);
                 self.advance(1);
             }
             b'!' => {
@@ -473,15 +558,27 @@ impl LexerState {
                     self.advance(1);
                 }
             }
-            b'<' => {
-                if self.peek_char(1) == Some(b'=') {
+            b'<' => match (self.peek_char(1), self.peek_char(2)) {
+                (Some(b'<'), Some(b'=')) => {
+                    self.emit_token(TokenKind::ShlEq, self.offset, 3);
+                    self.advance(3);
+                }
+                (Some(b'<'), _) => {
+                    self.emit_token(TokenKind::Shl, self.offset, 2);
+                    self.advance(2);
+                }
+                (Some(b'='), _) => {
                     self.emit_token(TokenKind::LtEq, self.offset, 2);
                     self.advance(2);
-                } else {
+                }
+                _ => {
                     self.emit_token(TokenKind::Lt, self.offset, 1);
                     self.advance(1);
                 }
-            }
+            },
+            // `>>` stays two `>` tokens so nested generics such as
+            // `list<list<int>>` close; the parser joins adjacent `>` `>` and
+            // `>` `>=` into a right shift
             b'>' => {
                 if self.peek_char(1) == Some(b'=') {
                     self.emit_token(TokenKind::GtEq, self.offset, 2);
@@ -496,13 +593,14 @@ impl LexerState {
                 if self.peek_char(1) == Some(b'"') && self.peek_char(2) == Some(b'"') {
                     self.tokenize_multiline_string();
                 } else {
-                    self.tokenize_string();
+                    self.tokenize_string(b'"');
                 }
             }
+            b'\'' => self.tokenize_string(b'\''),
             b'f' => {
                 // Check for f-string before treating as regular identifier
-                if self.peek_char(1) == Some(b'"') {
-                    self.tokenize_fstring();
+                if let Some(quote @ (b'"' | b'\'')) = self.peek_char(1) {
+                    self.tokenize_fstring(quote);
                 } else {
                     self.tokenize_identifier_or_keyword();
                 }
@@ -530,7 +628,7 @@ impl LexerState {
         }
     }
 
-    fn tokenize_string(&mut self) {
+    fn tokenize_string(&mut self, quote: u8) {
         let start = self.offset;
         self.advance(1); // Skip opening quote
 
@@ -548,7 +646,7 @@ impl LexerState {
             }
 
             match ch {
-                b'"' => {
+                ch if ch == quote => {
                     let span = Span::new(start, self.offset + 1);
                     self.tokens
                         .push(Token::new(TokenKind::StringLiteral(result), span));
@@ -572,10 +670,7 @@ impl LexerState {
                         self.advance(1);
                     }
                 }
-                _ => {
-                    result.push(ch as char);
-                    self.advance(1);
-                }
+                _ => self.push_source_char(&mut result),
             }
         }
 
@@ -634,10 +729,7 @@ impl LexerState {
                         self.advance(1);
                     }
                 }
-                _ => {
-                    result.push(ch as char);
-                    self.advance(1);
-                }
+                _ => self.push_source_char(&mut result),
             }
         }
 
@@ -650,7 +742,7 @@ impl LexerState {
         });
     }
 
-    fn tokenize_fstring(&mut self) {
+    fn tokenize_fstring(&mut self, quote: u8) {
         let start = self.offset;
         self.advance(2); // Skip f"
 
@@ -668,7 +760,7 @@ impl LexerState {
             }
 
             match ch {
-                b'"' => {
+                ch if ch == quote => {
                     let span = Span::new(start, self.offset + 1);
                     self.tokens
                         .push(Token::new(TokenKind::FString(result), span));
@@ -694,10 +786,7 @@ impl LexerState {
                         self.advance(1);
                     }
                 }
-                _ => {
-                    result.push(ch as char);
-                    self.advance(1);
-                }
+                _ => self.push_source_char(&mut result),
             }
         }
 
@@ -738,6 +827,21 @@ impl LexerState {
             }
         }
 
+        // Type suffix, as in `255u8` or `1.5f32`
+        let suffix_len = self.source[self.offset..]
+            .iter()
+            .take_while(|ch| ch.is_ascii_alphanumeric() || **ch == b'_')
+            .count();
+        if suffix_len > 0 {
+            let suffix = &self.source[self.offset..self.offset + suffix_len];
+            if NUMBER_SUFFIXES
+                .iter()
+                .any(|known| known.as_bytes() == suffix)
+            {
+                self.advance(suffix_len);
+            }
+        }
+
         let value = unsafe { std::str::from_utf8_unchecked(&self.source[start..self.offset]) };
         self.emit_token(
             TokenKind::Number(value.to_string()),
@@ -793,6 +897,13 @@ impl LexerState {
             "raise" => TokenKind::Raise,
             "struct" => TokenKind::Struct,
             "enum" => TokenKind::Enum,
+            "trait" => TokenKind::Trait,
+            "impl" => TokenKind::Impl,
+            "and" => TokenKind::And,
+            "or" => TokenKind::Or,
+            "yield" => TokenKind::Yield,
+            "with" => TokenKind::With,
+            "const" => TokenKind::Const,
             _ => TokenKind::Identifier(value.to_string()),
         };
 
@@ -818,6 +929,26 @@ impl LexerState {
         );
     }
 
+    fn skip_comment(&mut self, trailing: bool) {
+        let start = self.offset;
+        let mut end = start;
+        while end < self.source.len() && self.newline_len_at(end).is_none() {
+            end += 1;
+        }
+        if self.trivia.is_some() {
+            let text = String::from_utf8_lossy(&self.source[start..end])
+                .trim_end()
+                .to_string();
+            let kind = if trailing {
+                TriviaKind::TrailingComment(text)
+            } else {
+                TriviaKind::Comment(text)
+            };
+            self.emit_trivia(kind, start, end);
+        }
+        self.skip_to_end_of_line();
+    }
+
     fn skip_to_end_of_line(&mut self) {
         while self.current_char().is_some() {
             if self.current_newline_len().is_some() {
@@ -882,4 +1013,104 @@ mod tests {
 
         assert_eq!(newline_span, 2);
     }
+
+    #[test]
+    fn string_literals_keep_non_ascii_text() {
+        let kinds = token_kinds("print(\"✓ café\")\n");
+        assert!(kinds.contains(&TokenKind::StringLiteral("✓ café".to_string())));
+    }
+
+    #[test]
+    fn single_quoted_strings_match_double_quoted() {
+        assert_eq!(
+            token_kinds("let s = 'say \"hi\"'\nlet f = f'{s}\\''\n"),
+            token_kinds("let s = \"say \\\"hi\\\"\"\nlet f = f\"{s}'\"\n")
+        );
+    }
+
+    #[test]
+    fn line_breaks_inside_brackets_are_joined() {
+        let multi_line = token_kinds("let xs = [\n    1,\n\n    # two\n    2,\n]\nprint(xs)\n");
+        let single_line = token_kinds("let xs = [1, 2,]\nprint(xs)\n");
+        assert_eq!(multi_line, single_line);
+    }
+
+    #[test]
+    fn operators_take_the_longest_match() {
+        use TokenKind::*;
+        assert_eq!(
+            token_kinds("a ** b // c << d >> e **= f //= g <<= h >>= i ^ ~j & k | l\n"),
+            vec![
+                Identifier("a".into()),
+                StarStar,
+                Identifier("b".into()),
+                SlashSlash,
+                Identifier("c".into()),
+                Shl,
+                Identifier("d".into()),
+                Gt,
+                Gt,
+                Identifier("e".into()),
+                StarStarEq,
+                Identifier("f".into()),
+                SlashSlashEq,
+                Identifier("g".into()),
+                ShlEq,
+                Identifier("h".into()),
+                Gt,
+                GtEq,
+                Identifier("i".into()),
+                Caret,
+                Tilde,
+                Identifier("j".into()),
+                Amp,
+                Identifier("k".into()),
+                Pipe,
+                Identifier("l".into()),
+                Newline,
+                Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn number_literals_keep_type_suffixes() {
+        use TokenKind::*;
+        assert_eq!(
+            token_kinds("255u8 1.5f32 1_000_usize 3units\n"),
+            vec![
+                Number("255u8".into()),
+                Number("1.5f32".into()),
+                Number("1_000_usize".into()),
+                Number("3".into()),
+                Identifier("units".into()),
+                Newline,
+                Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn trivia_records_comments_and_blank_lines() {
+        let source = "# header\nlet x = 1  # trailing\n\n    \nprint(x)\n";
+        let (tokens, trivia) = tokenize_with_trivia(source).expect("lexing should succeed");
+
+        let kinds: Vec<TriviaKind> = trivia.iter().map(|item| item.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TriviaKind::Comment("# header".to_string()),
+                TriviaKind::TrailingComment("# trailing".to_string()),
+                TriviaKind::BlankLine,
+                TriviaKind::BlankLine,
+            ]
+        );
+        assert_eq!(
+            &source[trivia[1].span.start()..trivia[1].span.end()],
+            "# trailing"
+        );
+
+        let plain: Vec<Token> = tokenize(source).expect("lexing should succeed");
+        assert_eq!(tokens, plain);
+    }
 }
@@ -138,6 +138,11 @@ impl ModuleLoader {
                         exports.add_type(name.clone());
                     }
                 }
+                ast::nodes::Statement::Trait { name, public, .. } => {
+                    if *public {
+                        exports.add_type(name.clone());
+                    }
+                }
                 _ => {}
             }
         }
@@ -2,8 +2,9 @@ use chumsky::Stream;
 use chumsky::prelude::*;
 
 use ast::nodes::{
-    BinaryOp, Block, EnumVariant, ExceptHandler, Expr, FStringPart, Function, Literal, MatchArm,
-    NumberLiteral, Param, Pattern, Program, Statement, Type, UnaryOp, UseImport,
+    Annotation, BinaryOp, Block, EnumVariant, ExceptHandler, Expr, FStringPart, Function,
+    GenericParam, Literal, MatchArm, NumberLiteral, NumericType, Param, Pattern, Program,
+    Statement, Type, UnaryOp, UseImport,
 };
 
 use common::Span;
@@ -77,12 +78,26 @@ fn identifier_parser() -> impl Parser<TokenKind, String, Error = Simple<TokenKin
     select! { TokenKind::Identifier(name) => name }
 }
 
+/// Target of an `as` conversion: a numeric type name, or `int` or `float`
+fn numeric_type_parser() -> impl Parser<TokenKind, Type, Error = Simple<TokenKind>> {
+    identifier_parser().try_map(|name, span| {
+        if NumericType::from_name(&name).is_some() || name == "int" || name == "float" {
+            Ok(Type::Simple(name))
+        } else {
+            Err(Simple::custom(span, "expected a numeric type"))
+        }
+    })
+}
+
 fn identifier_or_keyword_parser() -> impl Parser<TokenKind, String, Error = Simple<TokenKind>> {
     select! {
         TokenKind::Identifier(name) => name,
         TokenKind::Def => "def".to_string(),
         TokenKind::Lambda => "lambda".to_string(),
         TokenKind::Return => "return".to_string(),
+        TokenKind::Yield => "yield".to_string(),
+        TokenKind::With => "with".to_string(),
+        TokenKind::Const => "const".to_string(),
         TokenKind::If => "if".to_string(),
         TokenKind::Else => "else".to_string(),
         TokenKind::Elif => "elif".to_string(),
@@ -111,9 +126,24 @@ fn identifier_or_keyword_parser() -> impl Parser<TokenKind, String, Error = Simp
 
 fn type_parser() -> impl Parser<TokenKind, Type, Error = Simple<TokenKind>> {
     recursive(|ty| {
-        identifier_parser()
+        // `fn` isn't a keyword, so function types key off the identifier
+        let function = just(TokenKind::Identifier("fn".to_string()))
+            .ignore_then(
+                ty.clone()
+                    .separated_by(just(TokenKind::Comma))
+                    .allow_trailing()
+                    .delimited_by(just(TokenKind::LParen), just(TokenKind::RParen)),
+            )
+            .then(just(TokenKind::Arrow).ignore_then(ty.clone()).or_not())
+            .map(|(params, ret)| Type::Function {
+                params,
+                ret: ret.map(Box::new),
+            });
+
+        let named = identifier_parser()
             .then(
-                ty.separated_by(just(TokenKind::Comma))
+                ty.clone()
+                    .separated_by(just(TokenKind::Comma))
                     .allow_trailing()
                     .delimited_by(just(TokenKind::Lt), just(TokenKind::Gt))
                     .or_not(),
@@ -121,7 +151,88 @@ fn type_parser() -> impl Parser<TokenKind, Type, Error = Simple<TokenKind>> {
             .map(|(base, args)| match args {
                 Some(args) => Type::Generic { base, args },
                 None => Type::Simple(base),
-            })
+            });
+
+        let tuple = parenthesized_items(ty).map(|(mut items, is_tuple)| {
+            if is_tuple {
+                Type::Tuple(items)
+            } else {
+                items.remove(0)
+            }
+        });
+
+        choice((function, named, tuple))
+    })
+}
+
+/// Comma-separated items in parentheses. `(a, b)` and `(a,)` are tuples;
+/// `(a)` is just a parenthesised `a`, reported with `false`.
+fn parenthesized_items<T, P>(
+    item: P,
+) -> impl Parser<TokenKind, (Vec<T>, bool), Error = Simple<TokenKind>> + Clone
+where
+    P: Parser<TokenKind, T, Error = Simple<TokenKind>> + Clone,
+{
+    item.clone()
+        .then(just(TokenKind::Comma).ignore_then(item).repeated())
+        .then(just(TokenKind::Comma).or_not())
+        .delimited_by(just(TokenKind::LParen), just(TokenKind::RParen))
+        .map(|((first, rest), trailing)| {
+            let is_tuple = !rest.is_empty() || trailing.is_some();
+            let mut items = vec![first];
+            items.extend(rest);
+            (items, is_tuple)
+        })
+}
+
+/// Generic parameters of a function, each with optional trait bounds:
+/// `<T: Ord + Show, U>`
+fn generic_params_parser() -> impl Parser<TokenKind, Vec<GenericParam>, Error = Simple<TokenKind>> {
+    let bounds = identifier_parser()
+        .separated_by(just(TokenKind::Plus))
+        .at_least(1);
+
+    identifier_parser()
+        .then(just(TokenKind::Colon).ignore_then(bounds).or_not())
+        .map(|(name, bounds)| GenericParam::new(name, bounds.unwrap_or_default()))
+        .separated_by(just(TokenKind::Comma))
+        .allow_trailing()
+        .delimited_by(just(TokenKind::Lt), just(TokenKind::Gt))
+        .or_not()
+        .map(|params| params.unwrap_or_default())
+}
+
+/// Methods get `self: Self` as their first parameter if they don't declare it
+fn with_self_param(mut params: Vec<Param>) -> Vec<Param> {
+    if params.is_empty() || params[0].name != "self" {
+        let self_type = Type::Simple("Self".to_string());
+        let self_param = Param::new("self".to_string(), Some(self_type), None).with_span(None);
+        params.insert(0, self_param);
+    }
+    params
+}
+
+/// Pattern on the left of a destructuring `let` or a `for` loop: names,
+/// `_` and (nested) tuples of those.
+fn binding_pattern_parser() -> impl Parser<TokenKind, Pattern, Error = Simple<TokenKind>> + Clone {
+    recursive(|pattern| {
+        let name = identifier_parser().map(|name| {
+            if name == "_" {
+                Pattern::Wildcard
+            } else {
+                Pattern::Identifier(name)
+            }
+        });
+
+        let tuple = parenthesized_items(pattern).map(|(mut items, is_tuple)| {
+            if is_tuple {
+                Pattern::Tuple(items)
+            } else {
+                items.remove(0)
+            }
+        });
+
+        choice((name, tuple))
     })
 }
 
@@ -233,22 +344,28 @@ fn literal_expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKin
     let string_lit =
         select! { TokenKind::StringLiteral(value) => Expr::Literal(Literal::String(value)) };
     let number_lit = select! { TokenKind::Number(value) => {
+        // Split off a type suffix such as `u8`
+        let (digits, suffix) = match value.find(|ch: char| ch.is_ascii_alphabetic()) {
+            Some(index) => (&value[..index], NumericType::from_name(&value[index..])),
+            None => (value.as_str(), None),
+        };
         // Remove underscores from the number
-        let clean_value = value.replace('_', "");
-        let is_float_literal = value.contains('.') || value.contains('e') || value.contains('E');
+        let clean_value = digits.replace('_', "");
+        let is_float_literal = digits.contains('.') || digits.contains('e') || digits.contains('E');
         // Check if it contains a decimal point or is an integer
-        if clean_value.contains('.') {
-            Expr::Literal(Literal::Number(NumberLiteral::new(
-                clean_value.parse().unwrap_or_default(),
-                true,
-            )))
+        let number = if clean_value.contains('.') {
+            NumberLiteral::new(clean_value.parse().unwrap_or_default(), true)
         } else {
-            // Parse as integer
-            match clean_value.parse::<i64>() {
-                Ok(int_val) => Expr::Literal(Literal::Number(NumberLiteral::new(int_val as f64, is_float_literal))),
-                Err(_) => Expr::Literal(Literal::Number(NumberLiteral::new(0.0, is_float_literal))),
+            // Keep integers exact, as `u64` literals may exceed what `f64`
+            // holds; the checker rejects those too large for their type
+            match clean_value.parse::<i128>() {
+                Ok(int_val) => NumberLiteral::from_integer(int_val),
+                Err(_) => {
+                    NumberLiteral::new(clean_value.parse().unwrap_or_default(), is_float_literal)
+                }
             }
-        }
+        };
+        Expr::Literal(Literal::Number(number.with_suffix(suffix)))
     }};
     let bool_lit = select! {
         TokenKind::True => Expr::Literal(Literal::Bool(true)),
@@ -269,6 +386,15 @@ fn literal_expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKin
     ))
 }
 
+/// A suffix applied to a primary expression
+#[derive(Clone)]
+enum Postfix {
+    Member(String),
+    Call(Vec<Expr>),
+    Index(Expr, Span),
+    Slice(Option<Expr>, Option<Expr>, Span),
+}
+
 fn expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> {
     recursive(|expr| {
         let lambda_param = identifier_parser()
@@ -391,8 +517,14 @@ fn expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> {
                 name,
                 span: Some(Span::new(span.start, span.end)),
             }),
-            expr.clone()
-                .delimited_by(just(TokenKind::LParen), just(TokenKind::RParen)),
+            // Parenthesised expression, or tuple literal (a, b) / (a,)
+            parenthesized_items(expr.clone()).map(|(mut items, is_tuple)| {
+                if is_tuple {
+                    Expr::Tuple(items)
+                } else {
+                    items.remove(0)
+                }
+            }),
             list_comprehension,
             // Array literal [expr, expr, ...]
             expr.clone()
@@ -412,18 +544,9 @@ fn expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> {
         ))
         .boxed();
 
-        let member_access = atom
-            .clone()
-            .then(
-                just(TokenKind::Dot)
-                    .ignore_then(identifier_or_keyword_parser())
-                    .repeated(),
-            )
-            .foldl(|object, field| Expr::Member {
-                object: Box::new(object),
-                field,
-            })
-            .boxed();
+        let member_suffix = just(TokenKind::Dot)
+            .ignore_then(identifier_or_keyword_parser())
+            .map(Postfix::Member);
 
         let call_suffix = just(TokenKind::LParen)
             .ignore_then(
@@ -433,15 +556,63 @@ fn expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> {
                     .or_not()
                     .map(|args| args.unwrap_or_default()),
             )
-            .then_ignore(just(TokenKind::RParen));
+            .then_ignore(just(TokenKind::RParen))
+            .map(Postfix::Call);
 
-        let call = member_access
+        // `[index]`, or `[start:end]` with either bound optional
+        let index_suffix = choice((
+            expr.clone()
+                .or_not()
+                .then_ignore(just(TokenKind::Colon))
+                .then(expr.clone().or_not())
+                .map_with_span(|(start, end), span: std::ops::Range<usize>| {
+                    Postfix::Slice(start, end, Span::new(span.start, span.end))
+                }),
+            expr.clone()
+                .map_with_span(|index, span: std::ops::Range<usize>| {
+                    Postfix::Index(index, Span::new(span.start, span.end))
+                }),
+        ))
+        .delimited_by(just(TokenKind::LBracket), just(TokenKind::RBracket));
+
+        // Member accesses, calls and subscripts chain in any order:
+        // `a.b(x)[0].c.d()`
+        // Each suffix carries the offset it ends at, so calls span from the
+        // start of the chain to their closing parenthesis
+        let call = atom
             .clone()
-            .then(call_suffix.repeated())
-            .foldl(|func, args| Expr::Call {
-                func: Box::new(func),
-                args,
+            .map_with_span(|atom, span: std::ops::Range<usize>| (atom, span.start))
+            .then(
+                choice((member_suffix, call_suffix, index_suffix))
+                    .map_with_span(|suffix, span: std::ops::Range<usize>| (suffix, span.end))
+                    .repeated(),
+            )
+            .foldl(|(object, start), (suffix, end)| {
+                let expr = match suffix {
+                    Postfix::Member(field) => Expr::Member {
+                        object: Box::new(object),
+                        field,
+                    },
+                    Postfix::Call(args) => Expr::Call {
+                        func: Box::new(object),
+                        args,
+                        span: Some(Span::new(start, end)),
+                    },
+                    Postfix::Index(index, span) => Expr::Index {
+                        object: Box::new(object),
+                        index: Box::new(index),
+                        span: Some(span),
+                    },
+                    Postfix::Slice(start, end, span) => Expr::Slice {
+                        object: Box::new(object),
+                        start: start.map(Box::new),
+                        end: end.map(Box::new),
+                        span: Some(span),
+                    },
+                };
+                (expr, start)
             })
+            .map(|(expr, _)| expr)
             .boxed();
 
         let await_expr = just(TokenKind::Await)
@@ -452,61 +623,91 @@ fn expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> {
             .ignore_then(call.clone())
             .map(|expr| Expr::Spawn(Box::new(expr)));
 
-        let unary = choice((
-            just(TokenKind::Minus).to(UnaryOp::Neg),
-            just(TokenKind::Bang).to(UnaryOp::Not),
-            just(TokenKind::Not).to(UnaryOp::Not),
-        ))
-        .then(choice((
-            await_expr.clone(),
-            spawn_expr.clone(),
-            call.clone(),
-        )))
-        .map(|(op, expr)| Expr::Unary {
-            op,
-            expr: Box::new(expr),
+        // `**` binds tighter than a unary operator on its left but not on its
+        // right, and groups to the right: `-2 ** -1 ** 2` is
+        // `-(2 ** (-(1 ** 2)))`
+        let unary = recursive(|unary| {
+            let power = choice((await_expr.clone(), spawn_expr.clone(), call.clone()))
+                .then(
+                    just(TokenKind::StarStar)
+                        .ignore_then(unary.clone())
+                        .or_not(),
+                )
+                .map(|(base, exponent)| match exponent {
+                    Some(exponent) => Expr::Binary {
+                        left: Box::new(base),
+                        op: BinaryOp::Pow,
+                        right: Box::new(exponent),
+                    },
+                    None => base,
+                });
+
+            choice((
+                just(TokenKind::Minus).to(UnaryOp::Neg),
+                just(TokenKind::Bang).to(UnaryOp::Not),
+                just(TokenKind::Not).to(UnaryOp::Not),
+                just(TokenKind::Tilde).to(UnaryOp::BitNot),
+            ))
+            .then(power.clone())
+            .map(|(op, expr)| Expr::Unary {
+                op,
+                expr: Box::new(expr),
+            })
+            .or(power)
         })
-        .or(await_expr)
-        .or(spawn_expr)
-        .or(call.clone())
         .boxed();
 
-        let product = unary
-            .clone()
+        // `as` binds tighter than every binary operator: `-x as u8 * 2` is
+        // `((-x) as u8) * 2`
+        let cast = unary
             .then(
-                choice((
-                    just(TokenKind::Star).to(BinaryOp::Mul),
-                    just(TokenKind::Slash).to(BinaryOp::Div),
-                    just(TokenKind::Percent).to(BinaryOp::Mod),
-                ))
-                .then(unary.clone())
-                .repeated(),
+                just(TokenKind::As)
+                    .ignore_then(numeric_type_parser())
+                    .repeated(),
             )
-            .foldl(|left, (op, right)| Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            });
+            .foldl(|expr, ty| Expr::Cast {
+                expr: Box::new(expr),
+                ty,
+            })
+            .boxed();
 
-        let sum = product
+        let product = binary_level(
+            cast,
+            choice((
+                just(TokenKind::Star).to(BinaryOp::Mul),
+                just(TokenKind::SlashSlash).to(BinaryOp::FloorDiv),
+                just(TokenKind::Slash).to(BinaryOp::Div),
+                just(TokenKind::Percent).to(BinaryOp::Mod),
+            )),
+        );
+
+        let sum = binary_level(
+            product,
+            choice((
+                just(TokenKind::Plus).to(BinaryOp::Add),
+                just(TokenKind::Minus).to(BinaryOp::Sub),
+            )),
+        );
+
+        let shift = binary_level(
+            sum,
+            choice((
+                just(TokenKind::Shl).to(BinaryOp::Shl),
+                adjacent(TokenKind::Gt, TokenKind::Gt).to(BinaryOp::Shr),
+            )),
+        );
+
+        let bit_and = binary_level(shift, just(TokenKind::Amp).to(BinaryOp::BitAnd));
+        let bit_xor = binary_level(bit_and, just(TokenKind::Caret).to(BinaryOp::BitXor));
+        let bit_or = binary_level(bit_xor, just(TokenKind::Pipe).to(BinaryOp::BitOr)).boxed();
+
+        let range = bit_or
             .clone()
             .then(
-                choice((
-                    just(TokenKind::Plus).to(BinaryOp::Add),
-                    just(TokenKind::Minus).to(BinaryOp::Sub),
-                ))
-                .then(product)
-                .repeated(),
+                just(TokenKind::DoubleDot)
+                    .ignore_then(bit_or.clone())
+                    .or_not(),
             )
-            .foldl(|left, (op, right)| Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            });
-
-        let range = sum
-            .clone()
-            .then(just(TokenKind::DoubleDot).ignore_then(sum.clone()).or_not())
             .map(|(start, end)| {
                 if let Some(end) = end {
                     Expr::Range {
@@ -538,30 +739,9 @@ fn expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> {
             is_operator,
         ));
 
-        let comparison = range
-            .clone()
-            .then(comparison_op.then(range.clone()).repeated())
-            .foldl(|left, (op, right)| Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            });
-
-        let logical = comparison
-            .clone()
-            .then(
-                choice((
-                    just(TokenKind::Amp).to(BinaryOp::And),
-                    just(TokenKind::Pipe).to(BinaryOp::Or),
-                ))
-                .then(comparison)
-                .repeated(),
-            )
-            .foldl(|left, (op, right)| Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            });
+        let comparison = binary_level(range, comparison_op);
+        let and = binary_level(comparison, just(TokenKind::And).to(BinaryOp::And));
+        let logical = binary_level(and, just(TokenKind::Or).to(BinaryOp::Or)).boxed();
 
         let newline = just(TokenKind::Newline).repeated().at_least(1);
         let match_case = just(TokenKind::Case)
@@ -610,6 +790,57 @@ fn expr_parser() -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> {
     })
 }
 
+/// One left-associative precedence level: `operand (op operand)*`
+fn binary_level<P, O>(
+    operand: P,
+    op: O,
+) -> impl Parser<TokenKind, Expr, Error = Simple<TokenKind>> + Clone
+where
+    P: Parser<TokenKind, Expr, Error = Simple<TokenKind>> + Clone,
+    O: Parser<TokenKind, BinaryOp, Error = Simple<TokenKind>> + Clone,
+{
+    operand
+        .clone()
+        .then(op.then(operand).repeated())
+        .foldl(|left, (op, right)| Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        })
+}
+
+/// Two tokens with nothing between them, such as the `>` `>` of a right shift
+fn adjacent(
+    first: TokenKind,
+    second: TokenKind,
+) -> impl Parser<TokenKind, (), Error = Simple<TokenKind>> + Clone {
+    just(first)
+        .map_with_span(|_, span: std::ops::Range<usize>| span.end)
+        .then(just(second).map_with_span(|_, span: std::ops::Range<usize>| span.start))
+        .validate(|(end, start), span, emit| {
+            if end != start {
+                emit(Simple::custom(
+                    span,
+                    "unexpected whitespace inside operator",
+                ));
+            }
+        })
+}
+
+/// A number literal in a pattern, optionally negated
+fn signed_number_parser() -> impl Parser<TokenKind, Literal, Error = Simple<TokenKind>> {
+    just(TokenKind::Minus)
+        .or_not()
+        .then(literal_expr_parser())
+        .try_map(|(minus, expr), span| match expr {
+            Expr::Literal(Literal::Number(number)) if minus.is_some() => {
+                Ok(Literal::Number(number.negated()))
+            }
+            Expr::Literal(Literal::Number(number)) => Ok(Literal::Number(number)),
+            _ => Err(Simple::custom(span, "expected a number")),
+        })
+}
+
 /// Pattern parser for match expressions
 fn pattern_parser(
     _expr: Recursive<'_, TokenKind, Expr, Simple<TokenKind>>,
@@ -622,6 +853,29 @@ fn pattern_parser(
             _ => Pattern::Wildcard, // Fallback
         });
 
+        let range_pattern = signed_number_parser()
+            .then_ignore(just(TokenKind::DoubleDot))
+            .then(signed_number_parser())
+            .map(|(start, end)| Pattern::Range { start, end });
+
+        let string_prefix_pattern = select! { TokenKind::StringLiteral(prefix) => prefix }
+            .then_ignore(just(TokenKind::DoubleDot))
+            .then(identifier_parser())
+            .map(|(prefix, rest)| Pattern::StringPrefix {
+                prefix,
+                rest: (rest != "_").then_some(rest),
+            });
+
+        let number_pattern = signed_number_parser().map(Pattern::Literal);
+
+        let dict_pattern = select! { TokenKind::StringLiteral(key) => key }
+            .then_ignore(just(TokenKind::Colon))
+            .then(pattern.clone())
+            .separated_by(just(TokenKind::Comma))
+            .allow_trailing()
+            .delimited_by(just(TokenKind::LBrace), just(TokenKind::RBrace))
+            .map(Pattern::Dict);
+
         let identifier_pattern = identifier_parser().map(Pattern::Identifier);
 
         let variant_name = choice((
@@ -649,6 +903,27 @@ fn pattern_parser(
                 fields: fields.unwrap_or_default(),
             });
 
+        // `Some(x)`: the enum is resolved from the matched value's type
+        let bare_variant_pattern = identifier_parser()
+            .then(
+                pattern
+                    .clone()
+                    .separated_by(just(TokenKind::Comma))
+                    .allow_trailing()
+                    .delimited_by(just(TokenKind::LParen), just(TokenKind::RParen)),
+            )
+            .map(|(variant, fields)| Pattern::EnumVariant {
+                enum_name: String::new(),
+                variant,
+                fields,
+            });
+
+        let none_pattern = just(TokenKind::None).to(Pattern::EnumVariant {
+            enum_name: String::new(),
+            variant: "None".to_string(),
+            fields: Vec::new(),
+        });
+
         let struct_pattern = identifier_parser()
             .then(
                 just(TokenKind::LBrace)
@@ -668,6 +943,14 @@ fn pattern_parser(
                     .collect(),
             });
 
+        let tuple_pattern = parenthesized_items(pattern.clone()).map(|(mut items, is_tuple)| {
+            if is_tuple {
+                Pattern::Tuple(items)
+            } else {
+                items.remove(0)
+            }
+        });
+
         let array_pattern = pattern
             .clone()
             .separated_by(just(TokenKind::Comma))
@@ -680,14 +963,47 @@ fn pattern_parser(
             )
             .map(|(patterns, rest)| Pattern::Array { patterns, rest });
 
-        choice((
+        let single = choice((
             wildcard,
+            range_pattern,
+            string_prefix_pattern,
+            number_pattern,
+            none_pattern,
             literal_pattern,
             enum_variant_pattern,
+            bare_variant_pattern,
             struct_pattern,
+            dict_pattern,
+            tuple_pattern,
             array_pattern,
             identifier_pattern,
-        ))
+        ));
+
+        let alternatives =
+            single
+                .separated_by(just(TokenKind::Pipe))
+                .at_least(1)
+                .map(|mut alternatives| {
+                    if alternatives.len() == 1 {
+                        alternatives.remove(0)
+                    } else {
+                        Pattern::Or(alternatives)
+                    }
+                });
+
+        alternatives
+            .then(
+                just(TokenKind::As)
+                    .ignore_then(identifier_parser())
+                    .or_not(),
+            )
+            .map(|(pattern, name)| match name {
+                Some(name) => Pattern::Binding {
+                    pattern: Box::new(pattern),
+                    name,
+                },
+                None => pattern,
+            })
     })
 }
 
@@ -698,63 +1014,129 @@ fn program_parser() -> impl Parser<TokenKind, Program, Error = Simple<TokenKind>
     let print_stmt = just(TokenKind::Print)
         .ignore_then(
             expr.clone()
+                .then_ignore(just(TokenKind::Comma).or_not())
                 .delimited_by(just(TokenKind::LParen), just(TokenKind::RParen)),
         )
-        .map(|arg| {
+        .map_with_span(|arg, span| {
             Statement::Expr(Expr::Call {
                 func: Box::new(Expr::Identifier {
                     name: "print".to_string(),
                     span: None,
                 }),
                 args: vec![arg],
+                span: Some(Span::new(span.start, span.end)),
             })
         });
 
+    // `return a, b` returns the tuple `(a, b)`; likewise for `yield`
+    let values = expr
+        .clone()
+        .then(just(TokenKind::Comma).ignore_then(expr.clone()).repeated())
+        .map(|(first, rest)| {
+            if rest.is_empty() {
+                first
+            } else {
+                let mut items = vec![first];
+                items.extend(rest);
+                Expr::Tuple(items)
+            }
+        });
+
     let return_stmt = just(TokenKind::Return)
-        .ignore_then(expr.clone().or_not())
+        .ignore_then(values.clone().or_not())
         .map(Statement::Return);
 
+    let yield_stmt = just(TokenKind::Yield)
+        .ignore_then(values)
+        .map(Statement::Yield);
+
     let pub_keyword = just(TokenKind::Pub).or_not();
 
     let let_stmt = pub_keyword
         .clone()
         .then(just(TokenKind::Let).or_not())
         .then(identifier_parser().map_with_span(|name, span| (name, span)))
+        .then(just(TokenKind::Colon).ignore_then(type_parser()).or_not())
         .then_ignore(just(TokenKind::Equals))
         .then(expr.clone())
         .map(
-            |(((pub_kw, _let), (name, name_span)), expr)| Statement::Let {
+            |((((pub_kw, _let), (name, name_span)), ty), expr)| Statement::Let {
                 name,
+                ty,
                 expr,
                 public: pub_kw.is_some(),
                 span: Some(Span::new(name_span.start, name_span.end)),
             },
         );
 
-    // Simple assignments (=) are handled by let_stmt (declaration or reassignment)
-    let assignment_stmt = identifier_parser()
-        .map_with_span(|name, span| (name, Span::new(span.start, span.end)))
-        .then(choice((
-            just(TokenKind::PlusEq).to(BinaryOp::Add),
-            just(TokenKind::MinusEq).to(BinaryOp::Sub),
-            just(TokenKind::StarEq).to(BinaryOp::Mul),
-            just(TokenKind::SlashEq).to(BinaryOp::Div),
-        )))
+    let destructure_stmt = just(TokenKind::Let)
+        .ignore_then(binding_pattern_parser().map_with_span(|pattern, span| (pattern, span)))
+        .then_ignore(just(TokenKind::Equals))
         .then(expr.clone())
-        .map(|(((name, name_span), op), rhs)| {
+        .map(|((pattern, pattern_span), expr)| Statement::Destructure {
+            pattern,
+            expr,
+            span: Some(Span::new(pattern_span.start, pattern_span.end)),
+        });
+
+    // Expression statements, and assignments to index and member targets.
+    // Plain `name = expr` is handled by let_stmt (declaration or
+    // reassignment); compound assignment works on any target.
+    let expr_or_assignment_stmt = expr
+        .clone()
+        .map_with_span(|target, span| (target, Span::new(span.start, span.end)))
+        .then(
+            choice((
+                just(TokenKind::Equals).to(None),
+                just(TokenKind::PlusEq).to(Some(BinaryOp::Add)),
+                just(TokenKind::MinusEq).to(Some(BinaryOp::Sub)),
+                just(TokenKind::StarEq).to(Some(BinaryOp::Mul)),
+                just(TokenKind::SlashEq).to(Some(BinaryOp::Div)),
+                just(TokenKind::StarStarEq).to(Some(BinaryOp::Pow)),
+                just(TokenKind::SlashSlashEq).to(Some(BinaryOp::FloorDiv)),
+                just(TokenKind::AmpEq).to(Some(BinaryOp::BitAnd)),
+                just(TokenKind::PipeEq).to(Some(BinaryOp::BitOr)),
+                just(TokenKind::CaretEq).to(Some(BinaryOp::BitXor)),
+                just(TokenKind::ShlEq).to(Some(BinaryOp::Shl)),
+                adjacent(TokenKind::Gt, TokenKind::GtEq).to(Some(BinaryOp::Shr)),
+            ))
+            .then(expr.clone())
+            .or_not(),
+        )
+        .validate(|((target, target_span), assignment), span, emit| {
+            let Some((op, rhs)) = assignment else {
+                return Statement::Expr(target);
+            };
+            let (span, left) = match &target {
+                Expr::Identifier { name, span } => (
+                    span.unwrap_or(target_span),
+                    Expr::Identifier {
+                        name: name.clone(),
+                        span: None,
+                    },
+                ),
+                Expr::Index { .. } | Expr::Member { .. } => (target_span, target.clone()),
+                _ => {
+                    emit(Simple::custom(
+                        span,
+                        "only names, indexes and fields can be assigned to",
+                    ));
+                    return Statement::Expr(target);
+                }
+            };
             // Desugar: x += y becomes x = x + y
-            let expr = Expr::Binary {
-                op,
-                left: Box::new(Expr::Identifier {
-                    name: name.clone(),
-                    span: None,
-                }),
-                right: Box::new(rhs),
+            let expr = match op {
+                Some(op) => Expr::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(rhs),
+                },
+                None => rhs,
             };
             Statement::Assignment {
-                name,
+                target,
                 expr,
-                span: Some(name_span),
+                span: Some(span),
             }
         });
 
@@ -878,8 +1260,20 @@ fn program_parser() -> impl Parser<TokenKind, Program, Error = Simple<TokenKind>
                 },
             );
 
+        // `for k, v in ...` binds a tuple just like `for (k, v) in ...`
+        let for_var = binding_pattern_parser()
+            .separated_by(just(TokenKind::Comma))
+            .at_least(1)
+            .map(|mut patterns| {
+                if patterns.len() == 1 {
+                    patterns.remove(0)
+                } else {
+                    Pattern::Tuple(patterns)
+                }
+            });
+
         let for_stmt = just(TokenKind::For)
-            .ignore_then(identifier_parser().map_with_span(|var, span| (var, span)))
+            .ignore_then(for_var.map_with_span(|var, span| (var, span)))
             .then_ignore(just(TokenKind::In))
             .then(expr.clone())
             .then_ignore(just(TokenKind::Colon))
@@ -983,11 +1377,34 @@ fn program_parser() -> impl Parser<TokenKind, Program, Error = Simple<TokenKind>
             .ignore_then(expr.clone().or_not())
             .map(Statement::Raise);
 
+        let with_stmt = just(TokenKind::With)
+            .ignore_then(expr.clone())
+            .then(
+                just(TokenKind::As)
+                    .ignore_then(identifier_parser())
+                    .or_not(),
+            )
+            .then_ignore(just(TokenKind::Colon))
+            .then_ignore(newline.clone())
+            .then(
+                stmt.clone()
+                    .repeated()
+                    .at_least(1)
+                    .delimited_by(just(TokenKind::Indent), just(TokenKind::Dedent))
+                    .map(Block::new),
+            )
+            .map(|((manager, alias), body)| Statement::With {
+                manager,
+                alias,
+                body,
+            });
+
         choice((
             print_stmt,
             return_stmt,
+            yield_stmt,
             let_stmt,
-            assignment_stmt,
+            destructure_stmt,
             use_stmt,
             pub_use_stmt,
             if_stmt,
@@ -998,7 +1415,8 @@ fn program_parser() -> impl Parser<TokenKind, Program, Error = Simple<TokenKind>
             pass_stmt,
             try_stmt,
             raise_stmt,
-            expr.clone().map(Statement::Expr),
+            with_stmt,
+            expr_or_assignment_stmt,
         ))
         .then_ignore(newline.clone().or_not())
         .boxed()
@@ -1036,25 +1454,74 @@ fn program_parser() -> impl Parser<TokenKind, Program, Error = Simple<TokenKind>
 
     let function_keyword = just(TokenKind::Def);
 
-    let function = pub_keyword
-        .clone()
-        .then(function_keyword.clone())
-        .then(identifier_parser())
+    // Annotations on the lines above a function: @name or @name(args)
+    let annotation = just(TokenKind::At)
+        .ignore_then(identifier_parser())
+        .then(
+            expr.clone()
+                .separated_by(just(TokenKind::Comma))
+                .allow_trailing()
+                .delimited_by(just(TokenKind::LParen), just(TokenKind::RParen))
+                .or_not(),
+        )
+        .then_ignore(newline.clone())
+        .map(|(name, args)| Annotation::new(name, args.unwrap_or_default()));
+
+    let function = annotation
+        .repeated()
+        .then(pub_keyword.clone())
+        .then(just(TokenKind::Const).or_not())
+        .then_ignore(function_keyword.clone())
+        .then(identifier_parser().map_with_span(|name, span| (name, span)))
+        .then(generic_params_parser())
         .then(function_params)
         .then(function_ret_type)
         .then_ignore(just(TokenKind::Colon))
         .then_ignore(newline.clone())
         .then(block.clone())
-        .map(|(((((pub_kw, _fn), name), params), ret_ty), body)| {
-            if pub_kw.is_some() {
-                Function::new_public(name, params, ret_ty, body)
-            } else {
-                Function::new(name, params, ret_ty, body)
-            }
-        })
+        .map(
+            |(
+                (
+                    (((((annotations, pub_kw), const_kw), (name, name_span)), generics), params),
+                    ret_ty,
+                ),
+                body,
+            )| {
+                let function = if pub_kw.is_some() {
+                    Function::new_public(name, params, ret_ty, body)
+                } else {
+                    Function::new(name, params, ret_ty, body)
+                };
+                function
+                    .with_generics(generics)
+                    .with_const(const_kw.is_some())
+                    .with_annotations(annotations)
+                    .with_span(Some(Span::new(name_span.start, name_span.end)))
+            },
+        )
         .map(Statement::Function)
         .then_ignore(newline.clone().or_not());
 
+    // Constant: const NAME: Type = expr
+    let const_def = pub_keyword
+        .clone()
+        .then_ignore(just(TokenKind::Const))
+        .then(identifier_parser().map_with_span(|name, span| (name, span)))
+        .then_ignore(just(TokenKind::Colon))
+        .then(type_parser())
+        .then_ignore(just(TokenKind::Equals))
+        .then(expr.clone())
+        .then_ignore(newline.clone().or_not())
+        .map(
+            |(((pub_kw, (name, name_span)), ty), expr)| Statement::Const {
+                name,
+                ty,
+                expr,
+                public: pub_kw.is_some(),
+                span: Some(Span::new(name_span.start, name_span.end)),
+            },
+        );
+
     //     field: Type
     //     def method(self, ...) -> ReturnType:
     //         ...
@@ -1128,29 +1595,25 @@ fn program_parser() -> impl Parser<TokenKind, Program, Error = Simple<TokenKind>
         .allow_trailing()
         .delimited_by(just(TokenKind::LParen), just(TokenKind::RParen))
         .or_not()
-        .map(|params| params.unwrap_or_default());
+        .map(|params| params.unwrap_or_default())
+        .boxed();
 
-    let method_function_ret_type = just(TokenKind::Arrow).ignore_then(type_parser()).or_not();
+    let method_function_ret_type = just(TokenKind::Arrow)
+        .ignore_then(type_parser())
+        .or_not()
+        .boxed();
 
     let struct_method_def = function_keyword
         .clone()
-        .then(identifier_parser())
-        .then(method_function_params)
-        .then(method_function_ret_type)
+        .then(identifier_parser().map_with_span(|name, span| (name, span)))
+        .then(method_function_params.clone())
+        .then(method_function_ret_type.clone())
         .then_ignore(just(TokenKind::Colon))
         .then_ignore(newline.clone())
         .then(block.clone())
-        .map(|((((_kw, name), params), ret_ty), body)| {
-            // Methods automatically get 'self' as first parameter if not present
-            let mut method_params = params;
-            if method_params.is_empty() || method_params[0].name != "self" {
-                // Add self parameter at the beginning
-                let self_type = Type::Simple("Self".to_string());
-                let self_param =
-                    Param::new("self".to_string(), Some(self_type), None).with_span(None);
-                method_params.insert(0, self_param);
-            }
-            Function::new(name, method_params, ret_ty, body)
+        .map(|((((_kw, (name, name_span)), params), ret_ty), body)| {
+            Function::new(name, with_self_param(params), ret_ty, body)
+                .with_span(Some(Span::new(name_span.start, name_span.end)))
         })
         .map(|method| (None::<(String, Type)>, Some(method)))
         .then_ignore(newline.clone().or_not());
@@ -1238,10 +1701,104 @@ fn program_parser() -> impl Parser<TokenKind, Program, Error = Simple<TokenKind>
             },
         );
 
+    // Trait: method signatures, optionally with a default body
+    //     trait Ord:
+    //         def cmp(self, other: Self) -> int
+    //         def max(self, other: Self) -> Self:
+    //             ...
+    let method_header = function_keyword
+        .clone()
+        .ignore_then(identifier_parser().map_with_span(|name, span| (name, span)))
+        .then(generic_params_parser())
+        .then(method_function_params.clone())
+        .then(method_function_ret_type.clone())
+        .boxed();
+
+    let trait_method_def = method_header
+        .clone()
+        .then(choice((
+            just(TokenKind::Colon)
+                .ignore_then(newline.clone())
+                .ignore_then(block.clone()),
+            newline.clone().to(Block::new(Vec::new())),
+        )))
+        .then_ignore(newline.clone().or_not())
+        .map(
+            |(((((name, name_span), generics), params), ret_ty), body)| {
+                Function::new(name, with_self_param(params), ret_ty, body)
+                    .with_generics(generics)
+                    .with_span(Some(Span::new(name_span.start, name_span.end)))
+            },
+        );
+
+    let trait_def = pub_keyword
+        .clone()
+        .then_ignore(just(TokenKind::Trait))
+        .then(identifier_parser())
+        .then_ignore(just(TokenKind::Colon))
+        .then_ignore(newline.clone())
+        .then(
+            trait_method_def
+                .repeated()
+                .at_least(1)
+                .delimited_by(just(TokenKind::Indent), just(TokenKind::Dedent)),
+        )
+        .then_ignore(newline.clone().or_not())
+        .map(|((pub_kw, name), methods)| Statement::Trait {
+            name,
+            methods,
+            public: pub_kw.is_some(),
+        });
+
+    // Impl block: impl Ord for int:
+    let impl_method_def = method_header
+        .then_ignore(just(TokenKind::Colon))
+        .then_ignore(newline.clone())
+        .then(block.clone())
+        .then_ignore(newline.clone().or_not())
+        .map(
+            |(((((name, name_span), generics), params), ret_ty), body)| {
+                Function::new(name, with_self_param(params), ret_ty, body)
+                    .with_generics(generics)
+                    .with_span(Some(Span::new(name_span.start, name_span.end)))
+            },
+        );
+
+    let impl_def = just(TokenKind::Impl)
+        .ignore_then(identifier_parser())
+        .then_ignore(just(TokenKind::For))
+        .then(identifier_parser())
+        .then_ignore(just(TokenKind::Colon))
+        .then_ignore(newline.clone())
+        .then(
+            impl_method_def
+                .repeated()
+                .at_least(1)
+                .delimited_by(just(TokenKind::Indent), just(TokenKind::Dedent)),
+        )
+        .then_ignore(newline.clone().or_not())
+        .map(|((trait_name, type_name), methods)| Statement::Impl {
+            trait_name,
+            type_name,
+            methods,
+        });
+
     newline
         .clone()
         .or_not()
-        .ignore_then(choice((struct_def, enum_def, type_alias_def, function, statement)).repeated())
+        .ignore_then(
+            choice((
+                struct_def,
+                enum_def,
+                type_alias_def,
+                trait_def,
+                impl_def,
+                const_def,
+                function,
+                statement,
+            ))
+            .repeated(),
+        )
         .then_ignore(newline.repeated().or_not())
         .then_ignore(just(TokenKind::Eof))
         .map(Program::new)
@@ -1286,6 +1843,468 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_let_type_annotations() {
+        let source = "let ratio: f64 = 0.5\nlet names: list<str> = []\n";
+        let tokens = lexer::tokenize(source).expect("tokenize annotated let");
+        let program = parse(&tokens).expect("parse annotated let");
+
+        let Statement::Let { ty, .. } = &program.statements[0] else {
+            panic!("expected let statement");
+        };
+        assert!(matches!(ty, Some(Type::Simple(name)) if name == "f64"));
+        let Statement::Let { ty, .. } = &program.statements[1] else {
+            panic!("expected let statement");
+        };
+        assert!(
+            matches!(ty, Some(Type::Generic { base, args }) if base == "list" && args.len() == 1)
+        );
+    }
+
+    #[test]
+    fn parses_method_chains_across_lines() {
+        let source = "let s = (\n    builder\n    .add(1)\n    .items\n)\n";
+        let tokens = lexer::tokenize(source).expect("tokenize chain");
+        let program = parse(&tokens).expect("parse chain");
+
+        let Statement::Let { expr, .. } = &program.statements[0] else {
+            panic!("expected let statement");
+        };
+        let Expr::Member { object, field } = expr else {
+            panic!("expected member access, got {:?}", expr);
+        };
+        assert_eq!(field, "items");
+        assert!(matches!(object.as_ref(), Expr::Call { args, .. } if args.len() == 1));
+    }
+
+    #[test]
+    fn parses_subscripts_and_assignment_targets() {
+        let source = "let row = grid[i][1:]\ngrid[0][j] = 1\npoint.x += step[:n]\n";
+        let tokens = lexer::tokenize(source).expect("tokenize subscripts");
+        let program = parse(&tokens).expect("parse subscripts");
+
+        let Statement::Let { expr, .. } = &program.statements[0] else {
+            panic!("expected let statement");
+        };
+        let Expr::Slice {
+            object, start, end, ..
+        } = expr
+        else {
+            panic!("expected slice, got {:?}", expr);
+        };
+        assert!(
+            matches!(object.as_ref(), Expr::Index { span: Some(span), .. } if span.text(source) == "i")
+        );
+        assert!(start.is_some() && end.is_none());
+
+        let Statement::Assignment { target, .. } = &program.statements[1] else {
+            panic!("expected assignment");
+        };
+        assert!(
+            matches!(target, Expr::Index { object, .. } if matches!(object.as_ref(), Expr::Index { .. }))
+        );
+
+        // Compound assignment desugars onto the same target
+        let Statement::Assignment { target, expr, .. } = &program.statements[2] else {
+            panic!("expected assignment");
+        };
+        assert!(matches!(target, Expr::Member { field, .. } if field == "x"));
+        let Expr::Binary { op, left, right } = expr else {
+            panic!("expected binary expression, got {:?}", expr);
+        };
+        assert_eq!(*op, BinaryOp::Add);
+        assert!(matches!(left.as_ref(), Expr::Member { .. }));
+        assert!(matches!(right.as_ref(), Expr::Slice { start: None, .. }));
+
+        let tokens = lexer::tokenize("f(x) = 1\n").expect("tokenize call target");
+        assert!(parse(&tokens).is_err());
+    }
+
+    #[test]
+    fn parses_tuples_and_destructuring() {
+        let source = "let pair: (int, str) = (1, \"a\")\nlet (q, (r, _)) = split(7)\nlet one = (x,)\nlet grouped = (x)\nfor k, v in d.items():\n    pass\nlet label = match pair:\n    case (0, name):\n        name\n    case _:\n        \"other\"\n";
+        let tokens = lexer::tokenize(source).expect("tokenize tuples");
+        let program = parse(&tokens).expect("parse tuples");
+
+        let Statement::Let { ty, expr, .. } = &program.statements[0] else {
+            panic!("expected let statement");
+        };
+        assert!(matches!(ty, Some(Type::Tuple(items)) if items.len() == 2));
+        assert!(matches!(expr, Expr::Tuple(items) if items.len() == 2));
+
+        let Statement::Destructure { pattern, .. } = &program.statements[1] else {
+            panic!("expected destructuring let");
+        };
+        assert_eq!(pattern.bindings(), vec!["q", "r"]);
+
+        let Statement::Let { expr, .. } = &program.statements[2] else {
+            panic!("expected let statement");
+        };
+        assert!(matches!(expr, Expr::Tuple(items) if items.len() == 1));
+        let Statement::Let { expr, .. } = &program.statements[3] else {
+            panic!("expected let statement");
+        };
+        assert!(matches!(expr, Expr::Identifier { name, .. } if name == "x"));
+
+        let Statement::For { var, .. } = &program.statements[4] else {
+            panic!("expected for loop");
+        };
+        assert!(matches!(var, Pattern::Tuple(items) if items.len() == 2));
+
+        let Statement::Let { expr, .. } = &program.statements[5] else {
+            panic!("expected let statement");
+        };
+        let Expr::Match { arms, .. } = expr else {
+            panic!("expected match, got {:?}", expr);
+        };
+        assert!(matches!(&arms[0].pattern, Pattern::Tuple(items) if items.len() == 2));
+
+        let tokens = lexer::tokenize("def f():\n    return a, b\n").expect("tokenize return");
+        let program = parse(&tokens).expect("parse tuple return");
+        let Statement::Function(function) = &program.statements[0] else {
+            panic!("expected function");
+        };
+        assert!(matches!(
+            &function.body.statements[0],
+            Statement::Return(Some(Expr::Tuple(items))) if items.len() == 2
+        ));
+    }
+
+    #[test]
+    fn parses_traits_impls_and_bounds() {
+        let source = "pub trait Ord:\n    def cmp(self, other: Self) -> int\n    def max(other: Self) -> Self:\n        return self\n\nimpl Ord for int:\n    def cmp(self, other: int) -> int:\n        return self - other\n\ndef biggest<T: Ord + Show, U>(a: T, b: T) -> T:\n    return a.max(b)\n";
+        let tokens = lexer::tokenize(source).expect("tokenize traits");
+        let program = parse(&tokens).expect("parse traits");
+        assert_eq!(program.statements.len(), 3);
+
+        let Statement::Trait {
+            name,
+            methods,
+            public,
+        } = &program.statements[0]
+        else {
+            panic!("expected trait, got {:?}", program.statements[0]);
+        };
+        assert_eq!(name, "Ord");
+        assert!(*public);
+        assert!(methods[0].is_signature());
+        assert!(!methods[1].is_signature());
+        assert_eq!(methods[1].params[0].name, "self");
+
+        let Statement::Impl {
+            trait_name,
+            type_name,
+            methods,
+        } = &program.statements[1]
+        else {
+            panic!("expected impl, got {:?}", program.statements[1]);
+        };
+        assert_eq!((trait_name.as_str(), type_name.as_str()), ("Ord", "int"));
+        assert_eq!(methods.len(), 1);
+
+        let Statement::Function(function) = &program.statements[2] else {
+            panic!("expected function");
+        };
+        assert_eq!(
+            function.generics,
+            vec![
+                GenericParam::new("T", vec!["Ord".to_string(), "Show".to_string()]),
+                GenericParam::new("U", Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_generators() {
+        let source = "def pairs(n: int) -> Iterator<(int, int)>:\n    for i in 0..n:\n        if i % 2 == 0:\n            yield i, i * i\n\ndef plain():\n    return 1\n";
+        let tokens = lexer::tokenize(source).expect("tokenize generator");
+        let program = parse(&tokens).expect("parse generator");
+
+        let Statement::Function(generator) = &program.statements[0] else {
+            panic!("expected function");
+        };
+        assert!(generator.is_generator());
+        let Statement::For { body, .. } = &generator.body.statements[0] else {
+            panic!("expected for loop");
+        };
+        let Statement::If { then_block, .. } = &body.statements[0] else {
+            panic!("expected if statement");
+        };
+        assert!(matches!(
+            &then_block.statements[0],
+            Statement::Yield(Expr::Tuple(items)) if items.len() == 2
+        ));
+
+        let Statement::Function(plain) = &program.statements[1] else {
+            panic!("expected function");
+        };
+        assert!(!plain.is_generator());
+    }
+
+    #[test]
+    fn parses_with_statements() {
+        let source = "def main():\n    with net.dial(addr) as conn:\n        net.send(conn, \"hi\")\n    with sync.lock(m):\n        pass\n";
+        let tokens = lexer::tokenize(source).expect("tokenize with");
+        let program = parse(&tokens).expect("parse with");
+
+        let Statement::Function(main) = &program.statements[0] else {
+            panic!("expected function");
+        };
+        let Statement::With {
+            manager: Expr::Call { .. },
+            alias: Some(alias),
+            body,
+        } = &main.body.statements[0]
+        else {
+            panic!("expected with statement binding a name");
+        };
+        assert_eq!(alias, "conn");
+        assert_eq!(body.statements.len(), 1);
+        assert!(matches!(
+            &main.body.statements[1],
+            Statement::With { alias: None, .. }
+        ));
+    }
+
+    #[test]
+    fn parses_constants_and_const_functions() {
+        let source = "pub const LIMIT: int = 1 << 10
+const def double(x: int) -> int:
+    return x * 2
+";
+        let tokens = lexer::tokenize(source).expect("tokenize const");
+        let program = parse(&tokens).expect("parse const");
+
+        let Statement::Const {
+            name, ty, public, ..
+        } = &program.statements[0]
+        else {
+            panic!("expected const declaration");
+        };
+        assert_eq!(name, "LIMIT");
+        assert!(matches!(ty, Type::Simple(name) if name == "int"));
+        assert!(*public);
+
+        let Statement::Function(double) = &program.statements[1] else {
+            panic!("expected function");
+        };
+        assert!(double.is_const);
+        assert!(!double.public);
+    }
+
+    #[test]
+    fn parses_function_annotations() {
+        let source = "@ignore\n@skip(\"needs network\", 2)\npub def test_fetch():\n    pass\n\ndef plain():\n    pass\n";
+        let tokens = lexer::tokenize(source).expect("tokenize annotations");
+        let program = parse(&tokens).expect("parse annotations");
+
+        let Statement::Function(fetch) = &program.statements[0] else {
+            panic!("expected function");
+        };
+        assert!(fetch.public);
+        assert_eq!(fetch.annotations.len(), 2);
+        assert!(fetch.annotation("ignore").unwrap().args.is_empty());
+        let skip = fetch.annotation("skip").unwrap();
+        assert!(matches!(
+            &skip.args[..],
+            [Expr::Literal(Literal::String(reason)), Expr::Literal(_)] if reason == "needs network"
+        ));
+
+        let Statement::Function(plain) = &program.statements[1] else {
+            panic!("expected function");
+        };
+        assert!(plain.annotations.is_empty());
+    }
+
+    #[test]
+    fn records_function_and_call_spans() {
+        let source = "def helper():\n    pass\n\ndef test_sum():\n    test.assert_eq(str(add(1, 2)), \"3\", \"sum\")\n";
+        let tokens = lexer::tokenize(source).expect("tokenize spans");
+        let program = parse(&tokens).expect("parse spans");
+
+        let Statement::Function(function) = &program.statements[1] else {
+            panic!("expected function");
+        };
+        let span = function.span.expect("function span");
+        assert_eq!(span.text(source), "test_sum");
+        assert_eq!(span.line_column(source), (4, 5));
+
+        let Statement::Expr(Expr::Call { span, args, .. }) = &function.body.statements[0] else {
+            panic!("expected call");
+        };
+        let span = span.expect("call span");
+        assert_eq!(
+            span.text(source),
+            "test.assert_eq(str(add(1, 2)), \"3\", \"sum\")"
+        );
+        assert_eq!(span.line_column(source), (5, 5));
+        let Expr::Call {
+            span: Some(inner), ..
+        } = &args[0]
+        else {
+            panic!("expected nested call");
+        };
+        assert_eq!(inner.text(source), "str(add(1, 2))");
+    }
+
+    #[test]
+    fn parses_function_types() {
+        let source = "struct Button:\n    on_click: fn(str) -> bool\n\nlet handlers: list<fn(int)> = []\nlet add = lambda (x: int) -> int: x + step\n";
+        let tokens = lexer::tokenize(source).expect("tokenize function types");
+        let program = parse(&tokens).expect("parse function types");
+
+        let Statement::Struct { fields, .. } = &program.statements[0] else {
+            panic!("expected struct");
+        };
+        let Type::Function { params, ret } = &fields[0].1 else {
+            panic!("expected function type, got {:?}", fields[0].1);
+        };
+        assert!(matches!(params.as_slice(), [Type::Simple(name)] if name == "str"));
+        assert!(matches!(ret.as_deref(), Some(Type::Simple(name)) if name == "bool"));
+
+        let Statement::Let {
+            ty: Some(Type::Generic { args, .. }),
+            ..
+        } = &program.statements[1]
+        else {
+            panic!("expected annotated let");
+        };
+        assert!(matches!(&args[0], Type::Function { ret: None, .. }));
+        assert!(matches!(
+            &program.statements[2],
+            Statement::Let {
+                expr: Expr::Lambda { .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_match_patterns() {
+        let source = "let r = match value:\n    case 1 | 2 | 3:\n        \"small\"\n    case -5..10:\n        \"range\"\n    case Some(x) as whole:\n        \"some\"\n    case (\"GET \"..path, [first]..rest):\n        path\n    case {\"id\": id, \"tags\": [_, _]}:\n        \"dict\"\n    case \"v\".._:\n        \"prefix\"\n";
+        let tokens = lexer::tokenize(source).expect("tokenize match patterns");
+        let program = parse(&tokens).expect("parse match patterns");
+        let Statement::Let {
+            expr: Expr::Match { arms, .. },
+            ..
+        } = &program.statements[0]
+        else {
+            panic!("expected match");
+        };
+        let patterns: Vec<_> = arms.iter().map(|arm| &arm.pattern).collect();
+
+        assert!(matches!(patterns[0], Pattern::Or(alternatives) if alternatives.len() == 3));
+        assert!(matches!(
+            patterns[1],
+            Pattern::Range {
+                start: Literal::Number(start),
+                end: Literal::Number(end),
+            } if start.value == -5.0 && end.value == 10.0
+        ));
+        let Pattern::Binding { pattern, name } = patterns[2] else {
+            panic!("expected `as` binding, got {:?}", patterns[2]);
+        };
+        assert_eq!(name, "whole");
+        assert!(matches!(
+            pattern.as_ref(),
+            Pattern::EnumVariant { enum_name, variant, .. } if enum_name.is_empty() && variant == "Some"
+        ));
+        assert_eq!(patterns[3].bindings(), vec!["path", "first", "rest"]);
+        assert!(matches!(patterns[4], Pattern::Dict(entries) if entries.len() == 2));
+        assert!(matches!(
+            patterns[5],
+            Pattern::StringPrefix { prefix, rest: None } if prefix == "v"
+        ));
+    }
+
+    /// Fully parenthesised rendering of an expression tree
+    fn grouping(expr: &Expr) -> String {
+        match expr {
+            Expr::Binary { left, op, right } => {
+                format!("({} {:?} {})", grouping(left), op, grouping(right))
+            }
+            Expr::Unary { op, expr } => format!("({:?} {})", op, grouping(expr)),
+            Expr::Identifier { name, .. } => name.clone(),
+            Expr::Literal(Literal::Number(number)) => number.value.to_string(),
+            other => panic!("unexpected expression {:?}", other),
+        }
+    }
+
+    #[test]
+    fn operator_precedence_and_associativity() {
+        let cases = [
+            (
+                "a or b and c == d | e ^ f & g << 1 + 2",
+                "(a Or (b And (c Eq (d BitOr (e BitXor (f BitAnd (g Shl (1 Add 2))))))))",
+            ),
+            ("a >> b > c", "((a Shr b) Gt c)"),
+            ("a > > b", ""),
+            ("-2 ** -x ** 2", "(Neg (2 Pow (Neg (x Pow 2))))"),
+            ("~a // b * c", "(((BitNot a) FloorDiv b) Mul c)"),
+        ];
+        for (source, expected) in cases {
+            let tokens = lexer::tokenize(&format!("let v = {source}\n")).expect("tokenize");
+            let Ok(program) = parse(&tokens) else {
+                assert_eq!(expected, "", "`{source}` should parse");
+                continue;
+            };
+            let Statement::Let { expr, .. } = &program.statements[0] else {
+                panic!("expected let statement");
+            };
+            assert_eq!(grouping(expr), expected, "`{source}`");
+        }
+
+        let tokens = lexer::tokenize("mask <<= 2\nmask >>= 1\nmask //= 3\n").expect("tokenize");
+        let program = parse(&tokens).expect("parse compound assignments");
+        let ops: Vec<_> = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Assignment {
+                    expr: Expr::Binary { op, .. },
+                    ..
+                } => *op,
+                other => panic!("expected compound assignment, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(ops, [BinaryOp::Shl, BinaryOp::Shr, BinaryOp::FloorDiv]);
+    }
+
+    #[test]
+    fn parses_numeric_suffixes_and_casts() {
+        let source = "let b = 255u8\nlet x = -n as u8 * 2\nwith open(p) as f:\n    pass\n";
+        let tokens = lexer::tokenize(source).expect("tokenize casts");
+        let program = parse(&tokens).expect("parse casts");
+
+        let Statement::Let {
+            expr: Expr::Literal(Literal::Number(number)),
+            ..
+        } = &program.statements[0]
+        else {
+            panic!("expected number literal");
+        };
+        assert_eq!(number.value, 255.0);
+        assert_eq!(number.suffix, Some(NumericType::U8));
+
+        let Statement::Let {
+            expr: Expr::Binary { left, op, .. },
+            ..
+        } = &program.statements[1]
+        else {
+            panic!("expected multiplication");
+        };
+        assert_eq!(*op, BinaryOp::Mul);
+        assert!(matches!(
+            left.as_ref(),
+            Expr::Cast { expr, ty: Type::Simple(name) }
+                if name == "u8" && matches!(expr.as_ref(), Expr::Unary { op: UnaryOp::Neg, .. })
+        ));
+        assert!(matches!(
+            &program.statements[2],
+            Statement::With { alias: Some(alias), .. } if alias == "f"
+        ));
+    }
+
     #[test]
     fn parses_core_stdlib_module() {
         let source = include_str!("../../../stdlib/otter/core.ot");
@@ -4,11 +4,12 @@ use std::process::Command as ProcessCommand;
 use std::time::Duration;
 
 use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use tracing::{debug, info, warn};
 
 use crate::codegen::{
     self, build_executable, BuildArtifact, CodegenOptLevel, CodegenOptions, TargetTriple,
+    TestHarness,
 };
 use crate::runtime::ffi;
 use crate::runtime::symbol_registry::SymbolRegistry;
@@ -101,12 +102,26 @@ enum Command {
         /// Files to format (defaults to all .ot files in current directory)
         #[arg(default_value = ".")]
         paths: Vec<PathBuf>,
+        /// Exit with a non-zero status if any file is not formatted, without rewriting it
+        #[arg(long, conflicts_with = "diff")]
+        check: bool,
+        /// Print a unified diff of the formatting changes instead of rewriting files
+        #[arg(long)]
+        diff: bool,
+        /// Read source from standard input and write the formatted result to standard output
+        #[arg(long)]
+        stdin: bool,
     },
     /// Profile OtterLang programs (memory or performance)
     Profile {
         #[command(subcommand)]
         subcommand: crate::tools::profiler::ProfileCommand,
     },
+    /// Inspect, prune and size-cap the build cache
+    Cache {
+        #[command(subcommand)]
+        subcommand: crate::tools::cache::CacheCommand,
+    },
     /// Run tests in OtterLang source files
     Test {
         /// Test files or directories to run (defaults to current directory)
@@ -121,9 +136,46 @@ enum Command {
         /// Update snapshots instead of comparing
         #[arg(long)]
         update_snapshots: bool,
+        /// Report format
+        #[arg(long, value_enum, default_value = "human")]
+        format: crate::test::ReportFormat,
+        /// Write the report to a file instead of standard output (junit, tap and json formats)
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        options: TestRunArgs,
     },
 }
 
+/// Options of `otter test` that pick which tests run, in what order and with what limits
+#[derive(Args, Debug, Clone)]
+pub struct TestRunArgs {
+    /// Only run tests whose name contains PATTERN, or matches it as a glob (repeatable)
+    #[arg(long = "filter", value_name = "PATTERN")]
+    filters: Vec<String>,
+    /// Match --filter patterns against whole test names
+    #[arg(long)]
+    exact: bool,
+    /// Also run tests marked @ignore
+    #[arg(long)]
+    include_ignored: bool,
+    /// Kill tests that run longer than this (e.g. 30, 2.5s, 500ms); @timeout overrides it
+    #[arg(long, value_name = "DURATION", value_parser = crate::test::parse_timeout)]
+    timeout: Option<Duration>,
+    /// Stop starting tests after the first failure
+    #[arg(long)]
+    fail_fast: bool,
+    /// Run test files, and the tests in each file, in random order
+    #[arg(long)]
+    shuffle: bool,
+    /// Seed for --shuffle, to repeat an earlier order (implies --shuffle)
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+    /// Run failing tests up to N more times; tests that then pass are reported as flaky
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retries: usize,
+}
+
 pub fn run() -> Result<()> {
     logger::init_logging();
     ffi::bootstrap_stdlib();
@@ -133,16 +185,34 @@ pub fn run() -> Result<()> {
         Command::Run { path } => handle_run(&cli, path),
         Command::Build { path, output } => handle_build(&cli, path, output.clone()),
         Command::Repl => handle_repl(),
-        Command::Fmt { paths } => handle_fmt(paths),
+        Command::Fmt {
+            paths,
+            check,
+            diff,
+            stdin,
+        } => handle_fmt(paths, *check, *diff, *stdin),
         Command::Profile { subcommand } => {
             crate::tools::profiler::run_profiler_subcommand(subcommand)
         }
+        Command::Cache { subcommand } => crate::tools::cache::run_cache_subcommand(subcommand),
         Command::Test {
             paths,
             parallel,
             verbose,
             update_snapshots,
-        } => handle_test(&cli, paths, *parallel, *verbose, *update_snapshots),
+            format,
+            output,
+            options,
+        } => handle_test(
+            &cli,
+            paths,
+            *parallel,
+            *verbose,
+            *update_snapshots,
+            *format,
+            output.as_deref(),
+            options,
+        ),
     }
 }
 
@@ -238,8 +308,8 @@ pub fn compile_pipeline(
     source: &str,
     settings: &CompilationSettings,
 ) -> Result<CompilationStage> {
-    let mut cache_manager = CacheManager::new();
     let cache_options = settings.cache_build_options();
+    let cache_manager = CacheManager::with_root(cache_options.cache_dir.clone());
     let mut profiler = Profiler::new();
     let source_id = path.display().to_string();
     let source_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
@@ -248,23 +318,14 @@ pub fn compile_pipeline(
     let stdlib_dir = find_stdlib_dir().ok();
 
     // Initial inputs without module dependencies (will be updated after parsing)
-    let mut inputs = CompilationInputs::new(path.to_path_buf(), Vec::new());
-
-    // Generate initial cache key for quick lookup check
-    let initial_cache_key = profiler.record_phase("Fingerprint", || {
-        cache_manager.fingerprint(&inputs, &cache_options, VERSION)
-    });
+    let mut inputs = CompilationInputs::new(path.to_path_buf(), Vec::new()).with_source(source);
 
+    // Quick lookup using the modules recorded by previous builds of this file
     if settings.allow_cache() {
-        if let Some(entry) =
-            profiler.record_phase("Cache lookup", || cache_manager.lookup(&initial_cache_key))
-        {
-            debug!(cache_hit = %entry.binary_path.display());
-            profiler.push_phase("Compile skipped", Duration::from_millis(0));
-            return Ok(CompilationStage {
-                profiler,
-                result: CompilationResult::CacheHit(entry),
-            });
+        if let Some(entry) = profiler.record_phase("Cache lookup", || {
+            cache_manager.lookup_source(&inputs, &cache_options, VERSION)
+        }) {
+            return Ok(cache_hit(&cache_manager, profiler, entry));
         }
     }
 
@@ -283,7 +344,7 @@ pub fn compile_pipeline(
         }
     }
 
-    let program = match profiler.record_phase("Parsing", || parse(&tokens)) {
+    let mut program = match profiler.record_phase("Parsing", || parse(&tokens)) {
         Ok(program) => {
             if settings.debug {
                 println!("Parsed successfully");
@@ -296,6 +357,11 @@ pub fn compile_pipeline(
         }
     };
 
+    // The harness runs each `@parametrize` case through a function of its own
+    if settings.test_harness.is_some() {
+        crate::test::add_parametrized_cases(&mut program)?;
+    }
+
     if settings.dump_ast {
         println!("\n== AST ==");
         println!("{:#?}", program);
@@ -303,7 +369,7 @@ pub fn compile_pipeline(
 
     // Process module imports
     let mut module_processor = ModuleProcessor::new(source_dir.clone(), stdlib_dir.clone());
-    let module_deps = profiler.record_phase("Module Resolution", || {
+    profiler.record_phase("Module Resolution", || {
         module_processor.process_imports(&program)
     })?;
 
@@ -338,12 +404,14 @@ pub fn compile_pipeline(
 
     let expr_types = type_checker.into_expr_type_map();
 
-    // Update inputs with module dependencies for accurate cache fingerprinting
-    inputs.imports = module_deps
+    // Update inputs with every transitive module for accurate cache fingerprinting
+    inputs.imports = module_processor
+        .dependencies()
         .iter()
         .map(|p| p.display().to_string())
         .collect();
-    let cache_key = profiler.record_phase("Fingerprint (with modules)", || {
+    inputs.imports.sort();
+    let cache_key = profiler.record_phase("Fingerprint", || {
         cache_manager.fingerprint(&inputs, &cache_options, VERSION)
     });
 
@@ -352,23 +420,39 @@ pub fn compile_pipeline(
         if let Some(entry) = profiler.record_phase("Cache lookup (with modules)", || {
             cache_manager.lookup(&cache_key)
         }) {
-            debug!(cache_hit = %entry.binary_path.display());
-            profiler.push_phase("Compile skipped", Duration::from_millis(0));
-            return Ok(CompilationStage {
-                profiler,
-                result: CompilationResult::CacheHit(entry),
-            });
+            return Ok(cache_hit(&cache_manager, profiler, entry));
         }
     }
 
-    let codegen_options = settings.codegen_options();
-    let binary_path = cache_manager
-        .binary_path(&cache_key)
-        .unwrap_or_else(|| PathBuf::from("./target/tmp_binary"));
+    let mut codegen_options = settings.codegen_options();
+    codegen_options.source = Some((path.to_path_buf(), source.to_string()));
+    // Uncached builds go to a directory that is removed with the stage
+    let scratch = if settings.enable_cache {
+        None
+    } else {
+        Some(
+            tempfile::Builder::new()
+                .prefix("otter-")
+                .tempdir()
+                .context("failed to create a build directory")?,
+        )
+    };
+    let staging_path = match &scratch {
+        Some(dir) => dir.path().join(format!("otter-{cache_key}")),
+        None => cache_manager
+            .staging_path(&cache_key)
+            .map_err(|e| anyhow::anyhow!("failed to prepare cache entry: {e}"))?,
+    };
 
-    let artifact = profiler.record_phase("LLVM Codegen", || {
-        build_executable(&program, &expr_types, &binary_path, &codegen_options)
-    })?;
+    let artifact = profiler
+        .record_phase("LLVM Codegen", || {
+            build_executable(&program, &expr_types, &staging_path, &codegen_options)
+        })
+        .inspect_err(|_| {
+            if settings.enable_cache {
+                cache_manager.discard_staging(&cache_key, &staging_path);
+            }
+        })?;
 
     let build_duration_ms = profiler
         .phases()
@@ -376,6 +460,15 @@ pub fn compile_pipeline(
         .map(|phase| phase.duration.as_millis())
         .unwrap_or_default();
 
+    let artifact = if settings.enable_cache {
+        let binary = cache_manager
+            .install_binary(&cache_key, &artifact.binary)
+            .map_err(|e| anyhow::anyhow!("failed to install cached binary: {e}"))?;
+        BuildArtifact { binary, ..artifact }
+    } else {
+        artifact
+    };
+
     let binary_size = std::fs::metadata(&artifact.binary)?.len();
 
     let metadata = CacheMetadata::new(
@@ -387,12 +480,17 @@ pub fn compile_pipeline(
         artifact.binary.clone(),
         binary_size,
         build_duration_ms as u64,
-        PathBuf::from("./cache"), // cache_path
+        cache_manager.entry_dir(&cache_key),
         inputs.imports.clone(),
     );
 
-    if let Err(e) = cache_manager.store(&metadata) {
-        warn!("Failed to store cache entry: {}", e);
+    if settings.enable_cache {
+        if let Err(e) = cache_manager.store(&metadata) {
+            warn!("Failed to store cache entry: {}", e);
+        }
+        if let Err(e) = cache_manager.evict_to_size(cache_options.max_cache_size as u64) {
+            warn!("Failed to trim build cache: {}", e);
+        }
     }
 
     info!(compiled = %artifact.binary.display(), size = binary_size);
@@ -400,12 +498,33 @@ pub fn compile_pipeline(
     Ok(CompilationStage {
         profiler,
         result: CompilationResult::Compiled { artifact, metadata },
+        scratch,
     })
 }
 
+fn cache_hit(
+    cache_manager: &CacheManager,
+    mut profiler: Profiler,
+    entry: CacheEntry,
+) -> CompilationStage {
+    debug!(cache_hit = %entry.binary_path.display());
+    if let Err(e) = cache_manager.record_hit(&entry.metadata.key) {
+        debug!("failed to record cache hit: {}", e);
+    }
+    profiler.push_phase("Compile skipped", Duration::from_millis(0));
+    CompilationStage {
+        profiler,
+        result: CompilationResult::CacheHit(entry),
+        scratch: None,
+    }
+}
+
 pub struct CompilationStage {
     profiler: Profiler,
     pub result: CompilationResult,
+    /// Directory holding the binary of an uncached build; dropping it
+    /// deletes the binary
+    pub scratch: Option<tempfile::TempDir>,
 }
 
 pub enum CompilationResult {
@@ -440,6 +559,7 @@ pub struct CompilationSettings {
     cache_dir: PathBuf,
     max_cache_size: usize,
     language_features: LanguageFeatureFlags,
+    test_harness: Option<TestHarness>,
 }
 
 impl CompilationSettings {
@@ -459,12 +579,19 @@ impl CompilationSettings {
             target: cli.target.clone(),
             no_cache: cli.no_cache,
             enable_cache: !cli.no_cache,
-            cache_dir: PathBuf::from("./cache"),
+            cache_dir: cache::build_cache_dir().unwrap_or_else(|_| PathBuf::from("./cache")),
             max_cache_size: 1024 * 1024 * 1024, // 1GB default
             language_features,
+            test_harness: None,
         }
     }
 
+    /// Build a test harness that runs the given test functions instead of `main`
+    pub fn with_test_harness(mut self, harness: TestHarness) -> Self {
+        self.test_harness = Some(harness);
+        self
+    }
+
     fn allow_cache(&self) -> bool {
         !(self.dump_tokens || self.dump_ast || self.dump_ir || self.no_cache)
     }
@@ -477,6 +604,9 @@ impl CompilationSettings {
             release: self.release,
             lto: self.release,
             emit_ir: self.dump_ir,
+            target: self.target.clone(),
+            llvm_version: codegen::current_llvm_version(),
+            test_harness: self.test_harness.is_some(),
         }
     }
 
@@ -501,6 +631,9 @@ impl CompilationSettings {
             pgo_profile_file: None,
             inline_threshold: None,
             target,
+            overflow_checks: !self.release,
+            test_harness: self.test_harness.clone(),
+            source: None,
         }
     }
 
@@ -580,7 +713,7 @@ fn canonical_or(path: &Path) -> PathBuf {
     path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
 }
 
-fn find_stdlib_dir() -> Result<PathBuf> {
+pub(crate) fn find_stdlib_dir() -> Result<PathBuf> {
     // Try environment variable first
     if let Ok(dir) = std::env::var("OTTER_STDLIB_DIR") {
         let path = PathBuf::from(dir);
@@ -670,16 +803,37 @@ fn print_timings(stage: &CompilationStage) {
     println!("  {:20} {:8.2}ms", "Total", total.as_secs_f64() * 1000.0);
 }
 
-fn handle_fmt(paths: &[PathBuf]) -> Result<()> {
-    use fmt::Formatter;
+/// How `otter fmt` reports formatting changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FmtMode {
+    /// Rewrite files in place
+    Write,
+    /// List files that would change and exit non-zero if there are any
+    Check,
+    /// Print a unified diff of the changes without touching files
+    Diff,
+}
+
+fn handle_fmt(paths: &[PathBuf], check: bool, diff: bool, stdin: bool) -> Result<()> {
     use glob::glob;
-    use lexer::tokenize;
-    use parser::parse;
 
-    println!("Formatting OtterLang files...");
+    let mode = if check {
+        FmtMode::Check
+    } else if diff {
+        FmtMode::Diff
+    } else {
+        FmtMode::Write
+    };
+
+    if stdin {
+        return format_stdin(mode);
+    }
+
+    if mode == FmtMode::Write {
+        println!("Formatting OtterLang files...");
+    }
 
-    let formatter = Formatter::new();
-    let mut formatted_count = 0;
+    let mut changed = Vec::new();
 
     // Collect all .ot files
     let mut files = Vec::new();
@@ -708,31 +862,81 @@ fn handle_fmt(paths: &[PathBuf]) -> Result<()> {
         let source = fs::read_to_string(&file_path)
             .with_context(|| format!("failed to read {}", file_path.display()))?;
 
-        let tokens = tokenize(&source)
-            .map_err(|_| anyhow::anyhow!("failed to tokenize {}", file_path.display()))?;
+        let config = fmt::FormatConfig::discover(&file_path)?;
+        let source_id = file_path.display().to_string();
+        let formatted = format_source(&fmt::Formatter::with_config(config), &source_id, &source)?;
 
-        let program = parse(&tokens)
-            .map_err(|_| anyhow::anyhow!("failed to parse {}", file_path.display()))?;
+        if formatted == source {
+            continue;
+        }
 
-        let formatted = formatter.format_program(&program);
+        match mode {
+            FmtMode::Write => {
+                fs::write(&file_path, &formatted)
+                    .with_context(|| format!("failed to write {}", file_path.display()))?;
+                println!("  {}", file_path.display());
+            }
+            FmtMode::Check => println!("Would reformat: {}", file_path.display()),
+            FmtMode::Diff => print!("{}", fmt::unified_diff(&source_id, &source, &formatted)),
+        }
+        changed.push(file_path);
+    }
 
-        if formatted != source {
-            fs::write(&file_path, formatted)
-                .with_context(|| format!("failed to write {}", file_path.display()))?;
-            println!("  {}", file_path.display());
-            formatted_count += 1;
+    match mode {
+        FmtMode::Write if changed.is_empty() => println!("All files are already formatted"),
+        FmtMode::Write => println!("\nFormatted {} file(s)", changed.len()),
+        FmtMode::Check if !changed.is_empty() => {
+            eprintln!("\n{} file(s) would be reformatted", changed.len());
+            std::process::exit(1);
         }
+        FmtMode::Check | FmtMode::Diff => {}
     }
 
-    if formatted_count == 0 {
-        println!("All files are already formatted");
-    } else {
-        println!("\nFormatted {} file(s)", formatted_count);
+    Ok(())
+}
+
+/// Format standard input to standard output, for editor integration. The
+/// configuration is discovered from the current directory.
+fn format_stdin(mode: FmtMode) -> Result<()> {
+    use std::io::{Read, Write};
+
+    let mut source = String::new();
+    std::io::stdin()
+        .read_to_string(&mut source)
+        .context("failed to read standard input")?;
+
+    let config = fmt::FormatConfig::discover(&std::env::current_dir()?)?;
+    let formatted = format_source(&fmt::Formatter::with_config(config), "<stdin>", &source)?;
+
+    match mode {
+        FmtMode::Write => std::io::stdout().write_all(formatted.as_bytes())?,
+        FmtMode::Diff => print!("{}", fmt::unified_diff("<stdin>", &source, &formatted)),
+        FmtMode::Check => {
+            if formatted != source {
+                eprintln!("<stdin> would be reformatted");
+                std::process::exit(1);
+            }
+        }
     }
 
     Ok(())
 }
 
+fn format_source(formatter: &fmt::Formatter, source_id: &str, source: &str) -> Result<String> {
+    match formatter.format_source(source) {
+        Ok(formatted) => Ok(formatted),
+        Err(fmt::FormatError::Lex(errors)) => {
+            emit_lexer_errors(source_id, source, &errors);
+            bail!("failed to tokenize {}", source_id);
+        }
+        Err(fmt::FormatError::Parse(errors)) => {
+            emit_parser_errors(source_id, source, &errors);
+            bail!("failed to parse {}", source_id);
+        }
+        Err(err) => bail!("failed to format {}: {}", source_id, err),
+    }
+}
+
 fn handle_repl() -> Result<()> {
     use crate::repl::{ReplEngine, Tui};
 
@@ -784,58 +988,122 @@ fn emit_parser_errors(source_id: &str, source: &str, errors: &[ParserError]) {
     emit_diagnostics(&diagnostics, source);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_test(
     cli: &OtterCli,
     paths: &[PathBuf],
     parallel: bool,
     verbose: bool,
     update_snapshots: bool,
+    format: crate::test::ReportFormat,
+    output: Option<&Path>,
+    options: &TestRunArgs,
 ) -> Result<()> {
-    use crate::test::{TestDiscovery, TestRunner, TestReporter};
+    use crate::test::{
+        reporter_for, shuffle, ReportFormat, TestCase, TestDiscovery, TestFilter, TestRunner,
+    };
     use rayon::prelude::*;
+    use std::io::{BufWriter, Write};
+    use std::time::Instant;
+
+    let report: Box<dyn Write> = match output {
+        Some(_) if format == ReportFormat::Human => {
+            bail!("--output needs --format junit, tap or json")
+        }
+        Some(path) => Box::new(BufWriter::new(
+            fs::File::create(path)
+                .with_context(|| format!("failed to create {}", path.display()))?,
+        )),
+        None => Box::new(std::io::stdout()),
+    };
 
     let settings = CompilationSettings::from_cli(cli);
     let mut discovery = TestDiscovery::new();
     discovery.discover_files(paths)?;
-    
-    let tests = discovery.discover_all_tests()?;
-    
-    if tests.is_empty() {
-        println!("No tests found");
-        return Ok(());
+
+    let filter = TestFilter::new(
+        options.filters.clone(),
+        options.exact,
+        options.include_ignored,
+    );
+    let tests = filter.apply(discovery.discover_all_tests()?);
+    let start = Instant::now();
+
+    let runner = TestRunner::new(settings, update_snapshots)
+        .with_timeout(options.timeout)
+        .with_retries(options.retries)
+        .with_fail_fast(options.fail_fast)
+        .with_color(
+            format == ReportFormat::Human && colored::control::SHOULD_COLORIZE.should_colorize(),
+        );
+    let mut reporter = reporter_for(format, verbose, report);
+
+    // Discovery yields the tests of each file together; every file is compiled once
+    let mut files: Vec<(PathBuf, Vec<TestCase>)> = Vec::new();
+    for test in tests {
+        match files.last_mut() {
+            Some((file_path, file_tests)) if *file_path == test.file_path => file_tests.push(test),
+            _ => files.push((test.file_path.clone(), vec![test])),
+        }
     }
 
-    println!("Running {} test(s)...\n", tests.len());
+    if options.shuffle || options.seed.is_some() {
+        let seed = options.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        // Keep machine-readable reports on stdout parseable
+        if format == ReportFormat::Human {
+            println!("Shuffling tests with --seed {}", seed);
+        } else {
+            eprintln!("Shuffling tests with --seed {}", seed);
+        }
+        shuffle(&mut files, seed);
+        for (index, (_, file_tests)) in files.iter_mut().enumerate() {
+            shuffle(file_tests, seed.wrapping_add(index as u64 + 1));
+        }
+    }
 
-    let runner = TestRunner::new(settings, update_snapshots);
-    let mut reporter = TestReporter::new(verbose);
+    let tests: Vec<TestCase> = files
+        .iter()
+        .flat_map(|(_, file_tests)| file_tests.iter().cloned())
+        .collect();
+    reporter.run_started(&tests)?;
 
+    let mut failed = false;
     if parallel {
-        // Run tests in parallel
-        let results: Vec<_> = tests
+        for test in files.iter().flat_map(|(_, file_tests)| file_tests) {
+            reporter.test_started(test)?;
+        }
+
+        // Compile files in parallel and spread each file's tests over worker processes
+        let workers = rayon::current_num_threads();
+        let results: Vec<_> = files
             .par_iter()
-            .map(|test| {
-                let result = runner.run_test(test);
-                (test.clone(), result)
-            })
+            .map(|(file_path, file_tests)| runner.run_file(file_path, file_tests, workers))
             .collect();
 
-        for (test, result) in results {
-            reporter.print_result(&test, &result);
-            reporter.record_result(test, result);
+        for (test, result) in results.into_iter().flatten() {
+            failed |= result.is_failure();
+            reporter.test_finished(&test, &result)?;
         }
     } else {
-        // Run tests sequentially
-        for test in tests {
-            let result = runner.run_test(&test);
-            reporter.print_result(&test, &result);
-            reporter.record_result(test, result);
+        // Run each file's tests in a single harness process
+        for (file_path, file_tests) in &files {
+            for test in file_tests {
+                reporter.test_started(test)?;
+            }
+            for (test, result) in runner.run_file(file_path, file_tests, 1) {
+                failed |= result.is_failure();
+                reporter.test_finished(&test, &result)?;
+            }
         }
     }
 
-    reporter.print_summary();
+    reporter.run_finished(start.elapsed())?;
 
-    if reporter.has_failures() {
+    if failed {
         std::process::exit(1);
     }
 
@@ -971,6 +1239,14 @@ fn type_spec_to_ffi_helper(spec: &TypeSpec, position: &str, function_name: &str)
         TypeSpec::F64 => Ok(FfiType::F64),
         TypeSpec::Str => Ok(FfiType::Str),
         TypeSpec::Opaque => Ok(FfiType::Opaque),
+        TypeSpec::I8 => Ok(FfiType::I8),
+        TypeSpec::I16 => Ok(FfiType::I16),
+        TypeSpec::U8 => Ok(FfiType::U8),
+        TypeSpec::U16 => Ok(FfiType::U16),
+        TypeSpec::U32 => Ok(FfiType::U32),
+        TypeSpec::U64 => Ok(FfiType::U64),
+        TypeSpec::Usize => Ok(FfiType::Usize),
+        TypeSpec::F32 => Ok(FfiType::F32),
     }
 }
 
@@ -0,0 +1,449 @@
+//! Free-variable analysis for closure conversion
+//!
+//! A lambda captures every name it reads without binding it itself.
+//! [`free_variables`] lists those names in evaluation order; codegen keeps the
+//! ones that are locals of the enclosing function and ignores globals and
+//! functions. [`reassigned_variables`] finds the locals a function assigns
+//! after declaring them: closures share those by reference so both sides see
+//! every update, and copy everything else by value. [`shared_variables`]
+//! combines the two for a whole function.
+
+use std::collections::HashSet;
+
+use ast::nodes::{Block, Expr, FStringPart, Param, Pattern, Statement};
+
+/// Names a lambda with these parameters and body reads from its environment
+pub fn free_variables(params: &[Param], body: &Block) -> Vec<String> {
+    let mut walker = FreeVariables::default();
+    walker.push_scope();
+    for param in params {
+        if let Some(default) = &param.default {
+            walker.expr(default);
+        }
+        walker.bind(&param.name);
+    }
+    walker.block(body);
+    walker.names
+}
+
+/// Locals of a function that some lambda in its body captures and that the
+/// function also reassigns; these live in shared cells
+pub fn shared_variables(params: &[Param], body: &Block) -> HashSet<String> {
+    let mut walker = FreeVariables::default();
+    walker.block(body);
+    let reassigned = reassigned_variables(params, body);
+    walker.captured.intersection(&reassigned).cloned().collect()
+}
+
+/// Locals of a function that are bound again after their declaration, by
+/// `name = ...` on a declared name or by compound assignment. Nested
+/// functions and lambdas are not searched.
+pub fn reassigned_variables(params: &[Param], body: &Block) -> HashSet<String> {
+    let mut declared = params.iter().map(|param| param.name.clone()).collect();
+    let mut names = HashSet::new();
+    collect_assignments(body, &mut declared, &mut names);
+    names
+}
+
+fn collect_assignments(block: &Block, declared: &mut HashSet<String>, names: &mut HashSet<String>) {
+    for statement in &block.statements {
+        match statement {
+            // `name = expr` parses as a `Let`, declaring or reassigning
+            Statement::Let { name, .. } if !declared.insert(name.clone()) => {
+                names.insert(name.clone());
+            }
+            Statement::Destructure { pattern, .. } => {
+                for name in pattern.bindings() {
+                    if !declared.insert(name.to_string()) {
+                        names.insert(name.to_string());
+                    }
+                }
+            }
+            Statement::Assignment {
+                target: Expr::Identifier { name, .. },
+                ..
+            } => {
+                names.insert(name.clone());
+            }
+            Statement::If {
+                then_block,
+                elif_blocks,
+                else_block,
+                ..
+            } => {
+                collect_assignments(then_block, declared, names);
+                for (_, block) in elif_blocks {
+                    collect_assignments(block, declared, names);
+                }
+                if let Some(block) = else_block {
+                    collect_assignments(block, declared, names);
+                }
+            }
+            Statement::For { body, .. }
+            | Statement::While { body, .. }
+            | Statement::With { body, .. }
+            | Statement::Block(body) => collect_assignments(body, declared, names),
+            Statement::Try {
+                body,
+                handlers,
+                else_block,
+                finally_block,
+            } => {
+                collect_assignments(body, declared, names);
+                for handler in handlers {
+                    collect_assignments(&handler.body, declared, names);
+                }
+                for block in [else_block, finally_block].into_iter().flatten() {
+                    collect_assignments(block, declared, names);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Default)]
+struct FreeVariables {
+    scopes: Vec<HashSet<String>>,
+    seen: HashSet<String>,
+    names: Vec<String>,
+    /// Free variables of every lambda met along the way
+    captured: HashSet<String>,
+}
+
+impl FreeVariables {
+    fn push_scope(&mut self) {
+        self.scopes.push(HashSet::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn bind(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        for name in pattern.bindings() {
+            self.bind(name);
+        }
+    }
+
+    fn use_name(&mut self, name: &str) {
+        let bound = self.scopes.iter().any(|scope| scope.contains(name));
+        if !bound && self.seen.insert(name.to_string()) {
+            self.names.push(name.to_string());
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.push_scope();
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+        self.pop_scope();
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let { name, expr, .. } | Statement::Const { name, expr, .. } => {
+                self.expr(expr);
+                self.bind(name);
+            }
+            Statement::Destructure { pattern, expr, .. } => {
+                self.expr(expr);
+                self.bind_pattern(pattern);
+            }
+            Statement::Assignment { target, expr, .. } => {
+                self.expr(target);
+                self.expr(expr);
+            }
+            Statement::If {
+                cond,
+                then_block,
+                elif_blocks,
+                else_block,
+            } => {
+                self.expr(cond);
+                self.block(then_block);
+                for (cond, block) in elif_blocks {
+                    self.expr(cond);
+                    self.block(block);
+                }
+                if let Some(block) = else_block {
+                    self.block(block);
+                }
+            }
+            Statement::For {
+                var,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expr(iterable);
+                self.push_scope();
+                self.bind_pattern(var);
+                self.block(body);
+                self.pop_scope();
+            }
+            Statement::While { cond, body } => {
+                self.expr(cond);
+                self.block(body);
+            }
+            Statement::Return(expr) | Statement::Raise(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+            }
+            Statement::Yield(expr) | Statement::Expr(expr) => self.expr(expr),
+            Statement::Function(function) => {
+                self.bind(&function.name);
+                self.push_scope();
+                for param in &function.params {
+                    self.bind(&param.name);
+                }
+                self.block(&function.body);
+                self.pop_scope();
+            }
+            Statement::Block(block) => self.block(block),
+            Statement::Try {
+                body,
+                handlers,
+                else_block,
+                finally_block,
+            } => {
+                self.block(body);
+                for handler in handlers {
+                    self.push_scope();
+                    if let Some(alias) = &handler.alias {
+                        self.bind(alias);
+                    }
+                    self.block(&handler.body);
+                    self.pop_scope();
+                }
+                for block in [else_block, finally_block].into_iter().flatten() {
+                    self.block(block);
+                }
+            }
+            Statement::With {
+                manager,
+                alias,
+                body,
+            } => {
+                self.expr(manager);
+                self.push_scope();
+                if let Some(alias) = alias {
+                    self.bind(alias);
+                }
+                self.block(body);
+                self.pop_scope();
+            }
+            Statement::Break
+            | Statement::Continue
+            | Statement::Pass
+            | Statement::Struct { .. }
+            | Statement::Enum { .. }
+            | Statement::TypeAlias { .. }
+            | Statement::Trait { .. }
+            | Statement::Impl { .. }
+            | Statement::Use { .. }
+            | Statement::PubUse { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Identifier { name, .. } => self.use_name(name),
+            Expr::Member { object, .. } => self.expr(object),
+            Expr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            Expr::Slice {
+                object, start, end, ..
+            } => {
+                self.expr(object);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            }
+            Expr::Call { func, args, .. } => {
+                self.expr(func);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { expr, .. }
+            | Expr::Cast { expr, .. }
+            | Expr::Await(expr)
+            | Expr::Spawn(expr) => self.expr(expr),
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond);
+                self.expr(then_branch);
+                if let Some(branch) = else_branch {
+                    self.expr(branch);
+                }
+            }
+            Expr::Match { value, arms } => {
+                self.expr(value);
+                for arm in arms {
+                    self.push_scope();
+                    self.bind_pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                    self.pop_scope();
+                }
+            }
+            Expr::Range { start, end } => {
+                self.expr(start);
+                self.expr(end);
+            }
+            Expr::Array(items) | Expr::Tuple(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            Expr::Dict(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::ListComprehension {
+                element,
+                var,
+                iterable,
+                condition,
+            } => {
+                self.expr(iterable);
+                self.push_scope();
+                self.bind(var);
+                self.expr(element);
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                self.pop_scope();
+            }
+            Expr::DictComprehension {
+                key,
+                value,
+                var,
+                iterable,
+                condition,
+            } => {
+                self.expr(iterable);
+                self.push_scope();
+                self.bind(var);
+                self.expr(key);
+                self.expr(value);
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                self.pop_scope();
+            }
+            Expr::FString { parts } => {
+                for part in parts {
+                    if let FStringPart::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+            Expr::Lambda { params, body, .. } => {
+                self.captured.extend(free_variables(params, body));
+                // A nested lambda's captures are reads of the outer one too
+                self.push_scope();
+                for param in params {
+                    if let Some(default) = &param.default {
+                        self.expr(default);
+                    }
+                    self.bind(&param.name);
+                }
+                self.block(body);
+                self.pop_scope();
+            }
+            Expr::Struct { fields, .. } => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lambda_in(source: &str) -> (Vec<Param>, Block) {
+        let tokens = lexer::tokenize(source).expect("tokenize");
+        let program = parser::parse(&tokens).expect("parse");
+        match &program.statements[0] {
+            Statement::Let {
+                expr: Expr::Lambda { params, body, .. },
+                ..
+            } => (params.clone(), body.clone()),
+            other => panic!("expected a lambda binding, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lists_free_variables_in_evaluation_order() {
+        let (params, body) = lambda_in(
+            "let f = lambda (x: int): scale * x + [y * offset for y in items if y > x] + scale\n",
+        );
+        assert_eq!(free_variables(&params, &body), ["scale", "items", "offset"]);
+    }
+
+    #[test]
+    fn nested_lambdas_pass_captures_outwards() {
+        let (params, body) = lambda_in("let f = lambda (x: int): lambda (y: int): x + y + base\n");
+        assert_eq!(free_variables(&params, &body), ["base"]);
+    }
+
+    #[test]
+    fn finds_reassigned_locals() {
+        let source = "def main(limit: int):\n    let total = 0\n    let fixed = 1\n    for i in 0..3:\n        total = total + i\n    fixed.count = 2\n    limit -= 1\n";
+        let tokens = lexer::tokenize(source).expect("tokenize");
+        let program = parser::parse(&tokens).expect("parse");
+        let Statement::Function(main) = &program.statements[0] else {
+            panic!("expected function");
+        };
+        let names = reassigned_variables(&main.params, &main.body);
+        assert!(names.contains("total"));
+        assert!(names.contains("limit"));
+        assert!(!names.contains("fixed"));
+    }
+
+    #[test]
+    fn shares_only_captured_reassigned_locals() {
+        let source = "def main():
+    let count = 0
+    let step = 2
+    let other = 0
+    let bump = lambda (): count + step
+    count = count + 1
+    other = other + 1
+";
+        let tokens = lexer::tokenize(source).expect("tokenize");
+        let program = parser::parse(&tokens).expect("parse");
+        let Statement::Function(main) = &program.statements[0] else {
+            panic!("expected function");
+        };
+        let shared = shared_variables(&main.params, &main.body);
+        assert_eq!(shared, HashSet::from(["count".to_string()]));
+    }
+}
@@ -0,0 +1,641 @@
+//! Compile-time evaluation of expressions
+//!
+//! Folding follows the lowering in `llvm.rs`: number literals are floats,
+//! bitwise operators work on whole numbers as 64-bit integers, and mixing an
+//! integer with a float yields a float. [`fold`] only combines unsuffixed
+//! literals, since `255u8 + 1u8` must be lowered in its own width;
+//! [`ConstEnv`] also evaluates `const` declarations, f-strings, `as`
+//! conversions and calls to `const def` functions.
+
+use std::collections::HashMap;
+
+use ast::nodes::{
+    BinaryOp, Block, Expr, FStringPart, Function, Literal, NumericType, Pattern, Statement, Type,
+    UnaryOp,
+};
+
+use crate::runtime::stdlib::math::otter_std_math_ipow;
+
+/// Calls nested deeper than this are assumed to recurse forever
+const MAX_CALL_DEPTH: usize = 256;
+/// Loop iterations and calls one evaluation may run before it gives up
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl Constant {
+    /// The value as an integer, for the operands of bitwise operators
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            Constant::Int(value) => Some(value),
+            Constant::Float(value)
+                if value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64 =>
+            {
+                Some(value as i64)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match *self {
+            Constant::Int(value) => Some(value as f64),
+            Constant::Float(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Name of the value's type, for error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Constant::Int(_) => "int",
+            Constant::Float(_) => "float",
+            Constant::Bool(_) => "bool",
+            Constant::Str(_) => "str",
+        }
+    }
+
+    /// The value as an f-string renders it, matching the `otter_format_*`
+    /// runtime helpers
+    pub fn to_text(&self) -> String {
+        match self {
+            Constant::Int(value) => value.to_string(),
+            Constant::Float(value) => format!("{:.9}", value)
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string(),
+            Constant::Bool(value) => value.to_string(),
+            Constant::Str(value) => value.clone(),
+        }
+    }
+}
+
+/// Evaluate `expr` if it only combines literals
+pub fn fold(expr: &Expr) -> Option<Constant> {
+    match expr {
+        Expr::Literal(Literal::Number(number)) if number.suffix.is_none() => {
+            Some(Constant::Float(number.value))
+        }
+        Expr::Literal(Literal::Bool(value)) => Some(Constant::Bool(*value)),
+        Expr::Unary { op, expr } => fold_unary(*op, expr),
+        Expr::Binary { left, op, right } => fold_binary(left, *op, right),
+        _ => None,
+    }
+}
+
+pub fn fold_unary(op: UnaryOp, operand: &Expr) -> Option<Constant> {
+    unary(op, fold(operand)?)
+}
+
+pub fn fold_binary(left: &Expr, op: BinaryOp, right: &Expr) -> Option<Constant> {
+    binary(fold(left)?, op, fold(right)?)
+}
+
+fn unary(op: UnaryOp, value: Constant) -> Option<Constant> {
+    match (op, value) {
+        (UnaryOp::Neg, Constant::Int(value)) => Some(Constant::Int(value.wrapping_neg())),
+        (UnaryOp::Neg, Constant::Float(value)) => Some(Constant::Float(-value)),
+        (UnaryOp::Not, Constant::Bool(value)) => Some(Constant::Bool(!value)),
+        (UnaryOp::BitNot, value) => Some(Constant::Int(!value.as_int()?)),
+        _ => None,
+    }
+}
+
+/// `value as ty`; integer results must hold the value exactly
+fn cast(value: Constant, ty: &Type) -> Result<Constant, String> {
+    let target = match ty {
+        Type::Simple(name) if name == "int" => NumericType::I64,
+        Type::Simple(name) if name == "float" => NumericType::F64,
+        Type::Simple(name) => NumericType::from_name(name)
+            .ok_or_else(|| format!("cannot convert to `{}` with `as`", name))?,
+        _ => return Err("`as` converts only to number types".to_string()),
+    };
+    if target.is_float() {
+        let number = match value {
+            Constant::Bool(flag) => f64::from(u8::from(flag)),
+            ref other => other
+                .as_float()
+                .ok_or_else(|| format!("cannot convert {} to {}", other.kind(), target.name()))?,
+        };
+        return Ok(Constant::Float(number));
+    }
+    let whole = match value {
+        Constant::Int(value) => i128::from(value),
+        Constant::Bool(flag) => i128::from(flag),
+        Constant::Float(value) if value.is_finite() => value.trunc() as i128,
+        Constant::Float(value) => {
+            return Err(format!("{} does not fit in {}", value, target.name()));
+        }
+        Constant::Str(_) => return Err(format!("cannot convert str to {}", target.name())),
+    };
+    match target.int_range() {
+        // `Int` holds the bits of a `u64` above `i64::MAX`, as the lowering does
+        Some((min, max)) if (min..=max).contains(&whole) => Ok(Constant::Int(whole as i64)),
+        _ => Err(format!("{} does not fit in {}", whole, target.name())),
+    }
+}
+
+fn binary(left: Constant, op: BinaryOp, right: Constant) -> Option<Constant> {
+    if let (Constant::Str(lhs), Constant::Str(rhs)) = (&left, &right) {
+        return match op {
+            BinaryOp::Add => Some(Constant::Str(format!("{lhs}{rhs}"))),
+            _ => compare(lhs.cmp(rhs), op),
+        };
+    }
+
+    if let (Constant::Bool(lhs), Constant::Bool(rhs)) = (&left, &right) {
+        let (lhs, rhs) = (*lhs, *rhs);
+        return match op {
+            BinaryOp::And => Some(Constant::Bool(lhs && rhs)),
+            BinaryOp::Or => Some(Constant::Bool(lhs || rhs)),
+            BinaryOp::Eq | BinaryOp::Is => Some(Constant::Bool(lhs == rhs)),
+            BinaryOp::Ne | BinaryOp::IsNot => Some(Constant::Bool(lhs != rhs)),
+            _ => None,
+        };
+    }
+
+    match op {
+        BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shl | BinaryOp::Shr => {
+            let (lhs, rhs) = (left.as_int()?, right.as_int()?);
+            let value = match op {
+                BinaryOp::BitAnd => lhs & rhs,
+                BinaryOp::BitOr => lhs | rhs,
+                BinaryOp::BitXor => lhs ^ rhs,
+                // Shift amounts wrap at the width, as in the lowered code
+                BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                _ => lhs.wrapping_shr(rhs as u32),
+            };
+            Some(Constant::Int(value))
+        }
+        _ => match (&left, &right) {
+            (Constant::Int(lhs), Constant::Int(rhs)) => fold_int(*lhs, op, *rhs),
+            _ => fold_float(left.as_float()?, op, right.as_float()?),
+        },
+    }
+}
+
+fn fold_int(lhs: i64, op: BinaryOp, rhs: i64) -> Option<Constant> {
+    let value = match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        // Division by zero is left to fail at run time
+        BinaryOp::Div => lhs.checked_div(rhs)?,
+        BinaryOp::Mod => lhs.checked_rem(rhs)?,
+        BinaryOp::FloorDiv => {
+            let quotient = lhs.checked_div(rhs)?;
+            if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        }
+        BinaryOp::Pow => otter_std_math_ipow(lhs, rhs),
+        _ => return compare(lhs.cmp(&rhs), op),
+    };
+    Some(Constant::Int(value))
+}
+
+fn fold_float(lhs: f64, op: BinaryOp, rhs: f64) -> Option<Constant> {
+    let value = match op {
+        BinaryOp::Add => lhs + rhs,
+        BinaryOp::Sub => lhs - rhs,
+        BinaryOp::Mul => lhs * rhs,
+        BinaryOp::Div => lhs / rhs,
+        BinaryOp::Mod => lhs % rhs,
+        BinaryOp::FloorDiv => (lhs / rhs).floor(),
+        BinaryOp::Pow => libm::pow(lhs, rhs),
+        _ => return compare(lhs.partial_cmp(&rhs)?, op),
+    };
+    Some(Constant::Float(value))
+}
+
+fn compare(ordering: std::cmp::Ordering, op: BinaryOp) -> Option<Constant> {
+    use std::cmp::Ordering;
+    let value = match op {
+        BinaryOp::Eq | BinaryOp::Is => ordering == Ordering::Equal,
+        BinaryOp::Ne | BinaryOp::IsNot => ordering != Ordering::Equal,
+        BinaryOp::Lt => ordering == Ordering::Less,
+        BinaryOp::LtEq => ordering != Ordering::Greater,
+        BinaryOp::Gt => ordering == Ordering::Greater,
+        BinaryOp::GtEq => ordering != Ordering::Less,
+        _ => return None,
+    };
+    Some(Constant::Bool(value))
+}
+
+/// Constants and `const def` functions visible to compile-time evaluation
+#[derive(Debug, Default)]
+pub struct ConstEnv<'a> {
+    constants: HashMap<String, Constant>,
+    functions: HashMap<String, &'a Function>,
+}
+
+impl<'a> ConstEnv<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn define(&mut self, name: impl Into<String>, value: Constant) {
+        self.constants.insert(name.into(), value);
+    }
+
+    pub fn define_function(&mut self, function: &'a Function) {
+        self.functions.insert(function.name.clone(), function);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Constant> {
+        self.constants.get(name)
+    }
+
+    /// Evaluate `expr`, or explain why it is not a constant expression
+    pub fn evaluate(&self, expr: &Expr) -> Result<Constant, String> {
+        Evaluation {
+            env: self,
+            steps: 0,
+            depth: 0,
+        }
+        .expr(expr, &Locals::new())
+    }
+}
+
+/// Parameters and variables of the `const def` call being evaluated
+type Locals = HashMap<String, Constant>;
+
+/// How control leaves a statement of a `const def` body
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Option<Constant>),
+}
+
+struct Evaluation<'e, 'a> {
+    env: &'e ConstEnv<'a>,
+    steps: usize,
+    depth: usize,
+}
+
+impl Evaluation<'_, '_> {
+    fn step(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(format!(
+                "evaluation did not finish within {} steps",
+                MAX_STEPS
+            ));
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr, locals: &Locals) -> Result<Constant, String> {
+        match expr {
+            // The checker rejects a constant whose value its type cannot
+            // hold, which is how `255u8 + 1u8` fails here
+            Expr::Literal(Literal::Number(number)) => Ok(match number.suffix {
+                Some(suffix) if !suffix.is_float() => Constant::Int(number.int_value() as i64),
+                _ => Constant::Float(number.value),
+            }),
+            Expr::Literal(Literal::Bool(value)) => Ok(Constant::Bool(*value)),
+            Expr::Literal(Literal::String(value)) => Ok(Constant::Str(value.clone())),
+            Expr::Identifier { name, .. } => locals
+                .get(name)
+                .or_else(|| self.env.get(name))
+                .cloned()
+                .ok_or_else(|| format!("`{}` is not a constant", name)),
+            Expr::Unary { op, expr } => {
+                let value = self.expr(expr, locals)?;
+                let kind = value.kind();
+                unary(*op, value).ok_or_else(|| format!("cannot apply {:?} to {}", op, kind))
+            }
+            Expr::Binary { left, op, right } => {
+                let left = self.expr(left, locals)?;
+                // `and` and `or` skip their right operand, as at run time
+                match (op, &left) {
+                    (BinaryOp::And, Constant::Bool(false))
+                    | (BinaryOp::Or, Constant::Bool(true)) => {
+                        return Ok(left);
+                    }
+                    _ => {}
+                }
+                let right = self.expr(right, locals)?;
+                if matches!(op, BinaryOp::Div | BinaryOp::Mod | BinaryOp::FloorDiv)
+                    && right == Constant::Int(0)
+                {
+                    return Err("division by zero".to_string());
+                }
+                let kinds = (left.kind(), right.kind());
+                binary(left, *op, right)
+                    .ok_or_else(|| format!("cannot apply {:?} to {} and {}", op, kinds.0, kinds.1))
+            }
+            Expr::Cast { expr, ty } => cast(self.expr(expr, locals)?, ty),
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch: Some(else_branch),
+            } => {
+                if self.condition(cond, locals)? {
+                    self.expr(then_branch, locals)
+                } else {
+                    self.expr(else_branch, locals)
+                }
+            }
+            Expr::FString { parts } => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        FStringPart::Text(literal) => text.push_str(literal),
+                        FStringPart::Expr(expr) => {
+                            text.push_str(&self.expr(expr, locals)?.to_text())
+                        }
+                    }
+                }
+                Ok(Constant::Str(text))
+            }
+            Expr::Call { func, args, .. } => match func.as_ref() {
+                Expr::Identifier { name, .. } => self.call(name, args, locals),
+                _ => Err(
+                    "only `const def` functions can be called in a constant expression".to_string(),
+                ),
+            },
+            _ => Err("this expression cannot be evaluated at compile time".to_string()),
+        }
+    }
+
+    fn condition(&mut self, cond: &Expr, locals: &Locals) -> Result<bool, String> {
+        match self.expr(cond, locals)? {
+            Constant::Bool(value) => Ok(value),
+            other => Err(format!("condition must be bool, got {}", other.kind())),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], locals: &Locals) -> Result<Constant, String> {
+        let Some(function) = self.env.functions.get(name).copied() else {
+            return Err(format!("`{}` is not a `const def` function", name));
+        };
+        if args.len() > function.params.len() {
+            return Err(format!(
+                "`{}` takes {} arguments but {} were given",
+                name,
+                function.params.len(),
+                args.len()
+            ));
+        }
+
+        let mut frame = Locals::new();
+        for (index, param) in function.params.iter().enumerate() {
+            let value = match (args.get(index), &param.default) {
+                (Some(arg), _) => self.expr(arg, locals)?,
+                (None, Some(default)) => self.expr(default, &Locals::new())?,
+                (None, None) => {
+                    return Err(format!("missing argument `{}` to `{}`", param.name, name))
+                }
+            };
+            frame.insert(param.name.clone(), value);
+        }
+
+        self.step()?;
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(format!(
+                "calls to `{}` nest more than {} deep",
+                name, MAX_CALL_DEPTH
+            ));
+        }
+        self.depth += 1;
+        let flow = self.block(&function.body, &mut frame);
+        self.depth -= 1;
+        match flow? {
+            Flow::Return(Some(value)) => Ok(value),
+            _ => Err(format!("`{}` did not return a value", name)),
+        }
+    }
+
+    fn block(&mut self, block: &Block, locals: &mut Locals) -> Result<Flow, String> {
+        for statement in &block.statements {
+            let flow = self.statement(statement, locals)?;
+            if !matches!(flow, Flow::Next) {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn statement(&mut self, statement: &Statement, locals: &mut Locals) -> Result<Flow, String> {
+        match statement {
+            Statement::Let { name, expr, .. }
+            | Statement::Assignment {
+                target: Expr::Identifier { name, .. },
+                expr,
+                ..
+            } => {
+                let value = self.expr(expr, locals)?;
+                locals.insert(name.clone(), value);
+            }
+            Statement::If {
+                cond,
+                then_block,
+                elif_blocks,
+                else_block,
+            } => {
+                if self.condition(cond, locals)? {
+                    return self.block(then_block, locals);
+                }
+                for (cond, block) in elif_blocks {
+                    if self.condition(cond, locals)? {
+                        return self.block(block, locals);
+                    }
+                }
+                if let Some(block) = else_block {
+                    return self.block(block, locals);
+                }
+            }
+            Statement::While { cond, body } => {
+                while self.condition(cond, locals)? {
+                    self.step()?;
+                    match self.block(body, locals)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            Statement::For {
+                var: Pattern::Identifier(var),
+                iterable: Expr::Range { start, end },
+                body,
+                ..
+            } => {
+                let start = self.expr(start, locals)?;
+                let end = self.expr(end, locals)?;
+                let (Some(mut current), Some(end)) = (start.as_float(), end.as_float()) else {
+                    return Err("range bounds must be numbers".to_string());
+                };
+                // Ranges of integers count in integers, anything else in floats
+                let integral = matches!(start, Constant::Int(_));
+                while current < end {
+                    self.step()?;
+                    let value = if integral {
+                        Constant::Int(current as i64)
+                    } else {
+                        Constant::Float(current)
+                    };
+                    locals.insert(var.clone(), value);
+                    match self.block(body, locals)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                    current += 1.0;
+                }
+            }
+            Statement::Return(value) => {
+                let value = match value {
+                    Some(expr) => Some(self.expr(expr, locals)?),
+                    None => None,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Statement::Break => return Ok(Flow::Break),
+            Statement::Continue => return Ok(Flow::Continue),
+            Statement::Pass => {}
+            Statement::Expr(expr) => {
+                self.expr(expr, locals)?;
+            }
+            _ => return Err("this statement cannot run at compile time".to_string()),
+        }
+        Ok(Flow::Next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(source: &str) -> Option<Constant> {
+        let tokens = lexer::tokenize(&format!("let v = {source}\n")).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let ast::nodes::Statement::Let { expr, .. } = &program.statements[0] else {
+            panic!("expected let statement");
+        };
+        fold(expr)
+    }
+
+    #[test]
+    fn folds_bitwise_operators_on_integers() {
+        assert_eq!(constant("240 | 15"), Some(Constant::Int(255)));
+        assert_eq!(constant("1 << 4 ^ 3"), Some(Constant::Int(19)));
+        assert_eq!(constant("-16 >> 2"), Some(Constant::Int(-4)));
+        assert_eq!(constant("~0 & 255"), Some(Constant::Int(255)));
+        assert_eq!(constant("1.5 & 1"), None);
+    }
+
+    #[test]
+    fn folds_power_and_floor_division() {
+        assert_eq!(constant("2 ** 10"), Some(Constant::Float(1024.0)));
+        assert_eq!(constant("(1 << 1) ** (3 | 0)"), Some(Constant::Int(8)));
+        assert_eq!(constant("-7 // 2"), Some(Constant::Float(-4.0)));
+        assert_eq!(constant("(0 | 7) // (0 | -2)"), Some(Constant::Int(-4)));
+        assert_eq!(constant("(0 | 1) // (0 | 0)"), None);
+        assert_eq!(constant("1 + 2 > 2"), Some(Constant::Bool(true)));
+        assert_eq!(constant("x + 1"), None);
+    }
+
+    #[test]
+    fn evaluates_constants_and_const_functions() {
+        let source = r#"
+const def fib(n: int) -> int:
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+
+const def odd_sum(limit: int) -> int:
+    let sum = 0
+    for i in 0..limit:
+        if i % 2 == 0:
+            continue
+        sum += i
+    return sum
+
+def shout(s: str) -> str:
+    return s
+
+const KB: int = 1 << 10
+const NAME: str = "otter"
+const BANNER: str = f"{NAME} v{1.5}: {KB * 4} bytes"
+const FIB: int = fib(10)
+const ODD: int = odd_sum(10)
+const LOUD: str = shout(NAME)
+const BAD: int = (0 | 1) // (0 | 0)
+const LATER: int = MISSING + 1
+"#;
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut env = ConstEnv::new();
+        for statement in &program.statements {
+            if let Statement::Function(function) = statement {
+                if function.is_const {
+                    env.define_function(function);
+                }
+            }
+        }
+        let mut values = Vec::new();
+        for statement in &program.statements {
+            if let Statement::Const { name, expr, .. } = statement {
+                let value = env.evaluate(expr);
+                if let Ok(value) = &value {
+                    env.define(name.clone(), value.clone());
+                }
+                values.push(value);
+            }
+        }
+
+        assert_eq!(
+            values,
+            vec![
+                Ok(Constant::Int(1024)),
+                Ok(Constant::Str("otter".to_string())),
+                Ok(Constant::Str("otter v1.5: 4096 bytes".to_string())),
+                Ok(Constant::Float(55.0)),
+                Ok(Constant::Float(25.0)),
+                Err("`shout` is not a `const def` function".to_string()),
+                Err("division by zero".to_string()),
+                Err("`MISSING` is not a constant".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_sized_literals_to_the_lowering_but_evaluates_casts() {
+        assert_eq!(constant("255u8 + 1u8"), None);
+        assert_eq!(constant("-128i8"), None);
+
+        let env = ConstEnv::new();
+        let evaluate = |source: &str| {
+            let tokens = lexer::tokenize(&format!("const V: int = {source}\n")).unwrap();
+            let program = parser::parse(&tokens).unwrap();
+            let Statement::Const { expr, .. } = &program.statements[0] else {
+                panic!("expected const declaration");
+            };
+            env.evaluate(expr)
+        };
+        assert_eq!(evaluate("255u8"), Ok(Constant::Int(255)));
+        assert_eq!(evaluate("200 as u8"), Ok(Constant::Int(200)));
+        assert_eq!(evaluate("-3.9 as i8"), Ok(Constant::Int(-3)));
+        assert_eq!(evaluate("true as u8"), Ok(Constant::Int(1)));
+        assert_eq!(evaluate("1u8 as f32"), Ok(Constant::Float(1.0)));
+        assert_eq!(
+            evaluate("300 as u8"),
+            Err("300 does not fit in u8".to_string())
+        );
+        assert_eq!(
+            evaluate("-1 as u64"),
+            Err("-1 does not fit in u64".to_string())
+        );
+    }
+}
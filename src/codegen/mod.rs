@@ -1,10 +1,12 @@
+pub mod captures;
+pub mod const_fold;
 pub mod llvm;
 pub mod symbols;
 pub mod target;
 
 pub use llvm::{
     build_executable, build_shared_library, current_llvm_version, BuildArtifact, CodegenOptLevel,
-    CodegenOptions,
+    CodegenOptions, TestHarness,
 };
 pub use symbols::{FfiFunction, FfiSignature, FfiType, SymbolRegistry};
 pub use target::TargetTriple;
@@ -188,8 +188,11 @@ impl TargetTriple {
 #include <stdbool.h>
 #include <ctype.h>
 #ifndef _WIN32
+#include <errno.h>
 #include <sys/time.h>
 #include <sys/types.h>
+#include <sys/wait.h>
+#include <unistd.h>
 #else
 #define WIN32_LEAN_AND_MEAN
 #ifndef NOMINMAX
@@ -397,6 +400,12 @@ char* otter_format_int(int64_t value) {
     return buffer;
 }
 
+char* otter_format_uint(uint64_t value) {
+    char* buffer = (char*)malloc(32);
+    if (buffer) snprintf(buffer, 32, "%llu", (unsigned long long)value);
+    return buffer;
+}
+
 char* otter_format_bool(bool value) {
     const char* str = value ? "true" : "false";
     size_t len = strlen(str);
@@ -559,6 +568,41 @@ char* otter_std_fmt_stringify_int(int64_t value) {
 }
 
 
+// Reference-counted blocks for closure environments and captured cells.
+// The count lives in a header in front of the data compiled code sees.
+typedef void (*otter_rc_drop_fn)(void*);
+typedef struct {
+    size_t refs;
+    otter_rc_drop_fn drop;
+} otter_rc_header;
+#define OTTER_RC_OFFSET ((sizeof(otter_rc_header) + 15) & ~(size_t)15)
+
+void* otter_rc_alloc(int64_t size, otter_rc_drop_fn drop) {
+    if (size < 0) return NULL;
+    char* block = (char*)calloc(1, OTTER_RC_OFFSET + (size_t)size);
+    if (!block) return NULL;
+    otter_rc_header* header = (otter_rc_header*)block;
+    header->refs = 1;
+    header->drop = drop;
+    return block + OTTER_RC_OFFSET;
+}
+
+void otter_rc_retain(void* data) {
+    if (data) ((otter_rc_header*)((char*)data - OTTER_RC_OFFSET))->refs++;
+}
+
+void otter_rc_release(void* data) {
+    if (!data) return;
+    otter_rc_header* header = (otter_rc_header*)((char*)data - OTTER_RC_OFFSET);
+    if (--header->refs > 0) return;
+    if (header->drop) header->drop(data);
+    free(header);
+}
+
+void otter_rc_release_contents(void* data) {
+    otter_rc_release(*(void**)data);
+}
+
 int otter_validate_utf8(const char* ptr) {
     if (!ptr) return 0;
     while (*ptr) {
@@ -578,6 +622,370 @@ int otter_validate_utf8(const char* ptr) {
     }
     return 1;
 }
+
+// Test harness: the `main` of a test build calls this with its test table.
+// `--list` prints the test names; otherwise the named tests (all of them when
+// none are named) run one after another, each in a forked child so that a
+// failed assertion or a crash only ends that test. Every test is framed by
+// marker lines on stdout that `otter test` reads back.
+//
+// `fixtures` holds setup_module, teardown_module, setup and teardown, each
+// null when the file does not define it. The module fixtures run in the
+// harness process around its tests, so the tests see what setup_module did;
+// setup and teardown run in each test's child.
+#define OTTER_TEST_MARKER "\x1eotter-test "
+
+// Set while a test runs, so a failed assertion still runs its teardown
+static void (*otter_test_teardown)(void) = NULL;
+
+static void otter_test_exit(int code) {
+    void (*teardown)(void) = otter_test_teardown;
+    // A failure inside teardown itself ends the test straight away
+    otter_test_teardown = NULL;
+    if (teardown) teardown();
+    fflush(stdout);
+    fflush(stderr);
+    exit(code);
+}
+
+static void otter_test_call(void (*test)(void), void (**fixtures)(void)) {
+    if (fixtures[2]) fixtures[2]();
+    otter_test_teardown = fixtures[3];
+    test();
+    otter_test_teardown = NULL;
+    if (fixtures[3]) fixtures[3]();
+}
+
+static int64_t otter_test_now_us(void) {
+    struct timeval tv;
+    gettimeofday(&tv, NULL);
+    return (int64_t)tv.tv_sec * 1000000 + tv.tv_usec;
+}
+
+static int otter_test_run_one(void (*test)(void), void (**fixtures)(void)) {
+    fflush(stdout);
+    fflush(stderr);
+#ifndef _WIN32
+    pid_t pid = fork();
+    if (pid < 0) {
+        return -1;
+    }
+    if (pid == 0) {
+        dup2(STDOUT_FILENO, STDERR_FILENO);
+        otter_test_call(test, fixtures);
+        fflush(stdout);
+        _exit(0);
+    }
+    int status = 0;
+    while (waitpid(pid, &status, 0) < 0) {
+        if (errno != EINTR) return -1;
+    }
+    if (WIFEXITED(status)) return WEXITSTATUS(status);
+    if (WIFSIGNALED(status)) return 128 + WTERMSIG(status);
+    return -1;
+#else
+    // Without fork a failing test ends the harness; `otter test` starts a
+    // new one for the tests that did not run
+    otter_test_call(test, fixtures);
+    fflush(stdout);
+    return 0;
+#endif
+}
+
+int otter_test_harness(int argc, char** argv, const char** names, void (**tests)(void), int64_t count,
+                       void (**fixtures)(void)) {
+    if (argc > 1 && strcmp(argv[1], "--list") == 0) {
+        for (int64_t i = 0; i < count; i++) {
+            printf("%s\n", names[i]);
+        }
+        return 0;
+    }
+
+    if (fixtures[0]) fixtures[0]();
+    fflush(stdout);
+    int failed = 0;
+    int64_t requested = argc > 1 ? (int64_t)(argc - 1) : count;
+    for (int64_t i = 0; i < requested; i++) {
+        int64_t index = argc > 1 ? -1 : i;
+        for (int64_t j = 0; index < 0 && j < count; j++) {
+            if (strcmp(argv[i + 1], names[j]) == 0) index = j;
+        }
+        if (index < 0) {
+            printf(OTTER_TEST_MARKER "missing %s\n", argv[i + 1]);
+            failed = 1;
+            continue;
+        }
+
+        printf(OTTER_TEST_MARKER "start %s\n", names[index]);
+        int64_t start = otter_test_now_us();
+        int code = otter_test_run_one(tests[index], fixtures);
+        int64_t elapsed = otter_test_now_us() - start;
+        printf(OTTER_TEST_MARKER "end %s %d %lld\n", names[index], code, (long long)elapsed);
+        fflush(stdout);
+        if (code != 0) failed = 1;
+    }
+    if (fixtures[1]) fixtures[1]();
+    return failed;
+}
+
+// Test assertions. Codegen calls `otter_test_set_location` before each
+// `test.assert*` call, so a failure can say where it happened and show the
+// asserted call's source text
+static const char* otter_test_file = NULL;
+static int64_t otter_test_line = 0;
+static int64_t otter_test_column = 0;
+static const char* otter_test_source = NULL;
+
+void otter_test_set_location(const char* file, int64_t line, int64_t column, const char* source) {
+    otter_test_file = file;
+    otter_test_line = line;
+    otter_test_column = column;
+    otter_test_source = source;
+}
+
+// CLICOLOR_FORCE wins over NO_COLOR, which wins over whether stderr is a
+// terminal; `otter test` sets one of the two for its harnesses
+static int otter_test_color(void) {
+    const char* force = getenv("CLICOLOR_FORCE");
+    if (force && strcmp(force, "0") != 0) return 1;
+    if (getenv("NO_COLOR")) return 0;
+#ifndef _WIN32
+    return isatty(STDERR_FILENO);
+#else
+    return 0;
+#endif
+}
+
+#define OTTER_TEST_GREEN "\x1b[32m"
+#define OTTER_TEST_RED "\x1b[31m"
+#define OTTER_TEST_RESET "\x1b[0m"
+
+static void otter_test_print_location(void) {
+    if (!otter_test_file) return;
+    // For `otter test`, which reports the failure at this line
+    printf(OTTER_TEST_MARKER "location %lld %lld\n", (long long)otter_test_line,
+           (long long)otter_test_column);
+    fflush(stdout);
+    fprintf(stderr, "Assertion failed at %s:%lld:%lld\n", otter_test_file,
+            (long long)otter_test_line, (long long)otter_test_column);
+    if (!otter_test_source) return;
+    const char* line = otter_test_source;
+    while (*line) {
+        size_t len = strcspn(line, "\n");
+        fprintf(stderr, "    %.*s\n", (int)len, line);
+        line += len;
+        if (*line == '\n') line++;
+    }
+}
+
+typedef struct {
+    const char* start;
+    size_t len;
+} OtterTestPiece;
+
+static int otter_test_piece_cmp(const void* a, const void* b) {
+    const OtterTestPiece* x = (const OtterTestPiece*)a;
+    const OtterTestPiece* y = (const OtterTestPiece*)b;
+    size_t len = x->len < y->len ? x->len : y->len;
+    int order = memcmp(x->start, y->start, len);
+    if (order != 0) return order;
+    return x->len < y->len ? -1 : x->len > y->len;
+}
+
+// Split a value into the lines the diff compares: the items of a `[...]`
+// list, the entries of a `{...}` dict (sorted, so key order does not count
+// as a difference) or the lines of a multi-line string. Returns 0 for a
+// value shown as a single line
+static size_t otter_test_split(const char* value, OtterTestPiece** out) {
+    size_t len = strlen(value);
+    size_t count = 0;
+    OtterTestPiece* pieces = NULL;
+    char open = len >= 2 ? value[0] : 0;
+    char close = len >= 2 ? value[len - 1] : 0;
+    int sequence = (open == '[' && close == ']') || (open == '{' && close == '}');
+
+    if (sequence) {
+        pieces = (OtterTestPiece*)malloc(sizeof(OtterTestPiece) * len);
+        if (!pieces) return 0;
+        int depth = 0;
+        char quote = 0;
+        size_t item = 1;
+        for (size_t i = 1; i < len; i++) {
+            char c = value[i];
+            if (quote) {
+                if (c == '\\' && i + 1 < len) i++;
+                else if (c == quote) quote = 0;
+                continue;
+            }
+            if (c == '"' || c == '\'') quote = c;
+            else if (c == '[' || c == '{' || c == '(') depth++;
+            else if ((c == ']' || c == '}' || c == ')') && depth > 0) depth--;
+            else if ((c == ',' && depth == 0) || i == len - 1) {
+                while (item < i && value[item] == ' ') item++;
+                if (i > item) {
+                    pieces[count].start = value + item;
+                    pieces[count].len = i - item;
+                    count++;
+                }
+                item = i + 1;
+            }
+        }
+        if (open == '{') qsort(pieces, count, sizeof(OtterTestPiece), otter_test_piece_cmp);
+    } else if (strchr(value, '\n')) {
+        pieces = (OtterTestPiece*)malloc(sizeof(OtterTestPiece) * (len + 1));
+        if (!pieces) return 0;
+        const char* line = value;
+        for (;;) {
+            size_t line_len = strcspn(line, "\n");
+            pieces[count].start = line;
+            pieces[count].len = line_len;
+            count++;
+            if (line[line_len] == 0) break;
+            line += line_len + 1;
+        }
+    }
+    *out = pieces;
+    return count;
+}
+
+// Line diff through a longest common subsequence table; inputs too large
+// for the table are shown as all removed, then all added
+static void otter_test_print_diff(OtterTestPiece* expected, size_t n, OtterTestPiece* actual, size_t m, int color) {
+    const char* green = color ? OTTER_TEST_GREEN : "";
+    const char* red = color ? OTTER_TEST_RED : "";
+    const char* reset = color ? OTTER_TEST_RESET : "";
+    uint32_t* table = NULL;
+    if (n <= 1000 && m <= 1000) {
+        table = (uint32_t*)calloc((n + 1) * (m + 1), sizeof(uint32_t));
+    }
+    if (table) {
+        for (size_t i = n; i-- > 0;) {
+            for (size_t j = m; j-- > 0;) {
+                uint32_t* cell = &table[i * (m + 1) + j];
+                if (otter_test_piece_cmp(&expected[i], &actual[j]) == 0) {
+                    *cell = table[(i + 1) * (m + 1) + j + 1] + 1;
+                } else {
+                    uint32_t down = table[(i + 1) * (m + 1) + j];
+                    uint32_t right = table[i * (m + 1) + j + 1];
+                    *cell = down > right ? down : right;
+                }
+            }
+        }
+    }
+
+    fprintf(stderr, "  diff (%s- expected%s, %s+ actual%s):\n", green, reset, red, reset);
+    size_t i = 0, j = 0;
+    while (i < n || j < m) {
+        if (table && i < n && j < m && otter_test_piece_cmp(&expected[i], &actual[j]) == 0) {
+            fprintf(stderr, "      %.*s\n", (int)expected[i].len, expected[i].start);
+            i++;
+            j++;
+        } else if (i < n && (j == m || !table || table[(i + 1) * (m + 1) + j] >= table[i * (m + 1) + j + 1])) {
+            fprintf(stderr, "    %s- %.*s%s\n", green, (int)expected[i].len, expected[i].start, reset);
+            i++;
+        } else {
+            fprintf(stderr, "    %s+ %.*s%s\n", red, (int)actual[j].len, actual[j].start, reset);
+            j++;
+        }
+    }
+    free(table);
+}
+
+// Show where two unequal values differ: a line diff for lists, dicts and
+// multi-line strings, otherwise both values with the differing middle
+// highlighted (or marked with a caret without color)
+static void otter_test_print_difference(const char* expected, const char* actual) {
+    int color = otter_test_color();
+    OtterTestPiece* expected_pieces = NULL;
+    OtterTestPiece* actual_pieces = NULL;
+    size_t n = otter_test_split(expected, &expected_pieces);
+    size_t m = otter_test_split(actual, &actual_pieces);
+    if (n > 0 && m > 0) {
+        otter_test_print_diff(expected_pieces, n, actual_pieces, m, color);
+        free(expected_pieces);
+        free(actual_pieces);
+        return;
+    }
+    free(expected_pieces);
+    free(actual_pieces);
+
+    size_t expected_len = strlen(expected);
+    size_t actual_len = strlen(actual);
+    size_t prefix = 0;
+    while (prefix < expected_len && prefix < actual_len && expected[prefix] == actual[prefix]) prefix++;
+    // Keep multi-byte UTF-8 characters whole
+    while (prefix > 0 && ((unsigned char)expected[prefix] & 0xC0) == 0x80) prefix--;
+    size_t suffix = 0;
+    while (suffix < expected_len - prefix && suffix < actual_len - prefix &&
+           expected[expected_len - 1 - suffix] == actual[actual_len - 1 - suffix]) suffix++;
+    while (suffix > 0 && ((unsigned char)expected[expected_len - suffix] & 0xC0) == 0x80) suffix--;
+
+    const char* values[2] = {expected, actual};
+    size_t lens[2] = {expected_len, actual_len};
+    const char* labels[2] = {"expected: ", "actual:   "};
+    const char* colors[2] = {OTTER_TEST_GREEN, OTTER_TEST_RED};
+    for (int k = 0; k < 2; k++) {
+        int middle = (int)(lens[k] - prefix - suffix);
+        fprintf(stderr, "  %s'%.*s%s%.*s%s%s'\n", labels[k], (int)prefix, values[k],
+                color ? colors[k] : "", middle, values[k] + prefix, color ? OTTER_TEST_RESET : "",
+                values[k] + lens[k] - suffix);
+    }
+    if (!color) {
+        size_t column = 0;
+        for (size_t i = 0; i < prefix; i++) {
+            if (((unsigned char)expected[i] & 0xC0) != 0x80) column++;
+        }
+        fprintf(stderr, "  %*s^\n", (int)(strlen(labels[0]) + 1 + column), "");
+    }
+}
+
+static void otter_test_fail(const char* message, const char* detail) {
+    // Keep what the test printed ahead of the failure
+    fflush(stdout);
+    otter_test_print_location();
+    if (message) fprintf(stderr, "%s: %s\n", message, detail);
+    else fprintf(stderr, "Assertion failed: %s\n", detail);
+}
+
+int32_t otter_test_assert(int64_t condition, const char* message) {
+    if (condition != 0) return 0;
+    otter_test_fail(message, "condition is false");
+    otter_test_exit(1);
+    return 1;
+}
+
+int32_t otter_test_assert_true(int64_t condition, const char* message) {
+    return otter_test_assert(condition, message);
+}
+
+int32_t otter_test_assert_false(int64_t condition, const char* message) {
+    return otter_test_assert(condition == 0, message);
+}
+
+int32_t otter_test_assert_eq(const char* left, const char* right, const char* message) {
+    if (strcmp(left, right) == 0) return 0;
+    otter_test_fail(message, "values are not equal");
+    otter_test_print_difference(right, left);
+    otter_test_exit(1);
+    return 1;
+}
+
+int32_t otter_test_assert_ne(const char* left, const char* right, const char* message) {
+    if (strcmp(left, right) != 0) return 0;
+    otter_test_fail(message, "values should not be equal");
+    fprintf(stderr, "  both:     '%s'\n", left);
+    otter_test_exit(1);
+    return 1;
+}
+
+int32_t otter_test_assert_approx_eq(double left, double right, double epsilon, const char* message) {
+    double diff = left > right ? left - right : right - left;
+    if (diff <= epsilon) return 0;
+    otter_test_fail(message, "values are not approximately equal");
+    fprintf(stderr, "  expected: %g\n  actual:   %g\n  diff:     %g (epsilon: %g)\n", right, left, diff, epsilon);
+    otter_test_exit(1);
+    return 1;
+}
 "#.to_string()
     }
 
@@ -804,6 +1212,10 @@ char* otter_format_int(int64_t value) {
     return otter_format_signed_uint(magnitude, negative);
 }
 
+char* otter_format_uint(uint64_t value) {
+    return otter_format_signed_uint(value, false);
+}
+
 char* otter_format_float(double value) {
     if (isnan(value)) return otter_dup_cstr("nan");
     if (isinf(value)) return value > 0 ? otter_dup_cstr("inf") : otter_dup_cstr("-inf");
@@ -974,6 +1386,41 @@ char* otter_std_fmt_stringify_int(int64_t value) {
     return otter_format_int(value);
 }
 
+// Reference-counted blocks for closure environments and captured cells.
+// The count lives in a header in front of the data compiled code sees.
+typedef void (*otter_rc_drop_fn)(void*);
+typedef struct {
+    size_t refs;
+    otter_rc_drop_fn drop;
+} otter_rc_header;
+#define OTTER_RC_OFFSET ((sizeof(otter_rc_header) + 15) & ~(size_t)15)
+
+void* otter_rc_alloc(int64_t size, otter_rc_drop_fn drop) {
+    if (size < 0) return NULL;
+    char* block = (char*)calloc(1, OTTER_RC_OFFSET + (size_t)size);
+    if (!block) return NULL;
+    otter_rc_header* header = (otter_rc_header*)block;
+    header->refs = 1;
+    header->drop = drop;
+    return block + OTTER_RC_OFFSET;
+}
+
+void otter_rc_retain(void* data) {
+    if (data) ((otter_rc_header*)((char*)data - OTTER_RC_OFFSET))->refs++;
+}
+
+void otter_rc_release(void* data) {
+    if (!data) return;
+    otter_rc_header* header = (otter_rc_header*)((char*)data - OTTER_RC_OFFSET);
+    if (--header->refs > 0) return;
+    if (header->drop) header->drop(data);
+    free(header);
+}
+
+void otter_rc_release_contents(void* data) {
+    otter_rc_release(*(void**)data);
+}
+
 int otter_validate_utf8(const char* ptr) {
     if (!ptr) return 0;
     while (*ptr) {
@@ -1085,6 +1532,13 @@ char* otter_format_int(int64_t value) {
     return buffer;
 }
 
+char* otter_format_uint(uint64_t value) {
+    // Minimal implementation
+    char* buffer = (char*)malloc(32);
+    if (buffer) buffer[0] = '\0';
+    return buffer;
+}
+
 char* otter_format_bool(bool value) {
     const char* str = value ? "true" : "false";
     char* buffer = (char*)malloc(strlen(str) + 1);
@@ -1107,6 +1561,41 @@ void otter_free_string(char* ptr) {
     if (ptr) free(ptr);
 }
 
+// Reference-counted blocks for closure environments and captured cells.
+// The count lives in a header in front of the data compiled code sees.
+typedef void (*otter_rc_drop_fn)(void*);
+typedef struct {
+    size_t refs;
+    otter_rc_drop_fn drop;
+} otter_rc_header;
+#define OTTER_RC_OFFSET ((sizeof(otter_rc_header) + 15) & ~(size_t)15)
+
+void* otter_rc_alloc(int64_t size, otter_rc_drop_fn drop) {
+    if (size < 0) return NULL;
+    char* block = (char*)calloc(1, OTTER_RC_OFFSET + (size_t)size);
+    if (!block) return NULL;
+    otter_rc_header* header = (otter_rc_header*)block;
+    header->refs = 1;
+    header->drop = drop;
+    return block + OTTER_RC_OFFSET;
+}
+
+void otter_rc_retain(void* data) {
+    if (data) ((otter_rc_header*)((char*)data - OTTER_RC_OFFSET))->refs++;
+}
+
+void otter_rc_release(void* data) {
+    if (!data) return;
+    otter_rc_header* header = (otter_rc_header*)((char*)data - OTTER_RC_OFFSET);
+    if (--header->refs > 0) return;
+    if (header->drop) header->drop(data);
+    free(header);
+}
+
+void otter_rc_release_contents(void* data) {
+    otter_rc_release(*(void**)data);
+}
+
 int otter_validate_utf8(const char* ptr) {
     if (!ptr) return 0;
     while (*ptr) {
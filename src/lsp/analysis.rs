@@ -0,0 +1,177 @@
+//! Cached per-document analysis and incremental text synchronization.
+//!
+//! Each open document is lexed, parsed and type checked once per version;
+//! requests share the result until the next edit to that document drops it.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{Diagnostic, TextDocumentContentChangeEvent};
+
+use super::signature_help::{self, DocumentSignatures};
+use super::{
+    build_symbol_table, lexer_error_to_diag, otter_diag_to_lsp, position_to_offset, SymbolTable,
+};
+use crate::runtime::symbol_registry::SymbolRegistry;
+use crate::typecheck::{self, TypeChecker, TypeInfo};
+use ast::nodes::{Expr, Program};
+use lexer::{tokenize, Token};
+use parser::parse;
+
+/// Everything the server knows about one version of a document
+#[derive(Debug)]
+pub(crate) struct Analysis {
+    pub(crate) version: i32,
+    pub(crate) text: String,
+    /// `None` when the document does not lex
+    pub(crate) tokens: Option<Vec<Token>>,
+    /// `None` when the document does not parse
+    pub(crate) program: Option<Program>,
+    /// Inferred expression types, keyed by the address of the expression in
+    /// `program`
+    expr_types: HashMap<usize, TypeInfo>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    pub(crate) symbol_table: SymbolTable,
+    pub(crate) signatures: Option<DocumentSignatures>,
+}
+
+impl Analysis {
+    pub(crate) fn new(text: String, version: i32) -> Self {
+        let source_id = "lsp";
+        let mut analysis = Self {
+            version,
+            text,
+            tokens: None,
+            program: None,
+            expr_types: HashMap::new(),
+            diagnostics: Vec::new(),
+            symbol_table: SymbolTable::new(),
+            signatures: None,
+        };
+        let text = analysis.text.as_str();
+
+        let tokens = match tokenize(text) {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                analysis.diagnostics = errors
+                    .iter()
+                    .map(|err| otter_diag_to_lsp(&lexer_error_to_diag(source_id, err), text))
+                    .collect();
+                return analysis;
+            }
+        };
+        let program = match parse(&tokens) {
+            Ok(program) => program,
+            Err(errors) => {
+                analysis.diagnostics = errors
+                    .into_iter()
+                    .map(|err| otter_diag_to_lsp(&err.to_diagnostic(source_id), text))
+                    .collect();
+                analysis.tokens = Some(tokens);
+                return analysis;
+            }
+        };
+
+        analysis.symbol_table = build_symbol_table(&program, &tokens, text);
+        let mut checker = TypeChecker::new().with_registry(SymbolRegistry::global());
+        if checker.check_program(&program).is_err() {
+            analysis.diagnostics =
+                typecheck::diagnostics_from_type_errors(checker.errors(), source_id, text)
+                    .into_iter()
+                    .map(|diag| otter_diag_to_lsp(&diag, text))
+                    .collect();
+        }
+        analysis.signatures = Some(signature_help::document_signatures(&program, &checker));
+        // The statements live on the heap, so the keys stay valid once the
+        // program moves into the analysis
+        analysis.expr_types = checker.into_expr_type_map();
+        analysis.tokens = Some(tokens);
+        analysis.program = Some(program);
+        analysis
+    }
+
+    /// The inferred type of `expr`, an expression of `self.program`
+    pub(crate) fn type_of(&self, expr: &Expr) -> Option<&TypeInfo> {
+        self.expr_types.get(&(expr as *const Expr as usize))
+    }
+
+    pub(crate) fn expr_types(&self) -> &HashMap<usize, TypeInfo> {
+        &self.expr_types
+    }
+}
+
+/// Apply one `didChange` content change: a replaced range, or the whole text
+/// when the change has no range
+pub(crate) fn apply_change(text: &mut String, change: TextDocumentContentChangeEvent) {
+    match change.range {
+        Some(range) => {
+            let start = position_to_offset(text, range.start);
+            let end = position_to_offset(text, range.end).max(start);
+            text.replace_range(start..end, &change.text);
+        }
+        None => *text = change.text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::nodes::Statement;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    fn change(range: Option<Range>, text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn ranged_changes_edit_in_place() {
+        let mut text = "let café = 1\nprint(café)\n".to_string();
+        let range = Range::new(Position::new(0, 11), Position::new(0, 12));
+        apply_change(&mut text, change(Some(range), "42"));
+        assert_eq!(text, "let café = 42\nprint(café)\n");
+
+        // An insertion at the end of the document
+        let range = Range::new(Position::new(2, 0), Position::new(2, 0));
+        apply_change(&mut text, change(Some(range), "print(1)\n"));
+        assert_eq!(text, "let café = 42\nprint(café)\nprint(1)\n");
+
+        apply_change(&mut text, change(None, "pass\n"));
+        assert_eq!(text, "pass\n");
+    }
+
+    #[test]
+    fn ranged_changes_count_utf16_code_units() {
+        // The emoji is one character but two UTF-16 code units
+        let mut text = "let s = \"🦦\"\nprint(s)\n".to_string();
+        let range = Range::new(Position::new(0, 12), Position::new(0, 12));
+        apply_change(&mut text, change(Some(range), " + \"!\""));
+        assert_eq!(text, "let s = \"🦦\" + \"!\"\nprint(s)\n");
+
+        let range = Range::new(Position::new(0, 13), Position::new(0, 18));
+        apply_change(&mut text, change(Some(range), "* 2"));
+        assert_eq!(text, "let s = \"🦦\" * 2\nprint(s)\n");
+    }
+
+    #[test]
+    fn analysis_keeps_tokens_program_and_types() {
+        let analysis = Analysis::new("let ratio = 1.5 * 2.0\n".to_string(), 3);
+        assert_eq!(analysis.version, 3);
+        assert!(analysis.diagnostics.is_empty());
+        assert!(analysis.symbol_table.find_definition("ratio").is_some());
+
+        let program = analysis.program.as_ref().expect("program");
+        let Statement::Let { expr, .. } = &program.statements[0] else {
+            panic!("expected let statement");
+        };
+        assert_eq!(analysis.type_of(expr), Some(&TypeInfo::F64));
+
+        // Documents that do not parse still have tokens for signature help
+        let analysis = Analysis::new("let x = scale(1.0, \n".to_string(), 4);
+        assert!(analysis.tokens.is_some());
+        assert!(analysis.program.is_none());
+        assert!(!analysis.diagnostics.is_empty());
+    }
+}
@@ -0,0 +1,1050 @@
+//! Code actions: annotating `let` bindings with their inferred type,
+//! extracting statements into a function and managing imports.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use once_cell::sync::Lazy;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionDisabled, CodeActionKind, CodeActionOrCommand, Diagnostic, Position,
+    Range, TextEdit, Url, WorkspaceEdit,
+};
+
+use super::analysis::Analysis;
+use super::inlay_hints::type_label;
+use super::{offset_to_position, position_to_offset};
+use crate::typecheck::TypeInfo;
+use ast::nodes::{Block, Expr, FStringPart, Function, Program, Statement};
+use lexer::token::TokenKind;
+use lexer::{tokenize, Token};
+use parser::parse;
+
+/// Indentation of the body of an extracted function
+const INDENT: &str = "    ";
+
+/// Names of the modules under the standard library directory, such as `math`
+static STDLIB_MODULES: Lazy<HashSet<String>> = Lazy::new(|| {
+    let Ok(entries) = crate::cli::find_stdlib_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) else {
+        return HashSet::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ot"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect()
+});
+
+/// The actions available for `range` of the analyzed document `uri`
+pub(crate) fn code_actions(
+    uri: &Url,
+    analysis: &Analysis,
+    range: Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let (Some(tokens), Some(program)) = (&analysis.tokens, &analysis.program) else {
+        return Vec::new();
+    };
+    let text = analysis.text.as_str();
+    let start = position_to_offset(text, range.start);
+    let end = position_to_offset(text, range.end);
+
+    let mut actions = Vec::new();
+    actions.extend(add_type_annotations(analysis, program, start, end));
+    if start < end {
+        actions.extend(extract_function(text, tokens, program, range));
+    }
+    actions.extend(import_missing_modules(
+        text,
+        tokens,
+        program,
+        range,
+        diagnostics,
+    ));
+    actions.extend(remove_unused_imports(text, tokens, program, range));
+
+    actions
+        .into_iter()
+        .map(|action| CodeActionOrCommand::CodeAction(action.into_code_action(uri)))
+        .collect()
+}
+
+struct Action {
+    title: String,
+    kind: CodeActionKind,
+    edits: Vec<TextEdit>,
+    /// Diagnostics the action resolves
+    diagnostics: Vec<Diagnostic>,
+    /// Why the action cannot be applied here
+    disabled: Option<String>,
+}
+
+impl Action {
+    fn new(title: String, kind: CodeActionKind, edits: Vec<TextEdit>) -> Self {
+        Self {
+            title,
+            kind,
+            edits,
+            diagnostics: Vec::new(),
+            disabled: None,
+        }
+    }
+
+    fn disabled(title: String, kind: CodeActionKind, reason: String) -> Self {
+        Self {
+            disabled: Some(reason),
+            ..Self::new(title, kind, Vec::new())
+        }
+    }
+
+    fn into_code_action(self, uri: &Url) -> CodeAction {
+        let enabled = self.disabled.is_none();
+        CodeAction {
+            title: self.title,
+            is_preferred: Some(enabled && !self.diagnostics.is_empty()),
+            kind: Some(self.kind),
+            diagnostics: (!self.diagnostics.is_empty()).then_some(self.diagnostics),
+            edit: enabled.then(|| WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), self.edits)])),
+                ..WorkspaceEdit::default()
+            }),
+            command: None,
+            disabled: self.disabled.map(|reason| CodeActionDisabled { reason }),
+            data: None,
+        }
+    }
+}
+
+fn insert(text: &str, offset: usize, new_text: String) -> TextEdit {
+    let position = offset_to_position(text, offset);
+    TextEdit {
+        range: Range::new(position, position),
+        new_text,
+    }
+}
+
+fn replace_lines(first: usize, last: usize, new_text: String) -> TextEdit {
+    TextEdit {
+        range: Range::new(
+            Position::new(first as u32, 0),
+            Position::new(last as u32 + 1, 0),
+        ),
+        new_text,
+    }
+}
+
+/// "Add type annotation" for unannotated `let` bindings whose name is in the
+/// range and whose type the checker inferred
+fn add_type_annotations(
+    analysis: &Analysis,
+    program: &Program,
+    start: usize,
+    end: usize,
+) -> Vec<Action> {
+    let mut lets = Vec::new();
+    collect_lets(&program.statements, &mut lets);
+
+    lets.into_iter()
+        .filter_map(|statement| {
+            let Statement::Let {
+                name,
+                ty: None,
+                expr,
+                span: Some(span),
+                ..
+            } = statement
+            else {
+                return None;
+            };
+            if span.end() < start || span.start() > end {
+                return None;
+            }
+            let ty = analysis.type_of(expr)?;
+            if *ty == TypeInfo::Unit {
+                return None;
+            }
+            let label = type_label(ty)?;
+            Some(Action::new(
+                format!("Add type annotation `{}: {}`", name, label),
+                CodeActionKind::REFACTOR_REWRITE,
+                vec![insert(&analysis.text, span.end(), format!(": {}", label))],
+            ))
+        })
+        .collect()
+}
+
+fn collect_lets<'a>(statements: &'a [Statement], lets: &mut Vec<&'a Statement>) {
+    for statement in statements {
+        match statement {
+            Statement::Let { .. } => lets.push(statement),
+            Statement::If {
+                then_block,
+                elif_blocks,
+                else_block,
+                ..
+            } => {
+                collect_lets(&then_block.statements, lets);
+                for (_, block) in elif_blocks {
+                    collect_lets(&block.statements, lets);
+                }
+                if let Some(block) = else_block {
+                    collect_lets(&block.statements, lets);
+                }
+            }
+            Statement::For { body, .. }
+            | Statement::While { body, .. }
+            | Statement::With { body, .. }
+            | Statement::Block(body) => collect_lets(&body.statements, lets),
+            Statement::Function(function) => collect_lets(&function.body.statements, lets),
+            Statement::Struct { methods, .. }
+            | Statement::Trait { methods, .. }
+            | Statement::Impl { methods, .. } => {
+                for method in methods {
+                    collect_lets(&method.body.statements, lets);
+                }
+            }
+            Statement::Try {
+                body,
+                handlers,
+                else_block,
+                finally_block,
+            } => {
+                collect_lets(&body.statements, lets);
+                for handler in handlers {
+                    collect_lets(&handler.body.statements, lets);
+                }
+                for block in else_block.iter().chain(finally_block) {
+                    collect_lets(&block.statements, lets);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// "Extract function" for the whole lines the selection touches. The
+/// variables the lines read from the enclosing function become parameters and
+/// a variable they assign that is read afterwards becomes the return value.
+fn extract_function(
+    text: &str,
+    tokens: &[Token],
+    program: &Program,
+    range: Range,
+) -> Option<Action> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut first = range.start.line as usize;
+    // A selection ending at the start of a line does not include that line
+    let mut last = if range.end.character == 0 && range.end.line > range.start.line {
+        range.end.line as usize - 1
+    } else {
+        range.end.line as usize
+    };
+    last = last.min(lines.len().checked_sub(1)?);
+    while first < last && lines[first].trim().is_empty() {
+        first += 1;
+    }
+    while last > first && lines[last].trim().is_empty() {
+        last -= 1;
+    }
+    if lines[first].trim().is_empty() {
+        return None;
+    }
+
+    // The selection must be whole statements of a single block
+    let base = indentation(lines[first]);
+    if lines[first..=last]
+        .iter()
+        .any(|line| !line.trim().is_empty() && indentation(line) < base)
+    {
+        return None;
+    }
+    let continues_block = |line: &str| {
+        let code = line.trim_start();
+        ["elif", "else", "except", "finally", "case"]
+            .iter()
+            .any(|keyword| code.starts_with(keyword))
+    };
+    if continues_block(lines[first]) {
+        return None;
+    }
+    if let Some(next) = lines[last + 1..].iter().find(|line| !is_blank(line)) {
+        if indentation(next) > base || (indentation(next) == base && continues_block(next)) {
+            return None;
+        }
+    }
+
+    let snippet: String = lines[first..=last]
+        .iter()
+        .map(|line| {
+            format!(
+                "{}\n",
+                line.get(base..).unwrap_or("").trim_end_matches('\r')
+            )
+        })
+        .collect();
+    let snippet_program = tokenize(&snippet)
+        .ok()
+        .and_then(|tokens| parse(&tokens).ok())?;
+    let mut selected = NameUse::default();
+    selected.statements(&snippet_program.statements);
+
+    // The top-level statement holding the selection, and the names bound there
+    let top = (0..=first)
+        .rev()
+        .find(|&index| !is_blank(lines[index]) && indentation(lines[index]) == 0)?;
+    let mut scope = NameUse::default();
+    scope.statements(&program.statements);
+    let mut locals: HashSet<String> = scope.assigned.into_iter().collect();
+    if top < first {
+        let header = line_tokens(text, tokens, top);
+        let header: Vec<&TokenKind> = header
+            .iter()
+            .map(|token| &token.kind)
+            .filter(|kind| !matches!(kind, TokenKind::Pub | TokenKind::Async))
+            .collect();
+        match header.as_slice() {
+            [TokenKind::Struct | TokenKind::Enum | TokenKind::Trait | TokenKind::Impl, ..] => {
+                return None
+            }
+            [TokenKind::Def, TokenKind::Identifier(name), ..] => {
+                let function = find_function(program, name)?;
+                locals.extend(function.params.iter().map(|param| param.name.clone()));
+                let mut body = NameUse::default();
+                body.statements(&function.body.statements);
+                locals.extend(body.assigned);
+            }
+            _ => {}
+        }
+    }
+
+    let parameters: Vec<&String> = selected
+        .free
+        .iter()
+        .filter(|name| locals.contains(*name))
+        .collect();
+
+    // Names read after the selection, up to the end of its top-level statement
+    let scope_end = (last + 1..lines.len())
+        .find(|&index| top < first && !is_blank(lines[index]) && indentation(lines[index]) == 0)
+        .unwrap_or(lines.len());
+    let read_after: HashSet<&str> = tokens
+        .iter()
+        .filter(|token| {
+            let line = offset_to_position(text, token.span.start()).line as usize;
+            line > last && line < scope_end
+        })
+        .filter_map(|token| match &token.kind {
+            TokenKind::Identifier(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let returned: Vec<&String> = selected
+        .assigned
+        .iter()
+        .filter(|name| read_after.contains(name.as_str()))
+        .collect();
+
+    let title = "Extract function".to_string();
+    let kind = CodeActionKind::REFACTOR_EXTRACT;
+    if selected.early_exit {
+        let reason = "The selection contains `return`, `yield`, `break` or `continue`".to_string();
+        return Some(Action::disabled(title, kind, reason));
+    }
+    if returned.len() > 1 {
+        let reason = format!(
+            "The selection assigns several variables used afterwards: {}",
+            returned
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        return Some(Action::disabled(title, kind, reason));
+    }
+
+    let name = unused_name(tokens, "extracted");
+    let arguments = parameters
+        .iter()
+        .map(|name| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut function = format!("def {}({}):\n", name, arguments);
+    for line in snippet.lines() {
+        if !line.trim().is_empty() {
+            function.push_str(INDENT);
+            function.push_str(line);
+        }
+        function.push('\n');
+    }
+    let mut call = format!("{}{}({})", &lines[first][..base], name, arguments);
+    if let Some(value) = returned.first() {
+        function.push_str(&format!("{}return {}\n", INDENT, value));
+        call = format!(
+            "{}let {} = {}({})",
+            &lines[first][..base],
+            value,
+            name,
+            arguments
+        );
+    }
+    call.push('\n');
+
+    let edits = if top == first {
+        vec![replace_lines(
+            first,
+            last,
+            format!("{}\n{}", function, call),
+        )]
+    } else {
+        vec![
+            insert(text, line_offset(&lines, top), format!("{}\n", function)),
+            replace_lines(first, last, call),
+        ]
+    };
+    Some(Action::new(title, kind, edits))
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Blank or comment-only lines do not affect the block structure
+fn is_blank(line: &str) -> bool {
+    let code = line.trim();
+    code.is_empty() || code.starts_with('#')
+}
+
+fn line_offset(lines: &[&str], line: usize) -> usize {
+    lines[..line].iter().map(|line| line.len() + 1).sum()
+}
+
+fn line_tokens<'a>(text: &str, tokens: &'a [Token], line: usize) -> Vec<&'a Token> {
+    tokens
+        .iter()
+        .filter(|token| offset_to_position(text, token.span.start()).line as usize == line)
+        .collect()
+}
+
+fn find_function<'a>(program: &'a Program, name: &str) -> Option<&'a Function> {
+    program
+        .statements
+        .iter()
+        .find_map(|statement| match statement {
+            Statement::Function(function) if function.name == name => Some(function),
+            _ => None,
+        })
+}
+
+/// `base`, or `base_2`, `base_3`... if the document already uses it
+fn unused_name(tokens: &[Token], base: &str) -> String {
+    let used: HashSet<&str> = tokens
+        .iter()
+        .filter_map(|token| match &token.kind {
+            TokenKind::Identifier(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    std::iter::once(base.to_string())
+        .chain((2..).map(|index| format!("{}_{}", base, index)))
+        .find(|name| !used.contains(name.as_str()))
+        .expect("unbounded candidates")
+}
+
+/// The name a `use` import binds: its alias, or the last segment of its path
+fn import_name(module: &str, alias: Option<&str>) -> String {
+    alias
+        .unwrap_or_else(|| module.rsplit([':', '.', '/']).next().unwrap_or(module))
+        .to_string()
+}
+
+fn imported_names(program: &Program) -> HashSet<String> {
+    program
+        .statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Use { imports } => Some(imports),
+            _ => None,
+        })
+        .flatten()
+        .map(|import| import_name(&import.module, import.alias.as_deref()))
+        .collect()
+}
+
+/// "Import `otter:<module>`" for standard library modules used as `module.`
+/// prefixes on the lines of the range without being imported
+fn import_missing_modules(
+    text: &str,
+    tokens: &[Token],
+    program: &Program,
+    range: Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<Action> {
+    let imported = imported_names(program);
+    let mut scope = NameUse::default();
+    scope.statements(&program.statements);
+    let mut offered = HashSet::new();
+
+    let mut wanted = |module: &str| {
+        STDLIB_MODULES.contains(module)
+            && !imported.contains(module)
+            && !scope.assigned.iter().any(|name| name == module)
+            && offered.insert(module.to_string())
+    };
+    let mut modules = Vec::new();
+    for window in tokens.windows(2) {
+        let (TokenKind::Identifier(module), TokenKind::Dot) = (&window[0].kind, &window[1].kind)
+        else {
+            continue;
+        };
+        let line = offset_to_position(text, window[0].span.start()).line;
+        let in_range = range.start.line <= line && line <= range.end.line;
+        let diagnosed = diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.message == format!("undefined variable: {}", module));
+        if (in_range || diagnosed) && wanted(module) {
+            modules.push(module.clone());
+        }
+    }
+
+    // After the last top-level import, or at the top of the document
+    let lines: Vec<&str> = text.split('\n').collect();
+    let last_import = lines
+        .iter()
+        .rposition(|line| line.starts_with("use ") || line.starts_with("pub use "));
+    let (offset, separator) = match last_import {
+        Some(line) => (line_offset(&lines, line + 1), ""),
+        None if lines[0].trim().is_empty() => (0, ""),
+        None => (0, "\n"),
+    };
+
+    modules
+        .into_iter()
+        .map(|module| {
+            let message = format!("undefined variable: {}", module);
+            Action {
+                diagnostics: diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.message == message)
+                    .cloned()
+                    .collect(),
+                ..Action::new(
+                    format!("Import `otter:{}`", module),
+                    CodeActionKind::QUICKFIX,
+                    vec![insert(
+                        text,
+                        offset,
+                        format!("use otter:{}\n{}", module, separator),
+                    )],
+                )
+            }
+        })
+        .collect()
+}
+
+/// "Remove unused imports" when the range touches a `use` line with an import
+/// the document never refers to
+fn remove_unused_imports(
+    text: &str,
+    tokens: &[Token],
+    program: &Program,
+    range: Range,
+) -> Option<Action> {
+    let mut scope = NameUse::default();
+    scope.statements(&program.statements);
+    // Type annotations such as `json.Value` only show up in the tokens
+    let mut used = scope.read;
+
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut use_lines = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if line.starts_with("use ") {
+            use_lines.push(index);
+        }
+    }
+    for token in tokens {
+        let line = offset_to_position(text, token.span.start()).line as usize;
+        if let (TokenKind::Identifier(name), false) = (&token.kind, use_lines.contains(&line)) {
+            used.insert(name.clone());
+        }
+    }
+
+    let mut edits = Vec::new();
+    let mut in_range = false;
+    for &line in &use_lines {
+        // Split the import list at top-level commas
+        let imports: Vec<&Token> = line_tokens(text, tokens, line)
+            .into_iter()
+            .filter(|token| !matches!(token.kind, TokenKind::Newline | TokenKind::Dedent))
+            .collect();
+        let segments: Vec<&[&Token]> = imports[1..]
+            .split(|token| token.kind == TokenKind::Comma)
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let mut kept = Vec::new();
+        let mut removed = false;
+        for segment in segments {
+            let source = &text[segment[0].span.start()..segment[segment.len() - 1].span.end()];
+            let name = match segment {
+                [.., as_token, alias] if as_token.kind == TokenKind::As => {
+                    token_text(text, alias).to_string()
+                }
+                _ => import_name(source, None),
+            };
+            if used.contains(&name) {
+                kept.push(source);
+            } else {
+                removed = true;
+            }
+        }
+        if !removed {
+            continue;
+        }
+        in_range |= range.start.line as usize <= line && line <= range.end.line as usize;
+        let new_text = if kept.is_empty() {
+            String::new()
+        } else {
+            format!("use {}\n", kept.join(", "))
+        };
+        edits.push(replace_lines(line, line, new_text));
+    }
+
+    in_range.then(|| {
+        Action::new(
+            "Remove unused imports".to_string(),
+            CodeActionKind::QUICKFIX,
+            edits,
+        )
+    })
+}
+
+fn token_text<'a>(text: &'a str, token: &Token) -> &'a str {
+    &text[token.span.start()..token.span.end()]
+}
+
+/// How a run of statements uses names: those it reads before binding them and
+/// the variables it binds, both in order of first appearance
+#[derive(Default)]
+struct NameUse {
+    bound: HashSet<String>,
+    free: Vec<String>,
+    assigned: Vec<String>,
+    /// Every name read, bound or not
+    read: HashSet<String>,
+    /// Whether control can leave the statements through `return` or `yield`,
+    /// or through `break`/`continue` of a loop around them
+    early_exit: bool,
+    loop_depth: usize,
+}
+
+impl NameUse {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.statements(&block.statements);
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let { name, expr, .. }
+            | Statement::Assignment {
+                target: Expr::Identifier { name, .. },
+                expr,
+                ..
+            } => {
+                self.expr(expr);
+                self.assign(name);
+            }
+            Statement::Destructure { pattern, expr, .. } => {
+                self.expr(expr);
+                for name in pattern.bindings() {
+                    self.assign(name);
+                }
+            }
+            // Storing into an element or field mutates a value the
+            // surrounding code still holds, so it only reads the name
+            Statement::Assignment { target, expr, .. } => {
+                self.expr(expr);
+                self.expr(target);
+            }
+            Statement::If {
+                cond,
+                then_block,
+                elif_blocks,
+                else_block,
+            } => {
+                self.expr(cond);
+                self.block(then_block);
+                for (cond, block) in elif_blocks {
+                    self.expr(cond);
+                    self.block(block);
+                }
+                if let Some(block) = else_block {
+                    self.block(block);
+                }
+            }
+            Statement::For {
+                var,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expr(iterable);
+                for name in var.bindings() {
+                    self.assign(name);
+                }
+                self.loop_body(body);
+            }
+            Statement::While { cond, body } => {
+                self.expr(cond);
+                self.loop_body(body);
+            }
+            Statement::Break | Statement::Continue => {
+                if self.loop_depth == 0 {
+                    self.early_exit = true;
+                }
+            }
+            Statement::Return(value) => {
+                self.early_exit = true;
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Statement::Yield(value) => {
+                self.early_exit = true;
+                self.expr(value);
+            }
+            Statement::Function(function) => {
+                self.bound.insert(function.name.clone());
+                self.function(function);
+            }
+            Statement::Struct { name, methods, .. } => {
+                self.bound.insert(name.clone());
+                for method in methods {
+                    self.function(method);
+                }
+            }
+            Statement::Trait { name, methods, .. } => {
+                self.bound.insert(name.clone());
+                for method in methods {
+                    self.function(method);
+                }
+            }
+            Statement::Impl { methods, .. } => {
+                for method in methods {
+                    self.function(method);
+                }
+            }
+            Statement::Enum { name, .. } | Statement::TypeAlias { name, .. } => {
+                self.bound.insert(name.clone());
+            }
+            Statement::Const { name, expr, .. } => {
+                self.expr(expr);
+                self.bound.insert(name.clone());
+            }
+            Statement::Use { imports } => {
+                for import in imports {
+                    self.bound
+                        .insert(import_name(&import.module, import.alias.as_deref()));
+                }
+            }
+            Statement::Expr(expr) | Statement::Raise(Some(expr)) => self.expr(expr),
+            Statement::Block(block) => self.block(block),
+            Statement::Try {
+                body,
+                handlers,
+                else_block,
+                finally_block,
+            } => {
+                self.block(body);
+                for handler in handlers {
+                    if let Some(alias) = &handler.alias {
+                        self.assign(alias);
+                    }
+                    self.block(&handler.body);
+                }
+                for block in else_block.iter().chain(finally_block) {
+                    self.block(block);
+                }
+            }
+            Statement::With {
+                manager,
+                alias,
+                body,
+            } => {
+                self.expr(manager);
+                if let Some(alias) = alias {
+                    self.assign(alias);
+                }
+                self.block(body);
+            }
+            Statement::Pass | Statement::PubUse { .. } | Statement::Raise(None) => {}
+        }
+    }
+
+    fn loop_body(&mut self, body: &Block) {
+        self.loop_depth += 1;
+        self.block(body);
+        self.loop_depth -= 1;
+    }
+
+    fn function(&mut self, function: &Function) {
+        let params = function.params.iter().map(|param| param.name.clone());
+        self.scoped(params, |this| this.block(&function.body));
+    }
+
+    fn assign(&mut self, name: &str) {
+        self.bound.insert(name.to_string());
+        if !self.assigned.iter().any(|assigned| assigned == name) {
+            self.assigned.push(name.to_string());
+        }
+    }
+
+    fn read(&mut self, name: &str) {
+        self.read.insert(name.to_string());
+        if !self.bound.contains(name) && !self.free.iter().any(|free| free == name) {
+            self.free.push(name.to_string());
+        }
+    }
+
+    /// Walk a nested scope such as a function body or a comprehension: the
+    /// names it binds, and its `return`s, stay inside it
+    fn scoped(&mut self, names: impl IntoIterator<Item = String>, walk: impl FnOnce(&mut Self)) {
+        let bound = self.bound.clone();
+        let assigned = self.assigned.len();
+        let early_exit = self.early_exit;
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.bound.extend(names);
+        walk(self);
+        self.bound = bound;
+        self.assigned.truncate(assigned);
+        self.early_exit = early_exit;
+        self.loop_depth = loop_depth;
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Identifier { name, .. } => self.read(name),
+            Expr::Member { object, .. } => self.expr(object),
+            Expr::Slice {
+                object, start, end, ..
+            } => {
+                self.expr(object);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            }
+            Expr::Call { func, args, .. } => {
+                self.expr(func);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Binary { left, right, .. }
+            | Expr::Index {
+                object: left,
+                index: right,
+                ..
+            }
+            | Expr::Range {
+                start: left,
+                end: right,
+            } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { expr, .. }
+            | Expr::Cast { expr, .. }
+            | Expr::Await(expr)
+            | Expr::Spawn(expr) => self.expr(expr),
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond);
+                self.expr(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.expr(else_branch);
+                }
+            }
+            Expr::Match { value, arms } => {
+                self.expr(value);
+                for arm in arms {
+                    let names = arm.pattern.bindings().into_iter().map(str::to_string);
+                    self.scoped(names, |this| {
+                        if let Some(guard) = &arm.guard {
+                            this.expr(guard);
+                        }
+                        this.expr(&arm.body);
+                    });
+                }
+            }
+            Expr::Array(items) | Expr::Tuple(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            Expr::Dict(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::ListComprehension {
+                element,
+                var,
+                iterable,
+                condition,
+            } => {
+                self.expr(iterable);
+                self.scoped([var.clone()], |this| {
+                    this.expr(element);
+                    if let Some(condition) = condition {
+                        this.expr(condition);
+                    }
+                });
+            }
+            Expr::DictComprehension {
+                key,
+                value,
+                var,
+                iterable,
+                condition,
+            } => {
+                self.expr(iterable);
+                self.scoped([var.clone()], |this| {
+                    this.expr(key);
+                    this.expr(value);
+                    if let Some(condition) = condition {
+                        this.expr(condition);
+                    }
+                });
+            }
+            Expr::FString { parts } => {
+                for part in parts {
+                    if let FStringPart::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+            Expr::Lambda { params, body, .. } => {
+                let params = params.iter().map(|param| param.name.clone());
+                self.scoped(params, |this| this.block(body));
+            }
+            Expr::Struct { fields, .. } => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri() -> Url {
+        Url::parse("file:///tmp/main.ot").unwrap()
+    }
+
+    fn actions(text: &str, range: Range) -> Vec<CodeAction> {
+        let analysis = Analysis::new(text.to_string(), 0);
+        code_actions(&uri(), &analysis, range, &[])
+            .into_iter()
+            .filter_map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => Some(action),
+                CodeActionOrCommand::Command(_) => None,
+            })
+            .collect()
+    }
+
+    fn apply(text: &str, action: &CodeAction) -> String {
+        let mut edits = action
+            .edit
+            .as_ref()
+            .expect("edit")
+            .changes
+            .as_ref()
+            .unwrap()[&uri()]
+            .clone();
+        edits.sort_by_key(|edit| edit.range.start);
+        let mut result = text.to_string();
+        // Apply from the end so earlier offsets stay valid
+        for edit in edits.iter().rev() {
+            let start = position_to_offset(&result, edit.range.start);
+            let end = position_to_offset(&result, edit.range.end);
+            result.replace_range(start..end, &edit.new_text);
+        }
+        result
+    }
+
+    fn titled<'a>(actions: &'a [CodeAction], title: &str) -> &'a CodeAction {
+        actions
+            .iter()
+            .find(|action| action.title.starts_with(title))
+            .unwrap_or_else(|| panic!("no `{}` action", title))
+    }
+
+    #[test]
+    fn annotates_let_with_inferred_type() {
+        let text = "def main():\n    let ratio = 1.5 * 2.0\n";
+        let found = actions(text, Range::new(Position::new(1, 9), Position::new(1, 9)));
+        let action = titled(&found, "Add type annotation");
+        assert_eq!(
+            apply(text, action),
+            "def main():\n    let ratio: f64 = 1.5 * 2.0\n"
+        );
+
+        // Annotated bindings are left alone
+        let text = "let ratio: f64 = 1.5\n";
+        let found = actions(text, Range::new(Position::new(0, 5), Position::new(0, 5)));
+        assert!(found
+            .iter()
+            .all(|action| !action.title.starts_with("Add type")));
+    }
+
+    #[test]
+    fn extracts_statements_with_parameters_and_return_value() {
+        let text = "def main():\n    let base = 2\n    let doubled = base * 2\n    let total = doubled + 1\n    print(total)\n";
+        let range = Range::new(Position::new(2, 0), Position::new(4, 0));
+        let found = actions(text, range);
+        let action = titled(&found, "Extract function");
+        assert_eq!(
+            apply(text, action),
+            "def extracted(base):\n    let doubled = base * 2\n    let total = doubled + 1\n    return total\n\ndef main():\n    let base = 2\n    let total = extracted(base)\n    print(total)\n"
+        );
+
+        // Leaving the selection early cannot be extracted
+        let text = "def main():\n    for i in 0..3:\n        if i > 1:\n            break\n";
+        let range = Range::new(Position::new(2, 0), Position::new(4, 0));
+        let found = actions(text, range);
+        let action = titled(&found, "Extract function");
+        assert!(action.disabled.is_some());
+        assert!(action.edit.is_none());
+    }
+
+    #[test]
+    fn imports_missing_modules_and_removes_unused_ones() {
+        let text = "use otter:io\n\ndef main():\n    print(math.sqrt(2.0))\n";
+        let found = actions(text, Range::new(Position::new(3, 10), Position::new(3, 10)));
+        let action = titled(&found, "Import `otter:math`");
+        assert_eq!(
+            apply(text, action),
+            "use otter:io\nuse otter:math\n\ndef main():\n    print(math.sqrt(2.0))\n"
+        );
+
+        let text = "use otter:io, otter:math as m\n\ndef main():\n    print(m.sqrt(2.0))\n";
+        let found = actions(text, Range::new(Position::new(0, 0), Position::new(0, 0)));
+        let action = titled(&found, "Remove unused imports");
+        assert_eq!(
+            apply(text, action),
+            "use otter:math as m\n\ndef main():\n    print(m.sqrt(2.0))\n"
+        );
+    }
+}
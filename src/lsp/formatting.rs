@@ -0,0 +1,250 @@
+//! Document, range and on-type formatting backed by the `fmt` crate.
+//!
+//! Edits are computed from a line diff of the document against its formatted
+//! form and narrowed to the characters that actually change, so the client
+//! keeps cursors and selections in place.
+
+use fmt::{FormatConfig, Formatter, LineChange};
+use tower_lsp::lsp_types::{FormattingOptions, Position, Range, TextEdit, Url};
+
+use super::offset_to_position;
+
+/// Formatter settings for `uri`: the nearest `otterfmt.toml`, falling back to
+/// the client's indentation settings when there is none.
+pub(crate) fn config_for(uri: &Url, options: &FormattingOptions) -> FormatConfig {
+    let config = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| FormatConfig::find(&path))
+        .and_then(|config_path| FormatConfig::load(&config_path).ok());
+    config.unwrap_or_else(|| FormatConfig {
+        indent_width: options.tab_size as usize,
+        ..FormatConfig::default()
+    })
+}
+
+/// Edits formatting the whole document, or `None` if it does not parse
+pub(crate) fn format_document(text: &str, config: FormatConfig) -> Option<Vec<TextEdit>> {
+    let formatted = Formatter::with_config(config).format_source(text).ok()?;
+    Some(edits_between(text, &formatted, |_| true))
+}
+
+/// Edits formatting the lines touched by `range`. The whole document is
+/// formatted and only the changes overlapping those lines are kept.
+pub(crate) fn format_range(
+    text: &str,
+    range: Range,
+    config: FormatConfig,
+) -> Option<Vec<TextEdit>> {
+    let formatted = Formatter::with_config(config).format_source(text).ok()?;
+    let first = range.start.line as usize;
+    // A selection ending at the start of a line does not include that line
+    let last = if range.end.character == 0 && range.end.line > range.start.line {
+        range.end.line as usize - 1
+    } else {
+        range.end.line as usize
+    };
+    Some(edits_between(text, &formatted, |change| {
+        let end = change.first_line + change.line_count.max(1);
+        change.first_line <= last && end > first
+    }))
+}
+
+/// Indentation for the line the cursor moved to after typing a newline: one
+/// level deeper after a line ending in `:`, otherwise the previous line's.
+pub(crate) fn format_on_newline(
+    text: &str,
+    position: Position,
+    indent_width: usize,
+) -> Option<Vec<TextEdit>> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let line_index = position.line as usize;
+    let line = lines.get(line_index)?.trim_end_matches('\r');
+
+    let previous = lines[..line_index]
+        .iter()
+        .rev()
+        .map(|line| line.trim_end_matches('\r'))
+        .find(|line| !line.trim().is_empty())?;
+    let previous_indent = leading_whitespace(previous).chars().count();
+    let opens_block = strip_comment(previous).trim_end().ends_with(':');
+    let indent = if opens_block {
+        previous_indent + indent_width
+    } else {
+        previous_indent
+    };
+
+    let current = leading_whitespace(line);
+    if current.len() == indent && current.chars().all(|c| c == ' ') {
+        return Some(Vec::new());
+    }
+    Some(vec![TextEdit {
+        range: Range {
+            start: Position {
+                line: position.line,
+                character: 0,
+            },
+            end: Position {
+                line: position.line,
+                character: current.chars().count() as u32,
+            },
+        },
+        new_text: " ".repeat(indent),
+    }])
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// `line` without a trailing `#` comment, ignoring `#` inside string literals
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, ch) in line.char_indices() {
+        match (quote, ch) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(open), ch) if ch == open => quote = None,
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Minimal edits turning `original` into `formatted`, keeping the line
+/// changes accepted by `include`.
+fn edits_between(
+    original: &str,
+    formatted: &str,
+    include: impl Fn(&LineChange) -> bool,
+) -> Vec<TextEdit> {
+    fmt::line_changes(original, formatted)
+        .into_iter()
+        .flat_map(|change| split_line_by_line(original, change))
+        .filter(|change| include(change))
+        .map(|change| {
+            let old = &original[change.original.clone()];
+            let new = change.replacement.as_str();
+
+            // Narrow the edit to the characters that differ
+            let prefix = common_prefix(old, new);
+            let suffix = common_suffix(&old[prefix..], &new[prefix..]);
+            let start = change.original.start + prefix;
+            let end = change.original.end - suffix;
+
+            TextEdit {
+                range: Range {
+                    start: offset_to_position(original, start),
+                    end: offset_to_position(original, end),
+                },
+                new_text: new[prefix..new.len() - suffix].to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Split a change that rewrites as many lines as it replaces into one change
+/// per line, so range formatting can keep just the selected ones.
+fn split_line_by_line(original: &str, change: LineChange) -> Vec<LineChange> {
+    let new_lines: Vec<&str> = change.replacement.split_inclusive('\n').collect();
+    if change.line_count <= 1 || new_lines.len() != change.line_count {
+        return vec![change];
+    }
+
+    let mut start = change.original.start;
+    original[change.original.clone()]
+        .split_inclusive('\n')
+        .zip(new_lines)
+        .enumerate()
+        .map(|(index, (old_line, new_line))| {
+            let line = LineChange {
+                original: start..start + old_line.len(),
+                first_line: change.first_line + index,
+                line_count: 1,
+                replacement: new_line.to_string(),
+            };
+            start += old_line.len();
+            line
+        })
+        .collect()
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((index, _), _)| index)
+}
+
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+        // Apply from the end so earlier positions stay valid
+        for edit in edits.iter().rev() {
+            let start = edit.range.start;
+            let end = edit.range.end;
+            let head: String = lines[start.line as usize]
+                .chars()
+                .take(start.character as usize)
+                .collect();
+            let tail: String = lines[end.line as usize]
+                .chars()
+                .skip(end.character as usize)
+                .collect();
+            let replaced = format!("{head}{}{tail}", edit.new_text);
+            lines.splice(
+                start.line as usize..=end.line as usize,
+                replaced.split('\n').map(str::to_string),
+            );
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn document_edits_only_touch_changed_text() {
+        let text = "let x = 1\nlet y =  2\n\ndef main():\n  print(x)\n";
+        let edits = format_document(text, FormatConfig::default()).expect("should format");
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].new_text, "");
+        assert_eq!(edits[0].range.start, Position::new(1, 8));
+        assert_eq!(edits[0].range.end, Position::new(1, 9));
+        assert_eq!(
+            apply(text, &edits),
+            "let x = 1\nlet y = 2\n\ndef main():\n    print(x)\n"
+        );
+    }
+
+    #[test]
+    fn range_edits_stay_inside_the_selection() {
+        let text = "let x =  1\nlet y =  2\n";
+        let range = Range::new(Position::new(1, 0), Position::new(1, 3));
+        let edits = format_range(text, range, FormatConfig::default()).expect("should format");
+        assert_eq!(apply(text, &edits), "let x =  1\nlet y = 2\n");
+    }
+
+    #[test]
+    fn newline_after_colon_indents_one_level() {
+        let text = "def main():  # entry\n\n";
+        let edits = format_on_newline(text, Position::new(1, 0), 4).expect("edits");
+        assert_eq!(apply(text, &edits), "def main():  # entry\n    \n");
+
+        let text = "def main():\n    let x = 1\n  \n";
+        let edits = format_on_newline(text, Position::new(2, 2), 4).expect("edits");
+        assert_eq!(apply(text, &edits), "def main():\n    let x = 1\n    \n");
+    }
+}
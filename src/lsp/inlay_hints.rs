@@ -0,0 +1,649 @@
+//! Inlay hints: inferred types of `let` bindings and loop variables,
+//! parameter names at call sites and inferred return types of functions.
+
+use std::collections::HashMap;
+use std::fs;
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use tower_lsp::lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, Range};
+
+use super::analysis::Analysis;
+use super::offset_to_position;
+use crate::typecheck::TypeInfo;
+use ast::nodes::{Block, Expr, FStringPart, Function, Pattern, Statement};
+use lexer::token::TokenKind;
+use lexer::{tokenize, Token};
+use parser::parse;
+
+/// Which hints to show, read from the `inlayHints` initialization option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct InlayHintSettings {
+    /// Inferred types after `let` bindings and `for` loop variables
+    pub type_hints: bool,
+    /// Parameter names before call arguments
+    pub parameter_names: bool,
+    /// Inferred return types on functions without an annotation
+    pub return_types: bool,
+}
+
+impl Default for InlayHintSettings {
+    fn default() -> Self {
+        Self {
+            type_hints: true,
+            parameter_names: true,
+            return_types: true,
+        }
+    }
+}
+
+/// Parameter names of the standard library wrappers around FFI functions,
+/// keyed by `module.function`
+static STDLIB_PARAMETERS: Lazy<HashMap<String, Vec<String>>> = Lazy::new(|| {
+    let mut parameters = HashMap::new();
+    let Ok(entries) = crate::cli::find_stdlib_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) else {
+        return parameters;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let Some(module) = path
+            .file_stem()
+            .filter(|_| path.extension().is_some_and(|ext| ext == "ot"))
+            .and_then(|stem| stem.to_str())
+        else {
+            continue;
+        };
+        let Some(program) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|source| tokenize(&source).ok())
+            .and_then(|tokens| parse(&tokens).ok())
+        else {
+            continue;
+        };
+        for statement in &program.statements {
+            if let Statement::Function(function) = statement {
+                parameters.insert(
+                    format!("{}.{}", module, function.name),
+                    parameter_names(function),
+                );
+            }
+        }
+    }
+    parameters
+});
+
+/// Parameter names of the standard library function `module.function`
+pub(super) fn stdlib_parameter_names(name: &str) -> Option<&'static [String]> {
+    STDLIB_PARAMETERS.get(name).map(Vec::as_slice)
+}
+
+/// Compute the hints of an analyzed document that fall inside `range`. The
+/// checker stops at the first hard error; whatever it inferred up to that
+/// point is still worth showing.
+pub(crate) fn inlay_hints(
+    analysis: &Analysis,
+    range: Range,
+    settings: InlayHintSettings,
+) -> Vec<InlayHint> {
+    let (Some(tokens), Some(program)) = (&analysis.tokens, &analysis.program) else {
+        return Vec::new();
+    };
+
+    let mut functions = HashMap::new();
+    collect_functions(&program.statements, &mut functions);
+
+    let mut collector = HintCollector {
+        text: &analysis.text,
+        tokens,
+        types: analysis.expr_types(),
+        functions,
+        settings,
+        def_headers: function_headers(tokens),
+        next_header: 0,
+        hints: Vec::new(),
+    };
+    collector.statements(&program.statements);
+
+    collector
+        .hints
+        .into_iter()
+        .filter(|hint| range.start <= hint.position && hint.position <= range.end)
+        .collect()
+}
+
+fn parameter_names(function: &Function) -> Vec<String> {
+    function
+        .params
+        .iter()
+        .map(|param| param.name.clone())
+        .filter(|name| name != "self")
+        .collect()
+}
+
+fn collect_functions(statements: &[Statement], functions: &mut HashMap<String, Vec<String>>) {
+    for statement in statements {
+        match statement {
+            Statement::Function(function) => {
+                functions.insert(function.name.clone(), parameter_names(function));
+                collect_functions(&function.body.statements, functions);
+            }
+            Statement::Block(block) => collect_functions(&block.statements, functions),
+            _ => {}
+        }
+    }
+}
+
+/// Name and end offset of the parameter list of every `def`, in source order
+fn function_headers(tokens: &[Token]) -> Vec<(String, usize)> {
+    let mut headers = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Def {
+            continue;
+        }
+        let Some(TokenKind::Identifier(name)) = tokens.get(index + 1).map(|t| &t.kind) else {
+            continue;
+        };
+        let mut depth = 0usize;
+        for token in &tokens[index + 2..] {
+            match token.kind {
+                TokenKind::LParen => depth += 1,
+                TokenKind::RParen => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        headers.push((name.clone(), token.span.end()));
+                        break;
+                    }
+                }
+                TokenKind::Colon if depth == 0 => break,
+                _ => {}
+            }
+        }
+    }
+    headers
+}
+
+/// Only types the checker fully inferred are shown
+pub(super) fn type_label(ty: &TypeInfo) -> Option<String> {
+    match ty {
+        TypeInfo::Unknown | TypeInfo::Error => None,
+        TypeInfo::Struct { name, .. } => Some(name.clone()),
+        TypeInfo::Enum { name, args, .. } => {
+            if args.is_empty() {
+                Some(name.clone())
+            } else {
+                let args = args.iter().map(type_label).collect::<Option<Vec<_>>>()?;
+                Some(format!("{}<{}>", name, args.join(", ")))
+            }
+        }
+        TypeInfo::List(element) => Some(format!("list<{}>", type_label(element)?)),
+        TypeInfo::Dict { key, value } => Some(format!(
+            "dict<{}, {}>",
+            type_label(key)?,
+            type_label(value)?
+        )),
+        other => {
+            let name = other.display_name();
+            (!name.contains('?')).then_some(name)
+        }
+    }
+}
+
+struct HintCollector<'a> {
+    text: &'a str,
+    tokens: &'a [Token],
+    types: &'a HashMap<usize, TypeInfo>,
+    /// Parameter names of the functions defined in the document
+    functions: HashMap<String, Vec<String>>,
+    settings: InlayHintSettings,
+    def_headers: Vec<(String, usize)>,
+    next_header: usize,
+    hints: Vec<InlayHint>,
+}
+
+impl HintCollector<'_> {
+    fn type_of(&self, expr: &Expr) -> Option<&TypeInfo> {
+        self.types.get(&(expr as *const Expr as usize))
+    }
+
+    fn push(&mut self, offset: usize, label: String, kind: InlayHintKind) {
+        // `x: i64` and `name: value` hug the code, `) -> f64` is spaced out
+        let parameter = kind == InlayHintKind::PARAMETER;
+        let return_type = label.starts_with("->");
+        self.hints.push(InlayHint {
+            position: offset_to_position(self.text, offset),
+            label: InlayHintLabel::String(label),
+            kind: Some(kind),
+            text_edits: None,
+            tooltip: None,
+            padding_left: Some(return_type),
+            padding_right: Some(parameter),
+            data: None,
+        });
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.statements(&block.statements);
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let { ty, expr, span, .. } => {
+                if let (true, None, Some(span)) = (self.settings.type_hints, ty, span) {
+                    if let Some(label) = self.type_of(expr).and_then(type_label) {
+                        self.push(span.end(), format!(": {}", label), InlayHintKind::TYPE);
+                    }
+                }
+                self.expr(expr);
+            }
+            Statement::Const { expr, .. } | Statement::Destructure { expr, .. } => self.expr(expr),
+            Statement::Assignment { target, expr, .. } => {
+                self.expr(target);
+                self.expr(expr);
+            }
+            Statement::If {
+                cond,
+                then_block,
+                elif_blocks,
+                else_block,
+            } => {
+                self.expr(cond);
+                self.block(then_block);
+                for (cond, block) in elif_blocks {
+                    self.expr(cond);
+                    self.block(block);
+                }
+                if let Some(block) = else_block {
+                    self.block(block);
+                }
+            }
+            Statement::For {
+                var,
+                iterable,
+                body,
+                var_span,
+            } => {
+                // Tuple loop variables would need one hint per name
+                if let (true, Pattern::Identifier(_), Some(span)) =
+                    (self.settings.type_hints, var, var_span)
+                {
+                    if let Some(label) = self.element_type(iterable) {
+                        self.push(span.end(), format!(": {}", label), InlayHintKind::TYPE);
+                    }
+                }
+                self.expr(iterable);
+                self.block(body);
+            }
+            Statement::While { cond, body }
+            | Statement::With {
+                manager: cond,
+                body,
+                ..
+            } => {
+                self.expr(cond);
+                self.block(body);
+            }
+            Statement::Return(Some(expr))
+            | Statement::Yield(expr)
+            | Statement::Raise(Some(expr))
+            | Statement::Expr(expr) => self.expr(expr),
+            Statement::Function(function) => self.function(function),
+            Statement::Struct { methods, .. }
+            | Statement::Trait { methods, .. }
+            | Statement::Impl { methods, .. } => {
+                for method in methods {
+                    self.function(method);
+                }
+            }
+            Statement::Block(block) => self.block(block),
+            Statement::Try {
+                body,
+                handlers,
+                else_block,
+                finally_block,
+            } => {
+                self.block(body);
+                for handler in handlers {
+                    self.block(&handler.body);
+                }
+                for block in [else_block, finally_block].into_iter().flatten() {
+                    self.block(block);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Type of the loop variable when iterating over `iterable`
+    fn element_type(&self, iterable: &Expr) -> Option<String> {
+        if let Expr::Range { start, .. } = iterable {
+            return self.type_of(start).and_then(type_label);
+        }
+        match self.type_of(iterable)? {
+            TypeInfo::List(element) => type_label(element),
+            TypeInfo::Dict { value, .. } => type_label(value),
+            TypeInfo::Str => Some("str".to_string()),
+            TypeInfo::Generic { base, args } if base == "Iterator" => type_label(args.first()?),
+            _ => None,
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        let header = self.def_headers[self.next_header..]
+            .iter()
+            .position(|(name, _)| *name == function.name)
+            .map(|index| self.next_header + index);
+        if let Some(index) = header {
+            self.next_header = index + 1;
+            if self.settings.return_types && function.ret_ty.is_none() {
+                if let Some(label) = self.return_type(&function.body) {
+                    let offset = self.def_headers[index].1;
+                    self.push(offset, format!("-> {}", label), InlayHintKind::TYPE);
+                }
+            }
+        }
+        self.block(&function.body);
+    }
+
+    /// The type every `return` in `body` agrees on, if any; a generator
+    /// returns an iterator over the type every `yield` agrees on
+    fn return_type(&self, body: &Block) -> Option<String> {
+        let mut returned = Vec::new();
+        collect_returns(&body.statements, &mut returned);
+        let mut labels = returned
+            .into_iter()
+            .map(|expr| self.type_of(expr).and_then(type_label));
+        let first = labels.next()??;
+        let agreed = labels
+            .all(|label| label.as_deref() == Some(first.as_str()))
+            .then_some(first)?;
+        if body.contains_yield() {
+            Some(format!("Iterator<{}>", agreed))
+        } else {
+            Some(agreed)
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Call { func, args, .. } => {
+                if self.settings.parameter_names {
+                    self.parameter_hints(func, args);
+                }
+                self.expr(func);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Member { object, .. } => self.expr(object),
+            Expr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            Expr::Slice {
+                object, start, end, ..
+            } => {
+                self.expr(object);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { expr, .. }
+            | Expr::Cast { expr, .. }
+            | Expr::Await(expr)
+            | Expr::Spawn(expr) => self.expr(expr),
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond);
+                self.expr(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.expr(else_branch);
+                }
+            }
+            Expr::Match { value, arms } => {
+                self.expr(value);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                }
+            }
+            Expr::Range { start, end } => {
+                self.expr(start);
+                self.expr(end);
+            }
+            Expr::Array(elements) | Expr::Tuple(elements) => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            Expr::Dict(pairs) => {
+                for (key, value) in pairs {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::ListComprehension {
+                element,
+                iterable,
+                condition,
+                ..
+            } => {
+                self.expr(iterable);
+                self.expr(element);
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+            }
+            Expr::DictComprehension {
+                key,
+                value,
+                iterable,
+                condition,
+                ..
+            } => {
+                self.expr(iterable);
+                self.expr(key);
+                self.expr(value);
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+            }
+            Expr::FString { parts } => {
+                for part in parts {
+                    if let FStringPart::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+            Expr::Lambda { body, .. } => self.block(body),
+            Expr::Struct { fields, .. } => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            Expr::Literal(_) | Expr::Identifier { .. } => {}
+        }
+    }
+
+    fn parameter_hints(&mut self, func: &Expr, args: &[Expr]) {
+        // Calls of document functions, or of standard library functions
+        // through their module (`math.pow(x, 2)`)
+        let (names, callee) = match func {
+            Expr::Identifier { name, span } => (self.functions.get(name).cloned(), *span),
+            Expr::Member { object, field } => match object.as_ref() {
+                Expr::Identifier { name, span } => (
+                    STDLIB_PARAMETERS
+                        .get(&format!("{}.{}", name, field))
+                        .cloned(),
+                    *span,
+                ),
+                _ => return,
+            },
+            _ => return,
+        };
+        let (Some(names), Some(callee)) = (names, callee) else {
+            return;
+        };
+        let Some(offsets) = self.argument_offsets(callee.start()) else {
+            return;
+        };
+
+        for ((name, arg), offset) in names.iter().zip(args).zip(offsets) {
+            // `f(count)` for a parameter named `count` needs no hint
+            if matches!(arg, Expr::Identifier { name: arg_name, .. } if arg_name == name) {
+                continue;
+            }
+            self.push(offset, format!("{}:", name), InlayHintKind::PARAMETER);
+        }
+    }
+
+    /// Start offsets of the arguments of the call whose callee starts at `callee_start`
+    fn argument_offsets(&self, callee_start: usize) -> Option<Vec<usize>> {
+        let start = self
+            .tokens
+            .iter()
+            .position(|token| token.span.start() == callee_start)?;
+        let open = self.tokens[start..]
+            .iter()
+            .position(|token| token.kind == TokenKind::LParen)?
+            + start;
+
+        let mut offsets = Vec::new();
+        let mut depth = 0usize;
+        let mut expect_argument = true;
+        for token in &self.tokens[open + 1..] {
+            match token.kind {
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace if depth == 0 => break,
+                TokenKind::Comma if depth == 0 => {
+                    expect_argument = true;
+                    continue;
+                }
+                TokenKind::Newline | TokenKind::Indent | TokenKind::Dedent => continue,
+                _ => {}
+            }
+            if expect_argument {
+                offsets.push(token.span.start());
+                expect_argument = false;
+            }
+            match token.kind {
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+        }
+        Some(offsets)
+    }
+}
+
+/// Returned (or, in a generator, yielded) expressions in `statements`, not
+/// descending into nested functions
+fn collect_returns<'a>(statements: &'a [Statement], returned: &mut Vec<&'a Expr>) {
+    for statement in statements {
+        match statement {
+            Statement::Return(Some(expr)) | Statement::Yield(expr) => returned.push(expr),
+            Statement::If {
+                then_block,
+                elif_blocks,
+                else_block,
+                ..
+            } => {
+                collect_returns(&then_block.statements, returned);
+                for (_, block) in elif_blocks {
+                    collect_returns(&block.statements, returned);
+                }
+                if let Some(block) = else_block {
+                    collect_returns(&block.statements, returned);
+                }
+            }
+            Statement::For { body, .. }
+            | Statement::While { body, .. }
+            | Statement::With { body, .. } => collect_returns(&body.statements, returned),
+            Statement::Block(block) => collect_returns(&block.statements, returned),
+            Statement::Try {
+                body,
+                handlers,
+                else_block,
+                finally_block,
+            } => {
+                collect_returns(&body.statements, returned);
+                for handler in handlers {
+                    collect_returns(&handler.body.statements, returned);
+                }
+                for block in [else_block, finally_block].into_iter().flatten() {
+                    collect_returns(&block.statements, returned);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    fn labels(text: &str, settings: InlayHintSettings) -> Vec<(u32, u32, String)> {
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        let analysis = Analysis::new(text.to_string(), 0);
+        inlay_hints(&analysis, everything, settings)
+            .into_iter()
+            .map(|hint| match hint.label {
+                InlayHintLabel::String(label) => {
+                    (hint.position.line, hint.position.character, label)
+                }
+                InlayHintLabel::LabelParts(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hints_types_parameters_and_return_types() {
+        let text = "def scale(value: float, factor: float):\n    return value * factor\n\ndef main():\n    let x = scale(2.0, 3.0)\n    let label = \"x\"\n    for item in [label]:\n        print(item)\n";
+        assert_eq!(
+            labels(text, InlayHintSettings::default()),
+            vec![
+                (0, 38, "-> f64".to_string()),
+                (4, 18, "value:".to_string()),
+                (4, 23, "factor:".to_string()),
+                (5, 13, ": str".to_string()),
+                (6, 12, ": str".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn settings_turn_hint_kinds_off() {
+        let text = "def id(value: float):\n    return value\n\nlet y = id(1.0)\n";
+        let settings = InlayHintSettings {
+            type_hints: false,
+            parameter_names: true,
+            return_types: false,
+        };
+        assert_eq!(labels(text, settings), vec![(3, 11, "value:".to_string())]);
+    }
+
+    #[test]
+    fn matching_argument_names_are_not_hinted() {
+        let text = "def id(value: float) -> float:\n    return value\n\nlet value = 1.0\nlet y = id(value)\n";
+        let settings = InlayHintSettings {
+            type_hints: false,
+            ..InlayHintSettings::default()
+        };
+        assert!(labels(text, settings).is_empty());
+    }
+}
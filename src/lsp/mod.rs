@@ -1,17 +1,25 @@
+mod analysis;
+mod code_actions;
+mod formatting;
+mod inlay_hints;
+mod signature_help;
+
 use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+use std::time::Duration;
 
+use serde::Deserialize;
 use tokio::sync::RwLock;
-use tower_lsp::jsonrpc::Result;
+use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
-use crate::runtime::symbol_registry::SymbolRegistry;
-use crate::typecheck::{self, TypeChecker};
+use self::analysis::Analysis;
+use self::inlay_hints::InlayHintSettings;
+use self::signature_help::DocumentSignatures;
 use ast::nodes::{Expr, Program, Statement};
 use common::Span;
-use lexer::{tokenize, LexerError, Token};
-use parser::parse;
+use lexer::{LexerError, Token};
 use utils::errors::{Diagnostic as OtterDiagnostic, DiagnosticSeverity as OtterDiagSeverity};
 
 #[derive(Debug, Clone)]
@@ -24,11 +32,13 @@ struct SymbolInfo {
 #[derive(Debug, Clone)]
 enum SymbolKind {
     Variable,
+    Constant,
     Parameter,
     Function,
     Struct,
     Enum,
     TypeAlias,
+    Trait,
     #[allow(dead_code)]
     Method,
 }
@@ -58,6 +68,17 @@ impl SymbolTable {
         );
     }
 
+    fn add_constant(&mut self, name: String, span: Span, ty: Option<String>) {
+        self.symbols.insert(
+            name.clone(),
+            SymbolInfo {
+                span,
+                kind: SymbolKind::Constant,
+                ty,
+            },
+        );
+    }
+
     fn add_parameter(&mut self, name: String, span: Span, ty: Option<String>) {
         self.symbols.insert(
             name.clone(),
@@ -102,6 +123,17 @@ impl SymbolTable {
         );
     }
 
+    fn add_trait(&mut self, name: String, span: Span) {
+        self.symbols.insert(
+            name.clone(),
+            SymbolInfo {
+                span,
+                kind: SymbolKind::Trait,
+                ty: None,
+            },
+        );
+    }
+
     fn add_type_alias(&mut self, name: String, span: Span) {
         self.symbols.insert(
             name.clone(),
@@ -136,16 +168,40 @@ impl SymbolTable {
     }
 }
 
+/// An open document: its latest text and the version the client gave it
+#[derive(Debug)]
+struct Document {
+    text: String,
+    version: i32,
+}
+
 #[derive(Default, Debug)]
 struct DocumentStore {
-    documents: HashMap<Url, String>,
-    symbol_tables: HashMap<Url, SymbolTable>,
+    documents: HashMap<Url, Document>,
+    /// Analysis of the current version of each document, computed on demand
+    /// and dropped when the document changes
+    analyses: HashMap<Url, Arc<Analysis>>,
+    /// Signatures from the last version of each document that parsed, so
+    /// they stay available while a call is being typed
+    signatures: HashMap<Url, DocumentSignatures>,
 }
 
-#[derive(Debug)]
+/// How long the document must stay unchanged before it is re-analyzed for
+/// diagnostics, so typing does not re-check it on every keystroke
+const DIAGNOSTICS_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Client preferences passed as `initializationOptions`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ServerSettings {
+    inlay_hints: InlayHintSettings,
+}
+
+#[derive(Debug, Clone)]
 pub struct Backend {
     client: Client,
     state: Arc<RwLock<DocumentStore>>,
+    settings: Arc<RwLock<ServerSettings>>,
 }
 
 impl Backend {
@@ -153,21 +209,54 @@ impl Backend {
         Self {
             client,
             state: Arc::new(RwLock::new(DocumentStore::default())),
+            settings: Arc::new(RwLock::new(ServerSettings::default())),
         }
     }
 
-    async fn upsert_document(&self, uri: Url, text: String) {
+    async fn upsert_document(&self, uri: Url, text: String, version: i32) {
         {
             let mut state = self.state.write().await;
-            state.documents.insert(uri.clone(), text);
+            state
+                .documents
+                .insert(uri.clone(), Document { text, version });
+            state.analyses.remove(&uri);
         }
-        self.publish_diagnostics(uri).await;
+        self.publish_diagnostics(uri, version).await;
+    }
+
+    /// Apply incremental changes and schedule diagnostics for once the
+    /// document has been left alone for `DIAGNOSTICS_DEBOUNCE`
+    async fn change_document(
+        &self,
+        uri: Url,
+        version: i32,
+        changes: Vec<TextDocumentContentChangeEvent>,
+    ) {
+        {
+            let mut state = self.state.write().await;
+            let Some(document) = state.documents.get_mut(&uri) else {
+                return;
+            };
+            for change in changes {
+                analysis::apply_change(&mut document.text, change);
+            }
+            document.version = version;
+            state.analyses.remove(&uri);
+        }
+
+        let backend = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(DIAGNOSTICS_DEBOUNCE).await;
+            backend.publish_diagnostics(uri, version).await;
+        });
     }
 
     async fn remove_document(&self, uri: &Url) {
         {
             let mut state = self.state.write().await;
             state.documents.remove(uri);
+            state.analyses.remove(uri);
+            state.signatures.remove(uri);
         }
         let _ = self
             .client
@@ -175,42 +264,106 @@ impl Backend {
             .await;
     }
 
-    async fn publish_diagnostics(&self, uri: Url) {
-        let text = {
+    /// Publish the diagnostics of `version` of the document, unless it has
+    /// changed again since
+    async fn publish_diagnostics(&self, uri: Url, version: i32) {
+        {
             let state = self.state.read().await;
-            state.documents.get(&uri).cloned()
+            match state.documents.get(&uri) {
+                Some(document) if document.version == version => {}
+                _ => return,
+            }
+        }
+        let Some(analysis) = self.analysis(&uri).await else {
+            return;
         };
+        if analysis.version != version {
+            return;
+        }
 
-        if let Some(text) = text {
-            let (diagnostics, symbol_table) = compute_lsp_diagnostics_and_symbols(&text);
+        if let Some(signatures) = &analysis.signatures {
+            let mut state = self.state.write().await;
+            state.signatures.insert(uri.clone(), signatures.clone());
+        }
 
-            // Store the symbol table
-            {
-                let mut state = self.state.write().await;
-                state.symbol_tables.insert(uri.clone(), symbol_table);
+        let _ = self
+            .client
+            .publish_diagnostics(uri, analysis.diagnostics.clone(), Some(version))
+            .await;
+    }
+
+    /// The analysis of the current version of a document, computing and
+    /// caching it if this is the first request since the last change
+    async fn analysis(&self, uri: &Url) -> Option<Arc<Analysis>> {
+        let (text, version) = {
+            let state = self.state.read().await;
+            if let Some(analysis) = state.analyses.get(uri) {
+                return Some(analysis.clone());
             }
+            let document = state.documents.get(uri)?;
+            (document.text.clone(), document.version)
+        };
 
-            let _ = self
-                .client
-                .publish_diagnostics(uri, diagnostics, None)
-                .await;
+        // Lexing, parsing and checking are CPU bound
+        let analysis = tokio::task::spawn_blocking(move || Analysis::new(text, version))
+            .await
+            .ok()?;
+        let analysis = Arc::new(analysis);
+
+        let mut state = self.state.write().await;
+        let current = state.documents.get(uri).map(|document| document.version);
+        if current == Some(version) {
+            state.analyses.insert(uri.clone(), analysis.clone());
+        }
+        Some(analysis)
+    }
+
+    /// Fail a request whose result was computed from a version of the
+    /// document that has since been edited, so the client asks again instead
+    /// of applying stale positions. Requests the client cancels outright are
+    /// dropped by tower-lsp.
+    async fn ensure_current(&self, uri: &Url, analysis: &Analysis) -> Result<()> {
+        let state = self.state.read().await;
+        match state.documents.get(uri) {
+            Some(document) if document.version != analysis.version => Err(Error {
+                code: ErrorCode::ContentModified,
+                message: "document changed while the request was processed".into(),
+                data: None,
+            }),
+            _ => Ok(()),
         }
     }
 
-    #[allow(dead_code)]
     async fn document_text(&self, uri: &Url) -> Option<String> {
         let state = self.state.read().await;
-        state.documents.get(uri).cloned()
+        state
+            .documents
+            .get(uri)
+            .map(|document| document.text.clone())
     }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        if let Some(options) = params.initialization_options {
+            match serde_json::from_value::<ServerSettings>(options) {
+                Ok(settings) => *self.settings.write().await = settings,
+                Err(err) => {
+                    self.client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("ignoring invalid initialization options: {err}"),
+                        )
+                        .await
+                }
+            }
+        }
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 completion_provider: Some(CompletionOptions {
@@ -254,6 +407,17 @@ impl LanguageServer for Backend {
                     .into(),
                 ),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                    retrigger_characters: Some(vec![")".to_string()]),
+                    work_done_progress_options: Default::default(),
+                }),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".to_string(),
+                    more_trigger_character: None,
+                }),
                 ..Default::default()
             },
             ..Default::default()
@@ -267,15 +431,15 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.upsert_document(params.text_document.uri, params.text_document.text)
+        let document = params.text_document;
+        self.upsert_document(document.uri, document.text, document.version)
             .await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        if let Some(change) = params.content_changes.into_iter().last() {
-            self.upsert_document(params.text_document.uri, change.text)
-                .await;
-        }
+        let document = params.text_document;
+        self.change_document(document.uri, document.version, params.content_changes)
+            .await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -293,22 +457,18 @@ impl LanguageServer for Backend {
         let uri = params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        let (text, symbol_table) = {
-            let state = self.state.read().await;
-            let text = state.documents.get(&uri).cloned();
-            let symbol_table = state.symbol_tables.get(&uri).cloned();
-            (text, symbol_table)
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
         };
-
-        if let (Some(text), Some(symbol_table)) = (text, symbol_table) {
-            if let Some(var_name) = word_at_position(&text, position) {
-                if let Some(symbol_info) = symbol_table.find_definition(&var_name) {
-                    let range = span_to_range(symbol_info.span, &text);
-                    return Ok(Some(GotoDefinitionResponse::Scalar(Location {
-                        uri: uri.clone(),
-                        range,
-                    })));
-                }
+        let (text, symbol_table) = (analysis.text.as_str(), &analysis.symbol_table);
+
+        if let Some(var_name) = word_at_position(text, position) {
+            if let Some(symbol_info) = symbol_table.find_definition(&var_name) {
+                let range = span_to_range(symbol_info.span, text);
+                return Ok(Some(GotoDefinitionResponse::Scalar(Location {
+                    uri: uri.clone(),
+                    range,
+                })));
             }
         }
 
@@ -335,35 +495,31 @@ impl LanguageServer for Backend {
         let uri = params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
 
-        let (text, symbol_table) = {
-            let state = self.state.read().await;
-            let text = state.documents.get(&uri).cloned();
-            let symbol_table = state.symbol_tables.get(&uri).cloned();
-            (text, symbol_table)
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
         };
+        let (text, symbol_table) = (analysis.text.as_str(), &analysis.symbol_table);
 
-        if let (Some(text), Some(symbol_table)) = (text, symbol_table) {
-            if let Some(var_name) = word_at_position(&text, position) {
-                let mut locations = Vec::new();
+        if let Some(var_name) = word_at_position(text, position) {
+            let mut locations = Vec::new();
 
-                // Add definition
-                if let Some(symbol_info) = symbol_table.find_definition(&var_name) {
-                    locations.push(Location {
-                        uri: uri.clone(),
-                        range: span_to_range(symbol_info.span, &text),
-                    });
-                }
-
-                // Add all references
-                for span in symbol_table.find_references(&var_name) {
-                    locations.push(Location {
-                        uri: uri.clone(),
-                        range: span_to_range(*span, &text),
-                    });
-                }
+            // Add definition
+            if let Some(symbol_info) = symbol_table.find_definition(&var_name) {
+                locations.push(Location {
+                    uri: uri.clone(),
+                    range: span_to_range(symbol_info.span, text),
+                });
+            }
 
-                return Ok(Some(locations));
+            // Add all references
+            for span in symbol_table.find_references(&var_name) {
+                locations.push(Location {
+                    uri: uri.clone(),
+                    range: span_to_range(*span, text),
+                });
             }
+
+            return Ok(Some(locations));
         }
 
         Ok(None)
@@ -374,42 +530,38 @@ impl LanguageServer for Backend {
         params: DocumentSymbolParams,
     ) -> Result<Option<DocumentSymbolResponse>> {
         let uri = params.text_document.uri;
-        let (text, symbol_table) = {
-            let state = self.state.read().await;
-            let text = state.documents.get(&uri).cloned();
-            let symbol_table = state.symbol_tables.get(&uri).cloned();
-            (text, symbol_table)
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
         };
-
-        if let (Some(text), Some(symbol_table)) = (text, symbol_table) {
-            let mut symbols = Vec::new();
-            for (name, info) in symbol_table.all_symbols() {
-                let kind = match info.kind {
-                    SymbolKind::Function => tower_lsp::lsp_types::SymbolKind::FUNCTION,
-                    SymbolKind::Variable => tower_lsp::lsp_types::SymbolKind::VARIABLE,
-                    SymbolKind::Parameter => tower_lsp::lsp_types::SymbolKind::VARIABLE,
-                    SymbolKind::Struct => tower_lsp::lsp_types::SymbolKind::STRUCT,
-                    SymbolKind::Enum => tower_lsp::lsp_types::SymbolKind::ENUM,
-                    SymbolKind::TypeAlias => tower_lsp::lsp_types::SymbolKind::TYPE_PARAMETER,
-                    SymbolKind::Method => tower_lsp::lsp_types::SymbolKind::METHOD,
-                };
-                #[allow(deprecated)]
-                let symbol = DocumentSymbol {
-                    name: name.clone(),
-                    detail: info.ty.clone(),
-                    kind,
-                    range: span_to_range(info.span, &text),
-                    selection_range: span_to_range(info.span, &text),
-                    children: None,
-                    deprecated: None,
-                    tags: None,
-                };
-                symbols.push(symbol);
-            }
-            return Ok(Some(DocumentSymbolResponse::Nested(symbols)));
+        let (text, symbol_table) = (analysis.text.as_str(), &analysis.symbol_table);
+
+        let mut symbols = Vec::new();
+        for (name, info) in symbol_table.all_symbols() {
+            let kind = match info.kind {
+                SymbolKind::Function => tower_lsp::lsp_types::SymbolKind::FUNCTION,
+                SymbolKind::Variable => tower_lsp::lsp_types::SymbolKind::VARIABLE,
+                SymbolKind::Constant => tower_lsp::lsp_types::SymbolKind::CONSTANT,
+                SymbolKind::Parameter => tower_lsp::lsp_types::SymbolKind::VARIABLE,
+                SymbolKind::Struct => tower_lsp::lsp_types::SymbolKind::STRUCT,
+                SymbolKind::Enum => tower_lsp::lsp_types::SymbolKind::ENUM,
+                SymbolKind::TypeAlias => tower_lsp::lsp_types::SymbolKind::TYPE_PARAMETER,
+                SymbolKind::Trait => tower_lsp::lsp_types::SymbolKind::INTERFACE,
+                SymbolKind::Method => tower_lsp::lsp_types::SymbolKind::METHOD,
+            };
+            #[allow(deprecated)]
+            let symbol = DocumentSymbol {
+                name: name.clone(),
+                detail: info.ty.clone(),
+                kind,
+                range: span_to_range(info.span, text),
+                selection_range: span_to_range(info.span, text),
+                children: None,
+                deprecated: None,
+                tags: None,
+            };
+            symbols.push(symbol);
         }
-
-        Ok(None)
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
     }
 
     async fn symbol(
@@ -420,35 +572,34 @@ impl LanguageServer for Backend {
         let mut results = Vec::new();
 
         let state = self.state.read().await;
-        for (uri, symbol_table) in &state.symbol_tables {
-            if let Some(text) = state.documents.get(uri) {
-                for (name, info) in symbol_table.all_symbols() {
-                    if name.to_lowercase().contains(&query) {
-                        let kind = match info.kind {
-                            SymbolKind::Function => tower_lsp::lsp_types::SymbolKind::FUNCTION,
-                            SymbolKind::Variable => tower_lsp::lsp_types::SymbolKind::VARIABLE,
-                            SymbolKind::Parameter => tower_lsp::lsp_types::SymbolKind::VARIABLE,
-                            SymbolKind::Struct => tower_lsp::lsp_types::SymbolKind::STRUCT,
-                            SymbolKind::Enum => tower_lsp::lsp_types::SymbolKind::ENUM,
-                            SymbolKind::TypeAlias => {
-                                tower_lsp::lsp_types::SymbolKind::TYPE_PARAMETER
-                            }
-                            SymbolKind::Method => tower_lsp::lsp_types::SymbolKind::METHOD,
-                        };
-                        #[allow(deprecated)]
-                        let info = SymbolInformation {
-                            name: name.clone(),
-                            kind,
-                            location: Location {
-                                uri: uri.clone(),
-                                range: span_to_range(info.span, text),
-                            },
-                            container_name: None,
-                            deprecated: None,
-                            tags: None,
-                        };
-                        results.push(info);
-                    }
+        for (uri, analysis) in &state.analyses {
+            let (text, symbol_table) = (analysis.text.as_str(), &analysis.symbol_table);
+            for (name, info) in symbol_table.all_symbols() {
+                if name.to_lowercase().contains(&query) {
+                    let kind = match info.kind {
+                        SymbolKind::Function => tower_lsp::lsp_types::SymbolKind::FUNCTION,
+                        SymbolKind::Variable => tower_lsp::lsp_types::SymbolKind::VARIABLE,
+                        SymbolKind::Constant => tower_lsp::lsp_types::SymbolKind::CONSTANT,
+                        SymbolKind::Parameter => tower_lsp::lsp_types::SymbolKind::VARIABLE,
+                        SymbolKind::Struct => tower_lsp::lsp_types::SymbolKind::STRUCT,
+                        SymbolKind::Enum => tower_lsp::lsp_types::SymbolKind::ENUM,
+                        SymbolKind::TypeAlias => tower_lsp::lsp_types::SymbolKind::TYPE_PARAMETER,
+                        SymbolKind::Trait => tower_lsp::lsp_types::SymbolKind::INTERFACE,
+                        SymbolKind::Method => tower_lsp::lsp_types::SymbolKind::METHOD,
+                    };
+                    #[allow(deprecated)]
+                    let info = SymbolInformation {
+                        name: name.clone(),
+                        kind,
+                        location: Location {
+                            uri: uri.clone(),
+                            range: span_to_range(info.span, text),
+                        },
+                        container_name: None,
+                        deprecated: None,
+                        tags: None,
+                    };
+                    results.push(info);
                 }
             }
         }
@@ -461,42 +612,39 @@ impl LanguageServer for Backend {
         let position = params.text_document_position.position;
         let new_name = params.new_name;
 
-        let (text, symbol_table) = {
-            let state = self.state.read().await;
-            let text = state.documents.get(&uri).cloned();
-            let symbol_table = state.symbol_tables.get(&uri).cloned();
-            (text, symbol_table)
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
         };
+        let (text, symbol_table) = (analysis.text.as_str(), &analysis.symbol_table);
 
-        if let (Some(text), Some(symbol_table)) = (text, symbol_table) {
-            if let Some(old_name) = word_at_position(&text, position) {
-                let mut changes = HashMap::new();
-                let mut edits = Vec::new();
-
-                // Add definition rename
-                if let Some(symbol_info) = symbol_table.find_definition(&old_name) {
-                    edits.push(TextEdit {
-                        range: span_to_range(symbol_info.span, &text),
-                        new_text: new_name.clone(),
-                    });
-                }
+        if let Some(old_name) = word_at_position(text, position) {
+            let mut changes = HashMap::new();
+            let mut edits = Vec::new();
 
-                // Add all references
-                for span in symbol_table.find_references(&old_name) {
-                    edits.push(TextEdit {
-                        range: span_to_range(*span, &text),
-                        new_text: new_name.clone(),
-                    });
-                }
+            // Add definition rename
+            if let Some(symbol_info) = symbol_table.find_definition(&old_name) {
+                edits.push(TextEdit {
+                    range: span_to_range(symbol_info.span, text),
+                    new_text: new_name.clone(),
+                });
+            }
 
-                if !edits.is_empty() {
-                    changes.insert(uri, edits);
-                    return Ok(Some(WorkspaceEdit {
-                        changes: Some(changes),
-                        document_changes: None,
-                        change_annotations: None,
-                    }));
-                }
+            // Add all references
+            for span in symbol_table.find_references(&old_name) {
+                edits.push(TextEdit {
+                    range: span_to_range(*span, text),
+                    new_text: new_name.clone(),
+                });
+            }
+
+            if !edits.is_empty() {
+                self.ensure_current(&uri, &analysis).await?;
+                changes.insert(uri, edits);
+                return Ok(Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    document_changes: None,
+                    change_annotations: None,
+                }));
             }
         }
 
@@ -507,37 +655,35 @@ impl LanguageServer for Backend {
         let uri = params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        let (text, symbol_table) = {
-            let state = self.state.read().await;
-            let text = state.documents.get(&uri).cloned();
-            let symbol_table = state.symbol_tables.get(&uri).cloned();
-            (text, symbol_table)
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
         };
-
-        if let (Some(text), Some(symbol_table)) = (text, symbol_table) {
-            if let Some(var_name) = word_at_position(&text, position) {
-                if let Some(symbol_info) = symbol_table.find_definition(&var_name) {
-                    let kind_str = match symbol_info.kind {
-                        SymbolKind::Function => "function",
-                        SymbolKind::Variable => "variable",
-                        SymbolKind::Parameter => "parameter",
-                        SymbolKind::Struct => "struct",
-                        SymbolKind::Enum => "enum",
-                        SymbolKind::TypeAlias => "type",
-                        SymbolKind::Method => "method",
-                    };
-                    let detail = symbol_info
-                        .ty
-                        .as_ref()
-                        .map(|ty| format!("{}: {}", kind_str, ty))
-                        .unwrap_or_else(|| kind_str.to_string());
-
-                    let contents = HoverContents::Scalar(MarkedString::String(detail));
-                    return Ok(Some(Hover {
-                        contents,
-                        range: Some(span_to_range(symbol_info.span, &text)),
-                    }));
-                }
+        let (text, symbol_table) = (analysis.text.as_str(), &analysis.symbol_table);
+
+        if let Some(var_name) = word_at_position(text, position) {
+            if let Some(symbol_info) = symbol_table.find_definition(&var_name) {
+                let kind_str = match symbol_info.kind {
+                    SymbolKind::Function => "function",
+                    SymbolKind::Variable => "variable",
+                    SymbolKind::Constant => "constant",
+                    SymbolKind::Parameter => "parameter",
+                    SymbolKind::Struct => "struct",
+                    SymbolKind::Enum => "enum",
+                    SymbolKind::TypeAlias => "type",
+                    SymbolKind::Trait => "trait",
+                    SymbolKind::Method => "method",
+                };
+                let detail = symbol_info
+                    .ty
+                    .as_ref()
+                    .map(|ty| format!("{}: {}", kind_str, ty))
+                    .unwrap_or_else(|| kind_str.to_string());
+
+                let contents = HoverContents::Scalar(MarkedString::String(detail));
+                return Ok(Some(Hover {
+                    contents,
+                    range: Some(span_to_range(symbol_info.span, text)),
+                }));
             }
         }
 
@@ -548,12 +694,7 @@ impl LanguageServer for Backend {
         let uri = params.text_document_position.text_document.uri;
         let _position = params.text_document_position.position;
 
-        let (_text, symbol_table) = {
-            let state = self.state.read().await;
-            let text = state.documents.get(&uri).cloned();
-            let symbol_table = state.symbol_tables.get(&uri).cloned();
-            (text, symbol_table)
-        };
+        let analysis = self.analysis(&uri).await;
 
         let mut items = Vec::new();
 
@@ -566,15 +707,17 @@ impl LanguageServer for Backend {
         });
 
         // Add symbols from symbol table
-        if let Some(symbol_table) = symbol_table {
-            for (name, info) in symbol_table.all_symbols() {
+        if let Some(analysis) = &analysis {
+            for (name, info) in analysis.symbol_table.all_symbols() {
                 let kind = match info.kind {
                     SymbolKind::Function => CompletionItemKind::FUNCTION,
                     SymbolKind::Variable => CompletionItemKind::VARIABLE,
+                    SymbolKind::Constant => CompletionItemKind::CONSTANT,
                     SymbolKind::Parameter => CompletionItemKind::VARIABLE,
                     SymbolKind::Struct => CompletionItemKind::STRUCT,
                     SymbolKind::Enum => CompletionItemKind::ENUM,
                     SymbolKind::TypeAlias => CompletionItemKind::TYPE_PARAMETER,
+                    SymbolKind::Trait => CompletionItemKind::INTERFACE,
                     SymbolKind::Method => CompletionItemKind::METHOD,
                 };
                 items.push(CompletionItem {
@@ -589,8 +732,33 @@ impl LanguageServer for Backend {
         Ok(Some(CompletionResponse::Array(items)))
     }
 
-    async fn inlay_hint(&self, _params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
-        Ok(Some(Vec::new()))
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
+        };
+        let Some(tokens) = &analysis.tokens else {
+            return Ok(None);
+        };
+        let signatures = {
+            let state = self.state.read().await;
+            state.signatures.get(&uri).cloned().unwrap_or_default()
+        };
+        let offset = position_to_offset(&analysis.text, position);
+        Ok(signature_help::signature_help(tokens, offset, &signatures))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = params.text_document.uri;
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
+        };
+        let settings = self.settings.read().await.inlay_hints;
+        let hints = inlay_hints::inlay_hints(&analysis, params.range, settings);
+        self.ensure_current(&uri, &analysis).await?;
+        Ok(Some(hints))
     }
 
     async fn inlay_hint_resolve(&self, hint: InlayHint) -> Result<InlayHint> {
@@ -602,97 +770,102 @@ impl LanguageServer for Backend {
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
         let uri = params.text_document.uri;
-        let (text, symbol_table) = {
-            let state = self.state.read().await;
-            let text = state.documents.get(&uri).cloned();
-            let symbol_table = state.symbol_tables.get(&uri).cloned();
-            (text, symbol_table)
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
         };
+        let (text, symbol_table) = (analysis.text.as_str(), &analysis.symbol_table);
+
+        let mut tokens = Vec::new();
+        let mut prev_line = 0;
+        let mut prev_col = 0;
+
+        for (_name, info) in symbol_table.all_symbols() {
+            let pos = span_to_position(info.span.start(), text);
+            let token_type = match info.kind {
+                SymbolKind::Function | SymbolKind::Method => 0, // FUNCTION
+                SymbolKind::Variable | SymbolKind::Constant => 1, // VARIABLE
+                SymbolKind::Parameter => 2,                     // PARAMETER
+                SymbolKind::Struct => 4,                        // CLASS
+                SymbolKind::Enum => 5,                          // ENUM
+                SymbolKind::TypeAlias | SymbolKind::Trait => 3, // TYPE
+            };
+
+            let delta_line = pos.line as u32 - prev_line;
+            let delta_start = if delta_line == 0 {
+                pos.character as u32 - prev_col
+            } else {
+                pos.character as u32
+            };
+            let length = (info.span.end() - info.span.start()) as u32;
+
+            tokens.push(SemanticToken {
+                delta_line,
+                delta_start,
+                length,
+                token_type,
+                token_modifiers_bitset: 0,
+            });
+
+            prev_line = pos.line as u32;
+            prev_col = pos.character as u32;
+        }
 
-        if let (Some(text), Some(symbol_table)) = (text, symbol_table) {
-            let mut tokens = Vec::new();
-            let mut prev_line = 0;
-            let mut prev_col = 0;
-
-            for (_name, info) in symbol_table.all_symbols() {
-                let pos = span_to_position(info.span.start(), &text);
-                let token_type = match info.kind {
-                    SymbolKind::Function | SymbolKind::Method => 0, // FUNCTION
-                    SymbolKind::Variable => 1,                      // VARIABLE
-                    SymbolKind::Parameter => 2,                     // PARAMETER
-                    SymbolKind::Struct => 4,                        // CLASS
-                    SymbolKind::Enum => 5,                          // ENUM
-                    SymbolKind::TypeAlias => 3,                     // TYPE
-                };
-
-                let delta_line = pos.line as u32 - prev_line;
-                let delta_start = if delta_line == 0 {
-                    pos.character as u32 - prev_col
-                } else {
-                    pos.character as u32
-                };
-                let length = (info.span.end() - info.span.start()) as u32;
-
-                tokens.push(SemanticToken {
-                    delta_line,
-                    delta_start,
-                    length,
-                    token_type,
-                    token_modifiers_bitset: 0,
-                });
+        self.ensure_current(&uri, &analysis).await?;
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: tokens,
+        })))
+    }
 
-                prev_line = pos.line as u32;
-                prev_col = pos.character as u32;
-            }
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri;
+        let Some(text) = self.document_text(&uri).await else {
+            return Ok(None);
+        };
+        let config = formatting::config_for(&uri, &params.options);
+        Ok(formatting::format_document(&text, config))
+    }
 
-            return Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
-                result_id: None,
-                data: tokens,
-            })));
-        }
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri;
+        let Some(text) = self.document_text(&uri).await else {
+            return Ok(None);
+        };
+        let config = formatting::config_for(&uri, &params.options);
+        Ok(formatting::format_range(&text, params.range, config))
+    }
 
-        Ok(None)
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let position = params.text_document_position;
+        let Some(text) = self.document_text(&position.text_document.uri).await else {
+            return Ok(None);
+        };
+        let config = formatting::config_for(&position.text_document.uri, &params.options);
+        Ok(formatting::format_on_newline(
+            &text,
+            position.position,
+            config.indent_width,
+        ))
     }
 
     async fn code_action(
         &self,
         params: CodeActionParams,
     ) -> Result<Option<Vec<CodeActionOrCommand>>> {
-        let mut actions = Vec::new();
-
-        // Add "Add type annotation" action for variables
-        for diag in &params.context.diagnostics {
-            if diag.message.contains("type") {
-                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                    title: "Add type annotation".into(),
-                    kind: Some(CodeActionKind::QUICKFIX),
-                    diagnostics: Some(vec![diag.clone()]),
-                    edit: None,
-                    command: None,
-                    is_preferred: Some(true),
-                    disabled: None,
-                    data: None,
-                }));
-            }
-        }
-
-        // Add "Extract function" action
-        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-            title: "Extract function".into(),
-            kind: Some(CodeActionKind::REFACTOR_EXTRACT),
-            diagnostics: None,
-            edit: None,
-            command: None,
-            is_preferred: None,
-            disabled: None,
-            data: None,
-        }));
-
-        if actions.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(actions))
-        }
+        let uri = params.text_document.uri;
+        let Some(analysis) = self.analysis(&uri).await else {
+            return Ok(None);
+        };
+        let actions =
+            code_actions::code_actions(&uri, &analysis, params.range, &params.context.diagnostics);
+        self.ensure_current(&uri, &analysis).await?;
+        Ok((!actions.is_empty()).then_some(actions))
     }
 }
 
@@ -709,7 +882,7 @@ fn span_to_position(byte_offset: usize, text: &str) -> Position {
             line += 1;
             character = 0;
         } else {
-            character += 1;
+            character += ch.len_utf16() as u32;
         }
     }
 
@@ -747,13 +920,31 @@ fn build_symbol_table_from_statements(
     for stmt in statements {
         match stmt {
             Statement::Let {
-                name, span, expr, ..
+                name,
+                ty,
+                span,
+                expr,
+                ..
             } => {
                 if let Some(span) = span {
-                    let ty = infer_type_from_expr(expr);
+                    let ty = ty
+                        .as_ref()
+                        .map(format_type)
+                        .or_else(|| infer_type_from_expr(expr));
                     table.add_variable(name.clone(), *span, ty);
                 }
             }
+            Statement::Const {
+                name,
+                ty,
+                span: Some(span),
+                ..
+            } => table.add_constant(name.clone(), *span, Some(format_type(ty))),
+            Statement::Destructure {
+                pattern,
+                span: Some(span),
+                ..
+            } => add_pattern_variables(pattern, *span, table, tokens),
             Statement::Function(func) => {
                 // Find function name span from tokens
                 if let Some(span) = find_name_span(&func.name, tokens, text) {
@@ -783,6 +974,11 @@ fn build_symbol_table_from_statements(
                     table.add_type_alias(name.clone(), span);
                 }
             }
+            Statement::Trait { name, .. } => {
+                if let Some(span) = find_name_span(name, tokens, text) {
+                    table.add_trait(name.clone(), span);
+                }
+            }
             Statement::If {
                 then_block,
                 elif_blocks,
@@ -804,11 +1000,11 @@ fn build_symbol_table_from_statements(
                 ..
             } => {
                 if let Some(span) = var_span {
-                    table.add_variable(var.clone(), *span, None);
+                    add_pattern_variables(var, *span, table, tokens);
                 }
                 build_symbol_table_from_statements(&body.statements, table, tokens, text);
             }
-            Statement::While { body, .. } => {
+            Statement::While { body, .. } | Statement::With { body, .. } => {
                 build_symbol_table_from_statements(&body.statements, table, tokens, text);
             }
             Statement::Try {
@@ -859,6 +1055,7 @@ fn collect_references_from_statements(
                             span: None,
                         }),
                         args: vec![],
+                        span: None,
                     },
                     table,
                     tokens,
@@ -866,7 +1063,13 @@ fn collect_references_from_statements(
                 );
                 collect_references_from_statements(&func.body.statements, table, tokens, text);
             }
-            Statement::Let { expr, .. } => {
+            Statement::Let { expr, .. }
+            | Statement::Const { expr, .. }
+            | Statement::Destructure { expr, .. } => {
+                collect_references_from_expr(expr, table, tokens, text);
+            }
+            Statement::Assignment { target, expr, .. } => {
+                collect_references_from_expr(target, table, tokens, text);
                 collect_references_from_expr(expr, table, tokens, text);
             }
             Statement::If {
@@ -890,16 +1093,31 @@ fn collect_references_from_statements(
                 collect_references_from_expr(iterable, table, tokens, text);
                 collect_references_from_statements(&body.statements, table, tokens, text);
             }
-            Statement::While { cond, body } => {
+            Statement::While { cond, body }
+            | Statement::With {
+                manager: cond,
+                body,
+                ..
+            } => {
                 collect_references_from_expr(cond, table, tokens, text);
                 collect_references_from_statements(&body.statements, table, tokens, text);
             }
             Statement::Expr(expr) => {
                 collect_references_from_expr(expr, table, tokens, text);
             }
-            Statement::Return(Some(expr)) => {
+            Statement::Return(Some(expr)) | Statement::Yield(expr) => {
                 collect_references_from_expr(expr, table, tokens, text);
             }
+            Statement::Trait { methods, .. } | Statement::Impl { methods, .. } => {
+                for method in methods {
+                    collect_references_from_statements(
+                        &method.body.statements,
+                        table,
+                        tokens,
+                        text,
+                    );
+                }
+            }
             _ => {}
         }
     }
@@ -918,7 +1136,7 @@ fn collect_references_from_expr(
                 table.add_reference(name.clone(), span);
             }
         }
-        Expr::Call { func, args } => {
+        Expr::Call { func, args, .. } => {
             collect_references_from_expr(func, table, tokens, text);
             for arg in args {
                 collect_references_from_expr(arg, table, tokens, text);
@@ -927,11 +1145,23 @@ fn collect_references_from_expr(
         Expr::Member { object, .. } => {
             collect_references_from_expr(object, table, tokens, text);
         }
+        Expr::Index { object, index, .. } => {
+            collect_references_from_expr(object, table, tokens, text);
+            collect_references_from_expr(index, table, tokens, text);
+        }
+        Expr::Slice {
+            object, start, end, ..
+        } => {
+            collect_references_from_expr(object, table, tokens, text);
+            for bound in [start, end].into_iter().flatten() {
+                collect_references_from_expr(bound, table, tokens, text);
+            }
+        }
         Expr::Binary { left, right, .. } => {
             collect_references_from_expr(left, table, tokens, text);
             collect_references_from_expr(right, table, tokens, text);
         }
-        Expr::Unary { expr, .. } => {
+        Expr::Unary { expr, .. } | Expr::Cast { expr, .. } => {
             collect_references_from_expr(expr, table, tokens, text);
         }
         Expr::If {
@@ -945,7 +1175,7 @@ fn collect_references_from_expr(
                 collect_references_from_expr(else_expr, table, tokens, text);
             }
         }
-        Expr::Array(elements) => {
+        Expr::Array(elements) | Expr::Tuple(elements) => {
             for elem in elements {
                 collect_references_from_expr(elem, table, tokens, text);
             }
@@ -960,6 +1190,26 @@ fn collect_references_from_expr(
     }
 }
 
+/// Define every name bound by a `let` or `for` pattern, at its identifier
+/// inside the pattern's span
+fn add_pattern_variables(
+    pattern: &ast::nodes::Pattern,
+    span: Span,
+    table: &mut SymbolTable,
+    tokens: &[Token],
+) {
+    for name in pattern.bindings() {
+        let name_span = tokens
+            .iter()
+            .filter(|token| token.span.start() >= span.start() && token.span.end() <= span.end())
+            .find(|token| matches!(&token.kind, lexer::token::TokenKind::Identifier(id) if id == name))
+            .map(|token| token.span);
+        if let Some(name_span) = name_span {
+            table.add_variable(name.to_string(), name_span, None);
+        }
+    }
+}
+
 /// Find span of a name in tokens (approximate)
 fn find_name_span(name: &str, tokens: &[Token], _text: &str) -> Option<Span> {
     for token in tokens {
@@ -1001,6 +1251,21 @@ fn format_type(ty: &ast::nodes::Type) -> String {
             let args_str: Vec<String> = args.iter().map(format_type).collect();
             format!("{}<{}>", base, args_str.join(", "))
         }
+        ast::nodes::Type::Tuple(elements) => {
+            let elements_str: Vec<String> = elements.iter().map(format_type).collect();
+            if elements.len() == 1 {
+                format!("({},)", elements_str[0])
+            } else {
+                format!("({})", elements_str.join(", "))
+            }
+        }
+        ast::nodes::Type::Function { params, ret } => {
+            let params_str: Vec<String> = params.iter().map(format_type).collect();
+            match ret {
+                Some(ret) => format!("fn({}) -> {}", params_str.join(", "), format_type(ret)),
+                None => format!("fn({})", params_str.join(", ")),
+            }
+        }
     }
 }
 
@@ -1009,47 +1274,6 @@ fn infer_type_from_expr(_expr: &Expr) -> Option<String> {
     None // Could be enhanced with type inference
 }
 
-/// Compute diagnostics and build symbol table from source text
-fn compute_lsp_diagnostics_and_symbols(text: &str) -> (Vec<Diagnostic>, SymbolTable) {
-    let source_id = "lsp";
-    match tokenize(text) {
-        Ok(tokens) => match parse(&tokens) {
-            Ok(program) => {
-                // Build symbol table from the parsed program
-                let symbol_table = build_symbol_table(&program, &tokens, text);
-
-                let diagnostics = {
-                    let mut checker = TypeChecker::new().with_registry(SymbolRegistry::global());
-                    if checker.check_program(&program).is_err() {
-                        typecheck::diagnostics_from_type_errors(checker.errors(), source_id, text)
-                            .into_iter()
-                            .map(|diag| otter_diag_to_lsp(&diag, text))
-                            .collect()
-                    } else {
-                        Vec::new()
-                    }
-                };
-
-                (diagnostics, symbol_table)
-            }
-            Err(errors) => {
-                let diagnostics = errors
-                    .into_iter()
-                    .map(|err| otter_diag_to_lsp(&err.to_diagnostic(source_id), text))
-                    .collect();
-                (diagnostics, SymbolTable::new())
-            }
-        },
-        Err(errors) => {
-            let diagnostics = errors
-                .into_iter()
-                .map(|err| otter_diag_to_lsp(&lexer_error_to_diag(source_id, &err), text))
-                .collect();
-            (diagnostics, SymbolTable::new())
-        }
-    }
-}
-
 fn word_at_position(text: &str, position: Position) -> Option<String> {
     let line = text.lines().nth(position.line as usize)?;
     let chars: Vec<char> = line.chars().collect();
@@ -1127,6 +1351,8 @@ fn span_to_range(span: Span, text: &str) -> Range {
     }
 }
 
+/// Position of a byte offset. Positions count UTF-16 code units within a
+/// line, the encoding clients use since the server negotiates no other
 fn offset_to_position(text: &str, offset: usize) -> Position {
     let mut counted = 0usize;
     let mut line = 0u32;
@@ -1139,16 +1365,40 @@ fn offset_to_position(text: &str, offset: usize) -> Position {
             line += 1;
             character = 0;
         } else {
-            character += 1;
+            character += ch.len_utf16() as u32;
         }
         counted += ch.len_utf8();
     }
     Position { line, character }
 }
 
+/// Byte offset of `position` (see `offset_to_position`), clamped to the end
+/// of its line
+fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return text.len(),
+        }
+    }
+    let line = &text[line_start..];
+    let line_end = line.find('\n').unwrap_or(line.len());
+    let mut units = 0;
+    for (index, ch) in line[..line_end].char_indices() {
+        if units >= position.character as usize {
+            return line_start + index;
+        }
+        units += ch.len_utf16();
+    }
+    line_start + line_end
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use lexer::tokenize;
+    use parser::parse;
 
     #[test]
     fn test_build_symbol_table() {
@@ -1246,4 +1496,17 @@ for i in [1, 2, 3]:
             }
         }
     }
+
+    #[test]
+    fn positions_count_utf16_code_units() {
+        let text = "let s = \"🦦\" + x\nlet é = x\n";
+        let first_x = text.find('x').unwrap();
+        let second_x = text.rfind('x').unwrap();
+
+        assert_eq!(offset_to_position(text, first_x), Position::new(0, 15));
+        assert_eq!(offset_to_position(text, second_x), Position::new(1, 8));
+        assert_eq!(position_to_offset(text, Position::new(0, 15)), first_x);
+        assert_eq!(position_to_offset(text, Position::new(1, 8)), second_x);
+        assert_eq!(span_to_position(first_x, text), Position::new(0, 15));
+    }
 }
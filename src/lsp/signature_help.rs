@@ -0,0 +1,387 @@
+//! Signature help for calls of document functions, standard library
+//! functions and functions of `use rust:` crates.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{
+    Documentation, ParameterInformation, ParameterLabel, SignatureHelp, SignatureInformation,
+};
+
+use super::inlay_hints::stdlib_parameter_names;
+use crate::runtime::ffi::bootstrap_stdlib;
+use crate::runtime::symbol_registry::FfiType;
+use crate::typecheck::{TypeChecker, TypeInfo};
+use ::ffi::{BridgeSymbolRegistry, TypeSpec};
+use ast::nodes::{Function, Program, Statement};
+use lexer::token::TokenKind;
+use lexer::Token;
+
+/// A callable's parameter list as shown while typing its arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Signature {
+    /// What precedes the parameter list, e.g. `def scale` or `math.clamp`
+    name: String,
+    parameters: Vec<String>,
+    return_type: Option<String>,
+    documentation: Option<String>,
+}
+
+impl Signature {
+    fn information(&self) -> SignatureInformation {
+        let mut label = format!("{}(", self.name);
+        let mut parameters = Vec::with_capacity(self.parameters.len());
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            if index > 0 {
+                label.push_str(", ");
+            }
+            // Offsets are in UTF-16 code units, as for positions
+            let start = label.encode_utf16().count() as u32;
+            label.push_str(parameter);
+            let end = label.encode_utf16().count() as u32;
+            parameters.push(ParameterInformation {
+                label: ParameterLabel::LabelOffsets([start, end]),
+                documentation: None,
+            });
+        }
+        label.push(')');
+        if let Some(return_type) = &self.return_type {
+            label.push_str(" -> ");
+            label.push_str(return_type);
+        }
+
+        SignatureInformation {
+            label,
+            documentation: self.documentation.clone().map(Documentation::String),
+            parameters: Some(parameters),
+            active_parameter: None,
+        }
+    }
+}
+
+/// Signatures of the functions a document defines or imports, keyed by the
+/// callee as written at call sites (`scale`, `rand.random_f64`)
+pub(crate) type DocumentSignatures = HashMap<String, Signature>;
+
+/// Collect the signatures of `program` from a checker that has checked it
+pub(crate) fn document_signatures(program: &Program, checker: &TypeChecker) -> DocumentSignatures {
+    let mut signatures = DocumentSignatures::new();
+
+    // Builtins only have parameter types
+    for (name, ty) in &checker.context().functions {
+        if let TypeInfo::Function {
+            params,
+            return_type,
+            ..
+        } = ty
+        {
+            signatures.insert(
+                name.clone(),
+                Signature {
+                    name: name.clone(),
+                    parameters: params.iter().map(TypeInfo::display_name).collect(),
+                    return_type: type_name(return_type),
+                    documentation: None,
+                },
+            );
+        }
+    }
+
+    let formatter = fmt::Formatter::new();
+    for statement in &program.statements {
+        match statement {
+            Statement::Function(function) => {
+                let ty = checker.context().functions.get(&function.name);
+                signatures.insert(
+                    function.name.clone(),
+                    function_signature(function, ty, &formatter),
+                );
+            }
+            Statement::Use { imports } => {
+                for import in imports {
+                    if let Some(crate_name) = import.module.strip_prefix("rust:") {
+                        bridge_signatures(crate_name, import.alias.as_deref(), &mut signatures);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    signatures
+}
+
+fn function_signature(
+    function: &Function,
+    ty: Option<&TypeInfo>,
+    formatter: &fmt::Formatter,
+) -> Signature {
+    let (param_types, return_type) = match ty {
+        Some(TypeInfo::Function {
+            params,
+            return_type,
+            ..
+        }) => (params.as_slice(), type_name(return_type)),
+        _ => (&[][..], None),
+    };
+
+    let parameters = function
+        .params
+        .iter()
+        .enumerate()
+        .map(|(index, param)| {
+            let mut label = param.name.clone();
+            let ty = param_types
+                .get(index)
+                .and_then(type_name)
+                .or_else(|| param.ty.as_ref().map(super::format_type));
+            if let Some(ty) = ty {
+                label.push_str(": ");
+                label.push_str(&ty);
+            }
+            if let Some(default) = &param.default {
+                label.push_str(" = ");
+                label.push_str(&formatter.format_expression(default));
+            }
+            label
+        })
+        .collect();
+
+    Signature {
+        name: format!("def {}", function.name),
+        parameters,
+        return_type: return_type.or_else(|| function.ret_ty.as_ref().map(super::format_type)),
+        documentation: None,
+    }
+}
+
+/// Functions the bridge metadata of `crate_name` declares, under the crate
+/// name and its alias
+fn bridge_signatures(crate_name: &str, alias: Option<&str>, signatures: &mut DocumentSignatures) {
+    let Ok(metadata) = BridgeSymbolRegistry::global().ensure_metadata(crate_name) else {
+        return;
+    };
+    for function in &metadata.functions {
+        let path = function
+            .name
+            .strip_prefix(&format!("{}:", crate_name))
+            .unwrap_or(&function.name);
+        for prefix in std::iter::once(crate_name).chain(alias) {
+            let callee = format!("{}.{}", prefix, path);
+            signatures.insert(
+                callee.clone(),
+                Signature {
+                    name: callee,
+                    parameters: function.params.iter().map(type_spec_name).collect(),
+                    return_type: Some(type_spec_name(&function.result)),
+                    documentation: function.doc.clone(),
+                },
+            );
+        }
+    }
+}
+
+/// Signature of a standard library FFI function such as `math.clamp`
+fn stdlib_signature(callee: &str) -> Option<Signature> {
+    let function = bootstrap_stdlib().resolve(callee)?;
+    let types: Vec<&str> = function
+        .signature
+        .params
+        .iter()
+        .map(ffi_type_name)
+        .collect();
+    let parameters = match stdlib_parameter_names(callee) {
+        Some(names) if names.len() == types.len() => names
+            .iter()
+            .zip(&types)
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect(),
+        _ => types.iter().map(|ty| ty.to_string()).collect(),
+    };
+    Some(Signature {
+        name: callee.to_string(),
+        parameters,
+        return_type: Some(ffi_type_name(&function.signature.result).to_string()),
+        documentation: None,
+    })
+}
+
+fn type_name(ty: &TypeInfo) -> Option<String> {
+    let name = ty.display_name();
+    (!name.contains('?')).then_some(name)
+}
+
+fn ffi_type_name(ty: &FfiType) -> &'static str {
+    match ty {
+        FfiType::Unit => "None",
+        FfiType::Bool => "bool",
+        FfiType::I32 => "i32",
+        FfiType::I64 => "i64",
+        FfiType::F64 => "f64",
+        FfiType::I8 => "i8",
+        FfiType::I16 => "i16",
+        FfiType::U8 => "u8",
+        FfiType::U16 => "u16",
+        FfiType::U32 => "u32",
+        FfiType::U64 => "u64",
+        FfiType::Usize => "usize",
+        FfiType::F32 => "f32",
+        FfiType::Str => "str",
+        FfiType::Opaque => "opaque",
+        FfiType::List => "list",
+        FfiType::Map => "dict",
+    }
+}
+
+fn type_spec_name(ty: &TypeSpec) -> String {
+    match ty {
+        TypeSpec::Unit => "None",
+        TypeSpec::Bool => "bool",
+        TypeSpec::I32 => "i32",
+        TypeSpec::I64 => "i64",
+        TypeSpec::F64 => "f64",
+        TypeSpec::I8 => "i8",
+        TypeSpec::I16 => "i16",
+        TypeSpec::U8 => "u8",
+        TypeSpec::U16 => "u16",
+        TypeSpec::U32 => "u32",
+        TypeSpec::U64 => "u64",
+        TypeSpec::Usize => "usize",
+        TypeSpec::F32 => "f32",
+        TypeSpec::Str => "str",
+        TypeSpec::Opaque => "opaque",
+    }
+    .to_string()
+}
+
+/// The call surrounding `offset`: its callee path and the index of the
+/// argument being typed. Works on tokens so unfinished calls still resolve.
+fn enclosing_call(tokens: &[Token], offset: usize) -> Option<(String, u32)> {
+    // (index of the opening token, commas seen so far) per open bracket
+    let mut open: Vec<(usize, u32)> = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if token.span.start() >= offset {
+            break;
+        }
+        match token.kind {
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => open.push((index, 0)),
+            TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                open.pop();
+            }
+            TokenKind::Comma => {
+                if let Some((_, commas)) = open.last_mut() {
+                    *commas += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    open.iter().rev().find_map(|&(index, commas)| {
+        if tokens[index].kind != TokenKind::LParen {
+            return None;
+        }
+        callee_path(&tokens[..index]).map(|path| (path, commas))
+    })
+}
+
+/// The dotted name ending right before a call's `(`, e.g. `math.clamp`
+fn callee_path(tokens: &[Token]) -> Option<String> {
+    let mut segments = Vec::new();
+    let mut rest = tokens;
+    loop {
+        let (last, before) = rest.split_last()?;
+        match &last.kind {
+            TokenKind::Identifier(name) => segments.push(name.clone()),
+            TokenKind::Print if segments.is_empty() => segments.push("print".to_string()),
+            _ => return None,
+        }
+        match before.split_last() {
+            Some((dot, before)) if dot.kind == TokenKind::Dot => rest = before,
+            _ => break,
+        }
+    }
+    segments.reverse();
+    Some(segments.join("."))
+}
+
+/// Signature help at byte `offset` of a document tokenized into `tokens`
+pub(crate) fn signature_help(
+    tokens: &[Token],
+    offset: usize,
+    document: &DocumentSignatures,
+) -> Option<SignatureHelp> {
+    let (callee, active_parameter) = enclosing_call(tokens, offset)?;
+    let signature = document
+        .get(&callee)
+        .cloned()
+        .or_else(|| stdlib_signature(&callee))?;
+
+    Some(SignatureHelp {
+        signatures: vec![signature.information()],
+        active_signature: Some(0),
+        active_parameter: Some(active_parameter),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::tokenize;
+    use parser::parse;
+
+    fn help_at(text: &str, marker: &str, document: &DocumentSignatures) -> Option<SignatureHelp> {
+        let offset = text.find(marker).expect("marker") + marker.len();
+        let tokens = tokenize(text).expect("tokens");
+        signature_help(&tokens, offset, document)
+    }
+
+    #[test]
+    fn user_functions_show_types_defaults_and_active_parameter() {
+        let source =
+            "def scale(value: float, factor: float = 2.0) -> float:\n    return value * factor\n";
+        let tokens = tokenize(source).unwrap();
+        let program = parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let document = document_signatures(&program, &checker);
+
+        // The call is unfinished, as it is while typing
+        let text = format!("{}let x = scale(1.0, ", source);
+        let help = help_at(&text, "scale(1.0, ", &document).expect("signature help");
+        let signature = &help.signatures[0];
+        assert_eq!(
+            signature.label,
+            "def scale(value: f64, factor: f64 = 2.0) -> f64"
+        );
+        assert_eq!(help.active_parameter, Some(1));
+        let parameters = signature.parameters.as_ref().unwrap();
+        assert_eq!(parameters[1].label, ParameterLabel::LabelOffsets([22, 39]));
+    }
+
+    #[test]
+    fn nested_brackets_do_not_count_towards_the_outer_call() {
+        let mut document = DocumentSignatures::new();
+        document.insert(
+            "total".to_string(),
+            Signature {
+                name: "def total".to_string(),
+                parameters: vec!["items".to_string(), "start".to_string()],
+                return_type: None,
+                documentation: None,
+            },
+        );
+        let help = help_at("total([1, 2, 3", "[1, 2, 3", &document).expect("signature help");
+        assert_eq!(help.active_parameter, Some(0));
+        let help = help_at("total([1, 2], f(x, y), ", "y), ", &document).expect("signature help");
+        assert_eq!(help.active_parameter, Some(2));
+    }
+
+    #[test]
+    fn member_calls_resolve_by_dotted_path() {
+        let tokens = tokenize("let x = rand.range(1, ").unwrap();
+        assert_eq!(
+            enclosing_call(&tokens, 22),
+            Some(("rand.range".to_string(), 1))
+        );
+    }
+}
@@ -87,6 +87,10 @@ impl ReplEngine {
                         statements: vec![Statement::Expr(expr)],
                     },
                     public: false,
+                    generics: Vec::new(),
+                    is_const: false,
+                    annotations: Vec::new(),
+                    span: None,
                 }));
             }
         }
@@ -142,6 +142,9 @@ impl JitEngine {
             enable_pgo: false,
             pgo_profile_file: None,
             inline_threshold: None,
+            overflow_checks: true,
+            test_harness: None,
+            source: None,
         };
 
         let mut type_checker = TypeChecker::new().with_registry(SymbolRegistry::global());
@@ -316,6 +319,9 @@ impl JitEngine {
             enable_pgo: false,
             pgo_profile_file: None,
             inline_threshold: None,
+            overflow_checks: true,
+            test_harness: None,
+            source: None,
         };
 
         let mut type_checker = TypeChecker::new().with_registry(SymbolRegistry::global());
@@ -1,4 +1,5 @@
 use super::RuntimeConstant;
+use crate::codegen::const_fold::{self, Constant};
 use ast::nodes::{Expr, Literal};
 
 /// Propagates constant values through expressions
@@ -17,43 +18,51 @@ impl ConstantPropagator {
     }
 
     fn extract_constant_from_expr(&self, expr: &Expr) -> Option<RuntimeConstant> {
-        match expr {
-            Expr::Literal(lit) => self.literal_to_constant(lit),
-            _ => None,
-        }
+        self.fold(expr)
     }
 
     fn literal_to_constant(&self, lit: &Literal) -> Option<RuntimeConstant> {
         match lit {
             Literal::Bool(b) => Some(RuntimeConstant::Bool(*b)),
-            Literal::Number(n) => {
-                if n.value.fract() == 0.0 {
-                    // Integer
-                    if n.value >= i32::MIN as f64 && n.value <= i32::MAX as f64 {
-                        Some(RuntimeConstant::I32(n.value as i32))
-                    } else {
-                        Some(RuntimeConstant::I64(n.value as i64))
-                    }
-                } else {
-                    // Float
-                    Some(RuntimeConstant::from_f64(n.value))
-                }
-            }
+            Literal::Number(n) => Some(self.number_to_constant(n.value)),
             Literal::String(s) => Some(RuntimeConstant::Str(s.clone())),
             _ => None,
         }
     }
 
+    fn number_to_constant(&self, value: f64) -> RuntimeConstant {
+        if value.fract() == 0.0 {
+            // Integer
+            if value >= i32::MIN as f64 && value <= i32::MAX as f64 {
+                RuntimeConstant::I32(value as i32)
+            } else {
+                RuntimeConstant::I64(value as i64)
+            }
+        } else {
+            // Float
+            RuntimeConstant::from_f64(value)
+        }
+    }
+
     /// Check if an expression can be constant-folded
     pub fn can_fold(&self, expr: &Expr) -> bool {
-        matches!(expr, Expr::Literal(_))
+        self.fold(expr).is_some()
     }
 
     /// Fold a constant expression to its value
     pub fn fold(&self, expr: &Expr) -> Option<RuntimeConstant> {
         match expr {
             Expr::Literal(lit) => self.literal_to_constant(lit),
-            _ => None,
+            _ => match const_fold::fold(expr)? {
+                Constant::Int(value) => Some(
+                    i32::try_from(value)
+                        .map(RuntimeConstant::I32)
+                        .unwrap_or(RuntimeConstant::I64(value)),
+                ),
+                Constant::Float(value) => Some(self.number_to_constant(value)),
+                Constant::Bool(value) => Some(RuntimeConstant::Bool(value)),
+                Constant::Str(value) => Some(RuntimeConstant::Str(value)),
+            },
         }
     }
 }
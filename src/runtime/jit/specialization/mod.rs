@@ -36,6 +36,14 @@ impl From<FfiType> for RuntimeType {
             FfiType::F64 => RuntimeType::F64,
             FfiType::Str => RuntimeType::Str,
             FfiType::Opaque | FfiType::List | FfiType::Map => RuntimeType::Opaque,
+            FfiType::I8
+            | FfiType::I16
+            | FfiType::U8
+            | FfiType::U16
+            | FfiType::U32
+            | FfiType::U64
+            | FfiType::Usize
+            | FfiType::F32 => RuntimeType::Unknown,
         }
     }
 }
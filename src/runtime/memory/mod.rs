@@ -12,4 +12,4 @@ pub use config::{GcConfig, GcStrategy};
 pub use gc::{GcStrategyTrait, MarkSweepGC, RcGC};
 pub use object::OtterObject;
 pub use profiler::{AllocationInfo, MemoryProfiler};
-pub use rc::{RcOtter, WeakOtter};
+pub use rc::{RcDropFn, RcOtter, WeakOtter};
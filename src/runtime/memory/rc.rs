@@ -1,9 +1,11 @@
 //! Reference counting for OtterLang objects
 
+use std::alloc::{alloc_zeroed, dealloc, Layout};
 use std::ptr::NonNull;
 use std::sync::atomic::Ordering;
 
 use crate::runtime::memory::object::{ObjectHeader, OtterObject};
+use crate::runtime::memory::profiler::get_profiler;
 
 /// Reference-counted pointer to an OtterLang object
 pub struct RcOtter<T: OtterObject> {
@@ -175,3 +177,109 @@ impl<T: OtterObject> Drop for WeakOtter<T> {
         }
     }
 }
+
+/// Called with a block's data just before the block is freed, to release
+/// whatever the block holds
+pub type RcDropFn = extern "C" fn(*mut u8);
+
+/// Alignment of the data in blocks from [`otter_rc_alloc`]
+const RAW_ALIGN: usize = 16;
+
+/// Bookkeeping in front of the data of a block from [`otter_rc_alloc`].
+/// Compiled code only ever sees the data pointer.
+struct RawRcHeader {
+    header: ObjectHeader,
+    drop_fn: Option<RcDropFn>,
+}
+
+/// Distance from the start of a block to its data
+const RAW_DATA_OFFSET: usize = std::mem::size_of::<RawRcHeader>().next_multiple_of(RAW_ALIGN);
+
+fn raw_layout(size: usize) -> Option<Layout> {
+    Layout::from_size_align(RAW_DATA_OFFSET.checked_add(size)?, RAW_ALIGN).ok()
+}
+
+/// # Safety
+/// `data` must have come from [`otter_rc_alloc`] and still be alive.
+unsafe fn raw_header<'a>(data: *mut u8) -> &'a RawRcHeader {
+    unsafe { &*(data.sub(RAW_DATA_OFFSET) as *const RawRcHeader) }
+}
+
+/// Allocate `size` zeroed bytes with a reference count of one. Closure
+/// environments and the cells of variables captured by reference live in
+/// these blocks; `drop_fn`, if given, releases what the block holds once the
+/// last reference is gone.
+#[no_mangle]
+pub extern "C" fn otter_rc_alloc(size: i64, drop_fn: Option<RcDropFn>) -> *mut u8 {
+    let Some(layout) = usize::try_from(size).ok().and_then(raw_layout) else {
+        return std::ptr::null_mut();
+    };
+    unsafe {
+        let block = alloc_zeroed(layout);
+        if block.is_null() {
+            return block;
+        }
+        (block as *mut RawRcHeader).write(RawRcHeader {
+            header: ObjectHeader::new("closure", layout.size() - RAW_DATA_OFFSET),
+            drop_fn,
+        });
+        let data = block.add(RAW_DATA_OFFSET);
+        get_profiler().record_allocation(
+            data as usize,
+            layout.size(),
+            None,
+            None,
+            None,
+            Some("closure".to_string()),
+        );
+        data
+    }
+}
+
+/// Take another reference to a block from [`otter_rc_alloc`]
+///
+/// # Safety
+/// `data` must be null or a live block from [`otter_rc_alloc`].
+#[no_mangle]
+pub unsafe extern "C" fn otter_rc_retain(data: *mut u8) {
+    if data.is_null() {
+        return;
+    }
+    unsafe { raw_header(data) }.header.increment_ref();
+}
+
+/// Drop a reference to a block from [`otter_rc_alloc`], running its drop
+/// function and freeing it when that was the last one
+///
+/// # Safety
+/// `data` must be null or a live block from [`otter_rc_alloc`], and the caller
+/// must own the reference it gives up.
+#[no_mangle]
+pub unsafe extern "C" fn otter_rc_release(data: *mut u8) {
+    if data.is_null() {
+        return;
+    }
+    unsafe {
+        let raw = raw_header(data);
+        if raw.header.decrement_ref() > 0 {
+            return;
+        }
+        if let Some(drop_fn) = raw.drop_fn {
+            drop_fn(data);
+        }
+        let layout = raw_layout(raw.header.size).expect("layout of a live block");
+        get_profiler().record_deallocation(data as usize);
+        dealloc(data.sub(RAW_DATA_OFFSET), layout);
+    }
+}
+
+/// Drop function for a cell holding a single reference, such as a shared
+/// variable of function type: releases the block the cell points to
+///
+/// # Safety
+/// `data` must be a live block from [`otter_rc_alloc`] whose first field is
+/// null or a reference the cell owns.
+#[no_mangle]
+pub unsafe extern "C" fn otter_rc_release_contents(data: *mut u8) {
+    unsafe { otter_rc_release(*(data as *const *mut u8)) }
+}
@@ -64,6 +64,31 @@ fn value_to_string(value: &Value) -> String {
     }
 }
 
+/// Resolve a position into a sequence of `len` items; negative positions
+/// count from the end
+fn resolve_index(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 {
+        index.saturating_add(len as i64)
+    } else {
+        index
+    };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+/// Clamp slice bounds into `0..=len`, counting negative bounds from the end
+fn resolve_slice(len: usize, start: i64, end: i64) -> std::ops::Range<usize> {
+    let clamp = |bound: i64| {
+        let bound = if bound < 0 {
+            bound.saturating_add(len as i64)
+        } else {
+            bound
+        };
+        bound.clamp(0, len as i64) as usize
+    };
+    let (start, end) = (clamp(start), clamp(end));
+    start..end.max(start)
+}
+
 fn list_value(handle: HandleId, index: i64) -> Option<Value> {
     if index < 0 {
         return None;
@@ -74,6 +99,22 @@ fn list_value(handle: HandleId, index: i64) -> Option<Value> {
         .and_then(|list| list.items.get(index as usize).cloned())
 }
 
+/// Replace an element, returning 0 when `index` is out of range; the
+/// subscript lowering has already counted a negative index from the end
+fn set_list_value(handle: HandleId, index: i64, value: Value) -> i32 {
+    let mut lists = LISTS.write();
+    let slot = usize::try_from(index)
+        .ok()
+        .and_then(|index| lists.get_mut(&handle)?.items.get_mut(index));
+    match slot {
+        Some(slot) => {
+            *slot = value;
+            1
+        }
+        None => 0,
+    }
+}
+
 fn map_value(handle: HandleId, key: &str) -> Option<Value> {
     let maps = MAPS.read();
     maps.get(&handle)
@@ -221,6 +262,25 @@ pub unsafe extern "C" fn otter_builtin_str_contains(
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn otter_builtin_str_starts_with(
+    s: *const c_char,
+    prefix: *const c_char,
+) -> bool {
+    if s.is_null() || prefix.is_null() {
+        return false;
+    }
+    unsafe {
+        if let (Ok(str_ref), Ok(prefix_ref)) =
+            (CStr::from_ptr(s).to_str(), CStr::from_ptr(prefix).to_str())
+        {
+            str_ref.starts_with(prefix_ref)
+        } else {
+            false
+        }
+    }
+}
+
 // ============================================================================
 // append(x, val) - Append to a list
 // ============================================================================
@@ -440,6 +500,17 @@ pub unsafe extern "C" fn otter_builtin_map_get(handle: u64, key: *const c_char)
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn otter_builtin_map_has(handle: u64, key: *const c_char) -> bool {
+    if key.is_null() {
+        return false;
+    }
+    let key_str = unsafe { CStr::from_ptr(key).to_str().unwrap_or("") };
+    let maps = MAPS.read();
+    maps.get(&handle)
+        .is_some_and(|map| map.items.contains_key(key_str))
+}
+
 #[no_mangle]
 pub extern "C" fn otter_builtin_list_get_int(handle: u64, index: i64) -> i64 {
     match list_value(handle, index) {
@@ -704,6 +775,112 @@ pub unsafe extern "C" fn otter_builtin_map_set_map(
     }
 }
 
+// ============================================================================
+// list[i] = val - Replace a list element
+// ============================================================================
+
+#[no_mangle]
+pub unsafe extern "C" fn otter_builtin_list_set_string(
+    handle: u64,
+    index: i64,
+    val: *const c_char,
+) -> i32 {
+    if val.is_null() {
+        return 0;
+    }
+
+    let val_str = unsafe { CStr::from_ptr(val).to_str().unwrap_or("").to_string() };
+    set_list_value(handle, index, Value::String(val_str))
+}
+
+#[no_mangle]
+pub extern "C" fn otter_builtin_list_set_int(handle: u64, index: i64, val: i64) -> i32 {
+    set_list_value(handle, index, Value::I64(val))
+}
+
+#[no_mangle]
+pub extern "C" fn otter_builtin_list_set_float(handle: u64, index: i64, val: f64) -> i32 {
+    set_list_value(handle, index, Value::F64(val))
+}
+
+#[no_mangle]
+pub extern "C" fn otter_builtin_list_set_bool(handle: u64, index: i64, val: bool) -> i32 {
+    set_list_value(handle, index, Value::Bool(val))
+}
+
+#[no_mangle]
+pub extern "C" fn otter_builtin_list_set_list(handle: u64, index: i64, value_handle: u64) -> i32 {
+    set_list_value(handle, index, Value::List(value_handle))
+}
+
+#[no_mangle]
+pub extern "C" fn otter_builtin_list_set_map(handle: u64, index: i64, value_handle: u64) -> i32 {
+    set_list_value(handle, index, Value::Map(value_handle))
+}
+
+// ============================================================================
+// x[start:end] - Slice a list or string, and s[i] - Character of a string
+// Strings are indexed by character, so `str.get` counts a negative index
+// from the end itself rather than leaving that to the subscript lowering
+// ============================================================================
+
+#[no_mangle]
+pub extern "C" fn otter_builtin_list_slice(handle: u64, start: i64, end: i64) -> u64 {
+    let items = {
+        let lists = LISTS.read();
+        lists
+            .get(&handle)
+            .map(|list| list.items[resolve_slice(list.items.len(), start, end)].to_vec())
+            .unwrap_or_default()
+    };
+
+    let id = next_handle_id();
+    LISTS.write().insert(id, List { items });
+    id
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otter_builtin_str_get(s: *const c_char, index: i64) -> *mut c_char {
+    if s.is_null() {
+        return std::ptr::null_mut();
+    }
+    let Ok(str_ref) = (unsafe { CStr::from_ptr(s).to_str() }) else {
+        return std::ptr::null_mut();
+    };
+
+    let chars: Vec<char> = str_ref.chars().collect();
+    match resolve_index(chars.len(), index) {
+        Some(index) => CString::new(chars[index].to_string())
+            .ok()
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otter_builtin_str_slice(
+    s: *const c_char,
+    start: i64,
+    end: i64,
+) -> *mut c_char {
+    if s.is_null() {
+        return std::ptr::null_mut();
+    }
+    let Ok(str_ref) = (unsafe { CStr::from_ptr(s).to_str() }) else {
+        return std::ptr::null_mut();
+    };
+
+    let chars: Vec<char> = str_ref.chars().collect();
+    let slice: String = chars[resolve_slice(chars.len(), start, end)]
+        .iter()
+        .collect();
+    CString::new(slice)
+        .ok()
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
 // ============================================================================
 // panic(msg) - Terminate execution with error message
 // ============================================================================
@@ -1203,6 +1380,12 @@ fn register_builtin_symbols(registry: &SymbolRegistry) {
         signature: FfiSignature::new(vec![FfiType::Str, FfiType::Str], FfiType::Bool),
     });
 
+    registry.register(FfiFunction {
+        name: "str.starts_with".into(),
+        symbol: "otter_builtin_str_starts_with".into(),
+        signature: FfiSignature::new(vec![FfiType::Str, FfiType::Str], FfiType::Bool),
+    });
+
     // append() functions
     registry.register(FfiFunction {
         name: "append<list,string>".into(),
@@ -1352,6 +1535,12 @@ fn register_builtin_symbols(registry: &SymbolRegistry) {
         signature: FfiSignature::new(vec![FfiType::Map, FfiType::Str], FfiType::Map),
     });
 
+    registry.register(FfiFunction {
+        name: "map.has".into(),
+        symbol: "otter_builtin_map_has".into(),
+        signature: FfiSignature::new(vec![FfiType::Map, FfiType::Str], FfiType::Bool),
+    });
+
     registry.register(FfiFunction {
         name: "map.set".into(),
         symbol: "otter_builtin_map_set".into(),
@@ -1430,6 +1619,82 @@ fn register_builtin_symbols(registry: &SymbolRegistry) {
         signature: FfiSignature::new(vec![FfiType::Map, FfiType::Str, FfiType::Map], FfiType::I32),
     });
 
+    // Element assignment and slicing
+    registry.register(FfiFunction {
+        name: "set<list,string>".into(),
+        symbol: "otter_builtin_list_set_string".into(),
+        signature: FfiSignature::new(
+            vec![FfiType::List, FfiType::I64, FfiType::Str],
+            FfiType::I32,
+        ),
+    });
+
+    registry.register(FfiFunction {
+        name: "set<list,int>".into(),
+        symbol: "otter_builtin_list_set_int".into(),
+        signature: FfiSignature::new(
+            vec![FfiType::List, FfiType::I64, FfiType::I64],
+            FfiType::I32,
+        ),
+    });
+
+    registry.register(FfiFunction {
+        name: "set<list,float>".into(),
+        symbol: "otter_builtin_list_set_float".into(),
+        signature: FfiSignature::new(
+            vec![FfiType::List, FfiType::I64, FfiType::F64],
+            FfiType::I32,
+        ),
+    });
+
+    registry.register(FfiFunction {
+        name: "set<list,bool>".into(),
+        symbol: "otter_builtin_list_set_bool".into(),
+        signature: FfiSignature::new(
+            vec![FfiType::List, FfiType::I64, FfiType::Bool],
+            FfiType::I32,
+        ),
+    });
+
+    registry.register(FfiFunction {
+        name: "set<list,list>".into(),
+        symbol: "otter_builtin_list_set_list".into(),
+        signature: FfiSignature::new(
+            vec![FfiType::List, FfiType::I64, FfiType::List],
+            FfiType::I32,
+        ),
+    });
+
+    registry.register(FfiFunction {
+        name: "set<list,map>".into(),
+        symbol: "otter_builtin_list_set_map".into(),
+        signature: FfiSignature::new(
+            vec![FfiType::List, FfiType::I64, FfiType::Map],
+            FfiType::I32,
+        ),
+    });
+
+    registry.register(FfiFunction {
+        name: "list.slice".into(),
+        symbol: "otter_builtin_list_slice".into(),
+        signature: FfiSignature::new(
+            vec![FfiType::List, FfiType::I64, FfiType::I64],
+            FfiType::List,
+        ),
+    });
+
+    registry.register(FfiFunction {
+        name: "str.get".into(),
+        symbol: "otter_builtin_str_get".into(),
+        signature: FfiSignature::new(vec![FfiType::Str, FfiType::I64], FfiType::Str),
+    });
+
+    registry.register(FfiFunction {
+        name: "str.slice".into(),
+        symbol: "otter_builtin_str_slice".into(),
+        signature: FfiSignature::new(vec![FfiType::Str, FfiType::I64, FfiType::I64], FfiType::Str),
+    });
+
     // Error handling functions
     registry.register(FfiFunction {
         name: "panic".into(),
@@ -1583,3 +1848,36 @@ inventory::submit! {
         register: register_builtin_symbols,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_builtins_leave_negative_indices_unresolved() {
+        let list = otter_builtin_list_new();
+        otter_builtin_append_list_int(list, 7);
+        otter_builtin_append_list_int(list, 8);
+
+        assert_eq!(otter_builtin_list_get_int(list, 1), 8);
+        assert_eq!(otter_builtin_list_get_int(list, -1), 0);
+        assert_eq!(otter_builtin_list_set_int(list, -1, 9), 0);
+        assert_eq!(otter_builtin_list_set_int(list, 2, 9), 0);
+        assert_eq!(otter_builtin_list_set_int(list, 0, 9), 1);
+        assert_eq!(otter_builtin_list_get_int(list, 0), 9);
+    }
+
+    #[test]
+    fn string_index_counts_characters_from_either_end() {
+        let text = CString::new("añb").unwrap();
+        let char_at = |index| {
+            let ptr = unsafe { otter_builtin_str_get(text.as_ptr(), index) };
+            (!ptr.is_null()).then(|| unsafe { CString::from_raw(ptr) }.into_string().unwrap())
+        };
+
+        assert_eq!(char_at(1).as_deref(), Some("ñ"));
+        assert_eq!(char_at(-1).as_deref(), Some("b"));
+        assert_eq!(char_at(3), None);
+        assert_eq!(char_at(-4), None);
+    }
+}
@@ -1,6 +1,6 @@
 use std::ffi::{CStr, CString};
 use std::fs;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::os::raw::c_char;
 use std::sync::atomic::{AtomicU64, Ordering};
 
@@ -29,6 +29,9 @@ struct Buffer {
 static BUFFERS: Lazy<RwLock<std::collections::HashMap<HandleId, Buffer>>> =
     Lazy::new(|| RwLock::new(std::collections::HashMap::new()));
 
+static FILES: Lazy<RwLock<std::collections::HashMap<HandleId, fs::File>>> =
+    Lazy::new(|| RwLock::new(std::collections::HashMap::new()));
+
 // ============================================================================
 // File I/O Functions
 // ============================================================================
@@ -198,6 +201,86 @@ pub unsafe extern "C" fn otter_std_io_lines(path: *const c_char) -> u64 {
     }
 }
 
+// ============================================================================
+// File Handles
+// ============================================================================
+
+/// Open `path` for reading (`"r"`), writing (`"w"`) or appending (`"a"`),
+/// returning 0 if it cannot be opened
+#[no_mangle]
+pub unsafe extern "C" fn otter_std_io_open(path: *const c_char, mode: *const c_char) -> u64 {
+    if path.is_null() {
+        return 0;
+    }
+
+    let path_str = unsafe { CStr::from_ptr(path).to_str().unwrap_or("").to_string() };
+    let mode_str = if mode.is_null() {
+        "r".to_string()
+    } else {
+        unsafe { CStr::from_ptr(mode).to_str().unwrap_or("r").to_string() }
+    };
+
+    let mut options = fs::OpenOptions::new();
+    match mode_str.as_str() {
+        "r" => options.read(true),
+        "w" => options.write(true).create(true).truncate(true),
+        "a" => options.append(true).create(true),
+        _ => return 0,
+    };
+
+    match options.open(&path_str) {
+        Ok(file) => {
+            let id = next_handle_id();
+            FILES.write().insert(id, file);
+            id
+        }
+        Err(_) => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn otter_std_io_file_read(handle: u64) -> *mut c_char {
+    let mut files = FILES.write();
+    let Some(file) = files.get_mut(&handle) else {
+        return std::ptr::null_mut();
+    };
+
+    let mut content = String::new();
+    match file.read_to_string(&mut content) {
+        Ok(_) => CString::new(content)
+            .ok()
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otter_std_io_file_write(handle: u64, data: *const c_char) -> i32 {
+    if data.is_null() {
+        return 0;
+    }
+
+    let data_str = unsafe { CStr::from_ptr(data).to_str().unwrap_or("").to_string() };
+
+    let mut files = FILES.write();
+    match files.get_mut(&handle) {
+        Some(file) => match file.write_all(data_str.as_bytes()) {
+            Ok(_) => 1,
+            Err(_) => 0,
+        },
+        None => 0,
+    }
+}
+
+/// Flush and close a file handle; closing it again is a no-op
+#[no_mangle]
+pub extern "C" fn otter_std_io_file_close(handle: u64) {
+    if let Some(mut file) = FILES.write().remove(&handle) {
+        let _ = file.flush();
+    }
+}
+
 // ============================================================================
 // Buffer Operations
 // ============================================================================
@@ -514,6 +597,30 @@ fn register_std_io_symbols(registry: &SymbolRegistry) {
         signature: FfiSignature::new(vec![FfiType::Str], FfiType::Opaque),
     });
 
+    registry.register(FfiFunction {
+        name: "io.open".into(),
+        symbol: "otter_std_io_open".into(),
+        signature: FfiSignature::new(vec![FfiType::Str, FfiType::Str], FfiType::Opaque),
+    });
+
+    registry.register(FfiFunction {
+        name: "io.file.read".into(),
+        symbol: "otter_std_io_file_read".into(),
+        signature: FfiSignature::new(vec![FfiType::Opaque], FfiType::Str),
+    });
+
+    registry.register(FfiFunction {
+        name: "io.file.write".into(),
+        symbol: "otter_std_io_file_write".into(),
+        signature: FfiSignature::new(vec![FfiType::Opaque, FfiType::Str], FfiType::I32),
+    });
+
+    registry.register(FfiFunction {
+        name: "io.file.close".into(),
+        symbol: "otter_std_io_file_close".into(),
+        signature: FfiSignature::new(vec![FfiType::Opaque], FfiType::Unit),
+    });
+
     registry.register(FfiFunction {
         name: "io.buffer".into(),
         symbol: "otter_std_io_buffer".into(),
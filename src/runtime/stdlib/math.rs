@@ -15,6 +15,31 @@ pub extern "C" fn otter_std_math_pow(base: f64, exponent: f64) -> f64 {
     libm::pow(base, exponent)
 }
 
+/// Integer `**`: wraps on overflow, and a negative exponent truncates the
+/// fraction towards zero like integer division
+#[no_mangle]
+pub extern "C" fn otter_std_math_ipow(base: i64, exponent: i64) -> i64 {
+    if exponent >= 0 {
+        let mut result: i64 = 1;
+        let mut base = base;
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.wrapping_mul(base);
+            }
+            base = base.wrapping_mul(base);
+            exponent >>= 1;
+        }
+        return result;
+    }
+    match base {
+        1 => 1,
+        -1 if exponent % 2 == 0 => 1,
+        -1 => -1,
+        _ => 0,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn otter_std_math_exp(value: f64) -> f64 {
     libm::exp(value)
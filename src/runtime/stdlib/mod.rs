@@ -4,7 +4,9 @@ pub mod io;
 pub mod json;
 pub mod math;
 pub mod net;
+pub mod num;
 pub mod rand;
+pub mod resources;
 pub mod runtime;
 pub mod sync;
 pub mod sys;
@@ -0,0 +1,87 @@
+//! Wrapping and saturating arithmetic for the integer types
+//!
+//! Each helper is registered as a method of its type: `x.wrapping_add(y)` on
+//! a `u8` resolves to `u8.wrapping_add`, which calls
+//! `otter_std_num_u8_wrapping_add`. The plain operators trap on overflow in
+//! debug builds and wrap in release builds; these spell out the behaviour
+//! wanted in both.
+
+use crate::runtime::symbol_registry::{FfiFunction, FfiSignature, FfiType, SymbolRegistry};
+
+/// Methods every integer type has
+pub const INTEGER_METHODS: [&str; 6] = [
+    "wrapping_add",
+    "wrapping_sub",
+    "wrapping_mul",
+    "saturating_add",
+    "saturating_sub",
+    "saturating_mul",
+];
+
+macro_rules! integer_helpers {
+    ($($ty:ty, $name:literal, $ffi:expr, $module:ident;)*) => {
+        $(
+            mod $module {
+                #[export_name = concat!("otter_std_num_", $name, "_wrapping_add")]
+                pub extern "C" fn wrapping_add(a: $ty, b: $ty) -> $ty {
+                    a.wrapping_add(b)
+                }
+
+                #[export_name = concat!("otter_std_num_", $name, "_wrapping_sub")]
+                pub extern "C" fn wrapping_sub(a: $ty, b: $ty) -> $ty {
+                    a.wrapping_sub(b)
+                }
+
+                #[export_name = concat!("otter_std_num_", $name, "_wrapping_mul")]
+                pub extern "C" fn wrapping_mul(a: $ty, b: $ty) -> $ty {
+                    a.wrapping_mul(b)
+                }
+
+                #[export_name = concat!("otter_std_num_", $name, "_saturating_add")]
+                pub extern "C" fn saturating_add(a: $ty, b: $ty) -> $ty {
+                    a.saturating_add(b)
+                }
+
+                #[export_name = concat!("otter_std_num_", $name, "_saturating_sub")]
+                pub extern "C" fn saturating_sub(a: $ty, b: $ty) -> $ty {
+                    a.saturating_sub(b)
+                }
+
+                #[export_name = concat!("otter_std_num_", $name, "_saturating_mul")]
+                pub extern "C" fn saturating_mul(a: $ty, b: $ty) -> $ty {
+                    a.saturating_mul(b)
+                }
+            }
+        )*
+
+        fn register_std_num_symbols(registry: &SymbolRegistry) {
+            $(
+                for method in INTEGER_METHODS {
+                    registry.register(FfiFunction {
+                        name: format!("{}.{}", $name, method),
+                        symbol: format!("otter_std_num_{}_{}", $name, method),
+                        signature: FfiSignature::new(vec![$ffi, $ffi], $ffi),
+                    });
+                }
+            )*
+        }
+    };
+}
+
+integer_helpers! {
+    i8, "i8", FfiType::I8, i8_helpers;
+    i16, "i16", FfiType::I16, i16_helpers;
+    i32, "i32", FfiType::I32, i32_helpers;
+    i64, "i64", FfiType::I64, i64_helpers;
+    u8, "u8", FfiType::U8, u8_helpers;
+    u16, "u16", FfiType::U16, u16_helpers;
+    u32, "u32", FfiType::U32, u32_helpers;
+    u64, "u64", FfiType::U64, u64_helpers;
+    usize, "usize", FfiType::Usize, usize_helpers;
+}
+
+inventory::submit! {
+    crate::runtime::ffi::SymbolProvider {
+        register: register_std_num_symbols,
+    }
+}
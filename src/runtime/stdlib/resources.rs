@@ -0,0 +1,46 @@
+//! Standard library resources that a `with` statement releases on exit.
+
+/// A call acquiring a resource, paired with the function releasing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resource {
+    /// The acquiring call, e.g. `net.dial`
+    pub acquire: &'static str,
+    /// The function releasing the resource once the `with` body exits
+    pub release: &'static str,
+    /// Whether `release` takes the acquiring call's first argument rather
+    /// than its result, as `sync.unlock` takes the mutex `sync.lock` locked
+    pub releases_argument: bool,
+}
+
+const RESOURCES: &[Resource] = &[
+    Resource {
+        acquire: "io.open",
+        release: "io.file.close",
+        releases_argument: false,
+    },
+    Resource {
+        acquire: "fs.open",
+        release: "io.file.close",
+        releases_argument: false,
+    },
+    Resource {
+        acquire: "net.dial",
+        release: "net.close",
+        releases_argument: false,
+    },
+    Resource {
+        acquire: "net.listen",
+        release: "net.close",
+        releases_argument: false,
+    },
+    Resource {
+        acquire: "sync.lock",
+        release: "sync.unlock",
+        releases_argument: true,
+    },
+];
+
+/// The resource acquired by calling `name`, if any
+pub fn acquired_by(name: &str) -> Option<&'static Resource> {
+    RESOURCES.iter().find(|resource| resource.acquire == name)
+}
@@ -67,11 +67,18 @@ pub extern "C" fn otter_sync_mutex() -> u64 {
     id
 }
 
+fn mutex_for(handle: u64) -> Option<Arc<Mutex<()>>> {
+    MUTEXES
+        .read()
+        .get(&handle)
+        .map(|mutex| Arc::clone(&mutex.inner))
+}
+
+/// Lock the mutex until `otter_sync_unlock` is called on this thread
 #[no_mangle]
 pub extern "C" fn otter_sync_lock(handle: u64) {
-    let mutexes = MUTEXES.read();
-    if let Some(mutex) = mutexes.get(&handle) {
-        let _guard = mutex.inner.lock();
+    if let Some(mutex) = mutex_for(handle) {
+        std::mem::forget(mutex.lock());
         THREAD_LOCKS.with(|locks| {
             locks.borrow_mut().insert(handle);
         });
@@ -80,9 +87,12 @@ pub extern "C" fn otter_sync_lock(handle: u64) {
 
 #[no_mangle]
 pub extern "C" fn otter_sync_unlock(handle: u64) {
-    THREAD_LOCKS.with(|locks| {
-        locks.borrow_mut().remove(&handle);
-    });
+    let held = THREAD_LOCKS.with(|locks| locks.borrow_mut().remove(&handle));
+    if let (true, Some(mutex)) = (held, mutex_for(handle)) {
+        // SAFETY: this thread locked the mutex in `otter_sync_lock` and
+        // forgot the guard
+        unsafe { mutex.force_unlock() };
+    }
 }
 
 // ============================================================================
@@ -1,25 +1,225 @@
+use std::cell::RefCell;
 use std::ffi::CStr;
+use std::io::IsTerminal;
 use std::os::raw::c_char;
 
 use crate::runtime::symbol_registry::{FfiFunction, FfiSignature, FfiType, SymbolRegistry};
 
 
+/// Where the `test` assertion about to run is: file, line, column and the
+/// source text of the call
+struct AssertLocation {
+    file: String,
+    line: i64,
+    column: i64,
+    source: String,
+}
+
+thread_local! {
+    static LOCATION: RefCell<Option<AssertLocation>> = const { RefCell::new(None) };
+}
+
+fn c_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(ptr) }.to_string_lossy().to_string())
+    }
+}
+
+/// Called by codegen before each `test.assert*` call
 #[no_mangle]
-pub extern "C" fn otter_test_assert(condition: i64, message: *const c_char) -> i32 {
-    if condition != 0 {
-        return 0; // Success
+pub extern "C" fn otter_test_set_location(
+    file: *const c_char,
+    line: i64,
+    column: i64,
+    source: *const c_char,
+) {
+    let location = c_string(file).map(|file| AssertLocation {
+        file,
+        line,
+        column,
+        source: c_string(source).unwrap_or_default(),
+    });
+    LOCATION.with(|slot| *slot.borrow_mut() = location);
+}
+
+/// `CLICOLOR_FORCE` wins over `NO_COLOR`, which wins over whether stderr is
+/// a terminal; `otter test` sets one of the two for its harnesses
+fn use_color() -> bool {
+    if std::env::var("CLICOLOR_FORCE").is_ok_and(|value| value != "0") {
+        return true;
+    }
+    std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Print where the failing assertion is and its message, then end the test
+fn fail(message: *const c_char, detail: &str, extra: &[String]) -> ! {
+    LOCATION.with(|slot| {
+        if let Some(location) = slot.borrow().as_ref() {
+            eprintln!(
+                "Assertion failed at {}:{}:{}",
+                location.file, location.line, location.column
+            );
+            for line in location.source.lines() {
+                eprintln!("    {line}");
+            }
+        }
+    });
+    match c_string(message) {
+        Some(message) => eprintln!("{message}: {detail}"),
+        None => eprintln!("Assertion failed: {detail}"),
     }
+    for line in extra {
+        eprintln!("{line}");
+    }
+    std::process::exit(1);
+}
+
+/// The lines a diff compares: the items of a `[...]` list, the entries of a
+/// `{...}` dict (sorted, so key order does not count as a difference) or the
+/// lines of a multi-line string. `None` for a value shown as a single line
+fn split_value(value: &str) -> Option<Vec<&str>> {
+    let sequence = value.len() >= 2
+        && ((value.starts_with('[') && value.ends_with(']'))
+            || (value.starts_with('{') && value.ends_with('}')));
+    if !sequence {
+        return value.contains('\n').then(|| value.split('\n').collect());
+    }
+
+    let inner = &value[1..value.len() - 1];
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut item_start = 0;
+    for (index, c) in inner.char_indices() {
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == open {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '[' | '{' | '(' => depth += 1,
+            ']' | '}' | ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                items.push(inner[item_start..index].trim_start_matches(' '));
+                item_start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(inner[item_start..].trim_start_matches(' '));
+    items.retain(|item| !item.is_empty());
+    if value.starts_with('{') {
+        items.sort_unstable();
+    }
+    Some(items)
+}
 
-    let msg = if message.is_null() {
-        "Assertion failed".to_string()
+/// Line diff through a longest common subsequence table; inputs too large
+/// for the table are shown as all removed, then all added
+fn diff_lines(expected: &[&str], actual: &[&str], color: bool) -> Vec<String> {
+    let (green, red, reset) = if color {
+        (GREEN, RED, RESET)
     } else {
-        unsafe { CStr::from_ptr(message) }
-            .to_string_lossy()
-            .to_string()
+        ("", "", "")
     };
+    let (n, m) = (expected.len(), actual.len());
+    let table = (n <= 1000 && m <= 1000).then(|| {
+        let mut table = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                table[i][j] = if expected[i] == actual[j] {
+                    table[i + 1][j + 1] + 1
+                } else {
+                    table[i + 1][j].max(table[i][j + 1])
+                };
+            }
+        }
+        table
+    });
 
-    eprintln!("Assertion failed: {}", msg);
-    std::process::exit(1);
+    let mut lines = vec![format!(
+        "  diff ({green}- expected{reset}, {red}+ actual{reset}):"
+    )];
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if table.is_some() && i < n && j < m && expected[i] == actual[j] {
+            lines.push(format!("      {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < n
+            && (j == m
+                || table
+                    .as_ref()
+                    .is_none_or(|table| table[i + 1][j] >= table[i][j + 1]))
+        {
+            lines.push(format!("    {green}- {}{reset}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("    {red}+ {}{reset}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Where two unequal values differ: a line diff for lists, dicts and
+/// multi-line strings, otherwise both values with the differing middle
+/// highlighted (or marked with a caret without color)
+fn difference(expected: &str, actual: &str, color: bool) -> Vec<String> {
+    if let (Some(expected), Some(actual)) = (split_value(expected), split_value(actual)) {
+        return diff_lines(&expected, &actual, color);
+    }
+
+    let prefix = expected
+        .char_indices()
+        .zip(actual.chars())
+        .find(|((_, left), right)| left != right)
+        .map_or(expected.len().min(actual.len()), |((index, _), _)| index);
+    let suffix = expected[prefix..]
+        .chars()
+        .rev()
+        .zip(actual[prefix..].chars().rev())
+        .take_while(|(left, right)| left == right)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+
+    let mut lines = Vec::new();
+    for (label, value, highlight) in [("expected: ", expected, GREEN), ("actual:   ", actual, RED)]
+    {
+        let middle = &value[prefix..value.len() - suffix];
+        let (start, end) = if color { (highlight, RESET) } else { ("", "") };
+        lines.push(format!(
+            "  {label}'{}{start}{middle}{end}{}'",
+            &value[..prefix],
+            &value[value.len() - suffix..]
+        ));
+    }
+    if !color {
+        let column = "  actual:   '".len() + expected[..prefix].chars().count();
+        lines.push(format!("{}^", " ".repeat(column)));
+    }
+    lines
+}
+
+#[no_mangle]
+pub extern "C" fn otter_test_assert(condition: i64, message: *const c_char) -> i32 {
+    if condition != 0 {
+        return 0; // Success
+    }
+    fail(message, "condition is false", &[]);
 }
 
 #[no_mangle]
@@ -34,18 +234,11 @@ pub extern "C" fn otter_test_assert_eq(
     if left_str == right_str {
         return 0; // Success
     }
-
-    let msg = if message.is_null() {
-        format!("Assertion failed: expected '{}', got '{}'", right_str, left_str)
-    } else {
-        let custom_msg = unsafe { CStr::from_ptr(message) }
-            .to_string_lossy()
-            .to_string();
-        format!("{}: expected '{}', got '{}'", custom_msg, right_str, left_str)
-    };
-
-    eprintln!("{}", msg);
-    std::process::exit(1);
+    fail(
+        message,
+        "values are not equal",
+        &difference(&right_str, &left_str, use_color()),
+    );
 }
 
 #[no_mangle]
@@ -60,18 +253,11 @@ pub extern "C" fn otter_test_assert_ne(
     if left_str != right_str {
         return 0; // Success
     }
-
-    let msg = if message.is_null() {
-        format!("Assertion failed: values should not be equal, but both are '{}'", left_str)
-    } else {
-        let custom_msg = unsafe { CStr::from_ptr(message) }
-            .to_string_lossy()
-            .to_string();
-        format!("{}: values should not be equal, but both are '{}'", custom_msg, left_str)
-    };
-
-    eprintln!("{}", msg);
-    std::process::exit(1);
+    fail(
+        message,
+        "values should not be equal",
+        &[format!("  both:     '{left_str}'")],
+    );
 }
 
 #[no_mangle]
@@ -85,18 +271,15 @@ pub extern "C" fn otter_test_assert_approx_eq(
     if diff <= epsilon {
         return 0; // Success
     }
-
-    let msg = if message.is_null() {
-        format!("Assertion failed: expected approximately {}, got {} (diff: {}, epsilon: {})", right, left, diff, epsilon)
-    } else {
-        let custom_msg = unsafe { CStr::from_ptr(message) }
-            .to_string_lossy()
-            .to_string();
-        format!("{}: expected approximately {}, got {} (diff: {}, epsilon: {})", custom_msg, right, left, diff, epsilon)
-    };
-
-    eprintln!("{}", msg);
-    std::process::exit(1);
+    fail(
+        message,
+        "values are not approximately equal",
+        &[
+            format!("  expected: {right}"),
+            format!("  actual:   {left}"),
+            format!("  diff:     {diff} (epsilon: {epsilon})"),
+        ],
+    );
 }
 
 #[no_mangle]
@@ -211,3 +394,135 @@ inventory::submit! {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_lists_dicts_and_multi_line_strings() {
+        let cases: &[(&str, Option<&[&str]>)] = &[
+            ("plain", None),
+            ("[]", Some(&[])),
+            (
+                "[1, [2, 3], \"a,b\", {\"k\": 1}]",
+                Some(&["1", "[2, 3]", "\"a,b\"", "{\"k\": 1}"]),
+            ),
+            ("['it\\'s, ok', 2]", Some(&["'it\\'s, ok'", "2"])),
+            (
+                "{\"b\": [1, 2], \"a\": 1}",
+                Some(&["\"a\": 1", "\"b\": [1, 2]"]),
+            ),
+            ("one\ntwo\n", Some(&["one", "two", ""])),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(split_value(value).as_deref(), *expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn diffs_lines_in_order() {
+        let cases: &[(&[&str], &[&str], &[&str])] = &[
+            (
+                &["1", "2", "3"],
+                &["1", "4", "3"],
+                &["      1", "    - 2", "    + 4", "      3"],
+            ),
+            (&["a"], &["a", "b"], &["      a", "    + b"]),
+            (&["a", "b"], &["b"], &["    - a", "      b"]),
+            (&["x"], &["y"], &["    - x", "    + y"]),
+        ];
+        for (expected, actual, lines) in cases {
+            let diff = diff_lines(expected, actual, false);
+            assert_eq!(diff[0], "  diff (- expected, + actual):");
+            assert_eq!(&diff[1..], *lines, "{expected:?} -> {actual:?}");
+        }
+
+        let colored = diff_lines(&["x"], &["y"], true);
+        assert_eq!(colored[1], format!("    {GREEN}- x{RESET}"));
+        assert_eq!(colored[2], format!("    {RED}+ y{RESET}"));
+    }
+
+    #[test]
+    fn shows_where_values_differ() {
+        let cases: &[(&str, &str, &[&str])] = &[
+            (
+                "hello",
+                "help",
+                &[
+                    "  expected: 'hello'",
+                    "  actual:   'help'",
+                    "                ^",
+                ],
+            ),
+            (
+                "abc",
+                "abcd",
+                &[
+                    "  expected: 'abc'",
+                    "  actual:   'abcd'",
+                    "                ^",
+                ],
+            ),
+            // The difference follows a multi-byte character, so the caret
+            // counts characters rather than bytes
+            (
+                "añb",
+                "añc",
+                &["  expected: 'añb'", "  actual:   'añc'", "               ^"],
+            ),
+            (
+                "[1, 2]",
+                "[1, 3]",
+                &[
+                    "  diff (- expected, + actual):",
+                    "      1",
+                    "    - 2",
+                    "    + 3",
+                ],
+            ),
+            (
+                "{\"a\": 1, \"b\": 2}",
+                "{\"b\": 2, \"a\": 2}",
+                &[
+                    "  diff (- expected, + actual):",
+                    "    - \"a\": 1",
+                    "    + \"a\": 2",
+                    "      \"b\": 2",
+                ],
+            ),
+            (
+                "one\ntwo",
+                "one\nTWO",
+                &[
+                    "  diff (- expected, + actual):",
+                    "      one",
+                    "    - two",
+                    "    + TWO",
+                ],
+            ),
+        ];
+        for (expected, actual, lines) in cases {
+            assert_eq!(difference(expected, actual, false), *lines, "{expected}");
+        }
+    }
+
+    #[test]
+    fn highlights_whole_characters() {
+        // Only the differing characters are colored; the shared multi-byte
+        // prefix and suffix stay intact
+        assert_eq!(
+            difference("héllo wörld", "héllo wérld", true),
+            [
+                format!("  expected: 'héllo w{GREEN}ö{RESET}rld'"),
+                format!("  actual:   'héllo w{RED}é{RESET}rld'"),
+            ]
+        );
+        assert_eq!(
+            difference("xé", "yé", true),
+            [
+                format!("  expected: '{GREEN}x{RESET}é'"),
+                format!("  actual:   '{RED}y{RESET}é'"),
+            ]
+        );
+    }
+}
@@ -24,6 +24,15 @@ pub extern "C" fn otter_format_int(value: i64) -> *mut c_char {
         .unwrap_or_else(|_| std::ptr::null_mut())
 }
 
+/// Format an unsigned 64-bit integer value to string
+#[no_mangle]
+pub extern "C" fn otter_format_uint(value: u64) -> *mut c_char {
+    let formatted = format!("{}", value);
+    CString::new(formatted)
+        .map(CString::into_raw)
+        .unwrap_or_else(|_| std::ptr::null_mut())
+}
+
 /// Format a boolean value to string
 #[no_mangle]
 pub extern "C" fn otter_format_bool(value: bool) -> *mut c_char {
@@ -113,6 +122,12 @@ fn register_string_functions(registry: &SymbolRegistry) {
         signature: FfiSignature::new(vec![FfiType::I64], FfiType::Str),
     });
 
+    registry.register(FfiFunction {
+        name: "std.strings.format_uint".into(),
+        symbol: "otter_format_uint".into(),
+        signature: FfiSignature::new(vec![FfiType::U64], FfiType::Str),
+    });
+
     registry.register(FfiFunction {
         name: "std.strings.format_bool".into(),
         symbol: "otter_format_bool".into(),
@@ -17,6 +17,14 @@ pub enum FfiType {
     Opaque,
     List,
     Map,
+    I8,
+    I16,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+    F32,
 }
 
 #[repr(C)]
@@ -1,17 +1,34 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use anyhow::{Context, Result};
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
 use glob::glob;
 
-use ast::nodes::{Function, Statement};
+use ast::nodes::{Annotation, Block, Expr, Function, Literal, Program, Statement};
 use lexer::tokenize;
 use parser::parse;
 
+use crate::codegen::TestHarness;
+
 #[derive(Debug, Clone)]
 pub struct TestCase {
     pub file_path: PathBuf,
     pub function_name: String,
     pub function: Function,
     pub line_number: usize,
+    /// Set by `@ignore` or `@skip("reason")`; such tests are reported without running
+    pub marker: Option<TestMarker>,
+    /// Limit from `@timeout(seconds)`, which overrides `--timeout`
+    pub timeout: Option<Duration>,
+}
+
+/// Why a test does not run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestMarker {
+    /// `@ignore` or `@ignore("reason")`; `--include-ignored` runs these anyway
+    Ignore(Option<String>),
+    /// `@skip("reason")`
+    Skip(String),
 }
 
 pub struct TestDiscovery {
@@ -49,36 +66,91 @@ impl TestDiscovery {
     }
 
     pub fn discover_tests_in_file(&self, file_path: &Path) -> Result<Vec<TestCase>> {
+        Ok(match Self::parse_file(file_path)? {
+            Some((source, program)) => Self::tests_in_program(file_path, &source, &program),
+            None => Vec::new(),
+        })
+    }
+
+    /// The tests and fixtures of `file_path`, which its test harness runs
+    pub fn discover_harness(&self, file_path: &Path) -> Result<TestHarness> {
+        let Some((source, program)) = Self::parse_file(file_path)? else {
+            return Ok(TestHarness::default());
+        };
+        let fixture = |name: &str| {
+            program
+                .statements
+                .iter()
+                .any(|stmt| matches!(stmt, Statement::Function(func) if func.name == name))
+                .then(|| name.to_string())
+        };
+
+        Ok(TestHarness {
+            tests: Self::tests_in_program(file_path, &source, &program)
+                .into_iter()
+                .map(|test| test.function_name)
+                .collect(),
+            setup_module: fixture("setup_module"),
+            teardown_module: fixture("teardown_module"),
+            setup: fixture("setup"),
+            teardown: fixture("teardown"),
+        })
+    }
+
+    /// Source and syntax tree of `file_path`, or `None` when it does not
+    /// parse; compiling it reports why
+    fn parse_file(file_path: &Path) -> Result<Option<(String, Program)>> {
         let source = std::fs::read_to_string(file_path)
             .with_context(|| format!("failed to read {}", file_path.display()))?;
 
         let tokens = match tokenize(&source) {
             Ok(tokens) => tokens,
-            Err(_) => return Ok(Vec::new()),
+            Err(_) => return Ok(None),
         };
 
-        let program = match parse(&tokens) {
-            Ok(program) => program,
-            Err(_) => return Ok(Vec::new()),
-        };
+        Ok(parse(&tokens).ok().map(|program| (source, program)))
+    }
 
+    fn tests_in_program(file_path: &Path, source: &str, program: &Program) -> Vec<TestCase> {
         let mut tests = Vec::new();
 
-        for (idx, stmt) in program.statements.iter().enumerate() {
+        for stmt in &program.statements {
             if let Statement::Function(func) = stmt {
                 if Self::is_test_function(func) {
-                    let line_number = Self::estimate_line_number(&source, idx);
-                    tests.push(TestCase {
+                    let line_number = func.span.map_or(1, |span| span.line_column(source).0);
+                    let timeout = match func.annotation("timeout").map(annotation_timeout) {
+                        Some(Ok(timeout)) => Some(timeout),
+                        Some(Err(e)) => {
+                            eprintln!("Warning: {}:{}: {}", file_path.display(), line_number, e);
+                            None
+                        }
+                        None => None,
+                    };
+                    let test = TestCase {
                         file_path: file_path.to_path_buf(),
                         function_name: func.name.clone(),
                         function: func.clone(),
                         line_number,
-                    });
+                        marker: Self::test_marker(func),
+                        timeout,
+                    };
+
+                    // A broken `@parametrize` leaves the test as it is, and
+                    // compiling the file reports the error
+                    match parametrized_cases(func) {
+                        Ok(Some(cases)) => {
+                            tests.extend(cases.into_iter().map(|(id, _)| TestCase {
+                                function_name: case_name(&func.name, &id),
+                                ..test.clone()
+                            }))
+                        }
+                        Ok(None) | Err(_) => tests.push(test),
+                    }
                 }
             }
         }
 
-        Ok(tests)
+        tests
     }
 
     pub fn discover_all_tests(&self) -> Result<Vec<TestCase>> {
@@ -100,13 +172,13 @@ impl TestDiscovery {
         func.name.starts_with("test_") || (func.public && func.name.starts_with("test"))
     }
 
-    fn estimate_line_number(source: &str, statement_index: usize) -> usize {
-        let chars_before = source
-            .chars()
-            .take(statement_index * 50)
-            .filter(|&c| c == '\n')
-            .count();
-        chars_before + 1
+    fn test_marker(func: &Function) -> Option<TestMarker> {
+        if let Some(skip) = func.annotation("skip") {
+            let reason = string_arg(skip).unwrap_or_else(|| "skipped".to_string());
+            return Some(TestMarker::Skip(reason));
+        }
+        func.annotation("ignore")
+            .map(|ignore| TestMarker::Ignore(string_arg(ignore)))
     }
 }
 
@@ -116,3 +188,267 @@ impl Default for TestDiscovery {
     }
 }
 
+/// Parse a time limit such as `30`, `2.5s`, `500ms` or `2m`; a bare number means seconds
+pub fn parse_timeout(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let amount: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid timeout `{value}`"))?;
+    let seconds = match unit.trim().to_ascii_lowercase().as_str() {
+        "ms" => amount / 1000.0,
+        "" | "s" => amount,
+        "m" => amount * 60.0,
+        other => bail!("unknown timeout unit `{other}` (use ms, s or m)"),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// The limit of a `@timeout(5)` or `@timeout("500ms")` annotation
+fn annotation_timeout(annotation: &Annotation) -> Result<Duration> {
+    match annotation.args.first() {
+        Some(Expr::Literal(Literal::Number(number))) if number.value >= 0.0 => {
+            Ok(Duration::from_secs_f64(number.value))
+        }
+        Some(Expr::Literal(Literal::String(value))) => parse_timeout(value),
+        _ => bail!("@timeout takes a number of seconds or a string such as \"500ms\""),
+    }
+}
+
+/// A case of a `@parametrize` test: its name and the arguments the test is
+/// called with
+pub type ParametrizedCase = (String, Vec<Expr>);
+
+/// The cases of a `@parametrize` test. Cases come from a dict of named cases,
+/// `@parametrize({"empty": ("", 0), "unicode": ("héllo", 5)})`, or a list,
+/// whose cases are named by position; a tuple holds the arguments of a test
+/// taking more than one.
+pub fn parametrized_cases(func: &Function) -> Result<Option<Vec<ParametrizedCase>>> {
+    let Some(annotation) = func.annotation("parametrize") else {
+        return Ok(None);
+    };
+    let cases: Vec<(String, Expr)> = match annotation.args.as_slice() {
+        [Expr::Dict(entries)] => entries
+            .iter()
+            .map(|(key, value)| match key {
+                Expr::Literal(Literal::String(id)) => Ok((id.clone(), value.clone())),
+                _ => bail!("@parametrize case names must be string literals"),
+            })
+            .collect::<Result<_>>()?,
+        [Expr::Array(values)] => values
+            .iter()
+            .enumerate()
+            .map(|(position, value)| (position.to_string(), value.clone()))
+            .collect(),
+        _ => bail!("@parametrize takes a dict of named cases or a list of cases"),
+    };
+    if cases.is_empty() {
+        bail!("@parametrize on `{}` has no cases", func.name);
+    }
+
+    let mut seen = HashSet::new();
+    cases
+        .into_iter()
+        .map(|(id, value)| {
+            // The harness protocol separates test names by whitespace
+            if id.is_empty() || id.contains(char::is_whitespace) {
+                bail!("@parametrize case name `{id}` must be non-empty without whitespace");
+            }
+            if !seen.insert(id.clone()) {
+                bail!("@parametrize on `{}` has two cases named `{id}`", func.name);
+            }
+            let args = match value {
+                Expr::Tuple(items) if func.params.len() != 1 => items,
+                value => vec![value],
+            };
+            if args.len() != func.params.len() {
+                bail!(
+                    "@parametrize case `{id}` passes {} arguments, but `{}` takes {}",
+                    args.len(),
+                    func.name,
+                    func.params.len()
+                );
+            }
+            Ok((id, args))
+        })
+        .collect::<Result<_>>()
+        .map(Some)
+}
+
+/// How a parametrized case is named and reported, e.g. `test_parse[empty]`
+pub fn case_name(test: &str, id: &str) -> String {
+    format!("{test}[{id}]")
+}
+
+/// Add a function for each `@parametrize` case of the tests in `program`,
+/// named like the case, which calls the test with the case's arguments; the
+/// test harness runs these instead of the tests themselves
+pub fn add_parametrized_cases(program: &mut Program) -> Result<()> {
+    let mut cases = Vec::new();
+    for stmt in &program.statements {
+        let Statement::Function(func) = stmt else {
+            continue;
+        };
+        if !TestDiscovery::is_test_function(func) {
+            continue;
+        }
+        for (id, args) in parametrized_cases(func)?.into_iter().flatten() {
+            let call = Expr::Call {
+                func: Box::new(Expr::Identifier {
+                    name: func.name.clone(),
+                    span: None,
+                }),
+                args,
+                span: None,
+            };
+            let case = Function::new(
+                case_name(&func.name, &id),
+                Vec::new(),
+                None,
+                Block::new(vec![Statement::Expr(call)]),
+            )
+            .with_span(func.span);
+            cases.push(Statement::Function(case));
+        }
+    }
+    program.statements.extend(cases);
+    Ok(())
+}
+
+fn string_arg(annotation: &Annotation) -> Option<String> {
+    match annotation.args.first() {
+        Some(Expr::Literal(Literal::String(value))) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+def setup():
+    pass
+
+def teardown_module():
+    pass
+
+@parametrize({\"empty\": (\"\", 0), \"unicode\": (\"héllo\", 5)})
+def test_len(text: str, expected: int):
+    pass
+
+@parametrize([1, 2, 3])
+def test_positive(value: int):
+    pass
+
+def test_plain():
+    pass
+";
+
+    fn write_source(source: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cases.ot");
+        std::fs::write(&path, source).unwrap();
+        (dir, path)
+    }
+
+    fn function(program: &Program, name: &str) -> Function {
+        program
+            .statements
+            .iter()
+            .find_map(|stmt| match stmt {
+                Statement::Function(func) if func.name == name => Some(func.clone()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn parse_source(source: &str) -> Program {
+        parse(&tokenize(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn expands_parametrized_tests_into_named_cases() {
+        let (_dir, path) = write_source(SOURCE);
+        let discovery = TestDiscovery::new();
+
+        let tests = discovery.discover_tests_in_file(&path).unwrap();
+        let names: Vec<_> = tests
+            .iter()
+            .map(|test| test.function_name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "test_len[empty]",
+                "test_len[unicode]",
+                "test_positive[0]",
+                "test_positive[1]",
+                "test_positive[2]",
+                "test_plain"
+            ]
+        );
+        assert_eq!(tests[1].line_number, 8);
+
+        let harness = discovery.discover_harness(&path).unwrap();
+        assert_eq!(harness.tests, names);
+        assert_eq!(harness.setup.as_deref(), Some("setup"));
+        assert_eq!(harness.teardown_module.as_deref(), Some("teardown_module"));
+        assert_eq!(harness.teardown, None);
+        assert_eq!(harness.setup_module, None);
+    }
+
+    #[test]
+    fn adds_a_function_per_case() {
+        let mut program = parse_source(SOURCE);
+        add_parametrized_cases(&mut program).unwrap();
+
+        let case = function(&program, "test_len[unicode]");
+        assert!(case.params.is_empty());
+        match case.body.statements.as_slice() {
+            [Statement::Expr(Expr::Call { func, args, .. })] => {
+                assert!(
+                    matches!(func.as_ref(), Expr::Identifier { name, .. } if name == "test_len")
+                );
+                assert!(
+                    matches!(&args[0], Expr::Literal(Literal::String(text)) if text == "héllo")
+                );
+                assert_eq!(args.len(), 2);
+            }
+            other => panic!("unexpected case body {other:?}"),
+        }
+        assert_eq!(
+            function(&program, "test_positive[2]").body.statements.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_cases() {
+        let program = parse_source(
+            "\
+@parametrize({\"a\": 1, \"a\": 2})
+def test_twice(value: int):
+    pass
+
+@parametrize({\"two args\": 1})
+def test_spaced(value: int):
+    pass
+
+@parametrize([(1, 2)])
+def test_arity(value: int, other: int, third: int):
+    pass
+",
+        );
+        for name in ["test_twice", "test_spaced", "test_arity"] {
+            assert!(
+                parametrized_cases(&function(&program, name)).is_err(),
+                "{name}"
+            );
+        }
+        assert!(add_parametrized_cases(&mut program.clone()).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use glob::Pattern;
+
+use crate::test::{TestCase, TestMarker};
+
+/// Picks the tests `otter test` runs from the ones discovered
+#[derive(Debug, Clone, Default)]
+pub struct TestFilter {
+    /// Substrings or glob patterns; a test runs if its name matches any of them
+    patterns: Vec<String>,
+    /// Match names exactly instead of by substring
+    exact: bool,
+    /// Run `@ignore` tests instead of reporting them as ignored
+    include_ignored: bool,
+}
+
+impl TestFilter {
+    pub fn new(patterns: Vec<String>, exact: bool, include_ignored: bool) -> Self {
+        Self {
+            patterns,
+            exact,
+            include_ignored,
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        // `test_parse[empty]` names a case of `test_parse`; brackets in a
+        // pattern naming a case are not a glob character class
+        let test = name.split_once('[').map_or(name, |(test, _)| test);
+        self.patterns.is_empty()
+            || self.patterns.iter().any(|pattern| {
+                if name == pattern || (self.exact && test == pattern) {
+                    true
+                } else if is_glob(pattern) {
+                    Pattern::new(pattern).is_ok_and(|glob| glob.matches(name))
+                } else if self.exact {
+                    name == pattern
+                } else {
+                    name.contains(pattern.as_str())
+                }
+            })
+    }
+
+    pub fn apply(&self, tests: Vec<TestCase>) -> Vec<TestCase> {
+        tests
+            .into_iter()
+            .filter(|test| self.matches(&test.function_name))
+            .map(|mut test| {
+                if self.include_ignored && matches!(test.marker, Some(TestMarker::Ignore(_))) {
+                    test.marker = None;
+                }
+                test
+            })
+            .collect()
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Shuffle `items` in place with a generator seeded by `seed`, so the same
+/// seed always gives the same order
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    // splitmix64
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    // Fisher-Yates
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(patterns: &[&str], exact: bool) -> TestFilter {
+        TestFilter::new(
+            patterns.iter().map(|pattern| pattern.to_string()).collect(),
+            exact,
+            false,
+        )
+    }
+
+    #[test]
+    fn matches_substrings_globs_and_exact_names() {
+        assert!(filter(&[], false).matches("test_parse"));
+        assert!(filter(&["parse"], false).matches("test_parse_empty"));
+        assert!(!filter(&["parse"], true).matches("test_parse_empty"));
+        assert!(filter(&["test_parse"], true).matches("test_parse"));
+        assert!(filter(&["test_*_empty"], false).matches("test_parse_empty"));
+        assert!(!filter(&["test_*_empty"], false).matches("test_parse"));
+        assert!(filter(&["nothing", "lex"], false).matches("test_lexer"));
+    }
+
+    #[test]
+    fn matches_parametrized_cases() {
+        assert!(filter(&["test_parse[empty]"], false).matches("test_parse[empty]"));
+        assert!(!filter(&["test_parse[empty]"], false).matches("test_parse[unicode]"));
+        assert!(filter(&["test_parse"], true).matches("test_parse[unicode]"));
+        assert!(!filter(&["test_parse"], true).matches("test_parse_all[unicode]"));
+        assert!(filter(&["unicode"], false).matches("test_parse[unicode]"));
+    }
+
+    #[test]
+    fn shuffle_depends_only_on_the_seed() {
+        let original: Vec<u32> = (0..20).collect();
+        let mut first = original.clone();
+        let mut second = original.clone();
+        shuffle(&mut first, 42);
+        shuffle(&mut second, 42);
+        assert_eq!(first, second);
+        assert_ne!(first, original);
+
+        let mut other = original.clone();
+        shuffle(&mut other, 7);
+        assert_ne!(first, other);
+
+        first.sort();
+        assert_eq!(first, original);
+    }
+}
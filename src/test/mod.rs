@@ -1,10 +1,15 @@
 pub mod discovery;
+pub mod filter;
 pub mod runner;
 pub mod snapshot;
 pub mod reporter;
 
-pub use discovery::{TestDiscovery, TestCase};
+pub use discovery::{
+    add_parametrized_cases, case_name, parametrized_cases, parse_timeout, ParametrizedCase,
+    TestDiscovery, TestCase, TestMarker,
+};
+pub use filter::{shuffle, TestFilter};
 pub use runner::TestRunner;
 pub use snapshot::SnapshotManager;
-pub use reporter::{TestReporter, TestResult};
+pub use reporter::{reporter_for, ReportFormat, Reporter, TestReporter, TestResult};
 
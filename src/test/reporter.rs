@@ -1,5 +1,8 @@
+use std::io::Write;
 use std::time::Duration;
+use anyhow::Result;
 use colored::*;
+use serde_json::json;
 
 use crate::test::TestCase;
 
@@ -18,6 +21,84 @@ pub enum TestResult {
     Skipped {
         reason: String,
     },
+    /// Marked `@ignore` and run without `--include-ignored`
+    Ignored {
+        reason: Option<String>,
+    },
+    /// Killed after running longer than its time limit
+    TimedOut {
+        timeout: Duration,
+        output: String,
+    },
+    /// Failed, then passed when retried; `attempts` counts every run
+    Flaky {
+        duration: Duration,
+        output: String,
+        attempts: usize,
+    },
+}
+
+impl TestResult {
+    /// Whether the result makes the run fail
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            TestResult::Failed { .. } | TestResult::TimedOut { .. }
+        )
+    }
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            TestResult::Passed { duration, .. }
+            | TestResult::Failed { duration, .. }
+            | TestResult::Flaky { duration, .. } => *duration,
+            TestResult::TimedOut { timeout, .. } => *timeout,
+            TestResult::Skipped { .. } | TestResult::Ignored { .. } => Duration::ZERO,
+        }
+    }
+}
+
+/// Output formats of `otter test`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// Colored output for terminals
+    Human,
+    /// JUnit XML, as read by CI dashboards
+    Junit,
+    /// Test Anything Protocol, version 13
+    Tap,
+    /// One JSON object per line for every test event
+    Json,
+}
+
+/// Receives the events of a test run; `--format` picks the implementation
+pub trait Reporter {
+    /// Called once with every test before any of them runs
+    fn run_started(&mut self, tests: &[TestCase]) -> Result<()>;
+
+    /// Called when a test is handed to a harness process
+    fn test_started(&mut self, _test: &TestCase) -> Result<()> {
+        Ok(())
+    }
+
+    fn test_finished(&mut self, test: &TestCase, result: &TestResult) -> Result<()>;
+
+    /// Called once after the last test, with the time the whole run took
+    fn run_finished(&mut self, duration: Duration) -> Result<()>;
+}
+
+/// The reporter for `format`; machine-readable formats write to `output`
+pub fn reporter_for(
+    format: ReportFormat,
+    verbose: bool,
+    output: Box<dyn Write>,
+) -> Box<dyn Reporter> {
+    match format {
+        ReportFormat::Human => Box::new(TestReporter::new(verbose)),
+        ReportFormat::Junit => Box::new(JunitReporter::new(output)),
+        ReportFormat::Tap => Box::new(TapReporter::new(output)),
+        ReportFormat::Json => Box::new(JsonReporter::new(output)),
+    }
 }
 
 pub struct TestReporter {
@@ -75,39 +156,773 @@ impl TestReporter {
                 print!("{}", "⊘".yellow());
                 println!(" {} ({})", test.function_name, reason);
             }
+            TestResult::Ignored { reason } => {
+                print!("{}", "⊘".yellow());
+                println!(" {} ({})", test.function_name, ignored_reason(reason));
+            }
+            TestResult::TimedOut { timeout, output } => {
+                print!("{}", "✗".red());
+                println!(" {} ({})", test.function_name, timeout_message(*timeout));
+                if !output.is_empty() {
+                    println!("  {}:", "Output:".yellow());
+                    for line in output.lines() {
+                        println!("    {}", line);
+                    }
+                }
+            }
+            TestResult::Flaky {
+                duration,
+                output,
+                attempts,
+            } => {
+                print!("{}", "✓".yellow());
+                println!(
+                    " {} ({:.2}ms, flaky: passed on attempt {})",
+                    test.function_name,
+                    duration.as_secs_f64() * 1000.0,
+                    attempts
+                );
+                if self.verbose && !output.is_empty() {
+                    for line in output.lines() {
+                        println!("  {}", line);
+                    }
+                }
+            }
         }
     }
 
     pub fn print_summary(&self) {
         let total_duration = self.start_time.elapsed();
-        let passed = self.results.iter().filter(|(_, r)| matches!(r, TestResult::Passed { .. })).count();
-        let failed = self.results.iter().filter(|(_, r)| matches!(r, TestResult::Failed { .. })).count();
-        let skipped = self.results.iter().filter(|(_, r)| matches!(r, TestResult::Skipped { .. })).count();
+        let counts = ResultCounts::of(self.results.iter().map(|(_, r)| r));
         let total = self.results.len();
 
         println!("\n{}", "Test Summary".bold());
         println!("  Total:   {}", total);
-        println!("  {} {}", "Passed:".green(), passed);
-        println!("  {} {}", "Failed:".red(), failed);
-        if skipped > 0 {
-            println!("  {} {}", "Skipped:".yellow(), skipped);
+        println!("  {} {}", "Passed:".green(), counts.passed);
+        println!("  {} {}", "Failed:".red(), counts.failed);
+        if counts.timed_out > 0 {
+            println!("  {} {}", "Timed out:".red(), counts.timed_out);
+        }
+        if counts.flaky > 0 {
+            println!("  {} {}", "Flaky:".yellow(), counts.flaky);
+        }
+        if counts.skipped > 0 {
+            println!("  {} {}", "Skipped:".yellow(), counts.skipped);
+        }
+        if counts.ignored > 0 {
+            println!("  {} {}", "Ignored:".yellow(), counts.ignored);
         }
         println!("  Time:    {:.2}s", total_duration.as_secs_f64());
 
-        if failed > 0 {
+        if counts.failed + counts.timed_out > 0 {
             println!("\n{}", "Failed Tests:".red().bold());
             for (test, result) in &self.results {
-                if matches!(result, TestResult::Failed { .. }) {
-                    if let TestResult::Failed { error, .. } = result {
+                match result {
+                    TestResult::Failed { error, .. } => {
                         println!("  {} - {}", test.function_name.red(), error);
                     }
+                    TestResult::TimedOut { timeout, .. } => {
+                        println!(
+                            "  {} - {}",
+                            test.function_name.red(),
+                            timeout_message(*timeout)
+                        );
+                    }
+                    _ => {}
                 }
             }
         }
     }
 
     pub fn has_failures(&self) -> bool {
-        self.results.iter().any(|(_, r)| matches!(r, TestResult::Failed { .. }))
+        self.results.iter().any(|(_, r)| r.is_failure())
+    }
+}
+
+impl Reporter for TestReporter {
+    fn run_started(&mut self, tests: &[TestCase]) -> Result<()> {
+        if tests.is_empty() {
+            println!("No tests found");
+        } else {
+            println!("Running {} test(s)...\n", tests.len());
+        }
+        Ok(())
+    }
+
+    fn test_finished(&mut self, test: &TestCase, result: &TestResult) -> Result<()> {
+        self.print_result(test, result);
+        self.record_result(test.clone(), result.clone());
+        Ok(())
+    }
+
+    fn run_finished(&mut self, _duration: Duration) -> Result<()> {
+        if !self.results.is_empty() {
+            self.print_summary();
+        }
+        Ok(())
+    }
+}
+
+/// Writes a JUnit XML report, one `<testsuite>` per test file, once the run is over
+pub struct JunitReporter {
+    output: Box<dyn Write>,
+    results: Vec<(TestCase, TestResult)>,
+}
+
+impl JunitReporter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            results: Vec::new(),
+        }
+    }
+
+    fn write_testcase(&mut self, test: &TestCase, result: &TestResult) -> Result<()> {
+        let file = test.file_path.display().to_string();
+        write!(
+            self.output,
+            "    <testcase name=\"{}\" classname=\"{}\" file=\"{}\" line=\"{}\" time=\"{:.3}\"",
+            xml_escape(&test.function_name),
+            xml_escape(&file),
+            xml_escape(&file),
+            test.line_number,
+            result.duration().as_secs_f64()
+        )?;
+
+        match result {
+            TestResult::Passed { output, .. } if output.is_empty() => {
+                writeln!(self.output, "/>")?;
+                return Ok(());
+            }
+            TestResult::Passed { output, .. } => {
+                writeln!(self.output, ">")?;
+                writeln!(
+                    self.output,
+                    "      <system-out>{}</system-out>",
+                    xml_escape(output)
+                )?;
+            }
+            TestResult::Failed {
+                error,
+                output,
+                span,
+                ..
+            } => {
+                writeln!(self.output, ">")?;
+                let location = failure_location(test, span)
+                    .map(|location| format!("at {}\n", location))
+                    .unwrap_or_default();
+                writeln!(
+                    self.output,
+                    "      <failure message=\"{}\">{}</failure>",
+                    xml_escape(error),
+                    xml_escape(&location)
+                )?;
+                if !output.is_empty() {
+                    writeln!(
+                        self.output,
+                        "      <system-out>{}</system-out>",
+                        xml_escape(output)
+                    )?;
+                }
+            }
+            TestResult::Skipped { reason } => {
+                writeln!(self.output, ">")?;
+                writeln!(
+                    self.output,
+                    "      <skipped message=\"{}\"/>",
+                    xml_escape(reason)
+                )?;
+            }
+            TestResult::Ignored { reason } => {
+                writeln!(self.output, ">")?;
+                writeln!(
+                    self.output,
+                    "      <skipped message=\"{}\"/>",
+                    xml_escape(&ignored_reason(reason))
+                )?;
+            }
+            TestResult::TimedOut { timeout, output } => {
+                writeln!(self.output, ">")?;
+                writeln!(
+                    self.output,
+                    "      <failure message=\"{}\" type=\"timeout\"/>",
+                    xml_escape(&timeout_message(*timeout))
+                )?;
+                if !output.is_empty() {
+                    writeln!(
+                        self.output,
+                        "      <system-out>{}</system-out>",
+                        xml_escape(output)
+                    )?;
+                }
+            }
+            TestResult::Flaky {
+                output, attempts, ..
+            } => {
+                // JUnit has no flaky state; the test passed, and the attempts are kept as a property
+                writeln!(self.output, ">")?;
+                writeln!(
+                    self.output,
+                    "      <properties><property name=\"attempts\" value=\"{}\"/></properties>",
+                    attempts
+                )?;
+                if !output.is_empty() {
+                    writeln!(
+                        self.output,
+                        "      <system-out>{}</system-out>",
+                        xml_escape(output)
+                    )?;
+                }
+            }
+        }
+        writeln!(self.output, "    </testcase>")?;
+        Ok(())
+    }
+}
+
+impl Reporter for JunitReporter {
+    fn run_started(&mut self, _tests: &[TestCase]) -> Result<()> {
+        Ok(())
+    }
+
+    fn test_finished(&mut self, test: &TestCase, result: &TestResult) -> Result<()> {
+        self.results.push((test.clone(), result.clone()));
+        Ok(())
+    }
+
+    fn run_finished(&mut self, duration: Duration) -> Result<()> {
+        let results = std::mem::take(&mut self.results);
+        let counts = ResultCounts::of(results.iter().map(|(_, result)| result));
+
+        writeln!(self.output, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            self.output,
+            "<testsuites name=\"otter test\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            results.len(),
+            counts.failed + counts.timed_out,
+            counts.skipped + counts.ignored,
+            duration.as_secs_f64()
+        )?;
+
+        // Tests of a file arrive together, in discovery order
+        let mut start = 0;
+        while start < results.len() {
+            let file_path = &results[start].0.file_path;
+            let end = results[start..]
+                .iter()
+                .position(|(test, _)| &test.file_path != file_path)
+                .map_or(results.len(), |offset| start + offset);
+            let suite = &results[start..end];
+            let counts = ResultCounts::of(suite.iter().map(|(_, result)| result));
+            let time: f64 = suite
+                .iter()
+                .map(|(_, result)| result.duration().as_secs_f64())
+                .sum();
+
+            writeln!(
+                self.output,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+                xml_escape(&file_path.display().to_string()),
+                suite.len(),
+                counts.failed + counts.timed_out,
+                counts.skipped + counts.ignored,
+                time
+            )?;
+            for (test, result) in suite {
+                self.write_testcase(test, result)?;
+            }
+            writeln!(self.output, "  </testsuite>")?;
+            start = end;
+        }
+
+        writeln!(self.output, "</testsuites>")?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes Test Anything Protocol (version 13) lines as tests finish
+pub struct TapReporter {
+    output: Box<dyn Write>,
+    next_number: usize,
+}
+
+impl TapReporter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            next_number: 1,
+        }
+    }
+}
+
+impl Reporter for TapReporter {
+    fn run_started(&mut self, tests: &[TestCase]) -> Result<()> {
+        writeln!(self.output, "TAP version 13")?;
+        writeln!(self.output, "1..{}", tests.len())?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn test_finished(&mut self, test: &TestCase, result: &TestResult) -> Result<()> {
+        let number = self.next_number;
+        self.next_number += 1;
+
+        match result {
+            TestResult::Passed { .. } => {
+                writeln!(self.output, "ok {} - {}", number, test.function_name)?;
+            }
+            TestResult::Flaky { attempts, .. } => {
+                writeln!(
+                    self.output,
+                    "ok {} - {} # flaky, passed on attempt {}",
+                    number, test.function_name, attempts
+                )?;
+            }
+            TestResult::Skipped { reason } => {
+                writeln!(
+                    self.output,
+                    "ok {} - {} # SKIP {}",
+                    number, test.function_name, reason
+                )?;
+            }
+            TestResult::Ignored { reason } => {
+                writeln!(
+                    self.output,
+                    "ok {} - {} # SKIP {}",
+                    number,
+                    test.function_name,
+                    ignored_reason(reason)
+                )?;
+            }
+            TestResult::TimedOut { timeout, output } => {
+                writeln!(self.output, "not ok {} - {}", number, test.function_name)?;
+                writeln!(self.output, "  ---")?;
+                writeln!(
+                    self.output,
+                    "  message: {}",
+                    json!(timeout_message(*timeout))
+                )?;
+                if !output.is_empty() {
+                    writeln!(self.output, "  output: {}", json!(output))?;
+                }
+                writeln!(self.output, "  ...")?;
+            }
+            TestResult::Failed {
+                error,
+                duration,
+                output,
+                span,
+            } => {
+                // The YAML block holds JSON strings, which YAML reads as quoted scalars
+                writeln!(self.output, "not ok {} - {}", number, test.function_name)?;
+                writeln!(self.output, "  ---")?;
+                writeln!(self.output, "  message: {}", json!(error))?;
+                if let Some(location) = failure_location(test, span) {
+                    writeln!(self.output, "  at: {}", json!(location))?;
+                }
+                writeln!(
+                    self.output,
+                    "  duration_ms: {:.2}",
+                    duration.as_secs_f64() * 1000.0
+                )?;
+                if !output.is_empty() {
+                    writeln!(self.output, "  output: {}", json!(output))?;
+                }
+                writeln!(self.output, "  ...")?;
+            }
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn run_finished(&mut self, _duration: Duration) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes one JSON object per line for every event of the run
+pub struct JsonReporter {
+    output: Box<dyn Write>,
+    counts: ResultCounts,
+}
+
+impl JsonReporter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            counts: ResultCounts::default(),
+        }
+    }
+
+    fn emit(&mut self, event: serde_json::Value) -> Result<()> {
+        writeln!(self.output, "{}", event)?;
+        self.output.flush()?;
+        Ok(())
     }
 }
 
+impl Reporter for JsonReporter {
+    fn run_started(&mut self, tests: &[TestCase]) -> Result<()> {
+        self.emit(json!({ "event": "run_started", "tests": tests.len() }))
+    }
+
+    fn test_started(&mut self, test: &TestCase) -> Result<()> {
+        self.emit(json!({
+            "event": "started",
+            "name": test.function_name,
+            "file": test.file_path.display().to_string(),
+            "line": test.line_number,
+        }))
+    }
+
+    fn test_finished(&mut self, test: &TestCase, result: &TestResult) -> Result<()> {
+        self.counts.add(result);
+        let mut event = json!({
+            "name": test.function_name,
+            "file": test.file_path.display().to_string(),
+            "line": test.line_number,
+            "duration_ms": result.duration().as_secs_f64() * 1000.0,
+        });
+
+        match result {
+            TestResult::Passed { output, .. } => {
+                event["event"] = json!("passed");
+                event["output"] = json!(output);
+            }
+            TestResult::Failed {
+                error,
+                output,
+                span,
+                ..
+            } => {
+                event["event"] = json!("failed");
+                event["message"] = json!(error);
+                event["output"] = json!(output);
+                if let Some((line, _)) = span {
+                    event["location"] = json!({
+                        "file": test.file_path.display().to_string(),
+                        "line": line,
+                    });
+                }
+            }
+            TestResult::Skipped { reason } => {
+                event["event"] = json!("skipped");
+                event["reason"] = json!(reason);
+            }
+            TestResult::Ignored { reason } => {
+                event["event"] = json!("ignored");
+                event["reason"] = json!(reason);
+            }
+            TestResult::TimedOut { timeout, output } => {
+                event["event"] = json!("timed_out");
+                event["message"] = json!(timeout_message(*timeout));
+                event["timeout_ms"] = json!(timeout.as_secs_f64() * 1000.0);
+                event["output"] = json!(output);
+            }
+            TestResult::Flaky {
+                output, attempts, ..
+            } => {
+                event["event"] = json!("flaky");
+                event["attempts"] = json!(attempts);
+                event["output"] = json!(output);
+            }
+        }
+
+        self.emit(event)
+    }
+
+    fn run_finished(&mut self, duration: Duration) -> Result<()> {
+        self.emit(json!({
+            "event": "run_finished",
+            "passed": self.counts.passed,
+            "failed": self.counts.failed,
+            "timed_out": self.counts.timed_out,
+            "flaky": self.counts.flaky,
+            "skipped": self.counts.skipped,
+            "ignored": self.counts.ignored,
+            "duration_ms": duration.as_secs_f64() * 1000.0,
+        }))
+    }
+}
+
+#[derive(Debug, Default)]
+struct ResultCounts {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    ignored: usize,
+    timed_out: usize,
+    flaky: usize,
+}
+
+impl ResultCounts {
+    fn of<'a>(results: impl Iterator<Item = &'a TestResult>) -> Self {
+        let mut counts = Self::default();
+        for result in results {
+            counts.add(result);
+        }
+        counts
+    }
+
+    fn add(&mut self, result: &TestResult) {
+        match result {
+            TestResult::Passed { .. } => self.passed += 1,
+            TestResult::Failed { .. } => self.failed += 1,
+            TestResult::Skipped { .. } => self.skipped += 1,
+            TestResult::Ignored { .. } => self.ignored += 1,
+            TestResult::TimedOut { .. } => self.timed_out += 1,
+            TestResult::Flaky { .. } => self.flaky += 1,
+        }
+    }
+}
+
+fn timeout_message(timeout: Duration) -> String {
+    format!("Test timed out after {:?}", timeout)
+}
+
+fn ignored_reason(reason: &Option<String>) -> String {
+    match reason {
+        Some(reason) => format!("ignored: {}", reason),
+        None => "ignored".to_string(),
+    }
+}
+
+/// `file:line` of a failure, when the result knows where it happened
+fn failure_location(test: &TestCase, span: &Option<(usize, usize)>) -> Option<String> {
+    span.map(|(line, _)| format!("{}:{}", test.file_path.display(), line))
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newlines are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use ast::nodes::{Block, Function};
+
+    /// A writer the test can read back after handing it to a reporter
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    fn test_case(name: &str, line_number: usize) -> TestCase {
+        TestCase {
+            file_path: PathBuf::from("tests/math.ot"),
+            function_name: name.to_string(),
+            function: Function::new(name, Vec::new(), None, Block::new(Vec::new())),
+            line_number,
+            marker: None,
+            timeout: None,
+        }
+    }
+
+    fn run(format: ReportFormat) -> String {
+        let tests = [test_case("test_add", 1), test_case("test_div", 4)];
+        let results = [
+            TestResult::Passed {
+                duration: Duration::from_millis(2),
+                output: String::new(),
+            },
+            TestResult::Failed {
+                error: "expected '2', got '3' & <more>".to_string(),
+                duration: Duration::from_millis(1),
+                output: "dividing".to_string(),
+                span: Some((5, 5)),
+            },
+        ];
+        report(format, &tests, &results)
+    }
+
+    fn report(format: ReportFormat, tests: &[TestCase], results: &[TestResult]) -> String {
+        let buffer = Buffer::default();
+        let mut reporter = reporter_for(format, false, Box::new(buffer.clone()));
+        reporter.run_started(tests).unwrap();
+        for (test, result) in tests.iter().zip(results) {
+            reporter.test_started(test).unwrap();
+            reporter.test_finished(test, result).unwrap();
+        }
+        reporter.run_finished(Duration::from_millis(3)).unwrap();
+        buffer.text()
+    }
+
+    #[test]
+    fn junit_groups_tests_by_file_and_escapes_messages() {
+        let report = run(ReportFormat::Junit);
+
+        assert!(report.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+        assert!(report.contains(
+            "<testsuite name=\"tests/math.ot\" tests=\"2\" failures=\"1\" skipped=\"0\" time=\"0.003\">"
+        ));
+        assert!(report.contains(
+            "<testcase name=\"test_add\" classname=\"tests/math.ot\" file=\"tests/math.ot\" line=\"1\" time=\"0.002\"/>"
+        ));
+        assert!(report.contains(
+            "<failure message=\"expected &apos;2&apos;, got &apos;3&apos; &amp; &lt;more&gt;\">at tests/math.ot:5\n</failure>"
+        ));
+        assert!(report.contains("<system-out>dividing</system-out>"));
+        assert!(report.ends_with("</testsuites>\n"));
+    }
+
+    #[test]
+    fn tap_numbers_tests_and_describes_failures() {
+        let report = run(ReportFormat::Tap);
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(
+            &lines[..6],
+            &[
+                "TAP version 13",
+                "1..2",
+                "ok 1 - test_add",
+                "not ok 2 - test_div",
+                "  ---",
+                "  message: \"expected '2', got '3' & <more>\"",
+            ]
+        );
+        assert!(lines.contains(&"  at: \"tests/math.ot:5\""));
+        assert_eq!(lines.last(), Some(&"  ..."));
+    }
+
+    #[test]
+    fn json_emits_one_event_per_line() {
+        let report = run(ReportFormat::Json);
+        let events: Vec<serde_json::Value> = report
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let kinds: Vec<&str> = events
+            .iter()
+            .map(|event| event["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "run_started",
+                "started",
+                "passed",
+                "started",
+                "failed",
+                "run_finished"
+            ]
+        );
+        assert_eq!(
+            events[4]["location"],
+            json!({ "file": "tests/math.ot", "line": 5 })
+        );
+        assert_eq!(events[4]["output"], "dividing");
+        assert_eq!(events[5]["failed"], 1);
+    }
+
+    #[test]
+    fn reports_ignored_timed_out_and_flaky_tests() {
+        let tests = [
+            test_case("test_slow", 1),
+            test_case("test_hang", 4),
+            test_case("test_racy", 7),
+        ];
+        let results = [
+            TestResult::Ignored {
+                reason: Some("slow".to_string()),
+            },
+            TestResult::TimedOut {
+                timeout: Duration::from_millis(500),
+                output: "waiting".to_string(),
+            },
+            TestResult::Flaky {
+                duration: Duration::from_millis(1),
+                output: String::new(),
+                attempts: 2,
+            },
+        ];
+
+        let tap = report(ReportFormat::Tap, &tests, &results);
+        let lines: Vec<&str> = tap.lines().collect();
+        assert_eq!(lines[2], "ok 1 - test_slow # SKIP ignored: slow");
+        assert_eq!(lines[3], "not ok 2 - test_hang");
+        assert_eq!(lines[5], "  message: \"Test timed out after 500ms\"");
+        assert_eq!(lines[8], "ok 3 - test_racy # flaky, passed on attempt 2");
+
+        let junit = report(ReportFormat::Junit, &tests, &results);
+        assert!(junit.contains("tests=\"3\" failures=\"1\" skipped=\"1\""));
+        assert!(
+            junit.contains("<failure message=\"Test timed out after 500ms\" type=\"timeout\"/>")
+        );
+
+        let json = report(ReportFormat::Json, &tests, &results);
+        let events: Vec<serde_json::Value> = json
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events[2]["event"], "ignored");
+        assert_eq!(events[4]["event"], "timed_out");
+        assert_eq!(events[4]["timeout_ms"], 500.0);
+        assert_eq!(events[6]["event"], "flaky");
+        assert_eq!(events[6]["attempts"], 2);
+        assert_eq!(events[7]["timed_out"], 1);
+        assert_eq!(events[7]["flaky"], 1);
+    }
+
+    #[test]
+    fn reports_failures_at_the_failed_assertion() {
+        // `test_div` starts on line 4; its assertion fails on line 9
+        let tests = [test_case("test_div", 4)];
+        let results = [TestResult::Failed {
+            error: "Test failed with exit code 1".to_string(),
+            duration: Duration::from_millis(1),
+            output: "Assertion failed at tests/math.ot:9:5".to_string(),
+            span: Some((9, 9)),
+        }];
+
+        let tap = report(ReportFormat::Tap, &tests, &results);
+        assert!(tap.lines().any(|line| line == "  at: \"tests/math.ot:9\""));
+        assert!(!tap.contains("tests/math.ot:4"));
+
+        let junit = report(ReportFormat::Junit, &tests, &results);
+        assert!(junit.contains(">at tests/math.ot:9\n</failure>"));
+
+        let json = report(ReportFormat::Json, &tests, &results);
+        let failed: serde_json::Value = serde_json::from_str(json.lines().nth(2).unwrap()).unwrap();
+        assert_eq!(failed["event"], "failed");
+        assert_eq!(
+            failed["location"],
+            json!({ "file": "tests/math.ot", "line": 9 })
+        );
+    }
+}
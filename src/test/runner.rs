@@ -1,14 +1,50 @@
-use std::path::Path;
-use std::process::Command;
-use std::time::Instant;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 use anyhow::{Context, Result};
+use rayon::prelude::*;
 
 use crate::cli::CompilationSettings;
-use crate::test::{TestCase, TestResult};
+use crate::test::{TestCase, TestDiscovery, TestMarker, TestResult};
+
+/// Prefix of the lines the test harness prints around each test it runs
+/// (see `otter_test_harness` in the C runtime)
+const HARNESS_MARKER: &str = "\u{1e}otter-test ";
 
 pub struct TestRunner {
     settings: CompilationSettings,
     update_snapshots: bool,
+    /// Limit for tests without a `@timeout` of their own
+    timeout: Option<Duration>,
+    /// How many more times a failing test runs before it counts as failed
+    retries: usize,
+    fail_fast: bool,
+    /// Whether failed assertions color their diffs; `None` leaves it to the
+    /// harness, which colors when its output is a terminal
+    color: Option<bool>,
+    /// Set by `--fail-fast` once a test has failed; no further tests start
+    cancelled: AtomicBool,
+    /// Harness binary, or the compile error, of every test file seen so far
+    harnesses: Mutex<HashMap<PathBuf, Result<PathBuf, String>>>,
+    /// Directories of harnesses built without the cache, removed with the runner
+    scratch: Mutex<Vec<tempfile::TempDir>>,
+}
+
+/// One test as reported by a harness process
+struct HarnessRun {
+    name: String,
+    /// Exit code of the test, `None` when the harness has no such test
+    code: Option<i32>,
+    duration: Duration,
+    output: String,
+    /// Line of the assertion that failed, when the harness reported one
+    location: Option<usize>,
 }
 
 impl TestRunner {
@@ -16,80 +52,575 @@ impl TestRunner {
         Self {
             settings,
             update_snapshots,
+            timeout: None,
+            retries: 0,
+            fail_fast: false,
+            color: None,
+            cancelled: AtomicBool::new(false),
+            harnesses: Mutex::new(HashMap::new()),
+            scratch: Mutex::new(Vec::new()),
         }
     }
 
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Whether `--fail-fast` stopped the run
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
     pub fn run_test(&self, test: &TestCase) -> TestResult {
-        let start = Instant::now();
-        
-        let compile_result = self.compile_test_file(&test.file_path);
-        if let Err(e) = compile_result {
-            return TestResult::Failed {
-                error: format!("Compilation failed: {}", e),
-                duration: start.elapsed(),
-                output: String::new(),
-                span: Some((test.line_number, test.line_number)),
-            };
+        self.run_file(&test.file_path, std::slice::from_ref(test), 1)
+            .pop()
+            .map(|(_, result)| result)
+            .unwrap_or(TestResult::Skipped {
+                reason: "not run".to_string(),
+            })
+    }
+
+    /// Run tests from `file_path`, compiling its harness once and spreading
+    /// the tests over up to `workers` harness processes. Results come back in
+    /// the order of `tests`; `@ignore` and `@skip` tests are reported without
+    /// running.
+    pub fn run_file(
+        &self,
+        file_path: &Path,
+        tests: &[TestCase],
+        workers: usize,
+    ) -> Vec<(TestCase, TestResult)> {
+        let unmarked: Vec<TestCase> = tests
+            .iter()
+            .filter(|test| test.marker.is_none())
+            .cloned()
+            .collect();
+        let mut finished = self.run_unmarked(file_path, &unmarked, workers).into_iter();
+
+        tests
+            .iter()
+            .map(|test| match &test.marker {
+                Some(TestMarker::Ignore(reason)) => (
+                    test.clone(),
+                    TestResult::Ignored {
+                        reason: reason.clone(),
+                    },
+                ),
+                Some(TestMarker::Skip(reason)) => (
+                    test.clone(),
+                    TestResult::Skipped {
+                        reason: reason.clone(),
+                    },
+                ),
+                None => finished.next().expect("a result for every unmarked test"),
+            })
+            .collect()
+    }
+
+    fn run_unmarked(
+        &self,
+        file_path: &Path,
+        tests: &[TestCase],
+        workers: usize,
+    ) -> Vec<(TestCase, TestResult)> {
+        if tests.is_empty() || self.cancelled() {
+            return not_run(tests);
         }
 
-        let binary_path = compile_result.unwrap();
+        let start = Instant::now();
+        let binary_path = match self.harness(file_path) {
+            Ok(binary_path) => binary_path,
+            Err(e) => {
+                if self.fail_fast {
+                    self.cancelled.store(true, Ordering::SeqCst);
+                }
+                let duration = start.elapsed();
+                return tests
+                    .iter()
+                    .map(|test| {
+                        let result = TestResult::Failed {
+                            error: format!("Compilation failed: {}", e),
+                            duration,
+                            output: String::new(),
+                            span: Some((test.line_number, test.line_number)),
+                        };
+                        (test.clone(), result)
+                    })
+                    .collect();
+            }
+        };
+
+        let mut results = if workers <= 1 || tests.len() <= 1 {
+            self.run_batch(&binary_path, tests)
+        } else {
+            let batch_size = tests.len().div_ceil(workers);
+            let batches: Vec<_> = tests
+                .par_chunks(batch_size)
+                .map(|batch| self.run_batch(&binary_path, batch))
+                .collect();
+            batches.into_iter().flatten().collect()
+        };
 
-        let mut command = Command::new(&binary_path);
-        command.env("OTTER_TEST_MODE", "1");
-        command.env("OTTER_TEST_NAME", &test.function_name);
-        if self.update_snapshots {
-            command.env("OTTER_UPDATE_SNAPSHOTS", "1");
+        if self.retries > 0 {
+            self.retry_failures(&binary_path, &mut results);
+        }
+        results
+    }
+
+    /// Run each failed test again, on its own, up to `retries` times; a test
+    /// that passes on a retry is flaky, and keeps its first failure otherwise
+    fn retry_failures(&self, binary_path: &Path, results: &mut [(TestCase, TestResult)]) {
+        for (test, result) in results.iter_mut() {
+            if !result.is_failure() {
+                continue;
+            }
+            for attempt in 2..=self.retries + 1 {
+                if self.cancelled() {
+                    break;
+                }
+                if let Some((_, TestResult::Passed { duration, output })) = self
+                    .run_batch(binary_path, std::slice::from_ref(test))
+                    .pop()
+                {
+                    *result = TestResult::Flaky {
+                        duration,
+                        output,
+                        attempts: attempt,
+                    };
+                    break;
+                }
+            }
+            if result.is_failure() && self.fail_fast {
+                self.cancelled.store(true, Ordering::SeqCst);
+            }
         }
+    }
 
-        let output = command.output();
-        let duration = start.elapsed();
+    /// Run `tests` in one harness process, starting another for the rest
+    /// whenever a test takes the whole process down or runs out of time
+    fn run_batch(&self, binary_path: &Path, tests: &[TestCase]) -> Vec<(TestCase, TestResult)> {
+        let mut results = Vec::with_capacity(tests.len());
+        let mut pending = tests;
 
-        match output {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let combined_output = if stderr.is_empty() {
-                    stdout
-                } else {
-                    format!("{}\n{}", stdout, stderr)
+        while !pending.is_empty() {
+            if self.cancelled() {
+                results.extend(not_run(pending));
+                break;
+            }
+
+            let start = Instant::now();
+            let mut command = Command::new(binary_path);
+            command.args(pending.iter().map(|test| &test.function_name));
+            command.env("OTTER_TEST_MODE", "1");
+            if self.update_snapshots {
+                command.env("OTTER_UPDATE_SNAPSHOTS", "1");
+            }
+            // Harness output is piped, so it cannot tell whether it ends up
+            // on a terminal
+            match self.color {
+                Some(true) => command.env("CLICOLOR_FORCE", "1").env_remove("NO_COLOR"),
+                Some(false) => command.env("NO_COLOR", "1").env_remove("CLICOLOR_FORCE"),
+                None => &mut command,
+            };
+            command
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            // Tests run in forks of the harness; a group lets a timeout kill them all
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::CommandExt;
+                command.process_group(0);
+            }
+
+            let mut child = match command.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let duration = start.elapsed();
+                    for test in pending {
+                        let result = TestResult::Failed {
+                            error: format!("Failed to execute test: {}", e),
+                            duration,
+                            output: String::new(),
+                            span: Some((test.line_number, test.line_number)),
+                        };
+                        results.push((test.clone(), result));
+                    }
+                    break;
+                }
+            };
+
+            let lines = stream_lines(child.stdout.take());
+            let mut stderr = child.stderr.take();
+            let stderr = thread::spawn(move || {
+                let mut text = String::new();
+                if let Some(stderr) = stderr.as_mut() {
+                    let _ = stderr.read_to_string(&mut text);
+                }
+                text
+            });
+
+            let mut harness = HarnessOutput::default();
+            let mut test_start = start;
+            let mut timed_out = None;
+            while let Some(test) = pending.first() {
+                let line = match test.timeout.or(self.timeout) {
+                    Some(limit) => {
+                        match lines.recv_timeout(limit.saturating_sub(test_start.elapsed())) {
+                            Ok(line) => line,
+                            Err(RecvTimeoutError::Timeout) => {
+                                timed_out = Some(limit);
+                                break;
+                            }
+                            Err(RecvTimeoutError::Disconnected) => break,
+                        }
+                    }
+                    None => match lines.recv() {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    },
                 };
 
-                if output.status.success() {
-                    TestResult::Passed {
-                        duration,
-                        output: combined_output,
+                match harness.feed(&line) {
+                    Some(HarnessEvent::Started) => test_start = Instant::now(),
+                    Some(HarnessEvent::Finished(run)) => {
+                        let result = harness_result(test, run);
+                        if result.is_failure() && self.fail_fast && self.retries == 0 {
+                            self.cancelled.store(true, Ordering::SeqCst);
+                        }
+                        results.push((test.clone(), result));
+                        pending = &pending[1..];
+                        test_start = Instant::now();
+                        if self.cancelled() {
+                            break;
+                        }
                     }
+                    None => {}
+                }
+            }
+
+            if let Some(timeout) = timed_out {
+                kill(&mut child);
+                if self.fail_fast && self.retries == 0 {
+                    self.cancelled.store(true, Ordering::SeqCst);
+                }
+                let result = TestResult::TimedOut {
+                    timeout,
+                    output: harness.unfinished().unwrap_or_default(),
+                };
+                results.push((pending[0].clone(), result));
+                pending = &pending[1..];
+                continue;
+            }
+            if self.cancelled() {
+                kill(&mut child);
+                continue;
+            }
+
+            let status = child.wait();
+            let stderr = stderr.join().unwrap_or_default();
+
+            // The harness exited in the middle of (or before) the next test
+            if let Some(test) = pending.first() {
+                let stderr = stderr.trim_end_matches('\n').to_string();
+                let partial = harness.unfinished().unwrap_or_default();
+                let combined_output = if stderr.is_empty() {
+                    partial
+                } else if partial.is_empty() {
+                    stderr
                 } else {
-                    TestResult::Failed {
-                        error: format!("Test failed with exit code {}", output.status.code().unwrap_or(-1)),
-                        duration,
-                        output: combined_output,
-                        span: Some((test.line_number, test.line_number)),
-                    }
+                    format!("{}\n{}", partial, stderr)
+                };
+                let code = status.ok().and_then(|status| status.code()).unwrap_or(-1);
+                // A failed assertion ends the harness where tests cannot fork
+                let line = harness.location.take().unwrap_or(test.line_number);
+                let result = TestResult::Failed {
+                    error: format!("Test failed with exit code {}", code),
+                    duration: test_start.elapsed(),
+                    output: combined_output,
+                    span: Some((line, line)),
+                };
+                if self.fail_fast && self.retries == 0 {
+                    self.cancelled.store(true, Ordering::SeqCst);
                 }
+                results.push((test.clone(), result));
+                pending = &pending[1..];
             }
-            Err(e) => TestResult::Failed {
-                error: format!("Failed to execute test: {}", e),
-                duration,
-                output: String::new(),
-                span: Some((test.line_number, test.line_number)),
-            },
         }
+
+        results
+    }
+
+    /// The harness binary for `file_path`, compiled on first use
+    fn harness(&self, file_path: &Path) -> Result<PathBuf, String> {
+        if let Some(harness) = self.harnesses.lock().unwrap().get(file_path) {
+            return harness.clone();
+        }
+
+        let harness = self
+            .compile_harness(file_path)
+            .map_err(|e| format!("{:#}", e));
+        self.harnesses
+            .lock()
+            .unwrap()
+            .insert(file_path.to_path_buf(), harness.clone());
+        harness
     }
 
-    fn compile_test_file(&self, file_path: &Path) -> Result<std::path::PathBuf> {
+    /// Compile `file_path` with a `main` that dispatches to every test in it,
+    /// so the (cached) binary can run any subset of them
+    fn compile_harness(&self, file_path: &Path) -> Result<PathBuf> {
         use crate::cli::{compile_pipeline, read_source};
-        
+
+        let harness = TestDiscovery::new().discover_harness(file_path)?;
+        let settings = self.settings.clone().with_test_harness(harness);
+
         let source = read_source(file_path)?;
-        let stage = compile_pipeline(file_path, &source, &self.settings)
+        let stage = compile_pipeline(file_path, &source, &settings)
             .with_context(|| format!("failed to compile test file {}", file_path.display()))?;
 
         let binary_path = match &stage.result {
             crate::cli::CompilationResult::CacheHit(entry) => entry.binary_path.clone(),
             crate::cli::CompilationResult::Compiled { artifact, .. } => artifact.binary.clone(),
         };
+        // The harness runs until the runner is done with this file
+        if let Some(scratch) = stage.scratch {
+            self.scratch.lock().unwrap().push(scratch);
+        }
 
         Ok(binary_path)
     }
 }
 
+fn harness_result(test: &TestCase, run: HarnessRun) -> TestResult {
+    match run.code {
+        Some(0) => TestResult::Passed {
+            duration: run.duration,
+            output: run.output,
+        },
+        Some(code) => {
+            // A crash has no failed assertion to point at
+            let line = run.location.unwrap_or(test.line_number);
+            TestResult::Failed {
+                error: format!("Test failed with exit code {}", code),
+                duration: run.duration,
+                output: run.output,
+                span: Some((line, line)),
+            }
+        }
+        None => TestResult::Failed {
+            error: format!("Test {} not found in the compiled harness", run.name),
+            duration: run.duration,
+            output: run.output,
+            span: Some((test.line_number, test.line_number)),
+        },
+    }
+}
+
+fn not_run(tests: &[TestCase]) -> Vec<(TestCase, TestResult)> {
+    tests
+        .iter()
+        .map(|test| {
+            let result = TestResult::Skipped {
+                reason: "not run: --fail-fast stopped the run".to_string(),
+            };
+            (test.clone(), result)
+        })
+        .collect()
+}
+
+/// Send the lines of `output` over a channel, so the reader can give up
+/// waiting for them
+fn stream_lines(output: Option<impl Read + Send + 'static>) -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    if let Some(output) = output {
+        thread::spawn(move || {
+            let mut reader = BufReader::new(output);
+            let mut line = Vec::new();
+            while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                let text = String::from_utf8_lossy(&line);
+                if sender
+                    .send(text.trim_end_matches('\n').to_string())
+                    .is_err()
+                {
+                    break;
+                }
+                line.clear();
+            }
+        });
+    }
+    receiver
+}
+
+/// Kill a harness along with the test processes it forked
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        // The harness leads its own process group (see `run_batch`)
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+enum HarnessEvent {
+    Started,
+    Finished(HarnessRun),
+}
+
+/// Follows harness output line by line, collecting the output of the test
+/// that is running
+#[derive(Default)]
+struct HarnessOutput {
+    current: Option<String>,
+    /// Line of the running test's failed assertion
+    location: Option<usize>,
+}
+
+impl HarnessOutput {
+    fn feed(&mut self, line: &str) -> Option<HarnessEvent> {
+        let Some(position) = line.find(HARNESS_MARKER) else {
+            if let Some(output) = self.current.as_mut() {
+                output.push_str(line);
+                output.push('\n');
+            }
+            return None;
+        };
+
+        // Output that did not end with a newline runs into the marker
+        let (text, marker) = line.split_at(position);
+        if let Some(output) = self.current.as_mut() {
+            if !text.is_empty() {
+                output.push_str(text);
+                output.push('\n');
+            }
+        }
+
+        let mut fields = marker[HARNESS_MARKER.len()..].split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some("start"), Some(_)) => {
+                self.current = Some(String::new());
+                self.location = None;
+                Some(HarnessEvent::Started)
+            }
+            (Some("location"), Some(line)) => {
+                self.location = line.parse().ok();
+                None
+            }
+            (Some("end"), Some(name)) => {
+                let code = fields.next().and_then(|code| code.parse().ok());
+                let micros = fields.next().and_then(|micros| micros.parse().ok());
+                let output = self.current.take().unwrap_or_default();
+                Some(HarnessEvent::Finished(HarnessRun {
+                    name: name.to_string(),
+                    code: Some(code.unwrap_or(-1)),
+                    duration: Duration::from_micros(micros.unwrap_or(0)),
+                    output: output.trim_end_matches('\n').to_string(),
+                    location: self.location.take(),
+                }))
+            }
+            (Some("missing"), Some(name)) => Some(HarnessEvent::Finished(HarnessRun {
+                name: name.to_string(),
+                code: None,
+                duration: Duration::ZERO,
+                output: String::new(),
+                location: None,
+            })),
+            _ => None,
+        }
+    }
+
+    /// Output of a test that started but never finished
+    fn unfinished(&mut self) -> Option<String> {
+        self.current
+            .take()
+            .map(|output| output.trim_end_matches('\n').to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_harness_output_per_test() {
+        let stdout = "\u{1e}otter-test start test_a\n\
+                      hello\n\
+                      partial\u{1e}otter-test end test_a 0 1500\n\
+                      \u{1e}otter-test start test_b\n\
+                      \u{1e}otter-test location 7 5\n\
+                      Assertion failed at tests/math.ot:7:5\n\
+                      \u{1e}otter-test end test_b 1 20\n\
+                      \u{1e}otter-test missing test_c\n\
+                      \u{1e}otter-test start test_d\n\
+                      crashing\n";
+
+        let mut harness = HarnessOutput::default();
+        let runs: Vec<HarnessRun> = stdout
+            .lines()
+            .filter_map(|line| match harness.feed(line) {
+                Some(HarnessEvent::Finished(run)) => Some(run),
+                _ => None,
+            })
+            .collect();
+        let unfinished = harness.unfinished();
+
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].name, "test_a");
+        assert_eq!(runs[0].code, Some(0));
+        assert_eq!(runs[0].duration, Duration::from_micros(1500));
+        assert_eq!(runs[0].output, "hello\npartial");
+        assert_eq!(runs[0].location, None);
+        assert_eq!(runs[1].code, Some(1));
+        assert_eq!(runs[1].output, "Assertion failed at tests/math.ot:7:5");
+        assert_eq!(runs[1].location, Some(7));
+        assert_eq!(runs[2].name, "test_c");
+        assert_eq!(runs[2].code, None);
+        assert_eq!(unfinished.as_deref(), Some("crashing"));
+    }
+
+    #[test]
+    fn fails_at_the_assertion_or_the_test_definition() {
+        use ast::nodes::{Block, Function};
+
+        let test = TestCase {
+            file_path: PathBuf::from("tests/math.ot"),
+            function_name: "test_div".to_string(),
+            function: Function::new("test_div", Vec::new(), None, Block::new(Vec::new())),
+            line_number: 4,
+            marker: None,
+            timeout: None,
+        };
+        let run = |location| HarnessRun {
+            name: "test_div".to_string(),
+            code: Some(1),
+            duration: Duration::ZERO,
+            output: String::new(),
+            location,
+        };
+
+        let span = |result| match result {
+            TestResult::Failed { span, .. } => span,
+            _ => panic!("expected a failure"),
+        };
+        assert_eq!(span(harness_result(&test, run(Some(9)))), Some((9, 9)));
+        // A crash reports no assertion
+        assert_eq!(span(harness_result(&test, run(None))), Some((4, 4)));
+    }
+}
@@ -0,0 +1,262 @@
+//! Build cache management for OtterLang
+//!
+//! Backs the `otter cache` subcommand: listing, pruning and size-capping
+//! the compiled binaries stored by the `cache` crate.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use colored::Colorize;
+
+use cache::{CacheEntry, CacheManager};
+
+/// Cache command for CLI integration
+#[derive(Clone, Debug, clap::Subcommand)]
+pub enum CacheCommand {
+    /// List cached builds, most recently used first
+    List,
+    /// Remove cached builds (all of them unless --older-than is given)
+    Clean {
+        /// Only remove builds not used within this age (e.g. 30m, 12h, 7d, 2w)
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: Option<Duration>,
+    },
+    /// Evict least recently used builds until the cache fits in the given size
+    Gc {
+        /// Maximum total size of cached binaries (e.g. 500M, 2G)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: u64,
+    },
+    /// Show cache location, size and hit statistics
+    Stats,
+}
+
+pub fn run_cache_subcommand(command: &CacheCommand) -> Result<()> {
+    let manager = CacheManager::new();
+    match command {
+        CacheCommand::List => list_entries(&manager),
+        CacheCommand::Clean { older_than } => clean(&manager, *older_than),
+        CacheCommand::Gc { max_size } => gc(&manager, *max_size),
+        CacheCommand::Stats => show_stats(&manager),
+    }
+}
+
+fn list_entries(manager: &CacheManager) -> Result<()> {
+    let entries = manager.entries();
+    if entries.is_empty() {
+        println!("Cache is empty ({})", manager.root().display());
+        return Ok(());
+    }
+
+    let now = unix_now();
+    println!(
+        "{:<12} {:>10} {:>10} {:>10}  {}",
+        "Key".bold(),
+        "Size".bold(),
+        "Age".bold(),
+        "Last hit".bold(),
+        "Source".bold()
+    );
+    for entry in &entries {
+        let metadata = &entry.metadata;
+        let last_hit = metadata
+            .last_hit
+            .map(|hit| format_age(now.saturating_sub(hit)))
+            .unwrap_or_else(|| "never".to_string());
+        let source = if metadata.is_valid() {
+            metadata.source_path.display().to_string()
+        } else {
+            format!("{} {}", metadata.source_path.display(), "(stale)".yellow())
+        };
+        println!(
+            "{:<12} {:>10} {:>10} {:>10}  {}",
+            short_key(&metadata.key),
+            format_size(entry.size),
+            format_age(now.saturating_sub(metadata.created_at)),
+            last_hit,
+            source
+        );
+    }
+    Ok(())
+}
+
+fn clean(manager: &CacheManager, older_than: Option<Duration>) -> Result<()> {
+    let removed = match older_than {
+        Some(age) => {
+            let cutoff = unix_now().saturating_sub(age.as_secs());
+            manager
+                .prune_unused_since(cutoff)
+                .map_err(|e| anyhow!("failed to prune cache: {e}"))?
+        }
+        None => {
+            let entries = manager.entries();
+            let mut manager = CacheManager::with_root(manager.root().to_path_buf());
+            manager
+                .clear()
+                .map_err(|e| anyhow!("failed to clear cache: {e}"))?;
+            entries
+        }
+    };
+
+    report_removed("Removed", &removed);
+    Ok(())
+}
+
+fn gc(manager: &CacheManager, max_size: u64) -> Result<()> {
+    let removed = manager
+        .evict_to_size(max_size)
+        .map_err(|e| anyhow!("failed to collect cache: {e}"))?;
+    report_removed("Evicted", &removed);
+    println!(
+        "Cache size: {} (limit {})",
+        format_size(manager.stats().total_size),
+        format_size(max_size)
+    );
+    Ok(())
+}
+
+fn show_stats(manager: &CacheManager) -> Result<()> {
+    let stats = manager.stats();
+    let now = unix_now();
+
+    println!("Cache directory: {}", manager.root().display());
+    println!("  Entries:    {}", stats.entries);
+    if stats.stale_entries > 0 {
+        println!("  Stale:      {}", stats.stale_entries.to_string().yellow());
+    }
+    println!("  Total size: {}", format_size(stats.total_size));
+    println!("  Total hits: {}", stats.total_hits);
+    if let Some(oldest) = stats.oldest_entry {
+        println!(
+            "  Oldest:     {} ago",
+            format_age(now.saturating_sub(oldest))
+        );
+    }
+    if let Some(newest) = stats.newest_entry {
+        println!(
+            "  Newest:     {} ago",
+            format_age(now.saturating_sub(newest))
+        );
+    }
+    Ok(())
+}
+
+fn report_removed(verb: &str, removed: &[CacheEntry]) {
+    let freed: u64 = removed.iter().map(|entry| entry.size).sum();
+    if removed.is_empty() {
+        println!("Nothing to remove");
+    } else {
+        println!(
+            "{} {} cached build(s), freed {}",
+            verb,
+            removed.len(),
+            format_size(freed)
+        );
+    }
+}
+
+/// Parse an age like `90s`, `30m`, `12h`, `7d` or `2w`. A bare number means days.
+pub fn parse_age(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let amount: u64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid age `{value}`"))?;
+    let seconds = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        other => bail!("unknown age unit `{other}` (use s, m, h, d or w)"),
+    };
+    let seconds = amount
+        .checked_mul(seconds)
+        .ok_or_else(|| anyhow!("age `{value}` is too large"))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parse a size like `512K`, `500M`, `2G` or `2GiB`. A bare number means bytes.
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let amount: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid size `{value}`"))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier: u64 = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => bail!("unknown size unit `{unit}` (use K, M, G or T)"),
+    };
+    Ok((amount * multiplier as f64) as u64)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{s}s"),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
+
+fn short_key(key: &str) -> &str {
+    &key[..key.len().min(12)]
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ages() {
+        assert_eq!(parse_age("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_age("2h").unwrap(), Duration::from_secs(2 * 60 * 60));
+        assert_eq!(
+            parse_age("7").unwrap(),
+            Duration::from_secs(7 * 24 * 60 * 60)
+        );
+        assert_eq!(
+            parse_age("1W").unwrap(),
+            Duration::from_secs(7 * 24 * 60 * 60)
+        );
+        assert!(parse_age("3y").is_err());
+        assert!(parse_age("soon").is_err());
+        assert!(parse_age("99999999999999999w")
+            .unwrap_err()
+            .to_string()
+            .contains("too large"));
+    }
+}
@@ -1,7 +1,8 @@
 //! Developer tools for OtterLang
 //!
-//! Includes profiler tools
+//! Includes profiler and build cache tools
 
+pub mod cache;
 pub mod profiler;
 
 // LSP server requires tower-lsp dependency (optional feature)
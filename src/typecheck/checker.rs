@@ -1,11 +1,25 @@
 use anyhow::{bail, Result};
 use std::collections::HashMap;
 
+use crate::codegen::const_fold::{ConstEnv, Constant};
+use crate::runtime::stdlib::{num, resources};
 use crate::runtime::symbol_registry::{FfiType, SymbolRegistry};
-use crate::typecheck::types::{EnumDefinition, TypeContext, TypeError, TypeInfo};
-use ast::nodes::{Block, Expr, Function, Literal, Program, Statement};
+use crate::typecheck::exhaustiveness;
+use crate::typecheck::types::{EnumDefinition, TraitDefinition, TypeContext, TypeError, TypeInfo};
+use ast::nodes::{
+    Block, Expr, Function, GenericParam, Literal, NumberLiteral, NumericType, Program, Statement,
+    Type,
+};
+use common::Span;
 use language::LanguageFeatureFlags;
 
+const ITERABLE_HINT: &str =
+    "Iterate over a list, dict, str, range, generator, or a struct with `__iter__` or `__next__`";
+
+const CONST_HINT: &str = "Constants may only use literals, other constants, operators, f-strings and calls to `const def` functions";
+
+const MANAGER_HINT: &str = "Use a struct with an `__exit__` method, or a resource such as `io.open(...)`, `net.dial(...)` or `sync.lock(...)`";
+
 /// Type checker that validates and infers types in OtterLang programs
 pub struct TypeChecker {
     errors: Vec<TypeError>,
@@ -15,6 +29,8 @@ pub struct TypeChecker {
     features: LanguageFeatureFlags,
     /// Current function's return type (if inside a function)
     current_function_return_type: Option<TypeInfo>,
+    /// Element type of the generator being checked (if inside one)
+    current_yield_type: Option<TypeInfo>,
 }
 
 impl TypeChecker {
@@ -61,6 +77,7 @@ impl TypeChecker {
             expr_types: HashMap::new(),
             features,
             current_function_return_type: None,
+            current_yield_type: None,
         }
     }
 
@@ -144,6 +161,22 @@ impl TypeChecker {
             );
         }
 
+        // Wrapping and saturating arithmetic, callable as methods such as
+        // `x.wrapping_add(y)`; the runtime exports them from the `num` module
+        for ty in NumericType::ALL.into_iter().filter(|ty| !ty.is_float()) {
+            let info = TypeInfo::from_numeric(ty);
+            for method in num::INTEGER_METHODS {
+                context.functions.insert(
+                    format!("{}.{}", ty.name(), method),
+                    TypeInfo::Function {
+                        params: vec![info.clone(), info.clone()],
+                        param_defaults: vec![false, false],
+                        return_type: Box::new(info.clone()),
+                    },
+                );
+            }
+        }
+
         // min/max with two parameters
         for func_name in &["min", "max"] {
             context.functions.insert(
@@ -167,6 +200,34 @@ impl TypeChecker {
             if let Statement::Function(function) = statement {
                 let sig = self.infer_function_signature(function);
                 self.context.functions.insert(function.name.clone(), sig);
+                if !function.generics.is_empty() {
+                    self.context
+                        .generic_functions
+                        .insert(function.name.clone(), function.generics.clone());
+                }
+            }
+        }
+
+        // Constants are evaluated in declaration order, before the function
+        // bodies that read them
+        let mut const_env = ConstEnv::new();
+        for statement in &program.statements {
+            if let Statement::Function(function) = statement {
+                if function.is_const {
+                    const_env.define_function(function);
+                }
+            }
+        }
+        for statement in &program.statements {
+            if let Statement::Const {
+                name,
+                ty,
+                expr,
+                span,
+                ..
+            } = statement
+            {
+                self.check_const(name, ty, expr, *span, &mut const_env)?;
             }
         }
 
@@ -176,13 +237,36 @@ impl TypeChecker {
                 Statement::Function(function) => {
                     self.check_function(function)?;
                 }
-                Statement::Let { .. } | Statement::Expr(_) => {
+                Statement::Let { .. } | Statement::Destructure { .. } | Statement::Expr(_) => {
                     // Top-level let and expressions are allowed
                     self.check_statement(statement)?;
                 }
+                Statement::Trait { name, methods, .. } => {
+                    // Default methods may call any method of the trait on `self`
+                    self.context
+                        .generic_bounds
+                        .insert("Self".to_string(), vec![name.clone()]);
+                    for method in methods.iter().filter(|method| !method.is_signature()) {
+                        self.check_function(method)?;
+                    }
+                    self.context.generic_bounds.remove("Self");
+                }
+                Statement::Impl {
+                    type_name, methods, ..
+                } => {
+                    let target = self
+                        .context
+                        .type_from_annotation(&Type::Simple(type_name.clone()));
+                    self.with_self_type(target, |checker| {
+                        methods
+                            .iter()
+                            .try_for_each(|method| checker.check_function(method))
+                    })?;
+                }
                 Statement::Struct { .. }
                 | Statement::Enum { .. }
                 | Statement::TypeAlias { .. }
+                | Statement::Const { .. }
                 | Statement::Use { .. }
                 | Statement::PubUse { .. } => {}
                 _ => {
@@ -202,6 +286,75 @@ impl TypeChecker {
         Ok(())
     }
 
+    /// Type a module-level constant and evaluate its initializer, which must
+    /// be known at compile time and fit the declared type
+    fn check_const(
+        &mut self,
+        name: &str,
+        ty: &Type,
+        expr: &Expr,
+        span: Option<Span>,
+        env: &mut ConstEnv,
+    ) -> Result<()> {
+        if self.context.constants.contains_key(name) {
+            self.errors.push(
+                TypeError::new(format!("constant `{}` is already defined", name))
+                    .with_optional_span(span),
+            );
+        }
+
+        let declared = self.context.type_from_annotation(ty);
+        let expr_type = self.infer_expr_type(expr)?;
+        match env.evaluate(expr) {
+            Ok(value) => {
+                // Whole numbers are checked by value, so `const N: int = 64`
+                // holds even though number literals are floats
+                if !constant_fits(&value, &declared) {
+                    self.errors.push(
+                        TypeError::new(format!(
+                            "cannot initialize constant {} of type {} with {}",
+                            name,
+                            declared.display_name(),
+                            expr_type.display_name()
+                        ))
+                        .with_hint(format!(
+                            "The initializer evaluates to {}",
+                            constant_display(&value)
+                        ))
+                        .with_optional_span(span),
+                    );
+                }
+                env.define(name, value);
+            }
+            Err(reason) => {
+                self.errors.push(
+                    TypeError::new(format!(
+                        "constant `{}` is not initialized with a constant expression: {}",
+                        name, reason
+                    ))
+                    .with_hint(CONST_HINT.to_string())
+                    .with_optional_span(span),
+                );
+            }
+        }
+
+        self.context.define_constant(name.to_string(), declared);
+        Ok(())
+    }
+
+    fn check_not_constant(&mut self, name: &str, span: Option<Span>) {
+        if self.context.is_constant(name) {
+            self.errors.push(
+                TypeError::new(format!("cannot assign to constant `{}`", name))
+                    .with_hint(
+                        "Constants are fixed at compile time; use a `let` variable for values that change"
+                            .to_string(),
+                    )
+                    .with_optional_span(span),
+            );
+        }
+    }
+
     /// Infer function signature from declaration
     fn infer_function_signature(&mut self, function: &Function) -> TypeInfo {
         let mut param_types = Vec::new();
@@ -270,6 +423,8 @@ impl TypeChecker {
 
         let return_type = if let Some(ty) = &function.ret_ty {
             self.context.type_from_annotation(ty)
+        } else if function.is_generator() {
+            iterator_of(TypeInfo::Unknown)
         } else {
             TypeInfo::Unknown
         };
@@ -339,9 +494,257 @@ impl TypeChecker {
                     };
                     self.context.define_enum(definition);
                 }
+                Statement::Trait { name, methods, .. } => {
+                    self.context.define_trait(TraitDefinition {
+                        name: name.clone(),
+                        methods: methods.clone(),
+                    });
+                }
                 _ => {}
             }
         }
+
+        // Impls are checked once every trait and type is known
+        for statement in statements {
+            if let Statement::Impl {
+                trait_name,
+                type_name,
+                methods,
+            } = statement
+            {
+                self.register_impl(trait_name, type_name, methods);
+            }
+        }
+    }
+
+    /// Check an `impl Trait for Type` block against the trait and register its
+    /// methods (and the trait's defaults it doesn't override) as `Type.method`
+    fn register_impl(&mut self, trait_name: &str, type_name: &str, methods: &[Function]) {
+        let Some(definition) = self.context.get_trait(trait_name).cloned() else {
+            self.errors.push(
+                TypeError::new(format!("unknown trait `{}`", trait_name)).with_hint(format!(
+                    "Declare it with `trait {}:` before implementing it",
+                    trait_name
+                )),
+            );
+            return;
+        };
+
+        let target = self
+            .context
+            .type_from_annotation(&Type::Simple(type_name.to_string()));
+        if self
+            .context
+            .trait_impls
+            .get(trait_name)
+            .is_some_and(|types| types.contains(&target))
+        {
+            self.errors.push(TypeError::new(format!(
+                "trait `{}` is already implemented for `{}`",
+                trait_name, type_name
+            )));
+            return;
+        }
+
+        let key = target.impl_key();
+        self.with_self_type(target.clone(), |checker| {
+            for method in methods {
+                let signature = checker.infer_function_signature(method);
+                match definition.methods.iter().find(|m| m.name == method.name) {
+                    Some(declared) => {
+                        let actual = without_self(signature.clone());
+                        let expected = without_self(checker.infer_function_signature(declared));
+                        if !actual.is_compatible_with(&expected) {
+                            checker.errors.push(
+                                TypeError::new(format!(
+                                    "method `{}` in `impl {} for {}` has type {}, but the trait declares {}",
+                                    method.name,
+                                    trait_name,
+                                    type_name,
+                                    actual.display_name(),
+                                    expected.display_name()
+                                ))
+                                .with_hint(format!(
+                                    "Match the signature declared in trait `{}`",
+                                    trait_name
+                                )),
+                            );
+                        }
+                    }
+                    None => {
+                        checker.errors.push(
+                            TypeError::new(format!(
+                                "method `{}` is not a member of trait `{}`",
+                                method.name, trait_name
+                            ))
+                            .with_hint(format!(
+                                "Move it into a separate `impl` or add it to `trait {}`",
+                                trait_name
+                            )),
+                        );
+                    }
+                }
+                checker
+                    .context
+                    .insert_function(format!("{}.{}", key, method.name), signature);
+            }
+
+            for declared in &definition.methods {
+                if methods.iter().any(|method| method.name == declared.name) {
+                    continue;
+                }
+                if declared.is_signature() {
+                    checker.errors.push(
+                        TypeError::new(format!(
+                            "`impl {} for {}` is missing method `{}`",
+                            trait_name, type_name, declared.name
+                        ))
+                        .with_hint(format!(
+                            "Every method of trait `{}` without a default body must be implemented",
+                            trait_name
+                        )),
+                    );
+                    continue;
+                }
+                let signature = checker.infer_function_signature(declared);
+                checker
+                    .context
+                    .insert_function(format!("{}.{}", key, declared.name), signature);
+            }
+        });
+
+        self.context.add_trait_impl(trait_name.to_string(), target);
+    }
+
+    /// Run `f` with `Self` resolving to `ty`
+    fn with_self_type<R>(&mut self, ty: TypeInfo, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = self.context.type_aliases.insert("Self".to_string(), ty);
+        let result = f(self);
+        match previous {
+            Some(previous) => self
+                .context
+                .type_aliases
+                .insert("Self".to_string(), previous),
+            None => self.context.type_aliases.remove("Self"),
+        };
+        result
+    }
+
+    /// The type of `receiver.name(...)` as seen by the caller: the method's
+    /// signature without its `self` parameter
+    fn method_signature(&mut self, receiver: &TypeInfo, name: &str) -> Option<TypeInfo> {
+        let key = format!("{}.{}", receiver.impl_key(), name);
+        let signature = match self.context.get_function(&key) {
+            Some(signature) => signature.clone(),
+            None => {
+                // A generic parameter only has the methods of its bounds
+                let TypeInfo::Generic { base, args } = receiver else {
+                    return None;
+                };
+                if !args.is_empty() {
+                    return None;
+                }
+                let method = self
+                    .context
+                    .generic_bounds
+                    .get(base)?
+                    .iter()
+                    .filter_map(|bound| self.context.get_trait(bound))
+                    .find_map(|definition| definition.methods.iter().find(|m| m.name == name))?
+                    .clone();
+                self.with_self_type(receiver.clone(), |checker| {
+                    checker.infer_function_signature(&method)
+                })
+            }
+        };
+
+        Some(without_self(signature))
+    }
+
+    /// The type of the elements a `for` loop or comprehension draws from a
+    /// value of type `iterable`, if it can be iterated at all. Structs are
+    /// iterable through an `__iter__` method returning something iterable or
+    /// a `__next__` method returning `Option<T>`
+    fn element_type(&mut self, iterable: &TypeInfo) -> Option<TypeInfo> {
+        match iterable {
+            TypeInfo::List(elem) => Some(elem.as_ref().clone()),
+            TypeInfo::Dict { value, .. } => Some(value.as_ref().clone()),
+            TypeInfo::Str => Some(TypeInfo::Str),
+            // Ranges and values of not-yet-inferred type
+            TypeInfo::Unknown => Some(TypeInfo::Unknown),
+            TypeInfo::Generic { .. } => iterator_element(iterable),
+            TypeInfo::Struct { name, .. } => {
+                if let Some(TypeInfo::Function { return_type, .. }) =
+                    self.method_signature(iterable, "__iter__")
+                {
+                    // An `__iter__` returning the struct itself hands over to `__next__`
+                    if !matches!(return_type.as_ref(), TypeInfo::Struct { name: inner, .. } if inner == name)
+                    {
+                        return self.element_type(&return_type);
+                    }
+                }
+                match self.method_signature(iterable, "__next__")? {
+                    TypeInfo::Function { return_type, .. } => match *return_type {
+                        TypeInfo::Enum { name, mut args, .. }
+                        | TypeInfo::Generic {
+                            base: name,
+                            mut args,
+                        } if name == "Option" && args.len() == 1 => args.pop(),
+                        TypeInfo::Unknown => Some(TypeInfo::Unknown),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            TypeInfo::Alias { underlying, .. } => self.element_type(underlying),
+            _ => None,
+        }
+    }
+
+    /// The value a `with` statement binds for `manager`, if it manages a
+    /// resource: a standard library call acquiring one, or a struct with an
+    /// `__exit__` method, bound to what its `__enter__` returns or to itself
+    fn managed_type(&mut self, manager: &Expr, ty: &TypeInfo) -> Option<TypeInfo> {
+        if let Some(resource) = call_name(manager).and_then(|name| resources::acquired_by(&name)) {
+            return Some(if resource.releases_argument {
+                TypeInfo::Unit
+            } else {
+                ty.clone()
+            });
+        }
+
+        match ty {
+            TypeInfo::Unknown => Some(TypeInfo::Unknown),
+            TypeInfo::Alias { underlying, .. } => self.managed_type(manager, underlying),
+            _ => {
+                let TypeInfo::Function { params, .. } = self.method_signature(ty, "__exit__")?
+                else {
+                    return None;
+                };
+                // `__exit__` may take the message of the exception leaving the body
+                let takes_message = match params.as_slice() {
+                    [] => true,
+                    [param] => param.is_compatible_with(&TypeInfo::Str),
+                    _ => false,
+                };
+                if !takes_message {
+                    self.errors.push(
+                        TypeError::new(format!(
+                            "`__exit__` of {} must take no arguments or an error message",
+                            ty.impl_key()
+                        ))
+                        .with_hint(
+                            "Declare it as `def __exit__(self)` or `def __exit__(self, error: str)`"
+                                .to_string(),
+                        ),
+                    );
+                }
+                match self.method_signature(ty, "__enter__") {
+                    Some(TypeInfo::Function { return_type, .. }) => Some(*return_type),
+                    _ => Some(ty.clone()),
+                }
+            }
+        }
     }
 
     /// Type check a function
@@ -349,16 +752,42 @@ impl TypeChecker {
         // Determine function return type
         let return_type = if let Some(ret_ty) = &function.ret_ty {
             self.context.type_from_annotation(ret_ty)
+        } else if function.is_generator() {
+            iterator_of(TypeInfo::Unknown)
         } else {
             TypeInfo::Unit
         };
 
+        // A generator's body yields elements and may only `return` bare
+        let (return_type, yield_type) = if function.is_generator() {
+            let element = iterator_element(&return_type).unwrap_or_else(|| {
+                self.errors.push(
+                    TypeError::new(format!(
+                        "generator `{}` must return Iterator<T>, not {}",
+                        function.name,
+                        return_type.display_name()
+                    ))
+                    .with_hint(
+                        "Annotate the generator as `-> Iterator<T>` or drop the annotation"
+                            .to_string(),
+                    ),
+                );
+                TypeInfo::Unknown
+            });
+            (TypeInfo::Unit, Some(element))
+        } else {
+            (return_type, None)
+        };
+
         let mut fn_context = TypeContext::with_features(self.features.clone());
 
         // Add function parameters to context
         for param in &function.params {
             let param_type = if let Some(ty) = &param.ty {
                 self.context.type_from_annotation(ty)
+            } else if param.name == "self" {
+                self.context
+                    .type_from_annotation(&Type::Simple("Self".to_string()))
             } else {
                 TypeInfo::Unknown
             };
@@ -370,17 +799,29 @@ impl TypeChecker {
             fn_context.functions.insert(name.clone(), sig.clone());
         }
 
+        fn_context.constants = self.context.constants.clone();
         fn_context.structs = self.context.structs.clone();
         fn_context.type_aliases = self.context.type_aliases.clone();
         fn_context.enums = self.context.enums.clone();
+        fn_context.traits = self.context.traits.clone();
+        fn_context.trait_impls = self.context.trait_impls.clone();
+        fn_context.generic_functions = self.context.generic_functions.clone();
+        fn_context.generic_bounds = self.context.generic_bounds.clone();
+        for generic in &function.generics {
+            fn_context
+                .generic_bounds
+                .insert(generic.name.clone(), generic.bounds.clone());
+        }
 
         // Type check function body with return type tracking
         let old_context = std::mem::replace(&mut self.context, fn_context);
         let old_return_type =
             std::mem::replace(&mut self.current_function_return_type, Some(return_type));
+        let old_yield_type = std::mem::replace(&mut self.current_yield_type, yield_type);
         let result = self.check_block(&function.body);
         self.context = old_context;
         self.current_function_return_type = old_return_type;
+        self.current_yield_type = old_yield_type;
 
         result
     }
@@ -444,6 +885,19 @@ impl TypeChecker {
                     self.extract_generic_params(arg, params);
                 }
             }
+            ast::nodes::Type::Tuple(elements) => {
+                for element in elements {
+                    self.extract_generic_params(element, params);
+                }
+            }
+            ast::nodes::Type::Function { params: args, ret } => {
+                for arg in args {
+                    self.extract_generic_params(arg, params);
+                }
+                if let Some(ret) = ret {
+                    self.extract_generic_params(ret, params);
+                }
+            }
         }
     }
 
@@ -541,6 +995,17 @@ impl TypeChecker {
                         .iter()
                         .any(|arg| self.type_contains_enum_generic(arg, generics))
             }
+            ast::nodes::Type::Tuple(elements) => elements
+                .iter()
+                .any(|element| self.type_contains_enum_generic(element, generics)),
+            ast::nodes::Type::Function { params, ret } => {
+                params
+                    .iter()
+                    .any(|param| self.type_contains_enum_generic(param, generics))
+                    || ret
+                        .as_deref()
+                        .is_some_and(|ret| self.type_contains_enum_generic(ret, generics))
+            }
         }
     }
 
@@ -612,6 +1077,40 @@ impl TypeChecker {
                     }
                 }
             }
+            ast::nodes::Type::Tuple(elements) => {
+                if let TypeInfo::Tuple(actual_elements) = actual {
+                    for (expected_element, actual_element) in
+                        elements.iter().zip(actual_elements.iter())
+                    {
+                        self.infer_enum_generics_from_type(
+                            expected_element,
+                            actual_element,
+                            definition,
+                            inferred,
+                        );
+                    }
+                }
+            }
+            ast::nodes::Type::Function { params, ret } => {
+                if let TypeInfo::Function {
+                    params: actual_params,
+                    return_type,
+                    ..
+                } = actual
+                {
+                    for (expected_param, actual_param) in params.iter().zip(actual_params.iter()) {
+                        self.infer_enum_generics_from_type(
+                            expected_param,
+                            actual_param,
+                            definition,
+                            inferred,
+                        );
+                    }
+                    if let Some(ret) = ret {
+                        self.infer_enum_generics_from_type(ret, return_type, definition, inferred);
+                    }
+                }
+            }
         }
     }
 
@@ -649,8 +1148,8 @@ impl TypeChecker {
                 // If not bound yet, try to extract Generic type args and build enum
                 if !bound {
                     let args = if let TypeInfo::Generic { base, args } = &ty {
-                        if base == enum_name {
-                            Some(args.clone())
+                        if enum_name.is_empty() || base == enum_name {
+                            Some((base.clone(), args.clone()))
                         } else {
                             None
                         }
@@ -658,8 +1157,8 @@ impl TypeChecker {
                         None
                     };
 
-                    if let Some(args) = args {
-                        if let Some(built_enum) = self.context.build_enum_type(enum_name, args) {
+                    if let Some((base, args)) = args {
+                        if let Some(built_enum) = self.context.build_enum_type(&base, args) {
                             if let TypeInfo::Enum { variants, .. } = &built_enum {
                                 if let Some(variant_info) = variants.get(variant) {
                                     if fields.len() == variant_info.fields.len() {
@@ -709,6 +1208,20 @@ impl TypeChecker {
                     }
                 }
             }
+            ast::nodes::Pattern::Tuple(patterns) => match ty {
+                TypeInfo::Tuple(elements) if elements.len() == patterns.len() => {
+                    for (pattern, element) in patterns.iter().zip(elements.iter()) {
+                        self.bind_pattern_variables(pattern, element);
+                    }
+                }
+                _ => {
+                    // Arity or type errors are reported by validation; keep the
+                    // names defined so the rest of the body still checks
+                    for pattern in patterns {
+                        self.bind_pattern_variables(pattern, &TypeInfo::Unknown);
+                    }
+                }
+            },
             ast::nodes::Pattern::Array { patterns, rest } => {
                 if let TypeInfo::List(elem_type) = ty {
                     for pattern in patterns {
@@ -720,12 +1233,150 @@ impl TypeChecker {
                     }
                 }
             }
-            ast::nodes::Pattern::Wildcard | ast::nodes::Pattern::Literal(_) => {
+            ast::nodes::Pattern::Or(alternatives) => {
+                // Alternatives bind the same names; validation checks they agree
+                if let Some(first) = alternatives.first() {
+                    self.bind_pattern_variables(first, ty);
+                }
+            }
+            ast::nodes::Pattern::Binding { pattern, name } => {
+                self.bind_pattern_variables(pattern, ty);
+                self.context.insert_variable(name.clone(), ty.clone());
+            }
+            ast::nodes::Pattern::StringPrefix { rest, .. } => {
+                if let Some(rest) = rest {
+                    self.context.insert_variable(rest.clone(), TypeInfo::Str);
+                }
+            }
+            ast::nodes::Pattern::Dict(entries) => {
+                let value_type = match ty {
+                    TypeInfo::Dict { value, .. } => (**value).clone(),
+                    _ => TypeInfo::Unknown,
+                };
+                for (_, pattern) in entries {
+                    self.bind_pattern_variables(pattern, &value_type);
+                }
+            }
+            ast::nodes::Pattern::Wildcard
+            | ast::nodes::Pattern::Literal(_)
+            | ast::nodes::Pattern::Range { .. } => {
                 // No variables to bind
             }
         }
     }
 
+    /// Report a suffixed literal its type cannot hold, such as `256u8` or `1.5u8`
+    fn check_suffixed_literal(&mut self, number: &NumberLiteral, suffix: NumericType) {
+        let ty = TypeInfo::from_numeric(suffix);
+        if suffix.is_float() {
+            return;
+        }
+        if number.is_float_literal {
+            self.errors.push(
+                TypeError::new(format!(
+                    "float literal cannot have the integer suffix `{}`",
+                    suffix.name()
+                ))
+                .with_hint("Use a float suffix such as `f32`, or drop the fraction".to_string()),
+            );
+        } else if !number_fits(number, &ty) {
+            self.errors.push(
+                TypeError::new(format!(
+                    "literal {} is out of range for {}",
+                    number,
+                    ty.display_name()
+                ))
+                .with_hint(range_hint(&ty)),
+            );
+        }
+    }
+
+    /// The type of an arithmetic, bitwise or modulo operation where either
+    /// operand has one of the sized numeric types, or `None` if neither does.
+    /// An unsuffixed literal takes the other operand's type when it fits;
+    /// otherwise one operand must widen to the other's type.
+    fn sized_operation_type(
+        &mut self,
+        op: &ast::nodes::BinaryOp,
+        left: &Expr,
+        left_type: &TypeInfo,
+        right: &Expr,
+        right_type: &TypeInfo,
+    ) -> Option<TypeInfo> {
+        let is_sized = |ty: &TypeInfo| {
+            ty.numeric().is_some() && !matches!(ty, TypeInfo::I32 | TypeInfo::I64 | TypeInfo::F64)
+        };
+        if !is_sized(left_type) && !is_sized(right_type) {
+            return None;
+        }
+        let numeric = |ty: &TypeInfo| ty.numeric().is_some() || *ty == TypeInfo::Unknown;
+        let ty = if !numeric(left_type) || !numeric(right_type) {
+            None
+        } else if left_type == right_type || literal_fits(right, left_type) {
+            Some(left_type.clone())
+        } else if literal_fits(left, right_type) {
+            Some(right_type.clone())
+        } else if right_type.is_compatible_with(left_type) && *left_type != TypeInfo::Unknown {
+            Some(left_type.clone())
+        } else if left_type.is_compatible_with(right_type) {
+            Some(right_type.clone())
+        } else {
+            None
+        };
+        let Some(ty) = ty else {
+            self.errors.push(
+                TypeError::new(format!(
+                    "cannot apply {:?} to {} and {}",
+                    op,
+                    left_type.display_name(),
+                    right_type.display_name()
+                ))
+                .with_hint("Convert one operand with `as` so both have the same type".to_string()),
+            );
+            return Some(TypeInfo::Error);
+        };
+        let needs_integers = matches!(
+            op,
+            ast::nodes::BinaryOp::Mod
+                | ast::nodes::BinaryOp::BitAnd
+                | ast::nodes::BinaryOp::BitOr
+                | ast::nodes::BinaryOp::BitXor
+                | ast::nodes::BinaryOp::Shl
+                | ast::nodes::BinaryOp::Shr
+        );
+        if needs_integers && !ty.is_integer() {
+            self.errors.push(TypeError::new(format!(
+                "{:?} requires integer operands, got {} and {}",
+                op,
+                left_type.display_name(),
+                right_type.display_name()
+            )));
+            return Some(TypeInfo::Error);
+        }
+        Some(ty)
+    }
+
+    /// Report arms no value reaches and values no arm matches
+    fn check_match_coverage(&mut self, ty: &TypeInfo, arms: &[ast::nodes::MatchArm]) {
+        let report = exhaustiveness::check_match(&self.context, ty, arms);
+        for index in report.unreachable {
+            self.errors.push(
+                TypeError::new(format!("match arm {} is unreachable", index + 1)).with_hint(
+                    "Earlier arms already match every value this pattern matches".to_string(),
+                ),
+            );
+        }
+        if let Some(missing) = report.missing {
+            self.errors.push(
+                TypeError::new(format!(
+                    "non-exhaustive match: `{}` is not covered",
+                    missing
+                ))
+                .with_hint("Add an arm for it, or end the match with `case _:`".to_string()),
+            );
+        }
+    }
+
     fn validate_pattern_against_type(&mut self, pattern: &ast::nodes::Pattern, ty: &TypeInfo) {
         match pattern {
             ast::nodes::Pattern::Wildcard => {
@@ -738,16 +1389,18 @@ impl TypeChecker {
                 // Check literal type matches expected type
                 let lit_type = match lit {
                     ast::nodes::Literal::String(_) => TypeInfo::Str,
-                    ast::nodes::Literal::Number(n) => {
-                        if n.value.fract() == 0.0
+                    ast::nodes::Literal::Number(n) => match n.suffix {
+                        Some(suffix) => TypeInfo::from_numeric(suffix),
+                        // Sized types take the literals in their range
+                        None if ty.numeric().is_some() && number_fits(n, ty) => ty.clone(),
+                        None if n.value.fract() == 0.0
                             && n.value >= i32::MIN as f64
-                            && n.value <= i32::MAX as f64
+                            && n.value <= i32::MAX as f64 =>
                         {
                             TypeInfo::I32
-                        } else {
-                            TypeInfo::F64
                         }
-                    }
+                        None => TypeInfo::F64,
+                    },
                     ast::nodes::Literal::Bool(_) => TypeInfo::Bool,
                     ast::nodes::Literal::None | ast::nodes::Literal::Unit => TypeInfo::Unit,
                 };
@@ -772,13 +1425,15 @@ impl TypeChecker {
                         args: _,
                         variants,
                     } => {
-                        if name != enum_name {
+                        // `Some(x)` leaves the enum to the value's type
+                        if !enum_name.is_empty() && name != enum_name {
                             self.errors.push(TypeError::new(format!(
                                 "enum pattern '{}' does not match value type {}",
                                 enum_name, name
                             )));
                             return;
                         }
+                        let enum_name = name;
 
                         // Find the variant in the enum definition
                         if let Some(variant_def) = variants.get(variant) {
@@ -802,23 +1457,29 @@ impl TypeChecker {
                             )));
                         }
                     }
-                    TypeInfo::Generic { base, args } if base == enum_name => {
+                    TypeInfo::Generic { base, args }
+                        if enum_name.is_empty() || base == enum_name =>
+                    {
                         // Try to build the enum type from generic
-                        if let Some(built_enum) =
-                            self.context.build_enum_type(enum_name, args.clone())
-                        {
+                        if let Some(built_enum) = self.context.build_enum_type(base, args.clone()) {
                             self.validate_pattern_against_type(pattern, &built_enum);
                         } else {
                             self.errors.push(TypeError::new(format!(
                                 "cannot resolve generic enum '{}' with args {:?}",
-                                enum_name, args
+                                base, args
                             )));
                         }
                     }
+                    TypeInfo::Unknown => {}
                     _ => {
+                        let shown = if enum_name.is_empty() {
+                            variant
+                        } else {
+                            enum_name
+                        };
                         self.errors.push(TypeError::new(format!(
                             "cannot match enum pattern '{}' against non-enum type {}",
-                            enum_name,
+                            shown,
                             ty.display_name()
                         )));
                     }
@@ -890,6 +1551,117 @@ impl TypeChecker {
                     }
                 }
             }
+            ast::nodes::Pattern::Tuple(patterns) => match ty {
+                TypeInfo::Tuple(elements) => {
+                    if elements.len() != patterns.len() {
+                        self.errors.push(TypeError::new(format!(
+                            "tuple pattern has {} element(s), but the value has type {}",
+                            patterns.len(),
+                            ty.display_name()
+                        )));
+                    } else {
+                        for (pattern, element) in patterns.iter().zip(elements.iter()) {
+                            self.validate_pattern_against_type(pattern, element);
+                        }
+                    }
+                }
+                TypeInfo::Unknown => {}
+                _ => {
+                    self.errors.push(TypeError::new(format!(
+                        "cannot match tuple pattern against non-tuple type {}",
+                        ty.display_name()
+                    )));
+                }
+            },
+            ast::nodes::Pattern::Or(alternatives) => {
+                let mut expected: Option<Vec<&str>> = None;
+                for alternative in alternatives {
+                    self.validate_pattern_against_type(alternative, ty);
+                    let mut names = alternative.bindings();
+                    names.sort_unstable();
+                    match &expected {
+                        Some(expected) if *expected != names => {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "or-pattern alternatives bind different names: [{}] and [{}]",
+                                    expected.join(", "),
+                                    names.join(", ")
+                                ))
+                                .with_hint(
+                                    "Every alternative of `a | b` must bind the same variables"
+                                        .to_string(),
+                                ),
+                            );
+                            break;
+                        }
+                        Some(_) => {}
+                        None => expected = Some(names),
+                    }
+                }
+            }
+            ast::nodes::Pattern::Range { start, end } => {
+                let bounds = match (start, end) {
+                    (ast::nodes::Literal::Number(start), ast::nodes::Literal::Number(end)) => {
+                        Some((start.value, end.value))
+                    }
+                    _ => None,
+                };
+                match bounds {
+                    Some((start, end)) => {
+                        if ty.numeric().is_none() && *ty != TypeInfo::Unknown {
+                            self.errors.push(TypeError::new(format!(
+                                "range pattern cannot match a value of type {}",
+                                ty.display_name()
+                            )));
+                        } else if start >= end {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "range pattern {}..{} matches nothing",
+                                    start, end
+                                ))
+                                .with_hint(
+                                    "Ranges are half-open: the start is included and the end is not"
+                                        .to_string(),
+                                ),
+                            );
+                        }
+                    }
+                    None => self.errors.push(TypeError::new(
+                        "range pattern bounds must be numbers".to_string(),
+                    )),
+                }
+            }
+            ast::nodes::Pattern::Binding { pattern, .. } => {
+                self.validate_pattern_against_type(pattern, ty);
+            }
+            ast::nodes::Pattern::StringPrefix { .. } => {
+                if !TypeInfo::Str.is_compatible_with(ty) {
+                    self.errors.push(TypeError::new(format!(
+                        "string prefix pattern cannot match a value of type {}",
+                        ty.display_name()
+                    )));
+                }
+            }
+            ast::nodes::Pattern::Dict(entries) => match ty {
+                TypeInfo::Dict { key, value } => {
+                    if !TypeInfo::Str.is_compatible_with(key) {
+                        self.errors.push(TypeError::new(format!(
+                            "dict pattern keys are strings, but the value has type {}",
+                            ty.display_name()
+                        )));
+                    }
+                    for (_, pattern) in entries {
+                        self.validate_pattern_against_type(pattern, value);
+                    }
+                }
+                TypeInfo::Unknown => {}
+                _ => {
+                    self.errors.push(TypeError::new(format!(
+                        "cannot match dict pattern against non-dict type {}",
+                        ty.display_name()
+                    )));
+                }
+            },
         }
     }
 
@@ -904,11 +1676,63 @@ impl TypeChecker {
     /// Type check a statement
     fn check_statement(&mut self, statement: &Statement) -> Result<()> {
         match statement {
-            Statement::Let { name, expr, .. } => {
+            Statement::Let {
+                name,
+                ty,
+                expr,
+                span,
+                ..
+            } => {
+                self.check_not_constant(name, *span);
                 let expr_type = self.infer_expr_type(expr)?;
-                self.context.insert_variable(name.clone(), expr_type);
-            }
-            Statement::Assignment { name, expr, span } => {
+                let var_type = match ty {
+                    Some(ty) => {
+                        let declared = self.context.type_from_annotation(ty);
+                        if !expr_type.is_compatible_with(&declared)
+                            && !literal_fits(expr, &declared)
+                        {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "cannot initialize {} of type {} with {}",
+                                    name,
+                                    declared.display_name(),
+                                    expr_type.display_name()
+                                ))
+                                .with_hint(format!(
+                                    "The variable `{}` is annotated as `{}`",
+                                    name,
+                                    declared.display_name()
+                                ))
+                                .with_optional_span(*span),
+                            );
+                        }
+                        declared
+                    }
+                    None => expr_type,
+                };
+                self.context.insert_variable(name.clone(), var_type);
+            }
+            Statement::Destructure {
+                pattern,
+                expr,
+                span,
+            } => {
+                let expr_type = self.infer_expr_type(expr)?;
+                let errors_before = self.errors.len();
+                self.validate_pattern_against_type(pattern, &expr_type);
+                for error in &mut self.errors[errors_before..] {
+                    if error.span.is_none() {
+                        error.span = *span;
+                    }
+                }
+                self.bind_pattern_variables(pattern, &expr_type);
+            }
+            Statement::Assignment {
+                target: Expr::Identifier { name, .. },
+                expr,
+                span,
+            } => {
+                self.check_not_constant(name, *span);
                 let var_type = self
                     .context
                     .get_variable(name)
@@ -924,7 +1748,7 @@ impl TypeChecker {
                     .clone();
 
                 let expr_type = self.infer_expr_type(expr)?;
-                if !expr_type.is_compatible_with(&var_type) {
+                if !expr_type.is_compatible_with(&var_type) && !literal_fits(expr, &var_type) {
                     self.errors.push(TypeError::new(format!(
                         "cannot assign {} to {} (expected {})",
                         expr_type.display_name(),
@@ -936,6 +1760,49 @@ impl TypeChecker {
                     .with_optional_span(*span));
                 }
             }
+            Statement::Assignment { target, expr, span } => {
+                let target_type = self.infer_expr_type(target)?;
+                let expr_type = self.infer_expr_type(expr)?;
+                let indexed_type = match target {
+                    Expr::Index { object, .. } => self
+                        .expr_types
+                        .get(&(object.as_ref() as *const Expr as usize)),
+                    _ => None,
+                };
+                if matches!(indexed_type, Some(TypeInfo::Str)) {
+                    self.errors.push(
+                        TypeError::new("cannot assign to an index of str".to_string())
+                            .with_hint("Strings are immutable".to_string())
+                            .with_help("Build a new string with slices and `+` instead".to_string())
+                            .with_optional_span(*span),
+                    );
+                } else if matches!(indexed_type, Some(TypeInfo::Tuple(_))) {
+                    self.errors.push(
+                        TypeError::new("cannot assign to an element of a tuple".to_string())
+                            .with_hint("Tuples are immutable".to_string())
+                            .with_help("Build a new tuple instead".to_string())
+                            .with_optional_span(*span),
+                    );
+                } else if !expr_type.is_compatible_with(&target_type)
+                    && !literal_fits(expr, &target_type)
+                {
+                    let target = fmt::Formatter::new().format_expression(target);
+                    self.errors.push(
+                        TypeError::new(format!(
+                            "cannot assign {} to {} (expected {})",
+                            expr_type.display_name(),
+                            target,
+                            target_type.display_name()
+                        ))
+                        .with_hint(format!(
+                            "`{}` has type `{}`",
+                            target,
+                            target_type.display_name()
+                        ))
+                        .with_optional_span(*span),
+                    );
+                }
+            }
             Statement::If {
                 cond,
                 then_block,
@@ -965,28 +1832,33 @@ impl TypeChecker {
                 ..
             } => {
                 let iter_type = self.infer_expr_type(iterable)?;
-                let element_type = match &iter_type {
-                    TypeInfo::List(elem) => elem.as_ref().clone(),
-                    TypeInfo::Dict { value, .. } => value.as_ref().clone(),
-                    TypeInfo::Str => TypeInfo::Str,
-                    _ => {
-                        self.errors.push(TypeError::new(format!(
+                let element_type = self.element_type(&iter_type).unwrap_or_else(|| {
+                    self.errors.push(
+                        TypeError::new(format!(
                             "cannot iterate over type {}",
                             iter_type.display_name()
-                        )));
-                        TypeInfo::Unknown
-                    }
-                };
-
-                let previous = self.context.remove_variable(var);
-                self.context.insert_variable(var.clone(), element_type);
+                        ))
+                        .with_hint(ITERABLE_HINT.to_string()),
+                    );
+                    TypeInfo::Unknown
+                });
+
+                self.validate_pattern_against_type(var, &element_type);
+                let names = var.bindings();
+                let previous: Vec<_> = names
+                    .iter()
+                    .map(|name| self.context.remove_variable(name))
+                    .collect();
+                self.bind_pattern_variables(var, &element_type);
                 self.check_block(body)?;
-                match previous {
-                    Some(prev) => {
-                        self.context.insert_variable(var.clone(), prev);
-                    }
-                    None => {
-                        self.context.remove_variable(var);
+                for (name, previous) in names.into_iter().zip(previous) {
+                    match previous {
+                        Some(prev) => {
+                            self.context.insert_variable(name.to_string(), prev);
+                        }
+                        None => {
+                            self.context.remove_variable(name);
+                        }
                     }
                 }
             }
@@ -1000,13 +1872,23 @@ impl TypeChecker {
                 }
                 self.check_block(body)?;
             }
+            Statement::Return(Some(expr)) if self.current_yield_type.is_some() => {
+                self.infer_expr_type(expr)?;
+                self.errors.push(
+                    TypeError::new("cannot return a value from a generator".to_string()).with_hint(
+                        "Use `yield` to produce values and a bare `return` to stop".to_string(),
+                    ),
+                );
+            }
             Statement::Return(expr) => {
                 if let Some(expr) = expr {
                     let expr_type = self.infer_expr_type(expr)?;
 
                     // Check return type matches function signature
                     if let Some(expected_return_type) = &self.current_function_return_type {
-                        if !expr_type.is_compatible_with(expected_return_type) {
+                        if !expr_type.is_compatible_with(expected_return_type)
+                            && !literal_fits(expr, expected_return_type)
+                        {
                             self.errors.push(TypeError::new(format!(
                                 "return type mismatch: expected {}, got {}",
                                 expected_return_type.display_name(),
@@ -1036,6 +1918,25 @@ impl TypeChecker {
                     }
                 }
             }
+            Statement::Yield(expr) => {
+                let expr_type = self.infer_expr_type(expr)?;
+                match &self.current_yield_type {
+                    Some(expected) => {
+                        if !expr_type.is_compatible_with(expected) {
+                            self.errors.push(TypeError::new(format!(
+                                "yield type mismatch: expected {}, got {}",
+                                expected.display_name(),
+                                expr_type.display_name()
+                            )));
+                        }
+                    }
+                    None => {
+                        self.errors.push(TypeError::new(
+                            "yield statement outside of function".to_string(),
+                        ));
+                    }
+                }
+            }
             Statement::Function(_) => {
                 // Functions are handled separately
             }
@@ -1062,6 +1963,12 @@ impl TypeChecker {
             Statement::TypeAlias { .. } => {
                 // Type aliases are handled at the module level
             }
+            Statement::Const { .. } => {
+                // Constants are handled at the module level
+            }
+            Statement::Trait { .. } | Statement::Impl { .. } => {
+                // Traits and impls are handled at the module level
+            }
             Statement::Block(block) => {
                 self.check_block(block)?;
             }
@@ -1112,6 +2019,46 @@ impl TypeChecker {
                     self.check_block(finally_block)?;
                 }
             }
+            Statement::With {
+                manager,
+                alias,
+                body,
+            } => {
+                let manager_type = self.infer_expr_type(manager)?;
+                let bound_type = self
+                    .managed_type(manager, &manager_type)
+                    .unwrap_or_else(|| {
+                        self.errors.push(
+                            TypeError::new(format!(
+                                "type {} cannot be used in a `with` statement",
+                                manager_type.display_name()
+                            ))
+                            .with_hint(MANAGER_HINT.to_string()),
+                        );
+                        TypeInfo::Unknown
+                    });
+
+                let Some(alias) = alias else {
+                    return self.check_block(body);
+                };
+                if bound_type == TypeInfo::Unit {
+                    self.errors.push(TypeError::new(format!(
+                        "`with` statement has no value to bind to `{}`",
+                        alias
+                    )));
+                }
+                let previous = self.context.remove_variable(alias);
+                self.context.insert_variable(alias.clone(), bound_type);
+                self.check_block(body)?;
+                match previous {
+                    Some(prev) => {
+                        self.context.insert_variable(alias.clone(), prev);
+                    }
+                    None => {
+                        self.context.remove_variable(alias);
+                    }
+                }
+            }
             Statement::Raise(expr) => {
                 if let Some(expr) = expr {
                     // Type check the expression being raised
@@ -1137,12 +2084,16 @@ impl TypeChecker {
         let ty = (|| -> Result<TypeInfo> {
             match expr {
                 Expr::Literal(lit) => Ok(match lit {
-                    Literal::Number(_num) => {
+                    Literal::Number(num) => match num.suffix {
+                        Some(suffix) => {
+                            self.check_suffixed_literal(num, suffix);
+                            TypeInfo::from_numeric(suffix)
+                        }
                         // Always infer numeric literals as F64 for simplicity and to avoid
                         // type inference issues with float contexts
                         // This matches Python's behavior where all numbers are floats by default
-                        TypeInfo::F64
-                    }
+                        None => TypeInfo::F64,
+                    },
                     Literal::String(_) => TypeInfo::Str,
                     Literal::Bool(_) => TypeInfo::Bool,
                     Literal::None => TypeInfo::Unit,
@@ -1177,7 +2128,14 @@ impl TypeChecker {
                         ast::nodes::BinaryOp::Add
                         | ast::nodes::BinaryOp::Sub
                         | ast::nodes::BinaryOp::Mul
-                        | ast::nodes::BinaryOp::Div => {
+                        | ast::nodes::BinaryOp::Div
+                        | ast::nodes::BinaryOp::FloorDiv
+                        | ast::nodes::BinaryOp::Pow => {
+                            if let Some(ty) =
+                                self.sized_operation_type(op, left, &left_type, right, &right_type)
+                            {
+                                return Ok(ty);
+                            }
                             // Numeric operations
                             match (&left_type, &right_type) {
                                 // String concatenation (must come before numeric patterns)
@@ -1218,7 +2176,10 @@ impl TypeChecker {
                         | ast::nodes::BinaryOp::Gt
                         | ast::nodes::BinaryOp::GtEq => {
                             // Comparison operations return bool
-                            if left_type.is_compatible_with(&right_type) {
+                            if left_type.is_compatible_with(&right_type)
+                                || literal_fits(left, &right_type)
+                                || literal_fits(right, &left_type)
+                            {
                                 Ok(TypeInfo::Bool)
                             } else {
                                 self.errors.push(TypeError::new(format!(
@@ -1264,7 +2225,47 @@ impl TypeChecker {
                                 Ok(TypeInfo::Error)
                             }
                         }
+                        ast::nodes::BinaryOp::BitAnd
+                        | ast::nodes::BinaryOp::BitOr
+                        | ast::nodes::BinaryOp::BitXor
+                        | ast::nodes::BinaryOp::Shl
+                        | ast::nodes::BinaryOp::Shr => {
+                            if let Some(ty) =
+                                self.sized_operation_type(op, left, &left_type, right, &right_type)
+                            {
+                                return Ok(ty);
+                            }
+                            // Bitwise operations require integer operands
+                            match (
+                                integer_operand(left, &left_type),
+                                integer_operand(right, &right_type),
+                            ) {
+                                (Some(TypeInfo::I64), Some(_)) | (Some(_), Some(TypeInfo::I64)) => {
+                                    Ok(TypeInfo::I64)
+                                }
+                                (Some(_), Some(_)) => Ok(TypeInfo::I32),
+                                _ => {
+                                    self.errors.push(
+                                        TypeError::new(format!(
+                                            "bitwise operations require integer operands, got {} and {}",
+                                            left_type.display_name(),
+                                            right_type.display_name()
+                                        ))
+                                        .with_hint(
+                                            "annotate the operands as `int`, or use whole-number literals"
+                                                .to_string(),
+                                        ),
+                                    );
+                                    Ok(TypeInfo::Error)
+                                }
+                            }
+                        }
                         ast::nodes::BinaryOp::Mod => {
+                            if let Some(ty) =
+                                self.sized_operation_type(op, left, &left_type, right, &right_type)
+                            {
+                                return Ok(ty);
+                            }
                             // Modulo requires integer operands
                             match (&left_type, &right_type) {
                                 (TypeInfo::I32, TypeInfo::I32) => Ok(TypeInfo::I32),
@@ -1282,7 +2283,21 @@ impl TypeChecker {
                     }
                 }
                 Expr::Unary { op, expr } => {
-                    let expr_type = self.infer_expr_type(expr)?;
+                    let expr_type = match expr.as_ref() {
+                        // `-128i8` is in range although `128i8` is not
+                        Expr::Literal(Literal::Number(
+                            number @ NumberLiteral {
+                                suffix: Some(suffix),
+                                ..
+                            },
+                        )) if *op == ast::nodes::UnaryOp::Neg => {
+                            self.check_suffixed_literal(&number.negated(), *suffix);
+                            let ty = TypeInfo::from_numeric(*suffix);
+                            self.record_expr_type(expr, &ty);
+                            ty
+                        }
+                        _ => self.infer_expr_type(expr)?,
+                    };
                     match op {
                         ast::nodes::UnaryOp::Not => {
                             if expr_type.is_compatible_with(&TypeInfo::Bool) {
@@ -1296,7 +2311,21 @@ impl TypeChecker {
                             }
                         }
                         ast::nodes::UnaryOp::Neg => {
-                            if expr_type.is_compatible_with(&TypeInfo::I32)
+                            if expr_type.is_integer()
+                                && expr_type.numeric().is_some_and(|ty| !ty.is_signed())
+                            {
+                                self.errors.push(
+                                    TypeError::new(format!(
+                                        "cannot negate a value of unsigned type {}",
+                                        expr_type.display_name()
+                                    ))
+                                    .with_hint(
+                                        "Convert it to a signed type first, e.g. `x as i64`"
+                                            .to_string(),
+                                    ),
+                                );
+                                Ok(TypeInfo::Error)
+                            } else if expr_type.is_compatible_with(&TypeInfo::I32)
                                 || expr_type.is_compatible_with(&TypeInfo::I64)
                                 || expr_type.is_compatible_with(&TypeInfo::F64)
                             {
@@ -1309,19 +2338,38 @@ impl TypeChecker {
                                 Ok(TypeInfo::Error)
                             }
                         }
+                        ast::nodes::UnaryOp::BitNot => match integer_operand(expr, &expr_type) {
+                            Some(ty) => Ok(ty),
+                            None => {
+                                self.errors.push(TypeError::new(format!(
+                                    "bitwise not requires an integer operand, got {}",
+                                    expr_type.display_name()
+                                )));
+                                Ok(TypeInfo::Error)
+                            }
+                        },
                     }
                 }
-                Expr::Call { func, args } => {
+                Expr::Call { func, args, .. } => {
                     if let Some(enum_type) = self.try_eval_enum_constructor(func, args)? {
                         return Ok(enum_type);
                     }
                     let func_type = match func.as_ref() {
                         Expr::Identifier { name, span } => {
-                            self.context.get_function(name).cloned().ok_or_else(|| {
-                                let err = TypeError::new(format!("undefined function: {}", name))
-                                    .with_optional_span(*span);
-                                anyhow::Error::from(err)
-                            })?
+                            // A local holding a function value shadows a named function
+                            let local = self
+                                .context
+                                .get_variable(name)
+                                .filter(|ty| matches!(ty, TypeInfo::Function { .. }));
+                            local
+                                .or_else(|| self.context.get_function(name))
+                                .cloned()
+                                .ok_or_else(|| {
+                                    let err =
+                                        TypeError::new(format!("undefined function: {}", name))
+                                            .with_optional_span(*span);
+                                    anyhow::Error::from(err)
+                                })?
                         }
                         Expr::Member { object, field } => {
                             let full_name = self.build_member_path(object, field);
@@ -1379,11 +2427,36 @@ impl TypeChecker {
                                     
                                     // Method call: obj.method() - infer object type and look up method
                                     if let Ok(obj_type) = self.infer_expr_type(object) {
-                                        if let TypeInfo::Struct { name, .. } = obj_type {
+                                        if let Some(method) = self.method_signature(&obj_type, field) {
+                                            return method;
+                                        }
+                                        if let TypeInfo::Generic { base, args } = &obj_type {
+                                            if args.is_empty() && self.context.generic_bounds.contains_key(base) {
+                                                self.errors.push(TypeError::new(format!(
+                                                    "no method '{}' on type parameter `{}`",
+                                                    field, base
+                                                )).with_hint(format!(
+                                                    "Add a bound providing it, e.g. `{}: SomeTrait`",
+                                                    base
+                                                )));
+                                                return TypeInfo::Function {
+                                                    params: vec![],
+                                                    param_defaults: vec![],
+                                                    return_type: Box::new(TypeInfo::Error),
+                                                };
+                                            }
+                                        }
+                                        if let TypeInfo::Struct { name, fields } = obj_type {
                                             let method_name = format!("{}.{}", name, field);
                                             return self.context
                                                 .get_function(&method_name)
                                                 .cloned()
+                                                .or_else(|| {
+                                                    // Fields holding function values are callable
+                                                    fields.get(field).filter(|ty| {
+                                                        matches!(ty, TypeInfo::Function { .. })
+                                                    }).cloned()
+                                                })
                                                 .unwrap_or_else(|| {
                                                     self.errors.push(TypeError::new(format!(
                                                         "struct '{}' has no method '{}'",
@@ -1392,6 +2465,25 @@ impl TypeChecker {
                                                     TypeInfo::Error
                                                 });
                                         }
+                                        if let TypeInfo::Dict { key, value } = obj_type {
+                                            let element = match field.as_str() {
+                                                "items" => {
+                                                    Some(TypeInfo::Tuple(vec![*key, *value]))
+                                                }
+                                                "keys" => Some(*key),
+                                                "values" => Some(*value),
+                                                _ => None,
+                                            };
+                                            if let Some(element) = element {
+                                                return TypeInfo::Function {
+                                                    params: vec![],
+                                                    param_defaults: vec![],
+                                                    return_type: Box::new(TypeInfo::List(
+                                                        Box::new(element),
+                                                    )),
+                                                };
+                                            }
+                                        }
                                     }
                                     
                                     // Return a generic function type for unknown FFI functions
@@ -1402,15 +2494,31 @@ impl TypeChecker {
                                     }
                                 })
                         }
-                        _ => {
-                            self.errors.push(TypeError::new(
-                                "function calls must use identifier or module.function syntax"
-                                    .to_string(),
-                            ));
-                            return Ok(TypeInfo::Error);
-                        }
+                        other => match self.infer_expr_type(other)? {
+                            // Indexed elements and inline lambdas may be function values
+                            function @ TypeInfo::Function { .. } => function,
+                            TypeInfo::Error => return Ok(TypeInfo::Error),
+                            _ => {
+                                self.errors.push(TypeError::new(
+                                    "function calls must use identifier or module.function syntax"
+                                        .to_string(),
+                                ));
+                                return Ok(TypeInfo::Error);
+                            }
+                        },
                     };
 
+                    let generics: Vec<GenericParam> = match func.as_ref() {
+                        Expr::Identifier { name, .. } => self
+                            .context
+                            .generic_functions
+                            .get(name)
+                            .cloned()
+                            .unwrap_or_default(),
+                        _ => Vec::new(),
+                    };
+                    let mut bindings = HashMap::new();
+
                     match func_type {
                         TypeInfo::Function {
                             params,
@@ -1456,7 +2564,16 @@ impl TypeChecker {
                                     args.iter().zip(params.iter()).enumerate()
                                 {
                                     let arg_type = self.infer_expr_type(arg)?;
-                                    if !arg_type.is_compatible_with(param_type) {
+                                    bind_generic_params(
+                                        param_type,
+                                        &arg_type,
+                                        &generics,
+                                        &mut bindings,
+                                    );
+                                    let param_type = &param_type.substitute(&bindings);
+                                    if !arg_type.is_compatible_with(param_type)
+                                        && !literal_fits(arg, param_type)
+                                    {
                                         self.errors.push(TypeError::new(format!(
                                         "argument {} type mismatch: expected {}, got {}",
                                         i + 1,
@@ -1496,7 +2613,36 @@ impl TypeChecker {
                             } else {
                                 *return_type
                             };
-                            Ok(result_type)
+
+                            let callee = match func.as_ref() {
+                                Expr::Identifier { name, .. } => name.as_str(),
+                                _ => "",
+                            };
+                            for generic in &generics {
+                                let Some(ty) = bindings.get(&generic.name) else {
+                                    continue;
+                                };
+                                for bound in &generic.bounds {
+                                    if !self.context.implements(ty, bound) {
+                                        self.errors.push(
+                                            TypeError::new(format!(
+                                                "type {} does not implement trait `{}`",
+                                                ty.display_name(),
+                                                bound
+                                            ))
+                                            .with_hint(format!(
+                                                "`{}` requires `{}: {}`; add `impl {} for {}`",
+                                                callee,
+                                                generic.name,
+                                                bound,
+                                                bound,
+                                                ty.display_name()
+                                            )),
+                                        );
+                                    }
+                                }
+                            }
+                            Ok(result_type.substitute(&bindings))
                         }
                         _ => {
                             self.errors.push(
@@ -1697,22 +2843,38 @@ impl TypeChecker {
                         param_defaults.push(param.default.is_some());
                     }
 
-                    // Type check lambda body
+                    let annotated_return = ret_ty
+                        .as_ref()
+                        .map(|ty| self.context.type_from_annotation(ty));
+
+                    // Type check lambda body; `return` inside it answers to the lambda
                     let old_context = std::mem::replace(&mut self.context, lambda_context);
-                    let body_result = self.check_block(body);
+                    let old_return_type = self
+                        .current_function_return_type
+                        .replace(annotated_return.clone().unwrap_or(TypeInfo::Unknown));
+                    let body_result = match body.statements.as_slice() {
+                        // An expression body is the lambda's result
+                        [Statement::Expr(expr)] => self.infer_expr_type(expr).map(Some),
+                        _ => self.check_block(body).map(|_| None),
+                    };
+                    self.current_function_return_type = old_return_type;
                     self.context = old_context;
 
-                    if body_result.is_err() {
+                    let Ok(body_type) = body_result else {
                         return Ok(TypeInfo::Error);
+                    };
+                    if let (Some(expected), Some(actual)) = (&annotated_return, &body_type) {
+                        if *expected != TypeInfo::Unit && !actual.is_compatible_with(expected) {
+                            self.errors.push(TypeError::new(format!(
+                                "lambda returns {}, but its signature declares {}",
+                                actual.display_name(),
+                                expected.display_name()
+                            )));
+                        }
                     }
 
-                    let return_type = if let Some(ty) = ret_ty {
-                        self.context.type_from_annotation(ty)
-                    } else {
-                        // Try to infer return type from body
-                        // For now, assume unit if no return statement
-                        TypeInfo::Unit
-                    };
+                    // Without an annotation or an expression body, assume unit
+                    let return_type = annotated_return.or(body_type).unwrap_or(TypeInfo::Unit);
 
                     Ok(TypeInfo::Function {
                         params: param_types,
@@ -1756,6 +2918,12 @@ impl TypeChecker {
                         Ok(TypeInfo::List(Box::new(common_type)))
                     }
                 }
+                Expr::Tuple(elements) => Ok(TypeInfo::Tuple(
+                    elements
+                        .iter()
+                        .map(|e| self.infer_expr_type(e))
+                        .collect::<Result<Vec<_>>>()?,
+                )),
                 Expr::Dict(entries) => {
                     if entries.is_empty() {
                         // Empty dictionary - can't infer key/value types
@@ -1841,15 +3009,17 @@ impl TypeChecker {
                     condition,
                 } => {
                     let iterable_type = self.infer_expr_type(iterable)?;
-                    let element_iter_type = if let TypeInfo::List(elem) = &iterable_type {
-                        elem.as_ref().clone()
-                    } else {
-                        self.errors.push(TypeError::new(format!(
-                            "list comprehension expects list iterable, got {}",
-                            iterable_type.display_name()
-                        )));
-                        TypeInfo::Unknown
-                    };
+                    let element_iter_type =
+                        self.element_type(&iterable_type).unwrap_or_else(|| {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "list comprehension expects an iterable, got {}",
+                                    iterable_type.display_name()
+                                ))
+                                .with_hint(ITERABLE_HINT.to_string()),
+                            );
+                            TypeInfo::Unknown
+                        });
 
                     let previous = self.context.remove_variable(var);
                     self.context
@@ -1886,15 +3056,17 @@ impl TypeChecker {
                     condition,
                 } => {
                     let iterable_type = self.infer_expr_type(iterable)?;
-                    let element_iter_type = if let TypeInfo::List(elem) = &iterable_type {
-                        elem.as_ref().clone()
-                    } else {
-                        self.errors.push(TypeError::new(format!(
-                            "dict comprehension expects list iterable, got {}",
-                            iterable_type.display_name()
-                        )));
-                        TypeInfo::Unknown
-                    };
+                    let element_iter_type =
+                        self.element_type(&iterable_type).unwrap_or_else(|| {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "dict comprehension expects an iterable, got {}",
+                                    iterable_type.display_name()
+                                ))
+                                .with_hint(ITERABLE_HINT.to_string()),
+                            );
+                            TypeInfo::Unknown
+                        });
 
                     let previous = self.context.remove_variable(var);
                     self.context
@@ -1951,6 +3123,7 @@ impl TypeChecker {
 
                     // Type check each arm
                     let mut arm_types = Vec::new();
+                    let mut scrutinee_type = value_type.clone();
                     for arm in arms {
                         // Bind pattern variables before checking guard and body
                         // Ensure type is normalized before binding (in case it wasn't normalized above)
@@ -1960,10 +3133,10 @@ impl TypeChecker {
                         if let TypeInfo::Generic { base, args } = &normalized_type {
                             if let ast::nodes::Pattern::EnumVariant { enum_name, .. } = &arm.pattern
                             {
-                                if base == enum_name {
+                                if enum_name.is_empty() || base == enum_name {
                                     // Try to build the enum type directly using the pattern's enum name
                                     if let Some(built_enum) =
-                                        self.context.build_enum_type(enum_name, args.clone())
+                                        self.context.build_enum_type(base, args.clone())
                                     {
                                         normalized_type = built_enum;
                                     }
@@ -1973,6 +3146,9 @@ impl TypeChecker {
 
                         // Check pattern matches value type with sophisticated validation
                         self.validate_pattern_against_type(&arm.pattern, &normalized_type);
+                        if matches!(scrutinee_type, TypeInfo::Generic { .. }) {
+                            scrutinee_type = normalized_type.clone();
+                        }
 
                         let old_vars = self.context.variables.clone();
                         self.bind_pattern_variables(&arm.pattern, &normalized_type);
@@ -1996,6 +3172,10 @@ impl TypeChecker {
                         self.context.variables = old_vars;
                     }
 
+                    if self.features.match_exhaustiveness {
+                        self.check_match_coverage(&scrutinee_type, arms);
+                    }
+
                     // All arms must return compatible types
                     let common_type = arm_types.remove(0);
                     for (i, arm_type) in arm_types.iter().enumerate() {
@@ -2089,6 +3269,45 @@ impl TypeChecker {
                         fields: struct_fields,
                     })
                 }
+                Expr::Cast { expr: inner, ty } => {
+                    let source = self.infer_expr_type(inner)?;
+                    let target = self.context.type_from_annotation(ty);
+                    let convertible = match target.numeric() {
+                        Some(target) => {
+                            source.numeric().is_some()
+                                || source == TypeInfo::Unknown
+                                || (source == TypeInfo::Bool && !target.is_float())
+                        }
+                        None => false,
+                    };
+                    if !convertible {
+                        self.errors.push(
+                            TypeError::new(format!(
+                                "cannot convert {} to {} with `as`",
+                                source.display_name(),
+                                target.display_name()
+                            ))
+                            .with_hint(
+                                "`as` converts between numeric types, and from bool to integers"
+                                    .to_string(),
+                            ),
+                        );
+                        return Ok(TypeInfo::Error);
+                    }
+                    if let Some(number) = unsuffixed_literal(inner) {
+                        if !number_fits(&number, &target) {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "literal {} does not fit in {}",
+                                    number,
+                                    target.display_name()
+                                ))
+                                .with_hint(range_hint(&target)),
+                            );
+                        }
+                    }
+                    Ok(target)
+                }
                 Expr::Await(expr) => {
                     // Await expects an async/awaitable type
                     let inner_type = self.infer_expr_type(expr)?;
@@ -2112,6 +3331,117 @@ impl TypeChecker {
                     // For await, we return the inner type (unwrap the async wrapper)
                     Ok(inner_type)
                 }
+                Expr::Index {
+                    object,
+                    index,
+                    span,
+                } => {
+                    let object_type = self.infer_expr_type(object)?;
+                    let index_type = self.infer_expr_type(index)?;
+                    Ok(match &object_type {
+                        TypeInfo::List(element) => {
+                            self.check_position_index(&index_type, *span);
+                            element.as_ref().clone()
+                        }
+                        TypeInfo::Str => {
+                            self.check_position_index(&index_type, *span);
+                            TypeInfo::Str
+                        }
+                        TypeInfo::Tuple(elements) => match tuple_position(index) {
+                            Some(position) => {
+                                let resolved = if position < 0 {
+                                    position + elements.len() as i64
+                                } else {
+                                    position
+                                };
+                                match usize::try_from(resolved).ok().and_then(|i| elements.get(i)) {
+                                    Some(element) => element.clone(),
+                                    None => {
+                                        self.errors.push(
+                                            TypeError::new(format!(
+                                                "tuple index {} is out of range for {}",
+                                                position,
+                                                object_type.display_name()
+                                            ))
+                                            .with_optional_span(*span),
+                                        );
+                                        TypeInfo::Error
+                                    }
+                                }
+                            }
+                            None => {
+                                self.errors.push(
+                                    TypeError::new(
+                                        "tuple index must be an integer literal".to_string(),
+                                    )
+                                    .with_hint(format!(
+                                        "Elements of {} can have different types, so the position has to be known at compile time",
+                                        object_type.display_name()
+                                    ))
+                                    .with_optional_span(*span),
+                                );
+                                TypeInfo::Error
+                            }
+                        },
+                        TypeInfo::Dict { key, value } => {
+                            if !index_type.is_compatible_with(key) {
+                                self.errors.push(
+                                    TypeError::new(format!(
+                                        "dict key must be {}, got {}",
+                                        key.display_name(),
+                                        index_type.display_name()
+                                    ))
+                                    .with_optional_span(*span),
+                                );
+                            }
+                            value.as_ref().clone()
+                        }
+                        TypeInfo::Unknown => TypeInfo::Unknown,
+                        TypeInfo::Error => TypeInfo::Error,
+                        _ => {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "cannot index into type {}",
+                                    object_type.display_name()
+                                ))
+                                .with_hint(
+                                    "Only lists, dicts, tuples and strings support indexing"
+                                        .to_string(),
+                                )
+                                .with_optional_span(*span),
+                            );
+                            TypeInfo::Error
+                        }
+                    })
+                }
+                Expr::Slice {
+                    object,
+                    start,
+                    end,
+                    span,
+                } => {
+                    let object_type = self.infer_expr_type(object)?;
+                    for bound in [start, end].into_iter().flatten() {
+                        let bound_type = self.infer_expr_type(bound)?;
+                        self.check_position_index(&bound_type, *span);
+                    }
+                    match object_type {
+                        TypeInfo::List(_) | TypeInfo::Str | TypeInfo::Unknown | TypeInfo::Error => {
+                            Ok(object_type)
+                        }
+                        _ => {
+                            self.errors.push(
+                                TypeError::new(format!(
+                                    "cannot slice type {}",
+                                    object_type.display_name()
+                                ))
+                                .with_hint("Only lists and strings support slicing".to_string())
+                                .with_optional_span(*span),
+                            );
+                            Ok(TypeInfo::Error)
+                        }
+                    }
+                }
                 Expr::Spawn(expr) => {
                     // Spawn creates a task from an expression
                     // Type check the inner expression
@@ -2132,6 +3462,11 @@ impl TypeChecker {
         &self.errors
     }
 
+    /// Types, functions and variables known at the top level
+    pub fn context(&self) -> &TypeContext {
+        &self.context
+    }
+
     pub fn expr_type_map(&self) -> &HashMap<usize, TypeInfo> {
         &self.expr_types
     }
@@ -2140,6 +3475,19 @@ impl TypeChecker {
         self.expr_types
     }
 
+    /// List and string positions are numbers; negative ones count from the end
+    fn check_position_index(&mut self, index_type: &TypeInfo, span: Option<Span>) {
+        if !index_type.is_compatible_with(&TypeInfo::F64) {
+            self.errors.push(
+                TypeError::new(format!(
+                    "index must be a number, got {}",
+                    index_type.display_name()
+                ))
+                .with_optional_span(span),
+            );
+        }
+    }
+
     fn build_member_path(&self, object: &Expr, field: &str) -> String {
         match object {
             Expr::Identifier { name, .. } => format!("{}.{}", name, field),
@@ -2165,6 +3513,14 @@ fn ffi_type_to_typeinfo(ft: &FfiType) -> TypeInfo {
         FfiType::I32 => TypeInfo::I32,
         FfiType::I64 => TypeInfo::I64,
         FfiType::F64 => TypeInfo::F64,
+        FfiType::I8 => TypeInfo::I8,
+        FfiType::I16 => TypeInfo::I16,
+        FfiType::U8 => TypeInfo::U8,
+        FfiType::U16 => TypeInfo::U16,
+        FfiType::U32 => TypeInfo::U32,
+        FfiType::U64 => TypeInfo::U64,
+        FfiType::Usize => TypeInfo::Usize,
+        FfiType::F32 => TypeInfo::F32,
         FfiType::Str => TypeInfo::Str,
         FfiType::Opaque => TypeInfo::I64, // Opaque handles are i64 at type level
         FfiType::List => TypeInfo::List(Box::new(TypeInfo::Unknown)),
@@ -2175,6 +3531,202 @@ fn ffi_type_to_typeinfo(ft: &FfiType) -> TypeInfo {
     }
 }
 
+/// The integer type of a bitwise operand: an integer, or a whole-number
+/// literal (which would otherwise be inferred as a float)
+fn integer_operand(expr: &Expr, ty: &TypeInfo) -> Option<TypeInfo> {
+    match ty {
+        ty if ty.is_integer() => Some(ty.clone()),
+        TypeInfo::F64 if is_whole_number_literal(expr) => Some(TypeInfo::I32),
+        _ => None,
+    }
+}
+
+/// The value of an unsuffixed number literal, negated under a unary minus
+pub(crate) fn unsuffixed_literal(expr: &Expr) -> Option<NumberLiteral> {
+    match expr {
+        Expr::Literal(Literal::Number(number)) if number.suffix.is_none() => Some(*number),
+        Expr::Unary {
+            op: ast::nodes::UnaryOp::Neg,
+            expr,
+        } => unsuffixed_literal(expr).map(NumberLiteral::negated),
+        _ => None,
+    }
+}
+
+/// Whether `expr` is an unsuffixed number literal that `ty` can hold
+fn literal_fits(expr: &Expr, ty: &TypeInfo) -> bool {
+    unsuffixed_literal(expr).is_some_and(|number| number_fits(&number, ty))
+}
+
+/// Whether a number can be typed as `ty`: float types take any number,
+/// integer types the whole numbers in their range
+fn number_fits(number: &NumberLiteral, ty: &TypeInfo) -> bool {
+    match ty.numeric() {
+        Some(ty) if ty.is_float() => true,
+        Some(ty) => {
+            !number.is_float_literal
+                && number.value.fract() == 0.0
+                && ty
+                    .int_range()
+                    .is_some_and(|(min, max)| (min..=max).contains(&number.int_value()))
+        }
+        None => false,
+    }
+}
+
+fn range_hint(ty: &TypeInfo) -> String {
+    match ty.numeric().and_then(|numeric| numeric.int_range()) {
+        Some((min, max)) => format!("`{}` holds {} to {}", ty.display_name(), min, max),
+        None => format!("`{}` is not an integer type", ty.display_name()),
+    }
+}
+
+fn is_whole_number_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(Literal::Number(number)) => {
+            !number.is_float_literal && number.value.fract() == 0.0
+        }
+        Expr::Unary {
+            op: ast::nodes::UnaryOp::Neg | ast::nodes::UnaryOp::BitNot,
+            expr,
+        } => is_whole_number_literal(expr),
+        _ => false,
+    }
+}
+
+/// Whether a constant's value can be stored as `ty`; whole numbers fit the
+/// integer types whose range holds them
+fn constant_fits(value: &Constant, ty: &TypeInfo) -> bool {
+    match (value, ty) {
+        (_, TypeInfo::Unknown) => true,
+        (Constant::Bool(_), TypeInfo::Bool) | (Constant::Str(_), TypeInfo::Str) => true,
+        (Constant::Int(_) | Constant::Float(_), TypeInfo::F64) => true,
+        (value, TypeInfo::I32) => value
+            .as_int()
+            .is_some_and(|value| i32::try_from(value).is_ok()),
+        (value, TypeInfo::I64) => value.as_int().is_some(),
+        (Constant::Int(_) | Constant::Float(_), TypeInfo::F32) => true,
+        (value, ty) if ty.is_integer() => value.as_int().is_some_and(|value| {
+            ty.numeric()
+                .and_then(NumericType::int_range)
+                .is_some_and(|(min, max)| (min..=max).contains(&(value as i128)))
+        }),
+        _ => false,
+    }
+}
+
+fn constant_display(value: &Constant) -> String {
+    match value {
+        Constant::Str(text) => format!("{:?}", text),
+        other => other.to_text(),
+    }
+}
+
+/// The qualified name of the function `expr` calls, such as `net.dial`
+fn call_name(expr: &Expr) -> Option<String> {
+    let Expr::Call { func, .. } = expr else {
+        return None;
+    };
+    match func.as_ref() {
+        Expr::Identifier { name, .. } => Some(name.clone()),
+        Expr::Member { object, field } => match object.as_ref() {
+            Expr::Identifier { name, .. } => Some(format!("{}.{}", name, field)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `Iterator<element>`, the type of a generator call
+fn iterator_of(element: TypeInfo) -> TypeInfo {
+    TypeInfo::Generic {
+        base: "Iterator".to_string(),
+        args: vec![element],
+    }
+}
+
+/// The element type of an `Iterator<T>`
+fn iterator_element(ty: &TypeInfo) -> Option<TypeInfo> {
+    match ty {
+        TypeInfo::Generic { base, args } if base == "Iterator" => {
+            Some(args.first().cloned().unwrap_or(TypeInfo::Unknown))
+        }
+        _ => None,
+    }
+}
+
+/// A method signature without its leading `self` parameter
+fn without_self(signature: TypeInfo) -> TypeInfo {
+    match signature {
+        TypeInfo::Function {
+            mut params,
+            mut param_defaults,
+            return_type,
+        } if !params.is_empty() => {
+            params.remove(0);
+            param_defaults.remove(0);
+            TypeInfo::Function {
+                params,
+                param_defaults,
+                return_type,
+            }
+        }
+        other => other,
+    }
+}
+
+/// Bind the generic parameters that occur in `param` to the matching parts
+/// of `arg`; the first binding of each parameter wins
+fn bind_generic_params(
+    param: &TypeInfo,
+    arg: &TypeInfo,
+    generics: &[GenericParam],
+    bindings: &mut HashMap<String, TypeInfo>,
+) {
+    match (param, arg) {
+        (_, TypeInfo::Unknown) => {}
+        (TypeInfo::Generic { base, args }, _)
+            if args.is_empty() && generics.iter().any(|generic| generic.name == *base) =>
+        {
+            bindings.entry(base.clone()).or_insert_with(|| arg.clone());
+        }
+        (TypeInfo::List(param), TypeInfo::List(arg)) => {
+            bind_generic_params(param, arg, generics, bindings)
+        }
+        (
+            TypeInfo::Dict { key, value },
+            TypeInfo::Dict {
+                key: arg_key,
+                value: arg_value,
+            },
+        ) => {
+            bind_generic_params(key, arg_key, generics, bindings);
+            bind_generic_params(value, arg_value, generics, bindings);
+        }
+        (TypeInfo::Tuple(params), TypeInfo::Tuple(args)) => {
+            for (param, arg) in params.iter().zip(args) {
+                bind_generic_params(param, arg, generics, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Position written in a tuple subscript. Tuple elements can have different
+/// types, so only integer literals (optionally negated) are accepted.
+pub(crate) fn tuple_position(index: &Expr) -> Option<i64> {
+    match index {
+        Expr::Literal(Literal::Number(n)) if !n.is_float_literal && n.value.fract() == 0.0 => {
+            Some(n.value as i64)
+        }
+        Expr::Unary {
+            op: ast::nodes::UnaryOp::Neg,
+            expr,
+        } => tuple_position(expr).map(|position| -position),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2210,4 +3762,489 @@ mod tests {
         let ty = checker.infer_expr_type(&expr).unwrap();
         assert_eq!(ty, TypeInfo::F64);
     }
+
+    #[test]
+    fn test_let_annotation_must_match_initializer() {
+        let tokens = lexer::tokenize("let name: str = 1.5\n").unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        assert!(checker.errors().iter().any(|error| error
+            .message
+            .contains("cannot initialize name of type str with f64")));
+    }
+
+    #[test]
+    fn test_index_and_member_assignment_targets() {
+        let source = "struct Point:\n    x: float\n\ndef main():\n    let scores = {\"a\": 1.5}\n    let names = [\"otter\"]\n    let p = Point(x=1.0)\n    scores[\"b\"] += 2.0\n    let initial: str = names[0][:1]\n    names[0] = 1.5\n    names[0][0] = \"O\"\n    let missing = scores[1.0]\n    let first = names[\"x\"]\n    p.x = \"far\"\n";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot assign f64 to names[0] (expected str)",
+                "cannot assign to an index of str",
+                "dict key must be str, got f64",
+                "index must be a number, got str",
+                "cannot assign str to p.x (expected f64)",
+            ]
+        );
+
+        // Subscript errors point at the subscript
+        let span_text = |message: &str| {
+            checker
+                .errors()
+                .iter()
+                .find(|error| error.message == message)
+                .and_then(|error| error.span)
+                .map(|span| span.text(source))
+        };
+        assert_eq!(span_text("dict key must be str, got f64"), Some("1.0"));
+        assert_eq!(span_text("index must be a number, got str"), Some("\"x\""));
+    }
+
+    #[test]
+    fn test_tuples_and_destructuring() {
+        let source = "def divmod(a: float, b: float) -> (float, float):\n    return a / b, a - b\n\ndef main():\n    let (q, r) = divmod(7, 2)\n    let total: float = q + r\n    let pair = (1.5, \"x\")\n    let label: str = pair[1]\n    let last: str = pair[-1]\n    let bad = pair[2]\n    let (a, b, c) = pair\n    let scores = {\"a\": 1.5}\n    for name, score in scores.items():\n        let s: float = score\n        let n: str = name\n    pair[0] = 2.0\n";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "tuple index 2 is out of range for (f64, str)",
+                "tuple pattern has 3 element(s), but the value has type (f64, str)",
+                "cannot assign to an element of a tuple",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_traits_impls_and_bounds() {
+        let source = "trait Ord:\n    def cmp(self, other: Self) -> float\n    def max(self, other: Self) -> Self:\n        if self.cmp(other) > 0:\n            return self\n        return other\n\nimpl Ord for float:\n    def cmp(self, other: float) -> float:\n        return self - other\n\nimpl Ord for str:\n    def len(self) -> float:\n        return 0\n\ndef biggest<T: Ord>(a: T, b: T) -> T:\n    let label = a.show()\n    return a.max(b)\n\ndef main():\n    let big: float = biggest(1.5, 2.5)\n    let other: float = big.max(3.5)\n    let flag = biggest(true, false)\n";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "method `len` is not a member of trait `Ord`",
+                "`impl Ord for str` is missing method `cmp`",
+                "no method 'show' on type parameter `T`",
+                "type bool does not implement trait `Ord`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bitwise_operators_require_integers() {
+        let source = "def pack(flags: int) -> int:\n    let low = flags & 15\n    let mask = 1 << 4 | ~flags ^ -2\n    mask >>= 1\n    let half = 2.5\n    let bad = half | 1\n    let frac = 1.0 << 2\n    let p: float = half ** 2 // 1\n    return low | mask\n";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "bitwise operations require integer operands, got f64 and f64",
+                "bitwise operations require integer operands, got f64 and f64",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generators_and_iterator_protocol() {
+        let source = "\
+struct Countdown:
+    start: float
+
+    def __next__(self) -> Option<float>:
+        return None
+
+struct Bag:
+    items: List<str>
+
+    def __iter__(self) -> Iterator<str>:
+        for item in self.items:
+            yield item
+
+def upto(limit: float) -> Iterator<float>:
+    for i in 0..limit:
+        yield i
+    return
+
+def words():
+    yield \"a\"
+
+def labels(bag: Bag) -> Iterator<str>:
+    for item in bag:
+        yield item
+    yield 1.5
+
+def bad() -> int:
+    yield 1
+
+def stop(n: int) -> Iterator<int>:
+    yield n
+    return n
+
+def main():
+    for n in upto(10):
+        let m: float = n
+    let halves = [n / 2 for n in upto(4)]
+    for c in Countdown(start=3):
+        let k: float = c
+    for w in words():
+        print(w)
+    for x in 2.5:
+        pass
+";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "yield type mismatch: expected str, got f64",
+                "generator `bad` must return Iterator<T>, not i32",
+                "cannot return a value from a generator",
+                "cannot iterate over type f64",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_statements() {
+        let source = "\
+struct Session:
+    name: str
+
+    def __enter__(self) -> str:
+        return self.name
+
+    def __exit__(self, error: str):
+        pass
+
+struct Plain:
+    value: float
+
+    def __exit__(self):
+        pass
+
+struct Broken:
+    value: float
+
+    def __exit__(self, first: float, second: float):
+        pass
+
+def main():
+    with Session(name=\"db\") as label:
+        let s: str = label
+    with Plain(value=1.0) as plain:
+        let p: Plain = plain
+    with Broken(value=1.0):
+        pass
+    with 2.5:
+        pass
+";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "`__exit__` of Broken must take no arguments or an error message",
+                "type f64 cannot be used in a `with` statement",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constants() {
+        let source = "\
+const def kib(n: float) -> float:
+    return n * 1024
+
+def now() -> float:
+    return 0.0
+
+const BUFFER: int = 4 * 1024
+const LIMIT: float = kib(64.0)
+const NAME: str = \"otter\"
+const GREETING: str = f\"{NAME} uses {BUFFER} bytes\"
+const RATIO: int = 2.5
+const STARTED: float = now()
+
+def main():
+    let size: int = BUFFER
+    let text: str = GREETING
+    BUFFER = 8
+";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot initialize constant RATIO of type i32 with f64",
+                "constant `STARTED` is not initialized with a constant expression: `now` is not a `const def` function",
+                "cannot assign to constant `BUFFER`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_values_and_closures() {
+        let source = "\
+struct Button:
+    label: str
+    on_click: fn(str) -> float
+
+def apply(f: fn(float) -> float, x: float) -> float:
+    return f(x)
+
+def main():
+    let step = 2.5
+    let add = lambda (x: float): x + step
+    let scaled: float = apply(add, 1.0)
+    let handlers: list<fn(float) -> float> = [add, lambda (x: float) -> float: x * step]
+    let first: float = handlers[0](3.0)
+    let button = Button(label=\"ok\", on_click=lambda (name: str) -> float: 1.0)
+    let clicks: float = button.on_click(\"ok\")
+    let label: str = add(1.0)
+    let broken = lambda (x: float) -> str: x + step
+    let wrong: float = handlers[1](\"x\")
+";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages: Vec<&str> = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot initialize label of type str with f64",
+                "lambda returns f64, but its signature declares str",
+                "argument 1 type mismatch: expected f64, got str",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_patterns_and_exhaustiveness() {
+        let source = "\
+enum Option<T>:
+    Some: (T)
+    None
+
+enum Color:
+    Red
+    Green
+    Blue
+
+def name(c: Color) -> str:
+    return match c:
+        case Color.Red | Color.Green:
+            \"warm\"
+
+def size(n: float) -> str:
+    return match n:
+        case 0..10:
+            \"small\"
+        case 3 | 4:
+            \"unreachable\"
+        case _:
+            \"big\"
+
+def flags(p: (bool, Option<bool>)) -> str:
+    return match p:
+        case (true, _):
+            \"on\"
+        case (_, Some(false) | None) as whole:
+            \"off\"
+
+def items(xs: list<str>, path: str, m: dict<str, str>) -> str:
+    let first = match xs:
+        case []:
+            \"\"
+        case [x] | [_, y]:
+            x
+        case [a, _, _]..rest:
+            a
+    let route = match path:
+        case \"GET \"..rest:
+            rest
+        case \"GET /\":
+            \"root\"
+        case other:
+            other
+    return match m:
+        case {\"id\": id, \"name\": n}:
+            n
+        case {\"id\": \"0\"..x}:
+            x
+        case {}:
+            first
+";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+
+        let check = |features: LanguageFeatureFlags| {
+            let mut checker = TypeChecker::with_language_features(features);
+            let _ = checker.check_program(&program);
+            checker
+                .errors()
+                .iter()
+                .map(|error| error.message.clone())
+                .collect::<Vec<_>>()
+        };
+        let messages = check(LanguageFeatureFlags::default());
+        assert_eq!(
+            messages,
+            ["or-pattern alternatives bind different names: [x] and [y]"]
+        );
+
+        let messages = check(LanguageFeatureFlags {
+            match_exhaustiveness: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            messages,
+            [
+                "non-exhaustive match: `Color.Blue` is not covered",
+                "match arm 2 is unreachable",
+                "non-exhaustive match: `(false, Option.Some(true))` is not covered",
+                "or-pattern alternatives bind different names: [x] and [y]",
+                "match arm 2 is unreachable",
+            ]
+        );
+    }
+
+    #[test]
+    fn sized_numbers_convert_explicitly_and_keep_literals_in_range() {
+        let source = "
+def checksum(data: list<u8>, seed: u32) -> u32:
+    let sum: u32 = seed
+    for byte in data:
+        sum = sum.wrapping_add(byte as u32)
+    return sum ^ 65535
+
+def widen(a: u8, b: i16, c: f32) -> f64:
+    let x: u16 = a
+    let y: i32 = b + 1
+    let z = 255u8 - a
+    let big: u64 = 18446744073709551615
+    return c + 1.5 + (x as f64) + (y as f64) + (z as f64) + (big as f64)
+
+def mistakes(a: u8, b: i8, c: u64):
+    let w = a + b
+    let x: u8 = 256
+    let y = 300u8
+    let z = -c
+    let f = \"1\" as u8
+    let g: i8 = a
+    let ok = -128i8 + b % 3
+";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "cannot apply Add to u8 and i8",
+                "cannot initialize x of type u8 with f64",
+                "literal 300 is out of range for u8",
+                "cannot negate a value of unsigned type u64",
+                "cannot convert str to u8 with `as`",
+                "cannot initialize g of type i8 with u8",
+            ]
+        );
+    }
+
+    #[test]
+    fn integer_literal_ranges_are_checked_exactly() {
+        // Each literal past a bound rounds to the bound itself as an `f64`
+        let source = "
+def edges():
+    let top = 18446744073709551615u64
+    let past_top = 18446744073709551616u64
+    let max = 9223372036854775807i64
+    let past_max = 9223372036854775808i64
+    let min = -9223372036854775808i64
+    let past_min = -9223372036854775809i64
+    let unsuffixed_top: u64 = 18446744073709551615
+    let unsuffixed_past_top: u64 = 18446744073709551616
+    let odd: i64 = 9007199254740993
+";
+        let tokens = lexer::tokenize(source).unwrap();
+        let program = parser::parse(&tokens).unwrap();
+        let mut checker = TypeChecker::new();
+        let _ = checker.check_program(&program);
+        let messages = checker
+            .errors()
+            .iter()
+            .map(|error| error.message.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "literal 18446744073709551616 is out of range for u64",
+                "literal 9223372036854775808 is out of range for i64",
+                "literal -9223372036854775809 is out of range for i64",
+                "cannot initialize unsuffixed_past_top of type u64 with f64",
+            ]
+        );
+    }
 }
@@ -0,0 +1,496 @@
+//! Exhaustiveness and reachability of `match` arms
+//!
+//! Patterns are lowered to constructor trees over the matched type and run
+//! through the usefulness algorithm: an arm is unreachable when every value it
+//! matches is matched by an unguarded arm above it, and a match is exhaustive
+//! when a trailing `case _` would be unreachable. Numbers, strings and dicts
+//! have no finite set of constructors, so literal, range, prefix and dict
+//! patterns never cover such a type on their own; one range or prefix still
+//! makes later arms inside it unreachable.
+
+use ast::nodes::{Literal, MatchArm, Pattern};
+
+use crate::typecheck::types::{TypeContext, TypeInfo};
+
+/// Findings for one `match` expression
+#[derive(Debug, Default)]
+pub struct MatchReport {
+    /// Indices of arms no value can reach
+    pub unreachable: Vec<usize>,
+    /// A value no arm matches, written as a pattern
+    pub missing: Option<String>,
+}
+
+/// Check the arms of a match over a value of type `ty`
+pub fn check_match(context: &TypeContext, ty: &TypeInfo, arms: &[MatchArm]) -> MatchReport {
+    let analysis = Analysis { context };
+    let ty = analysis.resolve(ty);
+    let mut report = MatchReport::default();
+    let mut rows: Vec<Vec<Pat>> = Vec::new();
+    for (index, arm) in arms.iter().enumerate() {
+        let row = vec![analysis.lower(&arm.pattern, &ty)];
+        if analysis
+            .useful(&rows, &row, std::slice::from_ref(&ty))
+            .is_none()
+        {
+            report.unreachable.push(index);
+        }
+        // A guard may reject the value, so a guarded arm covers nothing
+        if arm.guard.is_none() {
+            rows.push(row);
+        }
+    }
+    report.missing = analysis
+        .useful(&rows, &[Pat::Wild], std::slice::from_ref(&ty))
+        .and_then(|mut witness| witness.pop());
+    report
+}
+
+/// A way of building a value, as far as matching can tell values apart
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Bool(bool),
+    Variant(String),
+    /// The only constructor of tuples and structs
+    Single,
+    /// A list of exactly this length
+    Len(usize),
+    /// A list of at least this length
+    MinLen(usize),
+    Number(f64),
+    /// Half-open `start..end`
+    Range(f64, f64),
+    Str(String),
+    Prefix(String),
+    /// A dict shape, which covers no other pattern
+    Opaque,
+}
+
+impl Ctor {
+    /// Whether every value this constructor builds is also built by `other`,
+    /// for constructors of types without a finite constructor set
+    fn covers(&self, other: &Ctor) -> bool {
+        match (self, other) {
+            (Ctor::Number(a), Ctor::Number(b)) => a == b,
+            (Ctor::Range(start, end), Ctor::Number(value)) => start <= value && value < end,
+            (Ctor::Range(start, end), Ctor::Range(inner_start, inner_end)) => {
+                start <= inner_start && inner_end <= end
+            }
+            (Ctor::Str(a), Ctor::Str(b)) => a == b,
+            (Ctor::Prefix(prefix), Ctor::Str(value) | Ctor::Prefix(value)) => {
+                value.starts_with(prefix.as_str())
+            }
+            (Ctor::Opaque, _) => false,
+            (a, b) => a == b,
+        }
+    }
+
+    fn is_list(&self) -> bool {
+        matches!(self, Ctor::Len(_) | Ctor::MinLen(_))
+    }
+}
+
+/// A pattern reduced to what matters for coverage; bindings are wildcards
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+struct Analysis<'a> {
+    context: &'a TypeContext,
+}
+
+impl Analysis<'_> {
+    /// Concrete form of `ty`, looking through generics and aliases
+    fn resolve(&self, ty: &TypeInfo) -> TypeInfo {
+        match self.context.normalize_type(ty.clone()) {
+            TypeInfo::Alias { underlying, .. } => self.resolve(&underlying),
+            other => other,
+        }
+    }
+
+    fn lower(&self, pattern: &Pattern, ty: &TypeInfo) -> Pat {
+        match pattern {
+            Pattern::Wildcard | Pattern::Identifier(_) => Pat::Wild,
+            Pattern::Binding { pattern, .. } => self.lower(pattern, ty),
+            Pattern::Or(alternatives) => Pat::Or(
+                alternatives
+                    .iter()
+                    .map(|alternative| self.lower(alternative, ty))
+                    .collect(),
+            ),
+            Pattern::Literal(literal) => match literal {
+                Literal::Bool(value) => Pat::Ctor(Ctor::Bool(*value), Vec::new()),
+                Literal::Number(number) => Pat::Ctor(Ctor::Number(number.value), Vec::new()),
+                Literal::String(value) => Pat::Ctor(Ctor::Str(value.clone()), Vec::new()),
+                Literal::None | Literal::Unit => Pat::Wild,
+            },
+            Pattern::Range { start, end } => match (start, end) {
+                (Literal::Number(start), Literal::Number(end)) => {
+                    Pat::Ctor(Ctor::Range(start.value, end.value), Vec::new())
+                }
+                _ => Pat::Ctor(Ctor::Opaque, Vec::new()),
+            },
+            Pattern::StringPrefix { prefix, .. } if prefix.is_empty() => Pat::Wild,
+            Pattern::StringPrefix { prefix, .. } => {
+                Pat::Ctor(Ctor::Prefix(prefix.clone()), Vec::new())
+            }
+            Pattern::Dict(entries) if entries.is_empty() => Pat::Wild,
+            Pattern::Dict(_) => Pat::Ctor(Ctor::Opaque, Vec::new()),
+            Pattern::Tuple(items) => {
+                let fields = self.fields(&Ctor::Single, ty, items.len());
+                Pat::Ctor(
+                    Ctor::Single,
+                    items
+                        .iter()
+                        .zip(&fields)
+                        .map(|(item, field)| self.lower(item, field))
+                        .collect(),
+                )
+            }
+            Pattern::Struct { fields, .. } => {
+                let TypeInfo::Struct {
+                    fields: field_types,
+                    ..
+                } = ty
+                else {
+                    return Pat::Wild;
+                };
+                let args = sorted_fields(field_types)
+                    .into_iter()
+                    .map(
+                        |(name, field_ty)| match fields.iter().find(|(field, _)| field == name) {
+                            Some((_, Some(pattern))) => {
+                                self.lower(pattern, &self.resolve(field_ty))
+                            }
+                            _ => Pat::Wild,
+                        },
+                    )
+                    .collect();
+                Pat::Ctor(Ctor::Single, args)
+            }
+            Pattern::EnumVariant {
+                variant, fields, ..
+            } => {
+                let ctor = Ctor::Variant(variant.clone());
+                let field_types = self.fields(&ctor, ty, fields.len());
+                Pat::Ctor(
+                    ctor,
+                    fields
+                        .iter()
+                        .zip(&field_types)
+                        .map(|(field, field_ty)| self.lower(field, field_ty))
+                        .collect(),
+                )
+            }
+            Pattern::Array { patterns, rest } => {
+                let element = match ty {
+                    TypeInfo::List(element) => self.resolve(element),
+                    _ => TypeInfo::Unknown,
+                };
+                let ctor = if rest.is_some() {
+                    Ctor::MinLen(patterns.len())
+                } else {
+                    Ctor::Len(patterns.len())
+                };
+                Pat::Ctor(
+                    ctor,
+                    patterns
+                        .iter()
+                        .map(|pattern| self.lower(pattern, &element))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// Types of the values a constructor of `ty` holds; `arity` stands in
+    /// when the type is not known
+    fn fields(&self, ctor: &Ctor, ty: &TypeInfo, arity: usize) -> Vec<TypeInfo> {
+        let fields = match (ctor, ty) {
+            (Ctor::Single, TypeInfo::Tuple(elements)) => elements.clone(),
+            (Ctor::Single, TypeInfo::Struct { fields, .. }) => sorted_fields(fields)
+                .into_iter()
+                .map(|(_, field)| field.clone())
+                .collect(),
+            (Ctor::Variant(variant), TypeInfo::Enum { variants, .. }) => variants
+                .get(variant)
+                .map(|info| info.fields.clone())
+                .unwrap_or_default(),
+            (Ctor::Len(len) | Ctor::MinLen(len), TypeInfo::List(element)) => {
+                vec![(**element).clone(); *len]
+            }
+            _ => Vec::new(),
+        };
+        if fields.len() == arity {
+            fields.iter().map(|field| self.resolve(field)).collect()
+        } else {
+            vec![TypeInfo::Unknown; arity]
+        }
+    }
+
+    /// Every constructor of `ty`, or `None` when there are too many to list.
+    /// `heads` are the constructors the arms use, which size the list
+    /// constructors and stand in for types that are not known.
+    fn constructors(&self, ty: &TypeInfo, heads: &[(Ctor, usize)]) -> Option<Vec<Ctor>> {
+        match ty {
+            TypeInfo::Bool => Some(vec![Ctor::Bool(true), Ctor::Bool(false)]),
+            TypeInfo::Tuple(_) | TypeInfo::Struct { .. } => Some(vec![Ctor::Single]),
+            TypeInfo::Enum { name, variants, .. } => {
+                let names = match self.context.get_enum(name) {
+                    Some(definition) => definition
+                        .variants
+                        .iter()
+                        .map(|variant| variant.name.clone())
+                        .collect(),
+                    None => {
+                        let mut names: Vec<String> = variants.keys().cloned().collect();
+                        names.sort();
+                        names
+                    }
+                };
+                Some(names.into_iter().map(Ctor::Variant).collect())
+            }
+            TypeInfo::List(_) => {
+                let longest = longest_list(heads.iter().map(|(ctor, _)| ctor));
+                let mut ctors: Vec<Ctor> = (0..=longest).map(Ctor::Len).collect();
+                ctors.push(Ctor::MinLen(longest + 1));
+                Some(ctors)
+            }
+            TypeInfo::Unknown | TypeInfo::Error | TypeInfo::Generic { .. } => {
+                // Assume the arms name every constructor rather than report
+                // values of a type the checker could not work out
+                let finite = heads.iter().all(|(ctor, _)| {
+                    matches!(ctor, Ctor::Bool(_) | Ctor::Variant(_) | Ctor::Single)
+                });
+                finite.then(|| heads.iter().map(|(ctor, _)| ctor.clone()).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Witness that `row` matches a value none of `rows` match, one pattern
+    /// per column, or `None` if `rows` already cover it
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat], tys: &[TypeInfo]) -> Option<Vec<String>> {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        let ty = &tys[0];
+        match head {
+            Pat::Or(alternatives) => alternatives.iter().find_map(|alternative| {
+                self.useful(rows, &with_head(alternative.clone(), rest), tys)
+            }),
+            // `[a]..rest` is every exact length the other arms name plus
+            // everything longer
+            Pat::Ctor(Ctor::MinLen(min), args) => {
+                let heads = column_heads(rows);
+                let longest = longest_list(heads.iter().map(|(ctor, _)| ctor)).max(*min);
+                let pad = |len: usize| {
+                    let mut args = args.clone();
+                    args.resize(len, Pat::Wild);
+                    args
+                };
+                let mut alternatives: Vec<Pat> = (*min..=longest)
+                    .map(|len| Pat::Ctor(Ctor::Len(len), pad(len)))
+                    .collect();
+                alternatives.push(Pat::Ctor(Ctor::MinLen(longest + 1), pad(longest + 1)));
+                self.useful(rows, &with_head(Pat::Or(alternatives), rest), tys)
+            }
+            Pat::Ctor(ctor, args) => self.useful_ctor(rows, ctor, args.clone(), rest, tys),
+            Pat::Wild => {
+                let heads = column_heads(rows);
+                let all = self.constructors(ty, &heads);
+                let complete = all.as_ref().filter(|all| {
+                    !heads.is_empty()
+                        && all
+                            .iter()
+                            .all(|ctor| heads.iter().any(|(head, _)| head_covers(head, ctor)))
+                });
+                if let Some(all) = complete {
+                    return all.iter().find_map(|ctor| {
+                        let arity = ctor_arity(ctor, ty, &heads);
+                        self.useful_ctor(rows, ctor, vec![Pat::Wild; arity], rest, tys)
+                    });
+                }
+                let default: Vec<Vec<Pat>> = rows
+                    .iter()
+                    .flat_map(|row| expand_or(row))
+                    .filter(|row| matches!(row[0], Pat::Wild))
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                let mut witness = self.useful(&default, rest, &tys[1..])?;
+                let missing = all
+                    .filter(|_| !heads.is_empty())
+                    .and_then(|all| {
+                        all.into_iter()
+                            .find(|ctor| !heads.iter().any(|(head, _)| head_covers(head, ctor)))
+                    })
+                    .map(|ctor| {
+                        let arity = ctor_arity(&ctor, ty, &heads);
+                        display(&ctor, ty, vec!["_".to_string(); arity])
+                    })
+                    .unwrap_or_else(|| "_".to_string());
+                witness.insert(0, missing);
+                Some(witness)
+            }
+        }
+    }
+
+    /// Usefulness of a row headed by `ctor(args)`
+    fn useful_ctor(
+        &self,
+        rows: &[Vec<Pat>],
+        ctor: &Ctor,
+        args: Vec<Pat>,
+        rest: &[Pat],
+        tys: &[TypeInfo],
+    ) -> Option<Vec<String>> {
+        let arity = args.len();
+        let specialized: Vec<Vec<Pat>> = rows
+            .iter()
+            .flat_map(|row| expand_or(row))
+            .filter_map(|row| specialize(&row, ctor, arity))
+            .collect();
+        let mut row = args;
+        row.extend(rest.iter().cloned());
+        let mut field_tys = self.fields(ctor, &tys[0], arity);
+        field_tys.extend(tys[1..].iter().cloned());
+        let mut witness = self.useful(&specialized, &row, &field_tys)?;
+        let fields = witness.drain(..arity).collect();
+        witness.insert(0, display(ctor, &tys[0], fields));
+        Some(witness)
+    }
+}
+
+/// Struct fields in a stable order
+fn sorted_fields(
+    fields: &std::collections::HashMap<String, TypeInfo>,
+) -> Vec<(&String, &TypeInfo)> {
+    let mut fields: Vec<_> = fields.iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    fields
+}
+
+fn with_head(head: Pat, rest: &[Pat]) -> Vec<Pat> {
+    let mut row = vec![head];
+    row.extend(rest.iter().cloned());
+    row
+}
+
+/// One row per alternative of an or-pattern in the first column
+fn expand_or(row: &[Pat]) -> Vec<Vec<Pat>> {
+    match row.first() {
+        Some(Pat::Or(alternatives)) => alternatives
+            .iter()
+            .flat_map(|alternative| expand_or(&with_head(alternative.clone(), &row[1..])))
+            .collect(),
+        _ => vec![row.to_vec()],
+    }
+}
+
+/// Constructors heading the first column, with their arity
+fn column_heads(rows: &[Vec<Pat>]) -> Vec<(Ctor, usize)> {
+    let mut heads: Vec<(Ctor, usize)> = Vec::new();
+    for row in rows.iter().flat_map(|row| expand_or(row)) {
+        if let Some(Pat::Ctor(ctor, args)) = row.first() {
+            if !heads.iter().any(|(head, _)| head == ctor) {
+                heads.push((ctor.clone(), args.len()));
+            }
+        }
+    }
+    heads
+}
+
+fn longest_list<'a>(ctors: impl Iterator<Item = &'a Ctor>) -> usize {
+    ctors
+        .filter_map(|ctor| match ctor {
+            Ctor::Len(len) | Ctor::MinLen(len) => Some(*len),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Whether a row headed by `head` matches every value `ctor` builds
+fn head_covers(head: &Ctor, ctor: &Ctor) -> bool {
+    match (head, ctor) {
+        (Ctor::MinLen(min), Ctor::Len(len) | Ctor::MinLen(len)) => min <= len,
+        (head, ctor) if head.is_list() => head == ctor,
+        (head, ctor) => head.covers(ctor),
+    }
+}
+
+/// Number of fields `ctor` holds in a value of type `ty`
+fn ctor_arity(ctor: &Ctor, ty: &TypeInfo, heads: &[(Ctor, usize)]) -> usize {
+    match (ctor, ty) {
+        (Ctor::Len(len) | Ctor::MinLen(len), _) => *len,
+        (Ctor::Single, TypeInfo::Tuple(elements)) => elements.len(),
+        (Ctor::Single, TypeInfo::Struct { fields, .. }) => fields.len(),
+        (Ctor::Variant(variant), TypeInfo::Enum { variants, .. }) => {
+            variants.get(variant).map_or(0, |info| info.fields.len())
+        }
+        _ => heads
+            .iter()
+            .find(|(head, _)| head == ctor)
+            .map_or(0, |(_, arity)| *arity),
+    }
+}
+
+/// The rest of `row` if its head matches every value `ctor` builds, with
+/// the head's fields spliced in
+fn specialize(row: &[Pat], ctor: &Ctor, arity: usize) -> Option<Vec<Pat>> {
+    let (head, rest) = row.split_first()?;
+    let mut fields = match head {
+        Pat::Wild => vec![Pat::Wild; arity],
+        Pat::Ctor(head, args) if head_covers(head, ctor) => {
+            let mut args = args.clone();
+            args.resize(arity, Pat::Wild);
+            args
+        }
+        _ => return None,
+    };
+    fields.extend(rest.iter().cloned());
+    Some(fields)
+}
+
+/// Render a constructor applied to rendered fields as OtterLang pattern syntax
+fn display(ctor: &Ctor, ty: &TypeInfo, fields: Vec<String>) -> String {
+    match ctor {
+        Ctor::Bool(value) => value.to_string(),
+        Ctor::Variant(variant) => {
+            let name = match ty {
+                TypeInfo::Enum { name, .. } => format!("{}.{}", name, variant),
+                _ => variant.clone(),
+            };
+            if fields.is_empty() {
+                name
+            } else {
+                format!("{}({})", name, fields.join(", "))
+            }
+        }
+        Ctor::Single => match ty {
+            TypeInfo::Struct {
+                name,
+                fields: field_types,
+            } => {
+                let fields: Vec<String> = sorted_fields(field_types)
+                    .into_iter()
+                    .zip(fields)
+                    .map(|((field, _), pattern)| format!("{}: {}", field, pattern))
+                    .collect();
+                format!("{} {{ {} }}", name, fields.join(", "))
+            }
+            _ if fields.len() == 1 => format!("({},)", fields[0]),
+            _ => format!("({})", fields.join(", ")),
+        },
+        Ctor::Len(_) => format!("[{}]", fields.join(", ")),
+        Ctor::MinLen(_) => format!("[{}].._", fields.join(", ")),
+        Ctor::Number(value) => value.to_string(),
+        Ctor::Range(start, end) => format!("{}..{}", start, end),
+        Ctor::Str(value) => format!("{:?}", value),
+        Ctor::Prefix(prefix) => format!("{:?}.._", prefix),
+        Ctor::Opaque => "_".to_string(),
+    }
+}
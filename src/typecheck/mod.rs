@@ -4,6 +4,7 @@
 
 pub mod checker;
 pub mod diagnostics;
+pub mod exhaustiveness;
 pub mod types;
 
 pub use checker::TypeChecker;
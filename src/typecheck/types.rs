@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use ast::nodes::{EnumVariant, Type};
+use ast::nodes::{EnumVariant, Function, GenericParam, NumericType, Type};
 use common::Span;
 
 use language::LanguageFeatureFlags;
@@ -18,10 +18,28 @@ pub enum TypeInfo {
     I64,
     /// 64-bit floating point
     F64,
+    /// 8-bit integer
+    I8,
+    /// 16-bit integer
+    I16,
+    /// 8-bit unsigned integer
+    U8,
+    /// 16-bit unsigned integer
+    U16,
+    /// 32-bit unsigned integer
+    U32,
+    /// 64-bit unsigned integer
+    U64,
+    /// Pointer-sized unsigned integer (64 bits)
+    Usize,
+    /// 32-bit floating point
+    F32,
     /// String type
     Str,
     /// List type with element type information
     List(Box<TypeInfo>),
+    /// Tuple type with one type per element
+    Tuple(Vec<TypeInfo>),
     /// Dictionary type with key/value types
     Dict {
         key: Box<TypeInfo>,
@@ -67,11 +85,61 @@ pub struct EnumVariantInfo {
 }
 
 impl TypeInfo {
+    /// The numeric type this is, for the integer and float types
+    pub fn numeric(&self) -> Option<NumericType> {
+        Some(match self {
+            TypeInfo::I8 => NumericType::I8,
+            TypeInfo::I16 => NumericType::I16,
+            TypeInfo::I32 => NumericType::I32,
+            TypeInfo::I64 => NumericType::I64,
+            TypeInfo::U8 => NumericType::U8,
+            TypeInfo::U16 => NumericType::U16,
+            TypeInfo::U32 => NumericType::U32,
+            TypeInfo::U64 => NumericType::U64,
+            TypeInfo::Usize => NumericType::Usize,
+            TypeInfo::F32 => NumericType::F32,
+            TypeInfo::F64 => NumericType::F64,
+            _ => return None,
+        })
+    }
+
+    pub fn from_numeric(ty: NumericType) -> TypeInfo {
+        match ty {
+            NumericType::I8 => TypeInfo::I8,
+            NumericType::I16 => TypeInfo::I16,
+            NumericType::I32 => TypeInfo::I32,
+            NumericType::I64 => TypeInfo::I64,
+            NumericType::U8 => TypeInfo::U8,
+            NumericType::U16 => TypeInfo::U16,
+            NumericType::U32 => TypeInfo::U32,
+            NumericType::U64 => TypeInfo::U64,
+            NumericType::Usize => TypeInfo::Usize,
+            NumericType::F32 => TypeInfo::F32,
+            NumericType::F64 => TypeInfo::F64,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.numeric().is_some_and(|ty| !ty.is_float())
+    }
+
     /// Check if this type is a generic type parameter
     pub fn is_generic_param(&self) -> bool {
         matches!(self, TypeInfo::Generic { base: _, args } if args.is_empty())
     }
 
+    /// Name under which methods implemented for this type are registered,
+    /// e.g. `Point.cmp` or `i32.cmp`
+    pub fn impl_key(&self) -> String {
+        match self {
+            TypeInfo::Struct { name, .. }
+            | TypeInfo::Enum { name, .. }
+            | TypeInfo::Alias { name, .. } => name.clone(),
+            TypeInfo::Generic { base, .. } => base.clone(),
+            other => other.display_name(),
+        }
+    }
+
     /// Substitute generic type parameters with concrete types
     pub fn substitute(&self, substitutions: &HashMap<String, TypeInfo>) -> TypeInfo {
         match self {
@@ -99,6 +167,12 @@ impl TypeInfo {
                 return_type: Box::new(return_type.substitute(substitutions)),
             },
             TypeInfo::List(element) => TypeInfo::List(Box::new(element.substitute(substitutions))),
+            TypeInfo::Tuple(elements) => TypeInfo::Tuple(
+                elements
+                    .iter()
+                    .map(|element| element.substitute(substitutions))
+                    .collect(),
+            ),
             TypeInfo::Dict { key, value } => TypeInfo::Dict {
                 key: Box::new(key.substitute(substitutions)),
                 value: Box::new(value.substitute(substitutions)),
@@ -157,14 +231,13 @@ impl TypeInfo {
             // Same types are compatible
             (TypeInfo::Unit, TypeInfo::Unit)
             | (TypeInfo::Bool, TypeInfo::Bool)
-            | (TypeInfo::I32, TypeInfo::I32)
-            | (TypeInfo::I64, TypeInfo::I64)
-            | (TypeInfo::F64, TypeInfo::F64)
             | (TypeInfo::Str, TypeInfo::Str) => true,
 
             // Numeric promotions
-            (TypeInfo::I32, TypeInfo::I64) | (TypeInfo::I32, TypeInfo::F64) => true,
-            (TypeInfo::I64, TypeInfo::F64) => true,
+            (from, to) if from.numeric().is_some() && to.numeric().is_some() => from
+                .numeric()
+                .zip(to.numeric())
+                .is_some_and(|(from, to)| numeric_widens(from, to)),
 
             // Unknown types are compatible with anything (during inference)
             (TypeInfo::Unknown, _) | (_, TypeInfo::Unknown) => true,
@@ -197,6 +270,9 @@ impl TypeInfo {
                         .all(|(t1, t2)| t1.is_compatible_with(t2))
             }
             (TypeInfo::List(elem1), TypeInfo::List(elem2)) => elem1.is_compatible_with(elem2),
+            (TypeInfo::Tuple(a), TypeInfo::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.is_compatible_with(y))
+            }
             (TypeInfo::Dict { key: k1, value: v1 }, TypeInfo::Dict { key: k2, value: v2 }) => {
                 k1.is_compatible_with(k2) && v1.is_compatible_with(v2)
             }
@@ -303,6 +379,14 @@ impl TypeInfo {
             TypeInfo::I32 => "i32".to_string(),
             TypeInfo::I64 => "i64".to_string(),
             TypeInfo::F64 => "f64".to_string(),
+            TypeInfo::I8 => "i8".to_string(),
+            TypeInfo::I16 => "i16".to_string(),
+            TypeInfo::U8 => "u8".to_string(),
+            TypeInfo::U16 => "u16".to_string(),
+            TypeInfo::U32 => "u32".to_string(),
+            TypeInfo::U64 => "u64".to_string(),
+            TypeInfo::Usize => "usize".to_string(),
+            TypeInfo::F32 => "f32".to_string(),
             TypeInfo::Str => "str".to_string(),
             TypeInfo::Function {
                 params,
@@ -317,6 +401,18 @@ impl TypeInfo {
                 format!("fn({}) -> {}", params_str, return_type.display_name())
             }
             TypeInfo::List(element) => format!("list<{}>", element.display_name()),
+            TypeInfo::Tuple(elements) => {
+                let elements_str = elements
+                    .iter()
+                    .map(|t| t.display_name())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if elements.len() == 1 {
+                    format!("({},)", elements_str)
+                } else {
+                    format!("({})", elements_str)
+                }
+            }
             TypeInfo::Dict { key, value } => {
                 format!("dict<{}, {}>", key.display_name(), value.display_name())
             }
@@ -370,9 +466,7 @@ impl From<&Type> for TypeInfo {
             Type::Simple(name) => match name.as_str() {
                 "unit" | "None" | "none" => TypeInfo::Unit,
                 "bool" => TypeInfo::Bool,
-                "i32" => TypeInfo::I32,
-                "i64" => TypeInfo::I64,
-                "f64" | "float" => TypeInfo::F64,
+                "float" => TypeInfo::F64,
                 "str" => TypeInfo::Str,
                 "list" | "List" => TypeInfo::List(Box::new(TypeInfo::Unknown)),
                 "dict" | "Dict" => TypeInfo::Dict {
@@ -380,9 +474,12 @@ impl From<&Type> for TypeInfo {
                     value: Box::new(TypeInfo::Unknown),
                 },
                 "Error" => TypeInfo::Error,
-                _ => TypeInfo::Generic {
-                    base: name.clone(),
-                    args: Vec::new(),
+                _ => match NumericType::from_name(name) {
+                    Some(ty) => TypeInfo::from_numeric(ty),
+                    None => TypeInfo::Generic {
+                        base: name.clone(),
+                        args: Vec::new(),
+                    },
                 },
             },
             Type::Generic { base, args } => match base.as_str() {
@@ -403,6 +500,12 @@ impl From<&Type> for TypeInfo {
                     args: args.iter().map(|t| t.into()).collect(),
                 },
             },
+            Type::Tuple(elements) => TypeInfo::Tuple(elements.iter().map(TypeInfo::from).collect()),
+            Type::Function { params, ret } => TypeInfo::Function {
+                params: params.iter().map(TypeInfo::from).collect(),
+                param_defaults: vec![false; params.len()],
+                return_type: Box::new(ret.as_deref().map(TypeInfo::from).unwrap_or(TypeInfo::Unit)),
+            },
         }
     }
 }
@@ -412,23 +515,38 @@ impl From<&str> for TypeInfo {
         match name {
             "unit" => TypeInfo::Unit,
             "bool" => TypeInfo::Bool,
-            "i32" => TypeInfo::I32,
-            "i64" => TypeInfo::I64,
-            "f64" => TypeInfo::F64,
             "str" => TypeInfo::Str,
             "list" | "List" => TypeInfo::List(Box::new(TypeInfo::Unknown)),
             "dict" | "Dict" => TypeInfo::Dict {
                 key: Box::new(TypeInfo::Unknown),
                 value: Box::new(TypeInfo::Unknown),
             },
-            _ => TypeInfo::Generic {
-                base: name.to_string(),
-                args: Vec::new(),
+            _ => match NumericType::from_name(name) {
+                Some(ty) => TypeInfo::from_numeric(ty),
+                None => TypeInfo::Generic {
+                    base: name.to_string(),
+                    args: Vec::new(),
+                },
             },
         }
     }
 }
 
+/// Whether every value of `from` is also a value of `to`, so it converts
+/// implicitly. Integers also widen to `f64`, as `i64` always has.
+pub(crate) fn numeric_widens(from: NumericType, to: NumericType) -> bool {
+    if from == to || to == NumericType::F64 {
+        return true;
+    }
+    match (from.is_float(), to.is_float()) {
+        (true, _) => false,
+        (false, true) => from.bits() <= 16,
+        (false, false) if from.is_signed() => to.is_signed() && to.bits() >= from.bits(),
+        (false, false) if to.is_signed() => to.bits() > from.bits(),
+        (false, false) => to.bits() >= from.bits(),
+    }
+}
+
 /// Type checking error
 #[derive(Debug, Clone)]
 pub struct TypeError {
@@ -489,6 +607,8 @@ impl std::error::Error for TypeError {}
 pub struct TypeContext {
     /// Variables and their types
     pub variables: HashMap<String, TypeInfo>,
+    /// Module-level constants and their declared types
+    pub constants: HashMap<String, TypeInfo>,
     /// Functions and their signatures
     pub functions: HashMap<String, TypeInfo>,
     /// Generic type parameters in scope
@@ -499,6 +619,14 @@ pub struct TypeContext {
     pub type_aliases: HashMap<String, TypeInfo>,
     /// Enum definitions available in the current module
     pub enums: HashMap<String, EnumDefinition>,
+    /// Trait definitions: name -> declared methods
+    pub traits: HashMap<String, TraitDefinition>,
+    /// Trait implementations: trait name -> implementing types
+    pub trait_impls: HashMap<String, Vec<TypeInfo>>,
+    /// Generic parameters of generic functions: function name -> parameters
+    pub generic_functions: HashMap<String, Vec<GenericParam>>,
+    /// Trait bounds of the generic parameters in scope
+    pub generic_bounds: HashMap<String, Vec<String>>,
     /// Active language feature flags
     pub features: LanguageFeatureFlags,
 }
@@ -511,11 +639,16 @@ impl TypeContext {
     pub fn with_features(features: LanguageFeatureFlags) -> Self {
         Self {
             variables: HashMap::new(),
+            constants: HashMap::new(),
             functions: HashMap::new(),
             generic_params: Vec::new(),
             structs: HashMap::new(),
             type_aliases: HashMap::new(),
             enums: HashMap::new(),
+            traits: HashMap::new(),
+            trait_impls: HashMap::new(),
+            generic_functions: HashMap::new(),
+            generic_bounds: HashMap::new(),
             features,
         }
     }
@@ -533,8 +666,20 @@ impl TypeContext {
         self.variables.insert(name, ty);
     }
 
+    /// Type of a variable, or of the constant it refers to
     pub fn get_variable(&self, name: &str) -> Option<&TypeInfo> {
-        self.variables.get(name)
+        self.variables
+            .get(name)
+            .or_else(|| self.constants.get(name))
+    }
+
+    pub fn define_constant(&mut self, name: String, ty: TypeInfo) {
+        self.constants.insert(name, ty);
+    }
+
+    /// Whether `name` refers to a constant rather than a variable
+    pub fn is_constant(&self, name: &str) -> bool {
+        !self.variables.contains_key(name) && self.constants.contains_key(name)
     }
 
     pub fn remove_variable(&mut self, name: &str) -> Option<TypeInfo> {
@@ -596,6 +741,38 @@ impl TypeContext {
             .and_then(|definition| definition.variants.iter().find(|v| v.name == variant))
     }
 
+    pub fn define_trait(&mut self, definition: TraitDefinition) {
+        self.traits.insert(definition.name.clone(), definition);
+    }
+
+    pub fn get_trait(&self, name: &str) -> Option<&TraitDefinition> {
+        self.traits.get(name)
+    }
+
+    pub fn add_trait_impl(&mut self, trait_name: String, ty: TypeInfo) {
+        self.trait_impls.entry(trait_name).or_default().push(ty);
+    }
+
+    /// Whether `ty` implements the trait, either through an `impl` block or
+    /// because it is a generic parameter bounded by the trait
+    pub fn implements(&self, ty: &TypeInfo, trait_name: &str) -> bool {
+        if let TypeInfo::Generic { base, args } = ty {
+            if args.is_empty()
+                && self
+                    .generic_bounds
+                    .get(base)
+                    .is_some_and(|bounds| bounds.iter().any(|bound| bound == trait_name))
+            {
+                return true;
+            }
+        }
+        self.trait_impls.get(trait_name).is_some_and(|types| {
+            types
+                .iter()
+                .any(|implementor| ty.is_compatible_with(implementor))
+        })
+    }
+
     pub fn build_enum_type(&self, name: &str, args: Vec<TypeInfo>) -> Option<TypeInfo> {
         let definition = self.enums.get(name)?;
         let mut normalized_args = if args.is_empty() {
@@ -655,6 +832,11 @@ impl TypeContext {
             TypeInfo::Generic { base, args } => {
                 if let Some(enum_ty) = self.build_enum_type(&base, args.clone()) {
                     enum_ty
+                } else if let Some(fields) = self.structs.get(&base).filter(|_| args.is_empty()) {
+                    TypeInfo::Struct {
+                        name: base,
+                        fields: fields.clone(),
+                    }
                 } else {
                     TypeInfo::Generic { base, args }
                 }
@@ -664,6 +846,47 @@ impl TypeContext {
     }
 
     pub fn type_from_annotation(&self, ty: &Type) -> TypeInfo {
+        if let Type::Tuple(elements) = ty {
+            return TypeInfo::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.type_from_annotation(element))
+                    .collect(),
+            );
+        }
+        if let Type::Function { params, ret } = ty {
+            return TypeInfo::Function {
+                params: params
+                    .iter()
+                    .map(|param| self.type_from_annotation(param))
+                    .collect(),
+                param_defaults: vec![false; params.len()],
+                return_type: Box::new(
+                    ret.as_deref()
+                        .map(|ret| self.type_from_annotation(ret))
+                        .unwrap_or(TypeInfo::Unit),
+                ),
+            };
+        }
+        if let Type::Generic { base, args } = ty {
+            // Function elements resolve their own signatures, e.g. `list<fn(int) -> int>`
+            if matches!(base.as_str(), "List" | "list") {
+                if let [element @ Type::Function { .. }] = args.as_slice() {
+                    return TypeInfo::List(Box::new(self.type_from_annotation(element)));
+                }
+            }
+            // Arguments of other generic types may name aliases such as `int`
+            if !matches!(base.as_str(), "List" | "list" | "Dict" | "dict") {
+                let args = args
+                    .iter()
+                    .map(|arg| self.type_from_annotation(arg))
+                    .collect();
+                return self.normalize_type(TypeInfo::Generic {
+                    base: base.clone(),
+                    args,
+                });
+            }
+        }
         let mut info = TypeInfo::from(ty);
         if let TypeInfo::Generic { base, args } = &info {
             if args.is_empty() {
@@ -692,3 +915,10 @@ pub struct EnumDefinition {
     pub generics: Vec<String>,
     pub variants: Vec<EnumVariant>,
 }
+
+#[derive(Debug, Clone)]
+pub struct TraitDefinition {
+    pub name: String,
+    /// Declared methods; signature-only methods must be implemented
+    pub methods: Vec<Function>,
+}
@@ -66,7 +66,14 @@ function createClient(context: vscode.ExtensionContext): LanguageClient {
             fileEvents: vscode.workspace.createFileSystemWatcher('**/*.ot')
         },
         outputChannel: outputChannel,
-        traceOutputChannel: traceOutputChannel
+        traceOutputChannel: traceOutputChannel,
+        initializationOptions: {
+            inlayHints: {
+                typeHints: config.get<boolean>('inlayHints.typeHints', true),
+                parameterNames: config.get<boolean>('inlayHints.parameterNames', true),
+                returnTypes: config.get<boolean>('inlayHints.returnTypes', true)
+            }
+        }
     };
 
     const languageClient = new LanguageClient(